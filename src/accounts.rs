@@ -0,0 +1,430 @@
+use crate::api;
+use crate::audit;
+use crate::auth::{hash_password, load_users, save_users, CurrentUser, User};
+use crate::commands::{EXIT_FAILURE, EXIT_PERMISSION};
+use crate::cron;
+use crate::mail;
+use crate::profile;
+use crate::vfs;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+/// Held while an account is changed, so sessions changing accounts at the
+/// same time don't undo each other's changes to users.xml
+static CHANGING: Mutex<()> = Mutex::new(());
+
+fn changing() -> MutexGuard<'static, ()> {
+    CHANGING.lock().unwrap_or_else(|p| p.into_inner())
+}
+
+/// Why an account operation was refused
+#[derive(Debug)]
+pub enum AccountError {
+    NotFound(String),
+    PermissionDenied(String),
+    /// The change would break one of the account rules (root account, last
+    /// admin, duplicate name, ...)
+    Refused(String),
+    Storage(String),
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountError::NotFound(username) => write!(f, "User '{}' not found.", username),
+            AccountError::PermissionDenied(msg)
+            | AccountError::Refused(msg)
+            | AccountError::Storage(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+impl AccountError {
+    pub fn exit_status(&self) -> i32 {
+        match self {
+            AccountError::PermissionDenied(_) => EXIT_PERMISSION,
+            _ => EXIT_FAILURE,
+        }
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for AccountError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        AccountError::Storage(e.to_string())
+    }
+}
+
+// The account rules below are shared by the interactive commands and the
+// admin console, so the two can't disagree about what's allowed.
+
+/// Names users.xml uses for its own tags, which can't be usernames
+const RESERVED_USERNAMES: &[&str] = &["users", "password", "isadmin", "disabled"];
+
+const MAX_USERNAME_LEN: usize = 32;
+
+/// Usernames are used as XML tag names in users.xml and as file names
+/// under /home, /var/mail and /var/spool/cron, so they're kept to
+/// `[a-z_][a-z0-9_-]*`
+pub fn validate_username(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Username cannot be empty.".into());
+    }
+    if name.len() > MAX_USERNAME_LEN {
+        return Err(format!(
+            "Username cannot be longer than {} characters.",
+            MAX_USERNAME_LEN
+        ));
+    }
+    if !name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_') {
+        return Err("Username must start with a lowercase letter or '_'.".into());
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+    {
+        return Err("Username can only contain lowercase letters, digits, '_' and '-'.".into());
+    }
+    if RESERVED_USERNAMES.contains(&name) {
+        return Err(format!("'{}' is reserved and cannot be a username.", name));
+    }
+    Ok(())
+}
+
+/// The first user in users.xml is the root admin
+pub fn is_root(users: &[User], username: &str) -> bool {
+    users.first().map(|u| u.username == username).unwrap_or(false)
+}
+
+fn require_admin(actor: &CurrentUser, what: &str) -> Result<(), AccountError> {
+    if actor.is_admin {
+        Ok(())
+    } else {
+        Err(AccountError::PermissionDenied(format!(
+            "You must be an admin to {}.",
+            what
+        )))
+    }
+}
+
+fn find_index(users: &[User], username: &str) -> Result<usize, AccountError> {
+    users
+        .iter()
+        .position(|u| u.username == username)
+        .ok_or_else(|| AccountError::NotFound(username.to_string()))
+}
+
+/// Whether some enabled admin other than `username` would remain
+fn other_admin_exists(users: &[User], username: &str) -> bool {
+    users
+        .iter()
+        .any(|u| u.is_admin && !u.disabled && u.username != username)
+}
+
+pub fn create_user(
+    actor: &CurrentUser,
+    username: &str,
+    password: &str,
+    is_admin: bool,
+) -> Result<(), AccountError> {
+    require_admin(actor, "add users")?;
+    validate_username(username).map_err(AccountError::Refused)?;
+    let _changing = changing();
+    let mut users = load_users()?;
+    if users.iter().any(|u| u.username == username) {
+        return Err(AccountError::Refused(format!(
+            "User '{}' already exists.",
+            username
+        )));
+    }
+
+    users.push(User {
+        username: username.to_string(),
+        password_hash: hash_password(password),
+        is_admin,
+        disabled: false,
+    });
+    save_users(&users)?;
+    profile::ensure_home(username).map_err(|e| AccountError::Storage(e.to_string()))?;
+    let _ = audit::record(
+        &actor.username,
+        "create",
+        username,
+        if is_admin { "admin" } else { "user" },
+    );
+    Ok(())
+}
+
+/// Changes to an account that `update_user` makes together: all of them,
+/// or none if any is refused
+#[derive(Debug, Clone, Default)]
+pub struct AccountChanges {
+    pub password: Option<String>,
+    pub is_admin: Option<bool>,
+    pub disabled: Option<bool>,
+}
+
+pub fn set_password(
+    actor: &CurrentUser,
+    username: &str,
+    password: &str,
+) -> Result<(), AccountError> {
+    let changes = AccountChanges {
+        password: Some(password.to_string()),
+        ..AccountChanges::default()
+    };
+    update_user(actor, username, &changes)
+}
+
+pub fn set_admin(
+    actor: &CurrentUser,
+    username: &str,
+    is_admin: bool,
+) -> Result<(), AccountError> {
+    let changes = AccountChanges {
+        is_admin: Some(is_admin),
+        ..AccountChanges::default()
+    };
+    update_user(actor, username, &changes)
+}
+
+pub fn set_disabled(
+    actor: &CurrentUser,
+    username: &str,
+    disabled: bool,
+) -> Result<(), AccountError> {
+    let changes = AccountChanges {
+        disabled: Some(disabled),
+        ..AccountChanges::default()
+    };
+    update_user(actor, username, &changes)
+}
+
+/// Changes an account's password, admin status and whether it's disabled,
+/// in that order. Each change is checked against the account as the ones
+/// before it left it, and users.xml is only saved if all are allowed.
+pub fn update_user(
+    actor: &CurrentUser,
+    username: &str,
+    changes: &AccountChanges,
+) -> Result<(), AccountError> {
+    let _changing = changing();
+    let mut users = load_users()?;
+    let mut actions = Vec::new();
+    if let Some(password) = &changes.password {
+        actions.extend(change_password(actor, &mut users, username, password)?);
+    }
+    if let Some(is_admin) = changes.is_admin {
+        actions.extend(change_admin(actor, &mut users, username, is_admin)?);
+    }
+    if let Some(disabled) = changes.disabled {
+        actions.extend(change_disabled(actor, &mut users, username, disabled)?);
+    }
+    if actions.is_empty() {
+        return Ok(());
+    }
+
+    save_users(&users)?;
+    for action in actions {
+        let _ = audit::record(&actor.username, action, username, "");
+    }
+    Ok(())
+}
+
+// Each change below is made to `users` in memory, returning the audit
+// action to record once they're saved, or `None` if nothing changed.
+
+fn change_password(
+    actor: &CurrentUser,
+    users: &mut [User],
+    username: &str,
+    password: &str,
+) -> Result<Option<&'static str>, AccountError> {
+    if !actor.is_admin && actor.username != username {
+        return Err(AccountError::PermissionDenied(
+            "Non-admin users can only change their own password.".into(),
+        ));
+    }
+    if password.is_empty() {
+        return Err(AccountError::Refused("Password cannot be empty.".into()));
+    }
+    let index = find_index(users, username)?;
+    if index == 0 && !is_root(users, &actor.username) {
+        return Err(AccountError::PermissionDenied(
+            "Only the root user can change root's password.".into(),
+        ));
+    }
+
+    users[index].password_hash = hash_password(password);
+    Ok(Some("password"))
+}
+
+fn change_admin(
+    actor: &CurrentUser,
+    users: &mut [User],
+    username: &str,
+    is_admin: bool,
+) -> Result<Option<&'static str>, AccountError> {
+    require_admin(actor, "change admin privileges")?;
+    let index = find_index(users, username)?;
+    if index == 0 {
+        return Err(AccountError::Refused(
+            "Admin status of the root user cannot be changed.".into(),
+        ));
+    }
+    if users[index].is_admin == is_admin {
+        return Ok(None);
+    }
+    if !is_admin && !other_admin_exists(users, username) {
+        return Err(AccountError::Refused(
+            "Cannot remove the last admin user. Create another admin user first.".into(),
+        ));
+    }
+
+    users[index].is_admin = is_admin;
+    Ok(Some(if is_admin { "grant-admin" } else { "revoke-admin" }))
+}
+
+fn change_disabled(
+    actor: &CurrentUser,
+    users: &mut [User],
+    username: &str,
+    disabled: bool,
+) -> Result<Option<&'static str>, AccountError> {
+    require_admin(actor, "disable or enable accounts")?;
+    let index = find_index(users, username)?;
+    if index == 0 {
+        return Err(AccountError::Refused(
+            "The root user cannot be disabled.".into(),
+        ));
+    }
+    if users[index].disabled == disabled {
+        return Ok(None);
+    }
+    if disabled && users[index].is_admin && !other_admin_exists(users, username) {
+        return Err(AccountError::Refused(
+            "Cannot disable the last active admin user.".into(),
+        ));
+    }
+
+    users[index].disabled = disabled;
+    Ok(Some(if disabled { "disable" } else { "enable" }))
+}
+
+/// What happens to the files of a deleted account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileDisposal {
+    /// Give them to another account
+    ReassignTo(String),
+    Delete,
+}
+
+/// Deletes an account and its home directory. Like `delusr`, this has to be
+/// confirmed with the root user's password. The home directory can be
+/// archived first, and the account's other files are reassigned or deleted
+/// as asked. Returns the path of the archive, if one was made.
+pub fn delete_user(
+    actor: &CurrentUser,
+    username: &str,
+    root_password: &str,
+    files: &FileDisposal,
+    archive_home: bool,
+) -> Result<Option<String>, AccountError> {
+    require_admin(actor, "delete users")?;
+    let _changing = changing();
+    let mut users = load_users()?;
+    if users.len() <= 1 {
+        return Err(AccountError::Refused(
+            "Cannot delete users when there's only one user in the system.".into(),
+        ));
+    }
+    let index = find_index(&users, username)?;
+    if index == 0 {
+        return Err(AccountError::Refused(
+            "Cannot delete the first user (root admin).".into(),
+        ));
+    }
+    if hash_password(root_password) != users[0].password_hash {
+        let _ = audit::record(&actor.username, "delete-denied", username, "bad root password");
+        return Err(AccountError::PermissionDenied(
+            "Incorrect password. User deletion cancelled.".into(),
+        ));
+    }
+    if users[index].is_admin && !other_admin_exists(&users, username) {
+        return Err(AccountError::Refused("Cannot delete the last admin user.".into()));
+    }
+    if let FileDisposal::ReassignTo(heir) = files {
+        if heir == username {
+            return Err(AccountError::Refused(
+                "Files can't be given to the account being deleted.".into(),
+            ));
+        }
+        find_index(&users, heir)?;
+    }
+
+    let storage = |e: vfs::VfsError| AccountError::Storage(e.to_string());
+    let archive = if archive_home {
+        profile::archive_home(username).map_err(storage)?
+    } else {
+        None
+    };
+
+    users.remove(index);
+    save_users(&users)?;
+    profile::remove_home(username).map_err(storage)?;
+    cron::remove(username).map_err(storage)?;
+    mail::remove(username).map_err(storage)?;
+    api::revoke_owned(username).map_err(|e| AccountError::Storage(e.to_string()))?;
+    let mut detail = match files {
+        FileDisposal::ReassignTo(heir) => {
+            let count = vfs::lock().disown(username, Some(heir)).map_err(storage)?;
+            format!("{} files given to {}", count, heir)
+        }
+        FileDisposal::Delete => {
+            let count = vfs::lock().disown(username, None).map_err(storage)?;
+            format!("{} files deleted", count)
+        }
+    };
+    if let Some(archive) = &archive {
+        detail.push_str(&format!(", home archived to {}", archive));
+    }
+    let _ = audit::record(&actor.username, "delete", username, &detail);
+    Ok(archive)
+}
+
+/// Renames an account. Its home directory and files follow it.
+pub fn rename_user(actor: &CurrentUser, username: &str, new_name: &str) -> Result<(), AccountError> {
+    require_admin(actor, "rename users")?;
+    validate_username(new_name).map_err(AccountError::Refused)?;
+    let _changing = changing();
+    let mut users = load_users()?;
+    let index = find_index(&users, username)?;
+    if index == 0 {
+        return Err(AccountError::Refused("The root user cannot be renamed.".into()));
+    }
+    if users.iter().any(|u| u.username == new_name) {
+        return Err(AccountError::Refused(format!(
+            "User '{}' already exists.",
+            new_name
+        )));
+    }
+    let new_home = profile::user_home(new_name);
+    {
+        let fs = vfs::lock();
+        if fs.exists(&fs.root_user(), &new_home) {
+            return Err(AccountError::Refused(format!("{} already exists.", new_home)));
+        }
+    }
+
+    users[index].username = new_name.to_string();
+    save_users(&users)?;
+    let storage = |e: vfs::VfsError| AccountError::Storage(e.to_string());
+    profile::rename_home(username, new_name).map_err(storage)?;
+    cron::rename(username, new_name).map_err(storage)?;
+    mail::rename(username, new_name).map_err(storage)?;
+    api::rename_owner(username, new_name).map_err(|e| AccountError::Storage(e.to_string()))?;
+    vfs::lock().rename_account(username, new_name).map_err(storage)?;
+    let _ = audit::record(&actor.username, "rename", username, new_name);
+    Ok(())
+}
@@ -0,0 +1,954 @@
+use crate::accounts::{self, AccountChanges, AccountError, FileDisposal};
+use crate::audit;
+use crate::auth::{self, load_users, CurrentUser};
+use crate::clock;
+use crate::commands::audit::audit_table;
+use crate::commands::listusr::user_table;
+use crate::output::{self, json_string, Format, OutputOptions};
+use crate::remote::{Address, Listener, Stream};
+use crate::syslog::{self, Facility, Level};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// API tokens, next to users.xml. Only a hash of each token is kept, so
+/// the file can't be used to make requests.
+pub const TOKENS_FILE_PATH: &str = "api_tokens";
+
+/// How often the server looks for connections, and whether it's stopping
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+
+/// How long a client has to send its whole request, and to take the
+/// response
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest request line or header accepted
+const MAX_LINE_BYTES: usize = 8 * 1024;
+
+/// Most headers accepted in a request
+const MAX_HEADERS: usize = 64;
+
+/// Largest request body accepted
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Most connections served at once; more are turned away with 503
+const MAX_CONNECTIONS: usize = 16;
+
+/// Connections being served
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// An API token. Requests made with it act as its owner, who has to be an
+/// enabled admin when the request is made.
+#[derive(Debug, Clone)]
+pub struct Token {
+    /// Identifies the token in `apitoken list` and `revoke`
+    pub id: String,
+    hash: String,
+    pub owner: String,
+    /// Unix timestamp
+    pub created: u64,
+    /// What the token is for, as its owner described it
+    pub name: String,
+}
+
+/// Held while the token file is read and written back
+static TOKENS_CHANGING: Mutex<()> = Mutex::new(());
+
+fn tokens_changing() -> MutexGuard<'static, ()> {
+    TOKENS_CHANGING.lock().unwrap_or_else(|p| p.into_inner())
+}
+
+/// The API tokens, oldest first
+pub fn load_tokens() -> io::Result<Vec<Token>> {
+    let contents = match fs::read_to_string(TOKENS_FILE_PATH) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.splitn(5, '\t').collect();
+            let [id, hash, owner, created, name] = fields[..] else {
+                return None;
+            };
+            Some(Token {
+                id: id.to_string(),
+                hash: hash.to_string(),
+                owner: owner.to_string(),
+                created: created.parse().ok()?,
+                name: name.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Writes the token file beside the old one and moves it over, readable
+/// by MiniKern's own user only
+fn save_tokens(tokens: &[Token]) -> io::Result<()> {
+    let tmp = format!("{}.tmp", TOKENS_FILE_PATH);
+    let mut file = File::create(&tmp)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    for token in tokens {
+        writeln!(
+            file,
+            "{}\t{}\t{}\t{}\t{}",
+            token.id,
+            token.hash,
+            token.owner,
+            token.created,
+            token.name.replace(['\t', '\n', '\r'], " ")
+        )?;
+    }
+    file.flush()?;
+    fs::rename(&tmp, TOKENS_FILE_PATH)
+}
+
+/// Makes a token for `owner`, returning it along with the secret to send
+/// in requests. The secret isn't kept, so it can't be shown again.
+pub fn create_token(owner: &str, name: &str) -> io::Result<(Token, String)> {
+    let mut random = [0u8; 24];
+    File::open("/dev/urandom")?.read_exact(&mut random)?;
+    let secret = format!("mk_{}", hex::encode(random));
+    let hash = auth::hash_password(&secret);
+    let token = Token {
+        id: hash[..8].to_string(),
+        hash,
+        owner: owner.to_string(),
+        created: clock::unix_now(),
+        name: name.to_string(),
+    };
+    let _changing = tokens_changing();
+    let mut tokens = load_tokens()?;
+    tokens.push(token.clone());
+    save_tokens(&tokens)?;
+    Ok((token, secret))
+}
+
+/// Deletes a token, returning whether there was one with that ID
+pub fn revoke_token(id: &str) -> io::Result<bool> {
+    let _changing = tokens_changing();
+    let mut tokens = load_tokens()?;
+    let count = tokens.len();
+    tokens.retain(|token| token.id != id);
+    if tokens.len() == count {
+        return Ok(false);
+    }
+    save_tokens(&tokens)?;
+    Ok(true)
+}
+
+/// Deletes the tokens of a deleted account, so an account made later with
+/// the same name doesn't get them
+pub fn revoke_owned(username: &str) -> io::Result<()> {
+    let _changing = tokens_changing();
+    let mut tokens = load_tokens()?;
+    let count = tokens.len();
+    tokens.retain(|token| token.owner != username);
+    if tokens.len() != count {
+        save_tokens(&tokens)?;
+    }
+    Ok(())
+}
+
+/// Gives a renamed account's tokens its new name
+pub fn rename_owner(old: &str, new: &str) -> io::Result<()> {
+    let _changing = tokens_changing();
+    let mut tokens = load_tokens()?;
+    if !tokens.iter().any(|token| token.owner == old) {
+        return Ok(());
+    }
+    for token in tokens.iter_mut().filter(|token| token.owner == old) {
+        token.owner = new.to_string();
+    }
+    save_tokens(&tokens)
+}
+
+/// The running server: set `stopping` and it ends within `ACCEPT_POLL`
+struct Server {
+    stopping: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+
+/// Starts serving the API on `address`, until `stop`
+pub fn start(address: &Address) -> Result<(), String> {
+    let mut server = SERVER.lock().unwrap_or_else(|p| p.into_inner());
+    if server.is_some() {
+        return Err("already running".to_string());
+    }
+    let listener = Listener::bind(address)
+        .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+        .map_err(|e| format!("could not listen on {}: {}", address, e))?;
+    let stopping = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&stopping);
+    let thread = std::thread::spawn(move || serve(listener, &flag));
+    *server = Some(Server { stopping, thread });
+    let _ = syslog::record(
+        Level::Info,
+        Facility::Auth,
+        &format!("api listening on {}", address),
+    );
+    Ok(())
+}
+
+/// Stops the server, once it's no longer listening. Returns false if it
+/// wasn't running.
+pub fn stop() -> bool {
+    let server = SERVER.lock().unwrap_or_else(|p| p.into_inner()).take();
+    let Some(server) = server else {
+        return false;
+    };
+    server.stopping.store(true, Ordering::SeqCst);
+    let _ = server.thread.join();
+    true
+}
+
+fn serve(listener: Listener, stopping: &AtomicBool) {
+    while !stopping.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((mut stream, peer)) => {
+                let _ = stream.set_write_timeout(Some(REQUEST_TIMEOUT));
+                let Some(slot) = ConnectionSlot::take() else {
+                    let _ = syslog::record(
+                        Level::Warning,
+                        Facility::Auth,
+                        &format!("api: too many connections, turned {} away", peer),
+                    );
+                    respond(&mut stream, Response::error(503, "too many connections"));
+                    continue;
+                };
+                std::thread::spawn(move || {
+                    handle(stream, &peer);
+                    drop(slot);
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(ACCEPT_POLL),
+            Err(e) => {
+                let _ = syslog::record(
+                    Level::Err,
+                    Facility::Auth,
+                    &format!("api: could not accept a connection: {}", e),
+                );
+                std::thread::sleep(ACCEPT_POLL);
+            }
+        }
+    }
+}
+
+/// One of the `MAX_CONNECTIONS` connections served at once, given back
+/// when dropped
+struct ConnectionSlot;
+
+impl ConnectionSlot {
+    fn take() -> Option<ConnectionSlot> {
+        CONNECTIONS
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < MAX_CONNECTIONS).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| ConnectionSlot)
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct Request {
+    method: String,
+    /// Percent-decoded path segments
+    path: Vec<String>,
+    query: BTreeMap<String, String>,
+    authorization: Option<String>,
+    body: String,
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    /// JSON
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: String) -> Response {
+        Response { status, body }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response::json(status, format!("{{\"error\": {}}}\n", json_string(message)))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            414 => "URI Too Long",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+impl From<AccountError> for Response {
+    fn from(e: AccountError) -> Response {
+        let status = match e {
+            AccountError::NotFound(_) => 404,
+            AccountError::PermissionDenied(_) => 403,
+            AccountError::Refused(_) => 409,
+            AccountError::Storage(_) => 500,
+        };
+        Response::error(status, &e.to_string())
+    }
+}
+
+fn internal(e: impl std::fmt::Display) -> Response {
+    Response::error(500, &e.to_string())
+}
+
+/// Serves one request. Connections aren't kept open for another.
+fn handle(mut stream: Stream, peer: &str) {
+    let response = match read_request(&mut stream) {
+        Ok(Some(request)) => match authenticate(request.authorization.as_deref()) {
+            Ok(actor) => route(&actor, &request).unwrap_or_else(|e| e),
+            Err((status, reason)) => {
+                let _ = syslog::record(
+                    Level::Notice,
+                    Facility::Auth,
+                    &format!("api: rejected a request from {}: {}", peer, reason),
+                );
+                Response::error(status, &reason)
+            }
+        },
+        // The client went away, or took too long
+        Ok(None) => return,
+        Err(response) => response,
+    };
+    respond(&mut stream, response);
+}
+
+fn respond(stream: &mut Stream, response: Response) {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.reason(),
+        response.body.len()
+    );
+    if response.status == 401 {
+        head.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    let _ = write!(stream, "{}\r\n{}", head, response.body);
+    let _ = stream.flush();
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = text
+            .get(i + 1..i + 3)
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Reads from a client until a deadline for the whole request, rather than
+/// for each read, so one sending a byte at a time can't hold on forever
+struct Timed<'a> {
+    stream: &'a mut Stream,
+    deadline: Instant,
+}
+
+impl Read for Timed<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Reads a line of the request head, newline included, failing with
+/// `status` if it's longer than `MAX_LINE_BYTES`. `Ok(None)` if the
+/// connection ended or timed out first.
+fn read_head_line(reader: &mut impl BufRead, status: u16) -> Result<Option<String>, Response> {
+    let mut line = String::new();
+    match reader.take(MAX_LINE_BYTES as u64 + 1).read_line(&mut line) {
+        Ok(0) | Err(_) => Ok(None),
+        Ok(n) if n > MAX_LINE_BYTES => {
+            Err(Response::error(status, "request line or header too long"))
+        }
+        Ok(_) if !line.ends_with('\n') => Ok(None),
+        Ok(_) => Ok(Some(line)),
+    }
+}
+
+/// Reads a request. `Ok(None)` if the connection ended or timed out
+/// before a whole one arrived.
+fn read_request(stream: &mut Stream) -> Result<Option<Request>, Response> {
+    let mut reader = BufReader::new(Timed {
+        stream,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    });
+    let Some(line) = read_head_line(&mut reader, 414)? else {
+        return Ok(None);
+    };
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(Response::error(400, "malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(percent_decode)
+            .collect(),
+        query: query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key), percent_decode(value))
+            })
+            .collect(),
+        authorization: None,
+        body: String::new(),
+    };
+
+    let mut length = 0;
+    for count in 0.. {
+        let Some(header) = read_head_line(&mut reader, 431)? else {
+            return Ok(None);
+        };
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS {
+            return Err(Response::error(431, "too many headers"));
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(Response::error(400, "malformed header"));
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| Response::error(400, "bad Content-Length"))?
+            }
+            "authorization" => request.authorization = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    if length > MAX_BODY_BYTES {
+        return Err(Response::error(413, "request body too large"));
+    }
+    let mut body = vec![0u8; length];
+    if reader.read_exact(&mut body).is_err() {
+        return Ok(None);
+    }
+    request.body =
+        String::from_utf8(body).map_err(|_| Response::error(400, "request body isn't UTF-8"))?;
+    Ok(Some(request))
+}
+
+/// The admin a request acts as, from its `Authorization: Bearer` token,
+/// or the status to refuse it with and why
+fn authenticate(authorization: Option<&str>) -> Result<CurrentUser, (u16, String)> {
+    let Some(secret) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+        return Err((401, "an API token is required".to_string()));
+    };
+    let hash = auth::hash_password(secret.trim());
+    let tokens = load_tokens().map_err(|e| (500, e.to_string()))?;
+    let Some(token) = tokens.iter().find(|token| token.hash == hash) else {
+        return Err((401, "invalid API token".to_string()));
+    };
+    let users = load_users().map_err(|e| (500, e.to_string()))?;
+    match users.iter().find(|u| u.username == token.owner) {
+        Some(user) if user.is_admin && !user.disabled => Ok(CurrentUser {
+            username: user.username.clone(),
+            is_admin: true,
+        }),
+        _ => Err((
+            403,
+            format!(
+                "the account of token {} is no longer an enabled admin",
+                token.id
+            ),
+        )),
+    }
+}
+
+fn route(actor: &CurrentUser, request: &Request) -> Result<Response, Response> {
+    let path: Vec<&str> = request.path.iter().map(|s| s.as_str()).collect();
+    match (request.method.as_str(), path.as_slice()) {
+        ("GET", ["users"]) => list_users(),
+        ("POST", ["users"]) => create_user(actor, &Body::parse(&request.body)?),
+        ("GET", ["users", name]) => Ok(Response::json(200, user_json(name)?)),
+        ("PATCH", ["users", name]) => update_user(actor, name, &Body::parse(&request.body)?),
+        ("DELETE", ["users", name]) => delete_user(actor, name, &Body::parse(&request.body)?),
+        ("GET", ["audit"]) => list_audit(&request.query),
+        (_, ["users"] | ["users", _] | ["audit"]) => {
+            Err(Response::error(405, "method not allowed"))
+        }
+        _ => Err(Response::error(404, "no such endpoint")),
+    }
+}
+
+fn json_options() -> OutputOptions {
+    OutputOptions {
+        format: Format::Json,
+        columns: None,
+        sort: None,
+    }
+}
+
+/// `GET /users`: the records of `listusr --json`
+fn list_users() -> Result<Response, Response> {
+    let users = load_users().map_err(internal)?;
+    let body = output::render(&user_table(&users), &json_options()).map_err(internal)?;
+    Ok(Response::json(200, body))
+}
+
+fn user_json(username: &str) -> Result<String, Response> {
+    let users = load_users().map_err(internal)?;
+    let Some(index) = users.iter().position(|u| u.username == username) else {
+        return Err(AccountError::NotFound(username.to_string()).into());
+    };
+    let table = user_table(&users);
+    Ok(format!(
+        "{}\n",
+        output::json_record(&table, &table.rows[index])
+    ))
+}
+
+/// `POST /users` with `username`, `password` and optionally `admin`
+fn create_user(actor: &CurrentUser, body: &Body) -> Result<Response, Response> {
+    body.allow(&["username", "password", "admin"])?;
+    let username = body.required_string("username")?;
+    let password = body.required_string("password")?;
+    if password.is_empty() {
+        return Err(AccountError::Refused("Password cannot be empty.".into()).into());
+    }
+    accounts::create_user(
+        actor,
+        &username,
+        &password,
+        body.bool("admin")?.unwrap_or(false),
+    )?;
+    Ok(Response::json(201, user_json(&username)?))
+}
+
+/// `PATCH /users/NAME` with any of `password`, `admin` and `disabled`,
+/// changed together: if one is refused, none are
+fn update_user(actor: &CurrentUser, username: &str, body: &Body) -> Result<Response, Response> {
+    body.allow(&["password", "admin", "disabled"])?;
+    let password = body.string("password")?;
+    let admin = body.bool("admin")?;
+    let disabled = body.bool("disabled")?;
+    if password.is_none() && admin.is_none() && disabled.is_none() {
+        return Err(Response::error(400, "nothing to change"));
+    }
+    let changes = AccountChanges {
+        password,
+        is_admin: admin,
+        disabled,
+    };
+    accounts::update_user(actor, username, &changes)?;
+    Ok(Response::json(200, user_json(username)?))
+}
+
+/// `DELETE /users/NAME`. Like `delusr`, this takes the root user's
+/// password (`root_password`); the account's files go to root unless
+/// `files` names another user or is `delete`, and `archive_home` archives
+/// the home directory first.
+fn delete_user(actor: &CurrentUser, username: &str, body: &Body) -> Result<Response, Response> {
+    body.allow(&["root_password", "files", "archive_home"])?;
+    let root_password = body.required_string("root_password")?;
+    let files = match body.string("files")? {
+        Some(files) if files == "delete" => FileDisposal::Delete,
+        Some(heir) => FileDisposal::ReassignTo(heir),
+        None => {
+            let users = load_users().map_err(internal)?;
+            FileDisposal::ReassignTo(
+                users
+                    .first()
+                    .map(|u| u.username.clone())
+                    .unwrap_or_default(),
+            )
+        }
+    };
+    let archive_home = body.bool("archive_home")?.unwrap_or(false);
+    let archive = accounts::delete_user(actor, username, &root_password, &files, archive_home)?;
+    Ok(Response::json(
+        200,
+        format!(
+            "{{\"deleted\": {}, \"archive\": {}}}\n",
+            json_string(username),
+            archive.as_deref().map_or("null".to_string(), json_string)
+        ),
+    ))
+}
+
+/// `GET /audit`, filtered like `audit` with `user`, `action` and `limit`
+fn list_audit(query: &BTreeMap<String, String>) -> Result<Response, Response> {
+    let limit = match query.get("limit") {
+        Some(limit) => Some(
+            limit
+                .parse::<usize>()
+                .map_err(|_| Response::error(400, "limit must be a number"))?,
+        ),
+        None => None,
+    };
+    let mut entries: Vec<_> = audit::load_entries()
+        .map_err(internal)?
+        .into_iter()
+        .filter(|e| {
+            query
+                .get("user")
+                .is_none_or(|u| &e.actor == u || &e.target == u)
+        })
+        .filter(|e| query.get("action").is_none_or(|a| &e.action == a))
+        .collect();
+    if let Some(limit) = limit {
+        let skip = entries.len().saturating_sub(limit);
+        entries.drain(..skip);
+    }
+    let body = output::render(&audit_table(&entries), &json_options()).map_err(internal)?;
+    Ok(Response::json(200, body))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum JsonValue {
+    Str(String),
+    Bool(bool),
+    Number(String),
+    Null,
+}
+
+/// A request body: a JSON object whose values are strings, booleans,
+/// numbers or null
+struct Body(BTreeMap<String, JsonValue>);
+
+impl Body {
+    fn parse(text: &str) -> Result<Body, Response> {
+        if text.trim().is_empty() {
+            return Ok(Body(BTreeMap::new()));
+        }
+        parse_object(text)
+            .map(Body)
+            .map_err(|e| Response::error(400, &format!("bad JSON body: {}", e)))
+    }
+
+    /// Refuses fields other than `known`
+    fn allow(&self, known: &[&str]) -> Result<(), Response> {
+        match self.0.keys().find(|key| !known.contains(&key.as_str())) {
+            Some(key) => Err(Response::error(400, &format!("unknown field '{}'", key))),
+            None => Ok(()),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>, Response> {
+        match self.0.get(key) {
+            None | Some(JsonValue::Null) => Ok(None),
+            Some(JsonValue::Str(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(Response::error(400, &format!("'{}' must be a string", key))),
+        }
+    }
+
+    fn required_string(&self, key: &str) -> Result<String, Response> {
+        self.string(key)?
+            .ok_or_else(|| Response::error(400, &format!("'{}' is required", key)))
+    }
+
+    fn bool(&self, key: &str) -> Result<Option<bool>, Response> {
+        match self.0.get(key) {
+            None | Some(JsonValue::Null) => Ok(None),
+            Some(JsonValue::Bool(b)) => Ok(Some(*b)),
+            Some(_) => Err(Response::error(
+                400,
+                &format!("'{}' must be true or false", key),
+            )),
+        }
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Chars, wanted: char) -> Result<(), String> {
+    match chars.next() {
+        Some(c) if c == wanted => Ok(()),
+        Some(c) => Err(format!("expected '{}', found '{}'", wanted, c)),
+        None => Err(format!("expected '{}' before the end", wanted)),
+    }
+}
+
+fn parse_object(text: &str) -> Result<BTreeMap<String, JsonValue>, String> {
+    let mut chars = text.chars().peekable();
+    let mut object = BTreeMap::new();
+    skip_whitespace(&mut chars);
+    expect(&mut chars, '{')?;
+    skip_whitespace(&mut chars);
+    if chars.next_if_eq(&'}').is_none() {
+        loop {
+            skip_whitespace(&mut chars);
+            let key = parse_string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            expect(&mut chars, ':')?;
+            skip_whitespace(&mut chars);
+            let value = parse_value(&mut chars)?;
+            object.insert(key, value);
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err("expected ',' or '}'".to_string()),
+            }
+        }
+    }
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        Some(_) => Err("unexpected text after the object".to_string()),
+        None => Ok(object),
+    }
+}
+
+/// Reads the literal `word`, which stands for `value`
+fn literal(chars: &mut Chars, word: &str, value: JsonValue) -> Result<JsonValue, String> {
+    for wanted in word.chars() {
+        expect(chars, wanted)?;
+    }
+    Ok(value)
+}
+
+fn parse_value(chars: &mut Chars) -> Result<JsonValue, String> {
+    match chars.peek() {
+        Some('"') => parse_string(chars).map(JsonValue::Str),
+        Some('t') => literal(chars, "true", JsonValue::Bool(true)),
+        Some('f') => literal(chars, "false", JsonValue::Bool(false)),
+        Some('n') => literal(chars, "null", JsonValue::Null),
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| "+-.eE".contains(*c) || c.is_ascii_digit()) {
+                number.push(c);
+            }
+            Ok(JsonValue::Number(number))
+        }
+        Some('{' | '[') => Err("objects and arrays aren't supported as values".to_string()),
+        _ => Err("expected a value".to_string()),
+    }
+}
+
+fn parse_string(chars: &mut Chars) -> Result<String, String> {
+    expect(chars, '"')?;
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("unterminated string".to_string()),
+            Some('"') => return Ok(text),
+            Some('\\') => {
+                let c = match chars.next() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('/') => '/',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('u') => parse_unicode_escape(chars)?,
+                    _ => return Err("bad escape in string".to_string()),
+                };
+                text.push(c);
+            }
+            Some(c) => text.push(c),
+        }
+    }
+}
+
+/// The character of a `\uXXXX` escape, the `\u` already read. Characters
+/// outside the Basic Multilingual Plane take two, a surrogate pair.
+fn parse_unicode_escape(chars: &mut Chars) -> Result<char, String> {
+    let unit = |chars: &mut Chars| -> Result<u32, String> {
+        let hex: String = (0..4).filter_map(|_| chars.next()).collect();
+        u32::from_str_radix(&hex, 16).map_err(|_| format!("bad \\u escape '{}'", hex))
+    };
+    let high = unit(chars)?;
+    let code = if (0xd800..0xdc00).contains(&high) {
+        expect(chars, '\\')?;
+        expect(chars, 'u')?;
+        let low = unit(chars)?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return Err("unpaired surrogate in \\u escape".to_string());
+        }
+        0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+    } else {
+        high
+    };
+    char::from_u32(code).ok_or_else(|| "bad \\u escape".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(text: &str) -> Result<Vec<(String, JsonValue)>, String> {
+        parse_object(text).map(|object| object.into_iter().collect())
+    }
+
+    fn str(s: &str) -> JsonValue {
+        JsonValue::Str(s.to_string())
+    }
+
+    #[test]
+    fn parses_flat_objects() {
+        assert_eq!(object("{}"), Ok(vec![]));
+        assert_eq!(
+            object(" {\n \"name\" : \"alice\", \"admin\":true,\"n\": -1.5e3, \"x\": null } \n"),
+            Ok(vec![
+                ("admin".to_string(), JsonValue::Bool(true)),
+                ("n".to_string(), JsonValue::Number("-1.5e3".to_string())),
+                ("name".to_string(), str("alice")),
+                ("x".to_string(), JsonValue::Null),
+            ])
+        );
+    }
+
+    #[test]
+    fn decodes_string_escapes() {
+        let value = |text: &str| object(text).map(|mut o| o.remove(0).1);
+        assert_eq!(
+            value(r#"{"k": "a\"b\\c\/d\n\t"}"#),
+            Ok(str("a\"b\\c/d\n\t"))
+        );
+        assert_eq!(value(r#"{"k": "é☃"}"#), Ok(str("é☃")));
+        assert_eq!(value(r#"{"k": "😀"}"#), Ok(str("😀")));
+        assert!(value(r#"{"k": "\ud83d"}"#).is_err());
+        assert!(value(r#"{"k": "\ud83dA"}"#).is_err());
+        assert!(value(r#"{"k": "\uzzzz"}"#).is_err());
+        assert!(value(r#"{"k": "\q"}"#).is_err());
+    }
+
+    #[test]
+    fn refuses_what_it_cant_parse() {
+        for text in [
+            "",
+            "[]",
+            "{",
+            "{\"a\"}",
+            "{\"a\": }",
+            "{\"a\": 1,}",
+            "{\"a\": 1 \"b\": 2}",
+            "{\"a\": tru}",
+            "{\"a\": \"open}",
+            "{\"a\": {}}",
+            "{\"a\": [1]}",
+            "{a: 1}",
+            "{} {}",
+        ] {
+            assert!(parse_object(text).is_err(), "{:?} parsed", text);
+        }
+    }
+
+    #[test]
+    fn body_fields_are_checked() {
+        assert!(Body::parse("  ").unwrap().0.is_empty());
+        assert_eq!(Body::parse("{").err().map(|r| r.status), Some(400));
+
+        let body =
+            Body::parse(r#"{"name": "bob", "admin": false, "age": 3, "gone": null}"#).unwrap();
+        assert_eq!(body.string("name").ok(), Some(Some("bob".to_string())));
+        assert_eq!(body.string("gone").ok(), Some(None));
+        assert_eq!(body.string("missing").ok(), Some(None));
+        assert_eq!(body.string("admin").err().map(|r| r.status), Some(400));
+        assert_eq!(body.bool("admin").ok(), Some(Some(false)));
+        assert_eq!(body.bool("age").err().map(|r| r.status), Some(400));
+        assert_eq!(
+            body.required_string("missing").err().map(|r| r.status),
+            Some(400)
+        );
+        assert!(body.allow(&["name", "admin", "age", "gone"]).is_ok());
+        let refused = body.allow(&["name", "admin", "gone"]).unwrap_err();
+        assert_eq!(refused.status, 400);
+        assert!(refused.body.contains("'age'"), "{}", refused.body);
+    }
+
+    #[test]
+    fn percent_decodes_paths() {
+        assert_eq!(percent_decode("al%69ce"), "alice");
+        assert_eq!(percent_decode("a+b%2Fc"), "a b/c");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("%C3%A9"), "é");
+    }
+
+    #[test]
+    fn unknown_routes_are_refused_before_any_work() {
+        let actor = CurrentUser {
+            username: "root".to_string(),
+            is_admin: true,
+        };
+        let status = |method: &str, path: &[&str]| {
+            let request = Request {
+                method: method.to_string(),
+                path: path.iter().map(|s| s.to_string()).collect(),
+                query: BTreeMap::new(),
+                authorization: None,
+                body: String::new(),
+            };
+            match route(&actor, &request) {
+                Ok(response) | Err(response) => response.status,
+            }
+        };
+        assert_eq!(status("GET", &[]), 404);
+        assert_eq!(status("GET", &["groups"]), 404);
+        assert_eq!(status("GET", &["users", "alice", "mail"]), 404);
+        assert_eq!(status("PUT", &["users"]), 405);
+        assert_eq!(status("POST", &["users", "alice"]), 405);
+        assert_eq!(status("DELETE", &["audit"]), 405);
+        // Bodies are checked before any account is looked at
+        assert_eq!(status("POST", &["users"]), 400);
+        assert_eq!(status("PATCH", &["users", "alice"]), 400);
+    }
+}
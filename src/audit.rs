@@ -0,0 +1,63 @@
+use crate::clock;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+pub const AUDIT_FILE_PATH: &str = "audit.log";
+
+/// One line of the audit trail: who did what to which account
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub time: u64,
+    pub actor: String,
+    pub action: String,
+    pub target: String,
+    pub detail: String,
+}
+
+/// Tabs and newlines would break the one-entry-per-line format
+fn clean(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+/// Appends an entry to the audit trail. Failing to write the trail
+/// shouldn't undo the action that was audited, so callers usually ignore
+/// the error.
+pub fn record(actor: &str, action: &str, target: &str, detail: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(AUDIT_FILE_PATH)?;
+    writeln!(
+        file,
+        "{}\t{}\t{}\t{}\t{}",
+        clock::unix_now(),
+        clean(actor),
+        clean(action),
+        clean(target),
+        clean(detail)
+    )
+}
+
+/// Reads the whole audit trail, oldest entry first. Malformed lines are
+/// skipped.
+pub fn load_entries() -> io::Result<Vec<AuditEntry>> {
+    let contents = match fs::read_to_string(AUDIT_FILE_PATH) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    Ok(contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(5, '\t');
+            Some(AuditEntry {
+                time: fields.next()?.parse().ok()?,
+                actor: fields.next()?.to_string(),
+                action: fields.next()?.to_string(),
+                target: fields.next()?.to_string(),
+                detail: fields.next().unwrap_or("").to_string(),
+            })
+        })
+        .collect())
+}
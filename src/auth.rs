@@ -1,3 +1,5 @@
+use crate::console;
+use crate::tty;
 use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::reader::Reader;
 use quick_xml::writer::Writer;
@@ -14,6 +16,8 @@ pub struct User {
     pub username: String,
     pub password_hash: String,
     pub is_admin: bool,
+    /// Disabled accounts are kept but can't log in
+    pub disabled: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -29,8 +33,27 @@ pub fn hash_password(password: &str) -> String {
     hex::encode(result)
 }
 
+/// Checks a username and password against the user list.
+pub fn verify_login(
+    users: &[User],
+    username: &str,
+    password: &str,
+) -> Option<CurrentUser> {
+    users
+        .iter()
+        .find(|u| u.username == username)
+        .filter(|u| !u.disabled)
+        .filter(|u| u.password_hash == hash_password(password))
+        .map(|u| CurrentUser {
+            username: u.username.clone(),
+            is_admin: u.is_admin,
+        })
+}
+
 pub fn prompt_password_hidden(prompt_text: &str) -> io::Result<String> {
-    rpassword::prompt_password(prompt_text)
+    print!("{}", prompt_text);
+    console::stdout().flush()?;
+    tty::read_password()
 }
 
 pub fn get_confirmed_password(
@@ -70,8 +93,10 @@ pub fn load_users() -> Result<Vec<User>, Box<dyn std::error::Error>> {
     let mut current_username_tag: Option<String> = None;
     let mut current_password_hash: Option<String> = None;
     let mut current_is_admin: Option<bool> = None;
+    let mut current_disabled = false;
     let mut in_password_element = false;
     let mut in_isadmin_element = false;
+    let mut in_disabled_element = false;
 
     loop {
         match xml_reader.read_event_into(&mut buf)? {
@@ -90,26 +115,32 @@ pub fn load_users() -> Result<Vec<User>, Box<dyn std::error::Error>> {
                             in_isadmin_element = true;
                         }
                     }
+                    "disabled" => {
+                        if current_username_tag.is_some() {
+                            in_disabled_element = true;
+                        }
+                    }
                     uname_tag => {
                         current_username_tag = Some(uname_tag.to_string());
                         current_password_hash = None;
                         current_is_admin = None;
+                        current_disabled = false;
                     }
                 }
             }
-            Event::Text(e) => {
-                if current_username_tag.is_some() {
-                    // Corrected: unescape to Cow<[u8]>, then convert to String
-                    let unescaped_bytes = e.unescape()?;
-                    let text_content =
-                        unescaped_bytes.to_string();
-
-                    if in_password_element {
-                        current_password_hash = Some(text_content);
-                    } else if in_isadmin_element {
-                        current_is_admin =
-                            Some(text_content.eq_ignore_ascii_case("yes"));
-                    }
+            Event::Text(e) if current_username_tag.is_some() => {
+                // Corrected: unescape to Cow<[u8]>, then convert to String
+                let unescaped_bytes = e.unescape()?;
+                let text_content =
+                    unescaped_bytes.to_string();
+
+                if in_password_element {
+                    current_password_hash = Some(text_content);
+                } else if in_isadmin_element {
+                    current_is_admin =
+                        Some(text_content.eq_ignore_ascii_case("yes"));
+                } else if in_disabled_element {
+                    current_disabled = text_content.eq_ignore_ascii_case("yes");
                 }
             }
             Event::End(e) => {
@@ -118,6 +149,7 @@ pub fn load_users() -> Result<Vec<User>, Box<dyn std::error::Error>> {
                 match tag_name.as_str() {
                     "password" => in_password_element = false,
                     "isadmin" => in_isadmin_element = false,
+                    "disabled" => in_disabled_element = false,
                     "users" => {}
                     ended_username_tag => {
                         if Some(ended_username_tag.to_string())
@@ -136,6 +168,7 @@ pub fn load_users() -> Result<Vec<User>, Box<dyn std::error::Error>> {
                                     username,
                                     password_hash: hash,
                                     is_admin,
+                                    disabled: current_disabled,
                                 });
                             }
                         }
@@ -150,14 +183,17 @@ pub fn load_users() -> Result<Vec<User>, Box<dyn std::error::Error>> {
     Ok(users)
 }
 
+/// Writes the user list. It's written beside users.xml and then moved over
+/// it, so a session logging in meanwhile never reads half a file.
 pub fn save_users(
     users: &[User],
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = format!("{}.tmp", USERS_FILE_PATH);
     let file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(USERS_FILE_PATH)?;
+        .open(&tmp)?;
     let mut xml_writer = Writer::new(BufWriter::new(file));
 
     xml_writer
@@ -182,11 +218,20 @@ pub fn save_users(
         )))?;
         xml_writer.write_event(Event::End(BytesEnd::new("isadmin")))?;
 
+        // Only written for disabled accounts, so older files stay valid
+        if user.disabled {
+            xml_writer
+                .write_event(Event::Start(BytesStart::new("disabled")))?;
+            xml_writer.write_event(Event::Text(BytesText::new("yes")))?;
+            xml_writer.write_event(Event::End(BytesEnd::new("disabled")))?;
+        }
+
         // Corrected: Pass &user.username (which is &str) directly
         xml_writer.write_event(Event::End(BytesEnd::new(&user.username)))?;
     }
 
     xml_writer.write_event(Event::End(BytesEnd::new("users")))?;
     xml_writer.into_inner().flush()?;
+    std::fs::rename(&tmp, USERS_FILE_PATH)?;
     Ok(())
 }
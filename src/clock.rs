@@ -0,0 +1,118 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch. All MiniKern timestamps are UTC.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Formats the time of day of a Unix timestamp as `HH:MM:SS`.
+pub fn format_hms(secs: u64) -> String {
+    let day_secs = secs % 86_400;
+    format!(
+        "{:02}:{:02}:{:02}",
+        day_secs / 3600,
+        (day_secs % 3600) / 60,
+        day_secs % 60
+    )
+}
+
+/// Converts days since the Unix epoch to a (year, month, day) date.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days_from_civil inverse
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Converts a (year, month, day) date to days since the Unix epoch.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from(if month > 2 { month - 3 } else { month + 9 });
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parses `YYYY-MM-DD`, optionally followed by `HH:MM` or `HH:MM:SS`
+/// after a space or `T`, into a Unix timestamp.
+pub fn parse_datetime(text: &str) -> Result<u64, String> {
+    let invalid = || format!("'{}' is not a valid date.", text);
+    let (date, time) = match text.split_once([' ', 'T']) {
+        Some((date, time)) => (date, time),
+        None => (text, "00:00"),
+    };
+    let numbers = |part: &str, sep: char| -> Option<Vec<u32>> {
+        part.split(sep).map(|n| n.parse().ok()).collect()
+    };
+    let (year, month, day) = match numbers(date, '-').as_deref() {
+        Some(&[year, month, day]) if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+            (year, month, day)
+        }
+        _ => return Err(invalid()),
+    };
+    let (hours, minutes, seconds) = match numbers(time, ':').as_deref() {
+        Some(&[h, m]) => (h, m, 0),
+        Some(&[h, m, s]) => (h, m, s),
+        _ => return Err(invalid()),
+    };
+    if hours > 23 || minutes > 59 || seconds > 59 {
+        return Err(invalid());
+    }
+    let days = days_from_civil(year as i64, month, day);
+    u64::try_from(days * 86_400 + (hours * 3600 + minutes * 60 + seconds) as i64)
+        .map_err(|_| invalid())
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM:SS`.
+pub fn format_datetime(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!(
+        "{:04}-{:02}-{:02} {}",
+        year,
+        month,
+        day,
+        format_hms(secs)
+    )
+}
+
+/// Parses a duration such as `90`, `45s`, `30m`, `12h` or `7d` into seconds.
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let invalid = || format!("'{}' is not a valid duration.", text);
+    let (number, factor) = match text.char_indices().last() {
+        Some((i, 's')) => (&text[..i], 1),
+        Some((i, 'm')) => (&text[..i], 60),
+        Some((i, 'h')) => (&text[..i], 3600),
+        Some((i, 'd')) => (&text[..i], 86_400),
+        _ => (text, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(factor))
+        .ok_or_else(invalid)
+}
+
+/// Formats seconds as a short duration: `45s`, `30m`, `2h 5m`, `7d`, `1d 12h`.
+pub fn format_duration(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3600, secs % 3600 / 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, 0) => format!("{}h", h),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, 0, _) => format!("{}d", d),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
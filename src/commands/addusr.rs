@@ -1,29 +1,26 @@
-use crate::auth::{
-    self, hash_password, load_users, save_users, User,
-};
-use std::io::{self, Write};
+use crate::accounts;
+use crate::auth::{self, load_users};
+use crate::commands::CommandOutcome;
+use crate::console;
+use crate::session::Session;
+use crate::tty;
+use std::io::Write;
 
 pub fn run(
-    _current_user: &auth::CurrentUser, // Assumed admin by terminal
-) -> Result<(), Box<dyn std::error::Error>> {
+    session: &mut Session, // Assumed admin by the dispatcher
+    _args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
     println!("Create a new user");
     println!("-----------------------------");
 
     let username = loop {
         print!("Username: > ");
-        io::stdout().flush()?;
+        console::stdout().flush()?;
         let mut buffer = String::new();
-        io::stdin().read_line(&mut buffer)?;
+        tty::read_line(&mut buffer)?;
         let name = buffer.trim().to_string();
-        if name.is_empty() {
-            println!("Username cannot be empty.");
-            continue;
-        }
-        if name.contains(|c: char| c.is_whitespace() || "<>&\"'".contains(c))
-        {
-            println!(
-                "Username cannot contain spaces or XML special characters."
-            );
+        if let Err(e) = accounts::validate_username(&name) {
+            println!("{}", e);
             continue;
         }
         // Check if user already exists
@@ -36,13 +33,12 @@ pub fn run(
     };
 
     let password = auth::get_confirmed_password("Password")?;
-    let password_hash = hash_password(&password);
 
     let is_admin = loop {
         print!("Grant admin privileges? (y/n): > ");
-        io::stdout().flush()?;
+        console::stdout().flush()?;
         let mut buffer = String::new();
-        io::stdin().read_line(&mut buffer)?;
+        tty::read_line(&mut buffer)?;
         match buffer.trim().to_lowercase().as_str() {
             "y" | "yes" => break true,
             "n" | "no" => break false,
@@ -50,20 +46,13 @@ pub fn run(
         }
     };
 
-    let new_user = User {
-        username: username.clone(),
-        password_hash,
-        is_admin,
-    };
-
-    let mut users = load_users()?;
-    users.push(new_user);
-    save_users(&users)?;
+    if let Err(e) = accounts::create_user(&session.user, &username, &password, is_admin) {
+        return Ok(CommandOutcome::failure(e.exit_status(), e.to_string()));
+    }
 
-    println!(
+    Ok(CommandOutcome::success(format!(
         "User '{}' created{}.",
         username,
         if is_admin { " with admin privileges" } else { "" }
-    );
-    Ok(())
+    )))
 }
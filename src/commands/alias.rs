@@ -0,0 +1,115 @@
+use crate::commands::{CommandOutcome, EXIT_PERMISSION, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::profile::{self, SYSTEM_PROFILE_PATH};
+use crate::session::{Alias, Session, StartupScope};
+use crate::shell;
+
+/// `alias` lists aliases, `alias NAME` shows one and `alias NAME=VALUE`
+/// defines one and saves it to the user's startup file. Admins can pass
+/// `--system` to define an alias for everyone in the system profile.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let system = args.first().map(|a| a == "--system").unwrap_or(false);
+    let args = if system { &args[1..] } else { args };
+
+    if system && !session.user.is_admin {
+        return Ok(CommandOutcome::failure(
+            EXIT_PERMISSION,
+            "You must be an admin to define system aliases.",
+        ));
+    }
+
+    if args.is_empty() || args.iter().all(|a| a.starts_with("--")) {
+        return list(session, args);
+    }
+
+    for arg in args {
+        let Some((name, value)) = arg.split_once('=') else {
+            match session.aliases.get(arg.as_str()) {
+                Some(alias) => print_alias(arg, alias),
+                None => println!("alias: '{}' not found.", arg),
+            }
+            continue;
+        };
+
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || "'\"$;=".contains(c)) {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("'{}' is not a valid alias name.", name),
+            ));
+        }
+
+        // Definitions coming from the startup files are already saved
+        let from_startup = session.startup;
+        let system = system || from_startup == Some(StartupScope::System);
+        if from_startup.is_none() {
+            let path = if system {
+                SYSTEM_PROFILE_PATH.to_string()
+            } else {
+                profile::user_rc_path(&session.user.username)
+            };
+            let definition = format!("alias {}={}", name, shell::quote(value));
+            profile::replace_definition(&session.user, &path, "alias", name, Some(definition))?;
+        }
+
+        session.aliases.insert(
+            name.to_string(),
+            Alias {
+                value: value.to_string(),
+                system,
+            },
+        );
+    }
+    Ok(CommandOutcome::ok())
+}
+
+fn list(
+    session: &Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let options = match OutputOptions::parse(args) {
+        Ok((options, rest)) if rest.is_empty() => options,
+        Ok(_) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("Usage: alias {}", OUTPUT_USAGE),
+            ))
+        }
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+
+    if options.is_human() {
+        if session.aliases.is_empty() {
+            println!("No aliases defined.");
+        }
+        for (name, alias) in &session.aliases {
+            print_alias(name, alias);
+        }
+        return Ok(CommandOutcome::ok());
+    }
+
+    let mut table = Table::new(&["name", "value", "system"]);
+    for (name, alias) in &session.aliases {
+        table.push(vec![
+            name.as_str().into(),
+            alias.value.as_str().into(),
+            alias.system.into(),
+        ]);
+    }
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(CommandOutcome::ok())
+}
+
+fn print_alias(name: &str, alias: &Alias) {
+    println!(
+        "alias {}={}{}",
+        name,
+        shell::quote(&alias.value),
+        if alias.system { "  (system)" } else { "" }
+    );
+}
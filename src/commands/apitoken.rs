@@ -0,0 +1,92 @@
+use crate::api;
+use crate::audit;
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::output::{self, Format, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+
+const USAGE: &str = "Usage: apitoken [list] | apitoken create [NAME] | apitoken revoke ID";
+
+/// Lists, creates and revokes the tokens the HTTP admin API accepts. A
+/// token acts as the admin who created it.
+pub fn run(
+    session: &mut Session, // Assumed admin by the dispatcher
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    match args.first().map(|a| a.as_str()) {
+        None | Some("list") => list(args.get(1..).unwrap_or_default()),
+        Some(flag) if flag.starts_with("--") => list(args),
+        Some("create") => {
+            let name = args[1..].join(" ");
+            let (token, secret) = api::create_token(&session.user.username, &name)?;
+            let _ = audit::record(
+                &session.user.username,
+                "token-create",
+                &token.owner,
+                &token.id,
+            );
+            println!(
+                "Created token {} for {}. Keep it safe; it won't be shown again:",
+                token.id, token.owner
+            );
+            println!("{}", secret);
+            Ok(CommandOutcome::ok())
+        }
+        Some("revoke") => {
+            let [_, id] = args else {
+                return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+            };
+            let owner = api::load_tokens()?
+                .into_iter()
+                .find(|token| &token.id == id)
+                .map(|token| token.owner);
+            match owner {
+                Some(owner) if api::revoke_token(id)? => {
+                    let _ = audit::record(&session.user.username, "token-revoke", &owner, id);
+                    println!("Token {} revoked.", id);
+                    Ok(CommandOutcome::ok())
+                }
+                _ => Ok(CommandOutcome::failure(
+                    EXIT_FAILURE,
+                    format!("{}: no such token", id),
+                )),
+            }
+        }
+        Some(_) => Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+    }
+}
+
+fn list(args: &[String]) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let mut options = match OutputOptions::parse(args) {
+        Ok((options, rest)) if rest.is_empty() => options,
+        Ok(_) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("Usage: apitoken list {}", OUTPUT_USAGE),
+            ))
+        }
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let tokens = api::load_tokens()?;
+    if tokens.is_empty() && options.is_human() {
+        println!("No API tokens.");
+        return Ok(CommandOutcome::ok());
+    }
+    if options.format == Format::Human {
+        options.format = Format::Columns;
+    }
+    let mut table = Table::new(&["id", "owner", "created", "name"]);
+    for token in &tokens {
+        table.push(vec![
+            token.id.as_str().into(),
+            token.owner.as_str().into(),
+            clock::format_datetime(token.created).into(),
+            token.name.as_str().into(),
+        ]);
+    }
+    match output::render(&table, &options) {
+        Ok(text) => print!("{}", text),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(CommandOutcome::ok())
+}
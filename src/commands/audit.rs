@@ -0,0 +1,80 @@
+use crate::audit::{self, AuditEntry};
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, Format, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+
+const USAGE: &str = "Usage: audit [--user NAME] [--action ACTION] [--limit N]";
+
+/// Shows the account audit trail, newest entries last
+pub fn run(
+    _session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (mut options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+
+    let mut user: Option<String> = None;
+    let mut action: Option<String> = None;
+    let mut limit: Option<usize> = None;
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match (arg.as_str(), iter.next()) {
+            ("--user", Some(value)) => user = Some(value.clone()),
+            ("--action", Some(value)) => action = Some(value.clone()),
+            ("--limit", Some(value)) => match value.parse() {
+                Ok(n) => limit = Some(n),
+                Err(_) => return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+            },
+            _ => {
+                return Ok(CommandOutcome::failure(
+                    EXIT_USAGE,
+                    format!("{} {}", USAGE, OUTPUT_USAGE),
+                ))
+            }
+        }
+    }
+
+    let mut entries: Vec<AuditEntry> = audit::load_entries()?
+        .into_iter()
+        .filter(|e| {
+            user.as_ref()
+                .map(|u| &e.actor == u || &e.target == u)
+                .unwrap_or(true)
+        })
+        .filter(|e| action.as_ref().map(|a| &e.action == a).unwrap_or(true))
+        .collect();
+    if let Some(limit) = limit {
+        let skip = entries.len().saturating_sub(limit);
+        entries.drain(..skip);
+    }
+
+    if entries.is_empty() && options.is_human() {
+        println!("No audit entries.");
+        return Ok(CommandOutcome::ok());
+    }
+    if options.format == Format::Human {
+        options.format = Format::Columns;
+    }
+    match output::render(&audit_table(&entries), &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(CommandOutcome::ok())
+}
+
+pub fn audit_table(entries: &[AuditEntry]) -> Table {
+    let mut table = Table::new(&["time", "actor", "action", "target", "detail"]);
+    for entry in entries {
+        table.push(vec![
+            clock::format_datetime(entry.time).into(),
+            entry.actor.as_str().into(),
+            entry.action.as_str().into(),
+            entry.target.as_str().into(),
+            entry.detail.as_str().into(),
+        ]);
+    }
+    table
+}
@@ -0,0 +1,36 @@
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::process::{self, Signal, State};
+use crate::session::Session;
+
+/// Continues a stopped background job
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let spec = match args {
+        [] => None,
+        [spec] => Some(spec.as_str()),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: bg [%JOB]")),
+    };
+    let Some(job) = process::find_job(process::parent(session.pid), spec) else {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{}: no such job", spec.unwrap_or("current")),
+        ));
+    };
+    let number = job.job.unwrap_or_default();
+    let problem = match job.state {
+        State::Stopped => None,
+        State::Running => Some("already in background"),
+        State::Done(_) => Some("has finished"),
+    };
+    if let Some(problem) = problem {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("job {} {}", number, problem),
+        ));
+    }
+    process::signal(&session.user, job.pid, Signal::Cont)?;
+    println!("[{}] {} &", number, job.command);
+    Ok(CommandOutcome::ok())
+}
@@ -0,0 +1,33 @@
+use crate::commands::files::Errors;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::console;
+use crate::session::Session;
+use crate::vfs::{self, path};
+use std::io::Write;
+
+/// Prints the contents of files, or of its piped input when given none
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        let Some(input) = session.stdin.take() else {
+            return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: cat FILE..."));
+        };
+        console::stdout().write_all(&input)?;
+        console::stdout().flush()?;
+        return Ok(CommandOutcome::ok());
+    }
+
+    let mut errors = Errors::default();
+    let mut stdout = console::stdout();
+    for file in args {
+        let contents = vfs::lock().read_file(&session.user, &path::resolve(&session.cwd, file));
+        match contents {
+            Ok(bytes) => stdout.write_all(&bytes)?,
+            Err(e) => errors.report("cat", file, e),
+        }
+    }
+    stdout.flush()?;
+    Ok(errors.outcome())
+}
@@ -0,0 +1,55 @@
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path, perm};
+
+/// Changes the session's working directory. Without an argument it goes to
+/// `$HOME` (or `/`), and `cd -` goes back to the previous directory.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let target = match args {
+        [] => session.get_var("HOME").unwrap_or("/").to_string(),
+        [dir] if dir == "-" => match session.get_var("OLDPWD") {
+            Some(dir) => dir.to_string(),
+            None => return Ok(CommandOutcome::failure(EXIT_FAILURE, "OLDPWD is not set.")),
+        },
+        [dir] => dir.clone(),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: cd [DIR | -]")),
+    };
+
+    let full = path::resolve(&session.cwd, &target);
+    let fs = vfs::lock();
+    match fs.stat(&session.user, &full) {
+        Ok(meta) if meta.is_dir() => {
+            if let Err(e) = fs.access(&session.user, &full, perm::EXEC) {
+                return Ok(CommandOutcome::failure(
+                    EXIT_FAILURE,
+                    format!("{}: {}", target, e),
+                ));
+            }
+        }
+        Ok(_) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("{}: Not a directory", target),
+            ))
+        }
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("{}: {}", target, e),
+            ))
+        }
+    }
+
+    drop(fs);
+
+    let old = std::mem::replace(&mut session.cwd, full);
+    session.env.insert("OLDPWD".to_string(), old);
+    session.env.insert("PWD".to_string(), session.cwd.clone());
+    if args.first().map(|a| a == "-").unwrap_or(false) {
+        return Ok(CommandOutcome::success(session.cwd.clone()));
+    }
+    Ok(CommandOutcome::ok())
+}
@@ -0,0 +1,17 @@
+use crate::commands::chown::change_owner;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+
+/// Changes the group of files. Owners may pick any group they're in;
+/// admins any group.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let recursive = args.first().map(|a| a == "-R").unwrap_or(false);
+    let args = if recursive { &args[1..] } else { args };
+    let Some((group, targets)) = args.split_first().filter(|(_, t)| !t.is_empty()) else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: chgrp [-R] GROUP PATH..."));
+    };
+    change_owner(session, "chgrp", None, Some(group), targets, recursive)
+}
@@ -0,0 +1,38 @@
+use crate::commands::files::{for_each_path, Errors};
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path, perm};
+
+const USAGE: &str = "Usage: chmod [-R] MODE PATH...  (MODE is octal like 750, or u+x,go-w)";
+
+/// Changes permission bits. Only a file's owner or an admin may.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let recursive = args.first().map(|a| a == "-R").unwrap_or(false);
+    let args = if recursive { &args[1..] } else { args };
+    let Some((mode, targets)) = args.split_first().filter(|(_, t)| !t.is_empty()) else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    // Check the mode once up front rather than failing on every file
+    if let Err(e) = perm::parse_mode(mode, 0) {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, e));
+    }
+
+    let who = &session.user;
+    let mut errors = Errors::default();
+    let mut fs = vfs::lock();
+    for target in targets {
+        let full = path::resolve(&session.cwd, target);
+        let result = for_each_path(&mut fs, who, &full, recursive, &mut |fs, file| {
+            let current = fs.stat(who, file)?.mode;
+            let mode = perm::parse_mode(mode, current).unwrap_or(current);
+            fs.chmod(who, file, mode)
+        });
+        if let Err(e) = result {
+            errors.report("chmod", target, e);
+        }
+    }
+    Ok(errors.outcome())
+}
@@ -0,0 +1,72 @@
+use crate::auth::load_users;
+use crate::commands::files::{for_each_path, group_exists, Errors};
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path};
+
+const USAGE: &str = "Usage: chown [-R] OWNER[:GROUP] PATH...";
+
+/// Gives files to another user (admin only), optionally changing the group
+/// too. `:GROUP` on its own changes just the group, like `chgrp`.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let recursive = args.first().map(|a| a == "-R").unwrap_or(false);
+    let args = if recursive { &args[1..] } else { args };
+    let Some((spec, targets)) = args.split_first().filter(|(_, t)| !t.is_empty()) else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let (owner, group) = match spec.split_once(':') {
+        Some((owner, group)) => (owner, Some(group).filter(|g| !g.is_empty())),
+        None => (spec.as_str(), None),
+    };
+    let owner = Some(owner).filter(|o| !o.is_empty());
+    if owner.is_none() && group.is_none() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    }
+    change_owner(session, "chown", owner, group, targets, recursive)
+}
+
+/// Shared by `chown` and `chgrp`: checks that the new owner and group exist,
+/// then applies them to each target.
+pub fn change_owner(
+    session: &Session,
+    command: &str,
+    owner: Option<&str>,
+    group: Option<&str>,
+    targets: &[String],
+    recursive: bool,
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let users = load_users()?;
+    if let Some(owner) = owner {
+        if !users.iter().any(|u| u.username == owner) {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("User '{}' not found.", owner),
+            ));
+        }
+    }
+
+    let who = &session.user;
+    let mut errors = Errors::default();
+    let mut fs = vfs::lock();
+    if let Some(group) = group {
+        if !group_exists(&fs, &users, group) {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("Group '{}' not found.", group),
+            ));
+        }
+    }
+    for target in targets {
+        let full = path::resolve(&session.cwd, target);
+        let result = for_each_path(&mut fs, who, &full, recursive, &mut |fs, file| {
+            fs.chown(who, file, owner, group)
+        });
+        if let Err(e) = result {
+            errors.report(command, target, e);
+        }
+    }
+    Ok(errors.outcome())
+}
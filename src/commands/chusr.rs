@@ -1,220 +1,236 @@
-use crate::auth::{
-    self, hash_password, load_users, save_users, CurrentUser,
-};
-use crate::commands;
+use crate::accounts::{self, AccountError};
+use crate::auth::{self, load_users};
+use crate::commands::{self, CommandOutcome, SessionEffect, EXIT_FAILURE};
+use crate::console;
+use crate::session::Session;
+use crate::tty;
 use std::io::{self, Write};
 
-/// Result of the chusr command that indicates what was changed
-#[derive(Debug)]
-pub enum ChusrResult {
-    /// No changes were made
-    NoChange,
-    /// Password was changed for the specified user
-    PasswordChanged(String),
-    /// Admin status was changed for the specified user
-    AdminChanged(String),
-    /// Both password and admin status were changed for the specified user
-    BothChanged(String),
+/// What was changed for the selected user
+#[derive(Debug, Default)]
+struct Changes {
+    password: bool,
+    admin: bool,
+    disabled: bool,
+    enabled: bool,
+    renamed: bool,
 }
 
+impl Changes {
+    fn any(&self) -> bool {
+        self.password || self.admin || self.disabled || self.enabled || self.renamed
+    }
+}
+
+/// Builds the outcome for a change to `username`. Changing the logged-in
+/// account means the session has to log in again.
+fn changed(session: &Session, username: &str, changes: &Changes) -> CommandOutcome {
+    if username != session.user.username || !changes.any() {
+        return CommandOutcome::ok();
+    }
+    let message = match (changes.password, changes.admin, changes.disabled, changes.renamed) {
+        (_, _, true, _) => "Your account has been disabled. Logging out.",
+        (true, false, false, false) => "Your password has changed. Please log in again.",
+        (false, true, false, false) => "Your admin status has changed. Please log in again.",
+        (false, false, false, true) => "Your username has changed. Please log in again.",
+        _ => "Your account has been modified. Please log in again.",
+    };
+    CommandOutcome::success(message).with_effect(SessionEffect::IdentityChanged)
+}
+
+fn failed(e: AccountError) -> CommandOutcome {
+    CommandOutcome::failure(e.exit_status(), e.to_string())
+}
+
+fn confirm(prompt: &str) -> io::Result<bool> {
+    print!("{} (y/n): > ", prompt);
+    console::stdout().flush()?;
+    let mut input = String::new();
+    tty::read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Interactive front end for the account rules in `accounts`
 pub fn run(
-    current_user: &CurrentUser,
-) -> Result<ChusrResult, Box<dyn std::error::Error>> {
+    session: &mut Session,
+    _args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let current_user = session.user.clone();
     println!("Modify User");
     println!("-----------------------------");
-    
+
     // Show the current user list
-    commands::listusr::run()?;
+    commands::listusr::print_tree()?;
 
     print!("Enter Username: > ");
-    io::stdout().flush()?;
+    console::stdout().flush()?;
     let mut username_to_change = String::new();
-    io::stdin().read_line(&mut username_to_change)?;
+    tty::read_line(&mut username_to_change)?;
     let username_to_change = username_to_change.trim();
 
     if username_to_change.is_empty() {
-        println!("Username cannot be empty.");
-        return Ok(ChusrResult::NoChange);
+        return Ok(CommandOutcome::failure(EXIT_FAILURE, "Username cannot be empty."));
     }
 
-    let mut users = load_users()?;
-    let user_index = users
-        .iter()
-        .position(|u| u.username == username_to_change);
-
-    match user_index {
-        Some(index) => {
-            let is_self = username_to_change == current_user.username;
-            let is_root = index == 0;  // First user is root
-            let is_current_root = current_user.username == users[0].username;
-            let mut password_changed = false;
-            let mut admin_changed = false;
-            
-            // Permission checks for non-admin users
-            if !current_user.is_admin {
-                if !is_self {
-                    println!("Error: Non-admin users can only change their own password.");
-                    return Ok(ChusrResult::NoChange);
-                }
-                
-                // Non-admin users can only change their password
-                println!("Change your password:");
-                let new_password = auth::get_confirmed_password("Enter New Password")?;
-                users[index].password_hash = hash_password(&new_password);
-                println!("Your password has been updated successfully.");
-                
-                save_users(&users)?;
-                return Ok(ChusrResult::PasswordChanged(username_to_change.to_string()));
+    let users = load_users()?;
+    let Some(user) = users.iter().find(|u| u.username == username_to_change) else {
+        return Ok(failed(AccountError::NotFound(username_to_change.to_string())));
+    };
+    let is_self = username_to_change == current_user.username;
+    let is_root = accounts::is_root(&users, username_to_change);
+    let mut changes = Changes::default();
+
+    // Non-admin users can only change their own password
+    if !current_user.is_admin {
+        if !is_self {
+            return Ok(failed(AccountError::PermissionDenied(
+                "Non-admin users can only change their own password.".into(),
+            )));
+        }
+
+        println!("Change your password:");
+        let new_password = auth::get_confirmed_password("Enter New Password")?;
+        if let Err(e) = accounts::set_password(&current_user, username_to_change, &new_password) {
+            return Ok(failed(e));
+        }
+        println!("Your password has been updated successfully.");
+        changes.password = true;
+        return Ok(changed(session, username_to_change, &changes));
+    }
+
+    // Show current admin status
+    println!(
+        "User '{}' currently has {} privileges{}.",
+        username_to_change,
+        if user.is_admin { "ADMIN" } else { "standard" },
+        if user.disabled { " and is DISABLED" } else { "" }
+    );
+
+    // Root user special rules
+    if is_root {
+        println!("Note: This is the root user. Admin status cannot be changed.");
+
+        if !accounts::is_root(&users, &current_user.username) {
+            return Ok(failed(AccountError::PermissionDenied(
+                "Only the root user can change root's password.".into(),
+            )));
+        }
+
+        // Root can change its own password
+        if confirm("Change root password?")? {
+            let new_password = auth::get_confirmed_password("Enter New Password")?;
+            if let Err(e) = accounts::set_password(&current_user, username_to_change, &new_password) {
+                return Ok(failed(e));
             }
-            
-            // Admin users with additional permission checks
-            
-            // Show current admin status
-            println!(
-                "User '{}' currently has {} privileges.",
-                username_to_change,
-                if users[index].is_admin { "ADMIN" } else { "standard" }
-            );
-            
-            // Root user special rules
-            if is_root {
-                println!("Note: This is the root user. Admin status cannot be changed.");
-                
-                if !is_current_root {
-                    println!("Error: Only the root user can change root's password.");
-                    return Ok(ChusrResult::NoChange);
-                }
-                
-                // Root can change its own password
-                print!("Change root password? (y/n): > ");
-                io::stdout().flush()?;
-                let mut password_change_input = String::new();
-                io::stdin().read_line(&mut password_change_input)?;
-                
-                if password_change_input.trim().eq_ignore_ascii_case("y") {
-                    let new_password = auth::get_confirmed_password("Enter New Password")?;
-                    users[index].password_hash = hash_password(&new_password);
-                    password_changed = true;
-                    println!("Root password updated successfully.");
-                }
-                
-                // Save changes if password was changed
-                if password_changed {
-                    save_users(&users)?;
-                    return Ok(ChusrResult::PasswordChanged(username_to_change.to_string()));
+            changes.password = true;
+            println!("Root password updated successfully.");
+        }
+        return Ok(changed(session, username_to_change, &changes));
+    }
+
+    // Regular admin changing a non-root user. Every question is answered
+    // before anything changes, so Ctrl-C part way through leaves the
+    // account as it was.
+    let new_name = if confirm("Rename this account?")? {
+        print!("New username: > ");
+        console::stdout().flush()?;
+        let mut new_name = String::new();
+        tty::read_line(&mut new_name)?;
+        Some(new_name.trim().to_string())
+    } else {
+        None
+    };
+
+    let toggle_admin = if confirm("Change admin privileges?")? {
+        let confirmed = if user.is_admin && is_self {
+            println!("Warning: You are removing your own admin privileges.");
+            confirm("Are you sure?")?
+        } else {
+            true
+        };
+        if !confirmed {
+            println!("Admin privilege change cancelled.");
+        }
+        confirmed
+    } else {
+        false
+    };
+
+    let status_prompt = if user.disabled {
+        "Re-enable this account?"
+    } else {
+        "Disable this account?"
+    };
+    let toggle_disabled = confirm(status_prompt)?;
+
+    let new_password = if confirm("Change password?")? {
+        Some(auth::get_confirmed_password("Enter New Password")?)
+    } else {
+        None
+    };
+
+    // The remaining changes apply under the new name if the account is
+    // renamed. Rule violations don't stop the remaining changes.
+    let mut target = username_to_change.to_string();
+    if let Some(new_name) = new_name {
+        match accounts::rename_user(&current_user, username_to_change, &new_name) {
+            Ok(()) => {
+                changes.renamed = true;
+                println!(
+                    "User '{}' has been renamed to '{}'.",
+                    username_to_change, new_name
+                );
+                target = new_name;
+            }
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+
+    if toggle_admin {
+        let new_admin_status = !user.is_admin;
+        match accounts::set_admin(&current_user, &target, new_admin_status) {
+            Ok(()) => {
+                changes.admin = true;
+                if new_admin_status {
+                    println!("User '{}' has been granted admin privileges.", target);
                 } else {
-                    return Ok(ChusrResult::NoChange);
+                    println!("User '{}' admin privileges have been removed.", target);
                 }
-            } else {
-                // Regular admin changing a non-root user
-                
-                // Ask if admin status should be changed
-                print!("Change admin privileges? (y/n): > ");
-                io::stdout().flush()?;
-                let mut admin_change_input = String::new();
-                io::stdin().read_line(&mut admin_change_input)?;
-                
-                if admin_change_input.trim().eq_ignore_ascii_case("y") {
-                    let new_admin_status = !users[index].is_admin;
-                    
-                    // Safety checks
-                    if !new_admin_status { // Demoting from admin
-                        // Check if trying to demote self
-                        if is_self {
-                            println!("Warning: You are removing your own admin privileges.");
-                            print!("Are you sure? (y/n): > ");
-                            io::stdout().flush()?;
-                            let mut confirm = String::new();
-                            io::stdin().read_line(&mut confirm)?;
-                            if !confirm.trim().eq_ignore_ascii_case("y") {
-                                println!("Admin privilege change cancelled.");
-                                // Continue to password section
-                            } else {
-                                // Count how many admins would be left
-                                let admin_count = users.iter()
-                                    .filter(|u| u.is_admin && u.username != username_to_change)
-                                    .count();
-                                    
-                                if admin_count == 0 {
-                                    println!("Error: Cannot remove the last admin user.");
-                                    println!("Create another admin user first.");
-                                    // Continue to password section
-                                } else {
-                                    users[index].is_admin = new_admin_status;
-                                    admin_changed = true;
-                                    println!(
-                                        "User '{}' admin privileges have been removed.",
-                                        username_to_change
-                                    );
-                                }
-                            }
-                        } else {
-                            // Count how many admins would be left
-                            let admin_count = users.iter()
-                                .filter(|u| u.is_admin && u.username != username_to_change)
-                                .count();
-                                
-                            if admin_count == 0 {
-                                println!("Error: Cannot remove the last admin user.");
-                                println!("Create another admin user first.");
-                                // Continue to password section
-                            } else {
-                                users[index].is_admin = new_admin_status;
-                                admin_changed = true;
-                                println!(
-                                    "User '{}' admin privileges have been removed.",
-                                    username_to_change
-                                );
-                            }
-                        }
-                    } else { // Promoting to admin
-                        users[index].is_admin = new_admin_status;
-                        admin_changed = true;
-                        println!(
-                            "User '{}' has been granted admin privileges.",
-                            username_to_change
-                        );
-                    }
-                }
-                
-                // Ask if password should be changed
-                print!("Change password? (y/n): > ");
-                io::stdout().flush()?;
-                let mut password_change_input = String::new();
-                io::stdin().read_line(&mut password_change_input)?;
-                
-                if password_change_input.trim().eq_ignore_ascii_case("y") {
-                    let new_password =
-                        auth::get_confirmed_password("Enter New Password")?;
-                    users[index].password_hash = hash_password(&new_password);
-                    password_changed = true;
-                    println!(
-                        "Password for '{}' updated successfully.",
-                        username_to_change
-                    );
-                }
-                
-                // Save changes if any were made
-                if password_changed || admin_changed {
-                    save_users(&users)?;
-                    
-                    // Return appropriate result based on what changed
-                    if password_changed && admin_changed {
-                        return Ok(ChusrResult::BothChanged(username_to_change.to_string()));
-                    } else if password_changed {
-                        return Ok(ChusrResult::PasswordChanged(username_to_change.to_string()));
-                    } else {
-                        return Ok(ChusrResult::AdminChanged(username_to_change.to_string()));
-                    }
+            }
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+
+    if toggle_disabled {
+        match accounts::set_disabled(&current_user, &target, !user.disabled) {
+            Ok(()) => {
+                if user.disabled {
+                    changes.enabled = true;
+                    println!("User '{}' has been re-enabled.", target);
                 } else {
-                    println!("No changes were made to user '{}'.", username_to_change);
-                    return Ok(ChusrResult::NoChange);
+                    changes.disabled = true;
+                    println!("User '{}' has been disabled.", target);
                 }
             }
+            Err(e) => println!("Error: {}", e),
         }
-        None => {
-            println!("User '{}' not found.", username_to_change);
-            return Ok(ChusrResult::NoChange);
+    }
+
+    if let Some(new_password) = new_password {
+        match accounts::set_password(&current_user, &target, &new_password) {
+            Ok(()) => {
+                changes.password = true;
+                println!("Password for '{}' updated successfully.", target);
+            }
+            Err(e) => println!("Error: {}", e),
         }
     }
+
+    if !changes.any() {
+        return Ok(CommandOutcome::success(format!(
+            "No changes were made to user '{}'.",
+            target
+        )));
+    }
+    Ok(changed(session, username_to_change, &changes))
 }
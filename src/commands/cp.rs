@@ -0,0 +1,63 @@
+use crate::commands::files::{copy_tree, parse_flags, Errors};
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path};
+
+const USAGE: &str = "Usage: cp [-r] SOURCE... DEST";
+
+/// Copies files. With several sources, or when DEST is an existing
+/// directory, the copies go inside DEST. Directories need `-r`.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (flags, operands) = match parse_flags(args, "rR") {
+        Ok((flags, operands)) if operands.len() >= 2 => (flags, operands),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+    };
+    let recursive = flags.contains(['r', 'R']);
+    let Some((dest, sources)) = operands.split_last() else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let dest_full = path::resolve(&session.cwd, dest);
+
+    let mut fs = vfs::lock();
+    let into_dir = fs.stat(&session.user, &dest_full).map(|m| m.is_dir()).unwrap_or(false);
+    if sources.len() > 1 && !into_dir {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{}: Not a directory", dest),
+        ));
+    }
+
+    let mut errors = Errors::default();
+    for source in sources {
+        let from = path::resolve(&session.cwd, source);
+        let to = if into_dir {
+            path::join(&dest_full, path::file_name(&from))
+        } else {
+            dest_full.clone()
+        };
+
+        let result = match fs.stat(&session.user, &from) {
+            Ok(meta) if meta.is_dir() && !recursive => {
+                errors.report("cp", source, "Is a directory (use -r to copy it)");
+                continue;
+            }
+            Ok(meta) if meta.is_dir() && (to == from || to.starts_with(&format!("{}/", from))) => {
+                errors.report("cp", source, "Cannot copy a directory into itself");
+                continue;
+            }
+            Ok(_) if to == from => {
+                errors.report("cp", source, "Source and destination are the same file");
+                continue;
+            }
+            Ok(_) => copy_tree(&mut fs, &session.user, &from, &to),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            errors.report("cp", source, e);
+        }
+    }
+    Ok(errors.outcome())
+}
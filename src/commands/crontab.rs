@@ -0,0 +1,178 @@
+use crate::audit;
+use crate::auth::{self, CurrentUser};
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_PERMISSION, EXIT_USAGE};
+use crate::cron;
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::syslog::{self, Facility, Level};
+use crate::terminal;
+use crate::vfs::{self, path, VfsError};
+
+const USAGE: &str = "Usage: crontab [-u USER] -l | -e | -r | FILE";
+
+/// Lists (`-l`), edits (`-e`), removes (`-r`) or replaces with a file the
+/// crontab of the jobs run on a schedule as the user. Admins can work on
+/// another user's crontab with `-u`. The output flags list its jobs one
+/// per record.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let usage = || format!("{}\n       crontab [-u USER] -l {}", USAGE, OUTPUT_USAGE);
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let (owner, action) = match rest.as_slice() {
+        [flag, user, action] if flag == "-u" => (user.clone(), action),
+        [action] => (session.user.username.clone(), action),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, usage())),
+    };
+    if !options.is_human() && action != "-l" {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, usage()));
+    }
+    if owner != session.user.username {
+        if !session.user.is_admin {
+            return Ok(CommandOutcome::failure(
+                EXIT_PERMISSION,
+                "You must be an admin to use another user's crontab.",
+            ));
+        }
+        if !auth::load_users()?.iter().any(|u| u.username == owner) {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("User '{}' not found.", owner),
+            ));
+        }
+    }
+
+    match action.as_str() {
+        "-l" => match cron::load(&owner)? {
+            Some(text) if options.is_human() => {
+                print!("{}", text);
+                Ok(CommandOutcome::ok())
+            }
+            Some(text) => {
+                let mut table = Table::new(&["schedule", "command"]);
+                // Installed crontabs were checked, so this only skips what
+                // an admin broke by hand
+                for entry in cron::parse(&text).unwrap_or_default() {
+                    table.push(vec![entry.when.into(), entry.command.into()]);
+                }
+                match output::render(&table, &options) {
+                    Ok(rendered) => print!("{}", rendered),
+                    Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+                }
+                Ok(CommandOutcome::ok())
+            }
+            None => Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("no crontab for {}", owner),
+            )),
+        },
+        "-r" => {
+            if !cron::remove(&owner)? {
+                return Ok(CommandOutcome::failure(
+                    EXIT_FAILURE,
+                    format!("no crontab for {}", owner),
+                ));
+            }
+            changed(session, &owner, "DELETE");
+            Ok(CommandOutcome::ok())
+        }
+        "-e" => edit(session, &owner),
+        flag if flag.starts_with('-') => Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+        file => {
+            let full = path::resolve(&session.cwd, file);
+            let bytes = match vfs::lock().read_file(&session.user, &full) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    return Ok(CommandOutcome::failure(
+                        EXIT_FAILURE,
+                        format!("{}: {}", file, e),
+                    ))
+                }
+            };
+            install(session, &owner, &String::from_utf8_lossy(&bytes))
+        }
+    }
+}
+
+/// Opens a copy of the crontab in `edit`, installing it afterwards if it
+/// was changed and is valid
+fn edit(session: &mut Session, owner: &str) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let original = cron::load(owner)?.unwrap_or_default();
+    // The copy belongs to the user editing it, so `edit` can save it
+    let copy = path::join("/tmp", &format!("crontab.{}", session.pid));
+    if let Err(e) = create_private(&session.user, &copy, original.as_bytes()) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{}: {}", copy, e),
+        ));
+    }
+
+    let outcome = terminal::run_command(session, "edit", std::slice::from_ref(&copy));
+    let edited = vfs::lock().read_file(&session.user, &copy);
+    let _ = vfs::lock().unlink(&session.user, &copy);
+    if !outcome.succeeded() {
+        return Ok(outcome);
+    }
+    let edited = String::from_utf8_lossy(&edited?).into_owned();
+    if edited == original {
+        println!("No changes made to the crontab.");
+        return Ok(CommandOutcome::ok());
+    }
+    install(session, owner, &edited)
+}
+
+/// Makes `path` a new file holding `contents` that only `who` can read.
+/// Fails if something is there already, as someone else may have put it
+/// there to read the copy, and takes the file away again if it can't be
+/// filled in.
+fn create_private(who: &CurrentUser, path: &str, contents: &[u8]) -> Result<(), VfsError> {
+    let mut fs = vfs::lock();
+    if fs.exists(who, path) {
+        return Err(VfsError::AlreadyExists);
+    }
+    fs.touch(who, path)?;
+    let filled = fs
+        .chmod(who, path, 0o600)
+        .and_then(|()| fs.write_file(who, path, contents));
+    if filled.is_err() {
+        let _ = fs.unlink(who, path);
+    }
+    filled
+}
+
+/// Replaces the crontab, unless it has errors
+fn install(
+    session: &Session,
+    owner: &str,
+    text: &str,
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if let Err(e) = cron::parse(text) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("errors in crontab, not installed: {}", e),
+        ));
+    }
+    cron::install(owner, text)?;
+    changed(session, owner, "REPLACE");
+    Ok(CommandOutcome::success(format!(
+        "Installed new crontab for {}.",
+        owner
+    )))
+}
+
+/// Logs a change to a crontab, auditing it if it was someone else's
+fn changed(session: &Session, owner: &str, action: &str) {
+    let actor = &session.user.username;
+    let _ = syslog::record(
+        Level::Info,
+        Facility::Cron,
+        &format!("({}) {} ({})", actor, action, owner),
+    );
+    if actor != owner {
+        let _ = audit::record(actor, "crontab", owner, &action.to_lowercase());
+    }
+}
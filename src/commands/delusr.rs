@@ -1,92 +1,109 @@
-use crate::auth::{
-    self, hash_password, load_users, save_users, CurrentUser,
-};
-use crate::commands;
-use std::io::{self, Write};
-
-/// Result of the delusr command
-#[derive(Debug)]
-pub enum DeleteResult {
-    /// No user was deleted
-    NoDelete,
-    /// A user other than the current user was deleted
-    OtherUserDeleted(String),
-    /// The current user was deleted
-    CurrentUserDeleted,
-}
+use crate::accounts::{self, FileDisposal};
+use crate::auth::{self, load_users};
+use crate::commands::{self, CommandOutcome, SessionEffect, EXIT_FAILURE};
+use crate::console;
+use crate::profile::{self, HOME_ARCHIVE_DIR_PATH};
+use crate::session::Session;
+use crate::tty;
+use std::io::Write;
 
 pub fn run(
-    current_user: &CurrentUser,
-) -> Result<DeleteResult, Box<dyn std::error::Error>> {
+    session: &mut Session,
+    _args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let current_user = &session.user;
     println!("Delete User");
     println!("-----------------------------");
     
     // Show the current user list
-    commands::listusr::run()?;
+    commands::listusr::print_tree()?;
     
     // Load users
-    let mut users = load_users()?;
+    let users = load_users()?;
     
     if users.len() <= 1 {
-        println!("Cannot delete users when there's only one user in the system.");
-        return Ok(DeleteResult::NoDelete);
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            "Cannot delete users when there's only one user in the system.",
+        ));
     }
     
     // Prompt for username to delete
     print!("Enter username to delete: > ");
-    io::stdout().flush()?;
+    console::stdout().flush()?;
     let mut username_to_delete = String::new();
-    io::stdin().read_line(&mut username_to_delete)?;
+    tty::read_line(&mut username_to_delete)?;
     let username_to_delete = username_to_delete.trim();
     
     if username_to_delete.is_empty() {
-        println!("Username cannot be empty.");
-        return Ok(DeleteResult::NoDelete);
+        return Ok(CommandOutcome::failure(EXIT_FAILURE, "Username cannot be empty."));
     }
-    
-    // Find the user
-    let user_index = users.iter().position(|u| u.username == username_to_delete);
-    
-    match user_index {
-        Some(index) => {
-            // Check if it's the first user (index 0)
-            if index == 0 {
-                println!("Error: Cannot delete the first user (root admin).");
-                return Ok(DeleteResult::NoDelete);
-            }
-            
-            // Verify by asking for the root user's password
-            let root_user = &users[0];
-            print!("Enter password of {} (for verification): > ", root_user.username);
-            io::stdout().flush()?;
-            let password = auth::prompt_password_hidden("")?;
-            
-            // Verify password
-            if hash_password(&password) != root_user.password_hash {
-                println!("Incorrect password. User deletion cancelled.");
-                return Ok(DeleteResult::NoDelete);
-            }
-            
-            // Password is correct, delete the user
-            let deleted_username = users[index].username.clone();
-            println!("Deleting user '{}'...", deleted_username);
-            users.remove(index);
-            save_users(&users)?;
-            
-            println!("User '{}' has been deleted.", deleted_username);
-            
-            // Check if the current user was deleted
-            if deleted_username == current_user.username {
-                println!("You have deleted your own account. You will be logged out.");
-                return Ok(DeleteResult::CurrentUserDeleted);
-            } else {
-                return Ok(DeleteResult::OtherUserDeleted(deleted_username));
-            }
-        }
-        None => {
-            println!("User '{}' not found.", username_to_delete);
-            return Ok(DeleteResult::NoDelete);
+
+    if !users.iter().any(|u| u.username == username_to_delete) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("User '{}' not found.", username_to_delete),
+        ));
+    }
+    if accounts::is_root(&users, username_to_delete) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            "Cannot delete the first user (root admin).",
+        ));
+    }
+
+    // The account's files go to another user, or are deleted with it
+    println!(
+        "What should happen to {}'s files? Enter a username to give them to,",
+        username_to_delete
+    );
+    print!("or 'delete' to delete them [{}]: > ", users[0].username);
+    console::stdout().flush()?;
+    let mut answer = String::new();
+    tty::read_line(&mut answer)?;
+    let files = match answer.trim() {
+        "" => FileDisposal::ReassignTo(users[0].username.clone()),
+        "delete" => FileDisposal::Delete,
+        heir => FileDisposal::ReassignTo(heir.to_string()),
+    };
+    if let FileDisposal::ReassignTo(heir) = &files {
+        if heir == username_to_delete || !users.iter().any(|u| &u.username == heir) {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("Can't give the files to '{}'.", heir),
+            ));
         }
     }
-}
 
+    let home = profile::user_home(username_to_delete);
+    print!(
+        "Archive {} to {} before removing it? (y/n): > ",
+        home, HOME_ARCHIVE_DIR_PATH
+    );
+    console::stdout().flush()?;
+    let mut answer = String::new();
+    tty::read_line(&mut answer)?;
+    let archive_home = answer.trim().eq_ignore_ascii_case("y");
+
+    // Verify by asking for the root user's password
+    print!("Enter password of {} (for verification): > ", users[0].username);
+    console::stdout().flush()?;
+    let password = auth::prompt_password_hidden("")?;
+
+    match accounts::delete_user(current_user, username_to_delete, &password, &files, archive_home) {
+        Ok(Some(archive)) => println!("Home directory archived to {}.", archive),
+        Ok(None) => {}
+        Err(e) => return Ok(CommandOutcome::failure(e.exit_status(), e.to_string())),
+    }
+    println!("User '{}' has been deleted.", username_to_delete);
+
+    // Check if the current user was deleted
+    if username_to_delete == current_user.username {
+        Ok(CommandOutcome::success(
+            "You have deleted your own account. You will be logged out.",
+        )
+        .with_effect(SessionEffect::IdentityChanged))
+    } else {
+        Ok(CommandOutcome::ok())
+    }
+}
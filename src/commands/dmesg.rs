@@ -0,0 +1,96 @@
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::syslog::{self, Facility, Level};
+
+const USAGE: &str = "Usage: dmesg [-l LEVEL] [-f FACILITY] [-c]";
+
+/// Shows the messages logged since boot, with the seconds since boot each
+/// came at. `-l` leaves out those less serious than LEVEL, and `-c` clears
+/// them afterwards. Admin only, as they include failed logins and other
+/// users' cron jobs.
+pub fn run(
+    _session: &mut Session, // Assumed admin by the dispatcher
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let mut level = None;
+    let mut facility = None;
+    let mut clear = false;
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match (arg.as_str(), iter.clone().next()) {
+            ("-c", _) => clear = true,
+            ("-l", Some(value)) => {
+                level = match Level::parse(value) {
+                    Some(parsed) => Some(parsed),
+                    None => return Ok(unknown("level", value)),
+                };
+                iter.next();
+            }
+            ("-f", Some(value)) => {
+                facility = match Facility::parse(value) {
+                    Some(parsed) => Some(parsed),
+                    None => return Ok(unknown("facility", value)),
+                };
+                iter.next();
+            }
+            _ => {
+                return Ok(CommandOutcome::failure(
+                    EXIT_USAGE,
+                    format!("{} {}", USAGE, OUTPUT_USAGE),
+                ))
+            }
+        }
+    }
+
+    let entries: Vec<_> = syslog::since_boot()
+        .into_iter()
+        .filter(|(_, entry)| level.is_none_or(|level| entry.level <= level))
+        .filter(|(_, entry)| facility.is_none_or(|facility| entry.facility == facility))
+        .collect();
+    if options.is_human() {
+        for (uptime, entry) in &entries {
+            println!(
+                "[{:>5}.{:03}] {}: {}",
+                uptime.as_secs(),
+                uptime.subsec_millis(),
+                entry.facility,
+                entry.message
+            );
+        }
+    } else {
+        let mut table = Table::new(&["uptime_ms", "level", "facility", "message"]);
+        for (uptime, entry) in entries {
+            table.push(vec![
+                (uptime.as_millis() as u64).into(),
+                entry.level.name().into(),
+                entry.facility.name().into(),
+                entry.message.into(),
+            ]);
+        }
+        match output::render(&table, &options) {
+            Ok(rendered) => print!("{}", rendered),
+            Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+        }
+    }
+    if clear {
+        syslog::clear_since_boot();
+    }
+    Ok(CommandOutcome::ok())
+}
+
+/// Fails naming the values `-l` or `-f` take
+pub fn unknown(what: &str, value: &str) -> CommandOutcome {
+    let names: Vec<&str> = match what {
+        "level" => Level::ALL.iter().map(|l| l.name()).collect(),
+        _ => Facility::ALL.iter().map(|f| f.name()).collect(),
+    };
+    CommandOutcome::failure(
+        EXIT_USAGE,
+        format!("unknown {} '{}' (one of {})", what, value, names.join(", ")),
+    )
+}
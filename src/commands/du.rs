@@ -0,0 +1,142 @@
+use crate::auth::CurrentUser;
+use crate::commands::files::{parse_flags, Errors};
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::vfs::quota::format_size;
+use crate::vfs::{self, path, Vfs};
+
+const USAGE: &str = "Usage: du [-h] [-s] [-a] [PATH...]";
+
+struct Options {
+    /// `-h`: sizes like `1.5K` instead of bytes
+    human: bool,
+    /// `-s`: only the total of each PATH
+    summary: bool,
+    /// `-a`: files too, not just directories
+    all: bool,
+}
+
+/// What `total` found: the totals to list, with the paths they're for,
+/// and whether anything couldn't be read
+#[derive(Default)]
+struct Totals {
+    listed: Vec<(u64, String)>,
+    errors: Errors,
+}
+
+/// Prints the bytes stored under each directory, subdirectories before
+/// the directories that contain them
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let usage = || format!("{} {}", USAGE, OUTPUT_USAGE);
+    let (output_options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let Ok((flags, mut targets)) = parse_flags(&rest, "hsa") else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, usage()));
+    };
+    if targets.is_empty() {
+        targets.push(".".to_string());
+    }
+    let options = Options {
+        human: flags.contains('h'),
+        summary: flags.contains('s'),
+        all: flags.contains('a'),
+    };
+
+    let mut totals = Totals::default();
+    let mut fs = vfs::lock();
+    for target in &targets {
+        let full = path::resolve(&session.cwd, target);
+        total(
+            &mut fs,
+            &session.user,
+            &full,
+            target,
+            0,
+            &options,
+            &mut totals,
+        );
+    }
+    drop(fs);
+    let Totals { listed, errors } = totals;
+
+    if output_options.is_human() {
+        for (bytes, shown) in listed {
+            let size = if options.human {
+                format_size(bytes)
+            } else {
+                bytes.to_string()
+            };
+            println!("{}\t{}", size, shown);
+        }
+        return Ok(errors.outcome());
+    }
+    let mut table = Table::new(&["bytes", "path"]);
+    for (bytes, shown) in listed {
+        table.push(vec![bytes.into(), shown.into()]);
+    }
+    match output::render(&table, &output_options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("{}\n{}", e, usage()),
+            ))
+        }
+    }
+    Ok(errors.outcome())
+}
+
+/// Adds up the bytes of the files under `target`, noting the totals
+/// `options` asks for along the way. Directories the user may not read
+/// count as empty.
+fn total(
+    fs: &mut Vfs,
+    who: &CurrentUser,
+    target: &str,
+    shown: &str,
+    depth: usize,
+    options: &Options,
+    totals: &mut Totals,
+) -> u64 {
+    let meta = match fs.stat(who, target) {
+        Ok(meta) => meta,
+        Err(e) => {
+            totals.errors.report("du", shown, e);
+            return 0;
+        }
+    };
+    let bytes = if meta.is_dir() {
+        match fs.read_dir(who, target) {
+            Ok(entries) => entries
+                .iter()
+                .map(|entry| {
+                    let child = path::join(target, &entry.name);
+                    let child_shown = path::join(shown, &entry.name);
+                    total(fs, who, &child, &child_shown, depth + 1, options, totals)
+                })
+                .sum(),
+            Err(e) => {
+                totals.errors.report("du", shown, e);
+                0
+            }
+        }
+    } else {
+        meta.size
+    };
+
+    let listed = if options.summary {
+        depth == 0
+    } else {
+        meta.is_dir() || options.all || depth == 0
+    };
+    if listed {
+        totals.listed.push((bytes, shown.to_string()));
+    }
+    bytes
+}
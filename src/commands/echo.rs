@@ -0,0 +1,10 @@
+use crate::commands::CommandOutcome;
+use crate::session::Session;
+
+pub fn run(
+    _session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    println!("{}", args.join(" "));
+    Ok(CommandOutcome::ok())
+}
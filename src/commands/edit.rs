@@ -0,0 +1,412 @@
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::console;
+use crate::session::Session;
+use crate::tty;
+use crate::vfs::{self, path, perm, VfsError};
+use std::io::{self, Write};
+
+const HELP: &[&str] = &[
+    "Lines are addressed by number, '.' (current), '$' (last) or 'A,B'.",
+    "A bare ',' means the whole file. Without an address the current line is used.",
+    "  [A,B]p        print lines with their numbers",
+    "  [A]a          append lines after A, end with a single '.'",
+    "  [A]i          insert lines before A, end with a single '.'",
+    "  [A,B]c        change lines, end with a single '.'",
+    "  [A,B]d        delete lines",
+    "  [A,B]s/x/y/g  replace x with y (first on each line, or all with g)",
+    "  /text[/]      search forward for text",
+    "  ?text[?]      search backward for text",
+    "  =             show the number of lines",
+    "  w [FILE]      save, to FILE if given",
+    "  e FILE        open another file (e! discards unsaved changes)",
+    "  q  q!  wq     quit, quit discarding changes, save and quit",
+    "  h             this help",
+];
+
+/// First and last line, 1-based and inclusive
+type Range = (usize, usize);
+
+/// The file being edited
+struct Buffer {
+    path: String,
+    lines: Vec<String>,
+    /// 1-based, 0 when the buffer is empty
+    current: usize,
+    modified: bool,
+}
+
+/// A small line editor in the style of `ed`
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let [file] = args else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: edit FILE"));
+    };
+    let mut buffer = match open(session, file) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("edit: {}: {}", file, e),
+            ))
+        }
+    };
+    println!("Type 'h' for help, 'q' to quit.");
+
+    loop {
+        print!("edit:{}> ", buffer.current);
+        console::stdout().flush()?;
+        let line = match read_line() {
+            Ok(Some(line)) => line,
+            Ok(None) => {
+                if buffer.modified {
+                    println!("Unsaved changes to {} were discarded.", buffer.path);
+                }
+                break;
+            }
+            // Like `ed`, Ctrl-C only abandons the command being typed
+            Err(e) if tty::is_interrupt(&e) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        match execute(session, &mut buffer, line.trim_end()) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(message) => println!("? {}", message),
+        }
+    }
+    Ok(CommandOutcome::ok())
+}
+
+fn read_line() -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if tty::read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+}
+
+/// Loads a file. A file that doesn't exist yet starts out empty.
+fn open(session: &Session, file: &str) -> Result<Buffer, VfsError> {
+    let full = path::resolve(&session.cwd, file);
+    let mut fs = vfs::lock();
+    let lines = match fs.read_file(&session.user, &full) {
+        Ok(bytes) => {
+            let text = String::from_utf8(bytes).map_err(|_| VfsError::InvalidArgument)?;
+            let lines: Vec<String> = text.lines().map(|l| l.to_string()).collect();
+            let writable = fs.access(&session.user, &full, perm::WRITE).is_ok();
+            println!(
+                "{}: {} lines{}",
+                full,
+                lines.len(),
+                if writable { "" } else { " [read-only]" }
+            );
+            lines
+        }
+        Err(VfsError::NotFound) => {
+            println!("{}: new file", full);
+            Vec::new()
+        }
+        Err(e) => return Err(e),
+    };
+    Ok(Buffer {
+        path: full,
+        current: lines.len(),
+        lines,
+        modified: false,
+    })
+}
+
+fn save(session: &Session, buffer: &mut Buffer, file: Option<&str>) -> Result<(), String> {
+    let target = match file {
+        Some(file) => path::resolve(&session.cwd, file),
+        None => buffer.path.clone(),
+    };
+    let mut contents = buffer.lines.join("\n");
+    if !buffer.lines.is_empty() {
+        contents.push('\n');
+    }
+    vfs::lock()
+        .write_file(&session.user, &target, contents.as_bytes())
+        .map_err(|e| format!("{}: {}", target, e))?;
+    println!("{}: {} lines written", target, buffer.lines.len());
+    buffer.path = target;
+    buffer.modified = false;
+    Ok(())
+}
+
+/// Runs one editor command. Returns `Ok(false)` when the editor should
+/// quit.
+fn execute(session: &Session, buffer: &mut Buffer, input: &str) -> Result<bool, String> {
+    // Searches take the rest of the line as their text, less a closing
+    // delimiter as in ed's `/text/` and `?text?`
+    if let Some(text) = input.strip_prefix('/') {
+        let text = text.strip_suffix('/').unwrap_or(text);
+        buffer.current = search(buffer, text, true)?;
+        print_lines(buffer, buffer.current, buffer.current);
+        return Ok(true);
+    }
+    if let Some(text) = input.strip_prefix('?') {
+        let text = text.strip_suffix('?').unwrap_or(text);
+        buffer.current = search(buffer, text, false)?;
+        print_lines(buffer, buffer.current, buffer.current);
+        return Ok(true);
+    }
+
+    let (range, rest) = parse_range(buffer, input)?;
+    let mut chars = rest.chars();
+    let command = chars.next();
+    let raw = chars.as_str();
+    let argument = raw.trim();
+
+    match command {
+        None => {
+            // A bare address moves there, Enter alone moves to the next line
+            let line = match range {
+                Some((_, end)) => end,
+                None => buffer.current + 1,
+            };
+            check_line(buffer, line)?;
+            buffer.current = line;
+            print_lines(buffer, line, line);
+        }
+        Some('p') => {
+            let (start, end) = lines_or_current(buffer, range)?;
+            print_lines(buffer, start, end);
+            buffer.current = end;
+        }
+        Some('a') | Some('i') => {
+            let at = match range {
+                Some((_, end)) => end,
+                None => buffer.current,
+            };
+            let before_first = command == Some('i') && at == 0 && !buffer.lines.is_empty();
+            if at > buffer.lines.len() || before_first {
+                return Err("Invalid address".into());
+            }
+            // Insert goes before the line, append after it
+            let index = if command == Some('i') {
+                at.saturating_sub(1)
+            } else {
+                at
+            };
+            let added = read_input().map_err(|e| e.to_string())?;
+            let count = added.len();
+            buffer.lines.splice(index..index, added);
+            buffer.current = if count > 0 { index + count } else { at };
+            buffer.modified |= count > 0;
+        }
+        Some('c') => {
+            let (start, end) = lines_or_current(buffer, range)?;
+            let added = read_input().map_err(|e| e.to_string())?;
+            let count = added.len();
+            buffer.lines.splice(start - 1..end, added);
+            buffer.current = (start - 1 + count).min(buffer.lines.len());
+            buffer.modified = true;
+        }
+        Some('d') => {
+            let (start, end) = lines_or_current(buffer, range)?;
+            buffer.lines.drain(start - 1..end);
+            buffer.current = start.min(buffer.lines.len());
+            buffer.modified = true;
+        }
+        Some('s') => {
+            let (start, end) = lines_or_current(buffer, range)?;
+            substitute(buffer, start, end, raw)?;
+        }
+        Some('=') => println!("{}", buffer.lines.len()),
+        Some('w') if raw == "q" => {
+            save(session, buffer, None)?;
+            return Ok(false);
+        }
+        Some('w') => save(session, buffer, (!argument.is_empty()).then_some(argument))?,
+        Some('e') => {
+            let force = argument.starts_with('!');
+            let file = argument.trim_start_matches('!').trim();
+            if file.is_empty() {
+                return Err("Usage: e FILE".into());
+            }
+            if buffer.modified && !force {
+                return Err("Unsaved changes (use 'w' to save or 'e!' to discard them)".into());
+            }
+            *buffer = open(session, file).map_err(|e| format!("{}: {}", file, e))?;
+        }
+        Some('q') if raw == "!" => return Ok(false),
+        Some('q') if buffer.modified => {
+            return Err("Unsaved changes (use 'wq' to save or 'q!' to discard them)".into())
+        }
+        Some('q') => return Ok(false),
+        Some('h') => {
+            for line in HELP {
+                println!("{}", line);
+            }
+        }
+        Some(other) => return Err(format!("Unknown command '{}' (type 'h' for help)", other)),
+    }
+    Ok(true)
+}
+
+/// Reads lines to add until a line holding a single `.`
+fn read_input() -> io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    while let Some(line) = read_line()? {
+        if line == "." {
+            break;
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+/// Splits a leading address or range off a command. `None` means there
+/// was no address.
+fn parse_range<'a>(buffer: &Buffer, input: &'a str) -> Result<(Option<Range>, &'a str), String> {
+    // `,` on its own is the whole file, and `,B` is the same as `1,B`
+    if let Some(rest) = input.strip_prefix(',') {
+        return match parse_address(buffer, rest)? {
+            (Some(end), rest) => Ok((Some((1, end)), rest)),
+            (None, rest) => Ok((Some((1, buffer.lines.len())), rest)),
+        };
+    }
+    let (start, rest) = parse_address(buffer, input)?;
+    let Some(start) = start else {
+        return Ok((None, rest));
+    };
+    let Some(rest) = rest.strip_prefix(',') else {
+        return Ok((Some((start, start)), rest));
+    };
+    match parse_address(buffer, rest)? {
+        (Some(end), rest) if end >= start => Ok((Some((start, end)), rest)),
+        (Some(_), _) => Err("Invalid range".into()),
+        (None, _) => Err("Missing address after ','".into()),
+    }
+}
+
+fn parse_address<'a>(buffer: &Buffer, input: &'a str) -> Result<(Option<usize>, &'a str), String> {
+    if let Some(rest) = input.strip_prefix('.') {
+        return Ok((Some(buffer.current), rest));
+    }
+    if let Some(rest) = input.strip_prefix('$') {
+        return Ok((Some(buffer.lines.len()), rest));
+    }
+    let digits = input.len() - input.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return Ok((None, input));
+    }
+    let line = input[..digits]
+        .parse()
+        .map_err(|_| "Invalid address".to_string())?;
+    Ok((Some(line), &input[digits..]))
+}
+
+fn check_line(buffer: &Buffer, line: usize) -> Result<(), String> {
+    if line == 0 || line > buffer.lines.len() {
+        return Err("Invalid address".into());
+    }
+    Ok(())
+}
+
+/// The addressed lines, or the current one
+fn lines_or_current(buffer: &Buffer, range: Option<Range>) -> Result<Range, String> {
+    let (start, end) = range.unwrap_or((buffer.current, buffer.current));
+    check_line(buffer, start)?;
+    check_line(buffer, end)?;
+    Ok((start, end))
+}
+
+fn print_lines(buffer: &Buffer, start: usize, end: usize) {
+    for (n, line) in buffer.lines[start - 1..end].iter().enumerate() {
+        println!("{:>5}  {}", start + n, line);
+    }
+}
+
+/// Finds the next (or previous) line containing `text`, wrapping around
+/// the end of the file.
+fn search(buffer: &Buffer, text: &str, forward: bool) -> Result<usize, String> {
+    if text.is_empty() {
+        return Err("Nothing to search for".into());
+    }
+    let count = buffer.lines.len();
+    (1..=count)
+        .map(|step| {
+            if forward {
+                (buffer.current + step - 1) % count + 1
+            } else {
+                (buffer.current + count * 2 - step - 1) % count + 1
+            }
+        })
+        .find(|&line| buffer.lines[line - 1].contains(text))
+        .ok_or_else(|| format!("'{}' not found", text))
+}
+
+/// `s/old/new/[g]`, where any character can stand in for the `/`
+fn substitute(buffer: &mut Buffer, start: usize, end: usize, spec: &str) -> Result<(), String> {
+    let mut chars = spec.chars();
+    let Some(delimiter) = chars.next() else {
+        return Err("Usage: s/old/new/[g]".into());
+    };
+    let parts: Vec<&str> = chars.as_str().splitn(3, delimiter).collect();
+    let (old, new, flags) = match parts.as_slice() {
+        [old, new] => (*old, *new, ""),
+        [old, new, flags] => (*old, *new, *flags),
+        _ => return Err("Usage: s/old/new/[g]".into()),
+    };
+    let all = match flags {
+        "" => false,
+        "g" => true,
+        _ => return Err(format!("Unknown flags '{}'", flags)),
+    };
+    if old.is_empty() {
+        return Err("Nothing to replace".into());
+    }
+
+    let mut last = None;
+    for line in start..=end {
+        let text = &mut buffer.lines[line - 1];
+        if !text.contains(old) {
+            continue;
+        }
+        *text = if all {
+            text.replace(old, new)
+        } else {
+            text.replacen(old, new, 1)
+        };
+        last = Some(line);
+    }
+    let Some(line) = last else {
+        return Err(format!("'{}' not found", old));
+    };
+    buffer.current = line;
+    buffer.modified = true;
+    print_lines(buffer, line, line);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::CurrentUser;
+
+    #[test]
+    fn searches_drop_one_closing_delimiter() {
+        let session = Session::new(CurrentUser {
+            username: "alice".to_string(),
+            is_admin: false,
+        });
+        let lines = ["a/b", "x", "a", "a?"];
+        let mut buffer = Buffer {
+            path: "/tmp/notes".to_string(),
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+            current: 2,
+            modified: false,
+        };
+        let mut find = |input: &str| execute(&session, &mut buffer, input).map(|_| buffer.current);
+
+        assert_eq!(find("/a/"), Ok(3));
+        assert_eq!(find("/a/b/"), Ok(1));
+        assert_eq!(find("/a/b"), Ok(1));
+        assert_eq!(find("?a??"), Ok(4));
+        assert_eq!(find("?x?"), Ok(2));
+        assert!(find("//").is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let options = match OutputOptions::parse(args) {
+        Ok((options, rest)) if rest.is_empty() => options,
+        Ok(_) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("Usage: env {}", OUTPUT_USAGE),
+            ))
+        }
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+
+    if options.is_human() {
+        for (name, value) in &session.env {
+            println!("{}={}", name, value);
+        }
+        return Ok(CommandOutcome::ok());
+    }
+
+    let mut table = Table::new(&["name", "value"]);
+    for (name, value) in &session.env {
+        table.push(vec![name.as_str().into(), value.as_str().into()]);
+    }
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(CommandOutcome::ok())
+}
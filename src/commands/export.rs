@@ -0,0 +1,75 @@
+use crate::auth::CurrentUser;
+use crate::commands::files::Errors;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path, Vfs};
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Copies a file or directory tree from the virtual filesystem to the host
+/// (admin only). Host files keep the rwx bits they had in MiniKern.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let [source, host] = args else {
+        return Ok(CommandOutcome::failure(
+            EXIT_USAGE,
+            "Usage: export VFSPATH HOSTPATH",
+        ));
+    };
+    let source = path::resolve(&session.cwd, source);
+    let mut host = PathBuf::from(host);
+    // Like cp, an existing directory receives the copy
+    if host.is_dir() && source != "/" {
+        host.push(path::file_name(&source));
+    }
+
+    let mut fs = vfs::lock();
+    let mut errors = Errors::default();
+    export_tree(&mut fs, &session.user, &source, &host, &mut errors);
+    Ok(errors.outcome())
+}
+
+fn export_tree(fs: &mut Vfs, who: &CurrentUser, source: &str, host: &Path, errors: &mut Errors) {
+    let shown = host.display().to_string();
+    let meta = match fs.stat(who, source) {
+        Ok(meta) => meta,
+        Err(e) => return errors.report("export", source, e),
+    };
+
+    if meta.is_dir() {
+        match fs::create_dir(host) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && host.is_dir() => {}
+            Err(e) => return errors.report("export", &shown, e),
+            Ok(()) => {}
+        }
+        let entries = match fs.read_dir(who, source) {
+            Ok(entries) => entries,
+            Err(e) => return errors.report("export", source, e),
+        };
+        for entry in entries {
+            export_tree(
+                fs,
+                who,
+                &path::join(source, &entry.name),
+                &host.join(&entry.name),
+                errors,
+            );
+        }
+    } else {
+        let contents = match fs.read_file(who, source) {
+            Ok(contents) => contents,
+            Err(e) => return errors.report("export", source, e),
+        };
+        if let Err(e) = fs::write(host, contents) {
+            return errors.report("export", &shown, e);
+        }
+    }
+    let mode = u32::from(meta.mode & 0o777);
+    if let Err(e) = fs::set_permissions(host, fs::Permissions::from_mode(mode)) {
+        errors.report("export", &shown, e);
+    }
+}
@@ -0,0 +1,42 @@
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::console;
+use crate::process::{self, Signal, State};
+use crate::session::Session;
+
+/// Brings a background job to the foreground: continues it if it was
+/// stopped and waits for it to finish, taking on its exit status
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let spec = match args {
+        [] => None,
+        [spec] => Some(spec.as_str()),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: fg [%JOB]")),
+    };
+    let Some(job) = process::find_job(process::parent(session.pid), spec) else {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{}: no such job", spec.unwrap_or("current")),
+        ));
+    };
+
+    println!("{}", job.command);
+    if job.state == State::Stopped {
+        process::signal(&session.user, job.pid, Signal::Cont)?;
+    }
+    // Ctrl-C goes to the job while it's in the foreground
+    let foreground = console::foreground() == Some(session.pid);
+    if foreground {
+        console::set_foreground(Some(job.pid));
+    }
+    let status = process::wait(job.pid);
+    if foreground {
+        console::set_foreground(Some(session.pid));
+    }
+    match status {
+        Some(status) => Ok(CommandOutcome::exited(status)),
+        // Killed and reaped while we were waiting
+        None => Ok(CommandOutcome::exited(Signal::Kill.status())),
+    }
+}
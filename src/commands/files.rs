@@ -0,0 +1,128 @@
+use crate::auth::CurrentUser;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_SUCCESS};
+use crate::vfs::{path, FileType, Metadata, Vfs, VfsError};
+use std::fmt::Display;
+
+/// Splits single-letter flags (`-r`, `-rf`) from the operands. A lone `-`
+/// is an operand and everything after `--` is too. Returns the first flag
+/// not in `allowed` as the error.
+pub fn parse_flags(args: &[String], allowed: &str) -> Result<(String, Vec<String>), char> {
+    let mut flags = String::new();
+    let mut operands = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            operands.extend(iter.cloned());
+            break;
+        }
+        match arg.strip_prefix('-') {
+            Some(letters) if !letters.is_empty() => {
+                for c in letters.chars() {
+                    if !allowed.contains(c) {
+                        return Err(c);
+                    }
+                    flags.push(c);
+                }
+            }
+            _ => operands.push(arg.clone()),
+        }
+    }
+    Ok((flags, operands))
+}
+
+/// Tracks per-path errors, which are printed as they happen so the
+/// command can carry on with the remaining paths.
+#[derive(Default)]
+pub struct Errors {
+    failed: bool,
+}
+
+impl Errors {
+    pub fn report(&mut self, command: &str, path: &str, error: impl Display) {
+        eprintln!("Error: {}: {}: {}", command, path, error);
+        self.failed = true;
+    }
+
+    pub fn outcome(&self) -> CommandOutcome {
+        CommandOutcome::exited(if self.failed { EXIT_FAILURE } else { EXIT_SUCCESS })
+    }
+}
+
+/// Calls `f` on `target`, then with `recursive` on everything below it,
+/// parents before their contents.
+pub fn for_each_path(
+    fs: &mut Vfs,
+    who: &CurrentUser,
+    target: &str,
+    recursive: bool,
+    f: &mut dyn FnMut(&mut Vfs, &str) -> Result<(), VfsError>,
+) -> Result<(), VfsError> {
+    f(fs, target)?;
+    if !recursive || !fs.stat(who, target)?.is_dir() {
+        return Ok(());
+    }
+    for entry in fs.read_dir(who, target)? {
+        let child = path::join(target, &entry.name);
+        match entry.file_type {
+            FileType::Directory => for_each_path(fs, who, &child, true, f)?,
+            FileType::Regular => f(fs, &child)?,
+        }
+    }
+    Ok(())
+}
+
+/// Calls `f` on `target` and everything below it, parents before their
+/// contents, with each path as the user wrote it (`shown`) and its
+/// metadata. Whatever the user may not read is reported to `errors`, which
+/// `f` gets too, and skipped.
+pub fn walk(
+    fs: &mut Vfs,
+    who: &CurrentUser,
+    target: &str,
+    shown: &str,
+    errors: &mut Errors,
+    command: &str,
+    f: &mut dyn FnMut(&mut Vfs, &mut Errors, &str, &str, &Metadata),
+) {
+    let meta = match fs.stat(who, target) {
+        Ok(meta) => meta,
+        Err(e) => return errors.report(command, shown, e),
+    };
+    f(fs, errors, target, shown, &meta);
+    if !meta.is_dir() {
+        return;
+    }
+    let entries = match fs.read_dir(who, target) {
+        Ok(entries) => entries,
+        Err(e) => return errors.report(command, shown, e),
+    };
+    for entry in entries {
+        let child = path::join(target, &entry.name);
+        let child_shown = path::join(shown, &entry.name);
+        walk(fs, who, &child, &child_shown, errors, command, f);
+    }
+}
+
+/// Copies a file, or a directory and everything in it, merging into a
+/// directory that's already at `to`
+pub fn copy_tree(fs: &mut Vfs, who: &CurrentUser, from: &str, to: &str) -> Result<(), VfsError> {
+    if !fs.stat(who, from)?.is_dir() {
+        let contents = fs.read_file(who, from)?;
+        return fs.write_file(who, to, &contents);
+    }
+
+    match fs.mkdir(who, to) {
+        Err(VfsError::AlreadyExists) if fs.stat(who, to)?.is_dir() => {}
+        result => result?,
+    }
+    for entry in fs.read_dir(who, from)? {
+        copy_tree(fs, who, &path::join(from, &entry.name), &path::join(to, &entry.name))?;
+    }
+    Ok(())
+}
+
+/// Whether a group can be given to files: one from `/etc/group`, or a
+/// user's private group
+pub fn group_exists(fs: &Vfs, users: &[crate::auth::User], group: &str) -> bool {
+    users.iter().any(|u| u.username == group) || fs.groups().iter().any(|(name, _)| name == group)
+}
@@ -0,0 +1,133 @@
+use crate::clock;
+use crate::commands::files::{walk, Errors};
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::glob;
+use crate::session::Session;
+use crate::vfs::quota::parse_size;
+use crate::vfs::{self, path, FileType, Metadata};
+
+const USAGE: &str = "Usage: find [PATH...] [-name PATTERN] [-type f|d] [-user NAME] \
+                     [-size [+|-]SIZE] [-mtime [+|-]DAYS]";
+
+/// `+N` more than, `-N` less than, `N` exactly
+#[derive(Clone, Copy)]
+enum Compare {
+    More(u64),
+    Less(u64),
+    Exactly(u64),
+}
+
+impl Compare {
+    fn parse(text: &str, value: impl Fn(&str) -> Result<u64, String>) -> Result<Compare, String> {
+        if let Some(rest) = text.strip_prefix('+') {
+            Ok(Compare::More(value(rest)?))
+        } else if let Some(rest) = text.strip_prefix('-') {
+            Ok(Compare::Less(value(rest)?))
+        } else {
+            Ok(Compare::Exactly(value(text)?))
+        }
+    }
+
+    fn matches(self, actual: u64) -> bool {
+        match self {
+            Compare::More(n) => actual > n,
+            Compare::Less(n) => actual < n,
+            Compare::Exactly(n) => actual == n,
+        }
+    }
+}
+
+/// A test every printed path has to pass
+enum Predicate {
+    Name(String),
+    Type(FileType),
+    User(String),
+    /// In bytes; directories never match
+    Size(Compare),
+    /// In whole days since the last modification
+    Mtime(Compare),
+}
+
+impl Predicate {
+    fn matches(&self, shown: &str, meta: &Metadata, now: u64) -> bool {
+        match self {
+            Predicate::Name(pattern) => glob::matches(pattern, path::file_name(shown)),
+            Predicate::Type(file_type) => meta.file_type == *file_type,
+            Predicate::User(user) => meta.owner == *user,
+            Predicate::Size(compare) => !meta.is_dir() && compare.matches(meta.size),
+            Predicate::Mtime(compare) => {
+                compare.matches(now.saturating_sub(meta.modified) / 86_400)
+            }
+        }
+    }
+}
+
+fn parse_predicates(args: &[String]) -> Result<Vec<Predicate>, String> {
+    let mut predicates = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("{} needs an argument.", arg))?;
+        let predicate = match arg.as_str() {
+            "-name" => Predicate::Name(value.clone()),
+            "-type" => match value.as_str() {
+                "f" => Predicate::Type(FileType::Regular),
+                "d" => Predicate::Type(FileType::Directory),
+                _ => return Err(format!("Unknown type '{}', use f or d.", value)),
+            },
+            "-user" => Predicate::User(value.clone()),
+            "-size" => Predicate::Size(Compare::parse(value, parse_size)?),
+            "-mtime" => Predicate::Mtime(Compare::parse(value, |days| {
+                days.parse()
+                    .map_err(|_| format!("'{}' is not a number of days.", value))
+            })?),
+            _ => return Err(format!("Unknown predicate '{}'.\n{}", arg, USAGE)),
+        };
+        predicates.push(predicate);
+    }
+    Ok(predicates)
+}
+
+/// Prints the paths under each starting point that pass every predicate,
+/// one per line
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let split = args
+        .iter()
+        .position(|arg| arg.starts_with('-'))
+        .unwrap_or(args.len());
+    let (starts, rest) = args.split_at(split);
+    let predicates = match parse_predicates(rest) {
+        Ok(predicates) => predicates,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let starts = if starts.is_empty() {
+        vec![".".to_string()]
+    } else {
+        starts.to_vec()
+    };
+
+    let now = clock::unix_now();
+    let mut errors = Errors::default();
+    let mut fs = vfs::lock();
+    for start in &starts {
+        let full = path::resolve(&session.cwd, start);
+        walk(
+            &mut fs,
+            &session.user,
+            &full,
+            start,
+            &mut errors,
+            "find",
+            &mut |_, _, _, shown, meta| {
+                if predicates.iter().all(|p| p.matches(shown, meta, now)) {
+                    println!("{}", shown);
+                }
+            },
+        );
+    }
+    Ok(errors.outcome())
+}
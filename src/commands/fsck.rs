@@ -0,0 +1,102 @@
+use crate::audit;
+use crate::auth::load_users;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::syslog::{self, Facility, Level};
+use crate::vfs::{self, IMAGE_FILE_PATH};
+
+const USAGE: &str = "Usage: fsck [-y]";
+
+/// Checks the filesystem image for inconsistencies, repairing them with
+/// `-y`
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let repair = match rest.as_slice() {
+        [] => false,
+        [flag] if flag == "-y" => true,
+        _ => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("{} {}", USAGE, OUTPUT_USAGE),
+            ))
+        }
+    };
+    let users: Vec<String> = load_users()?.into_iter().map(|u| u.username).collect();
+    let report = match vfs::lock().fsck(&session.user, &users, repair) {
+        Ok(report) => report,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_FAILURE, e.to_string())),
+    };
+
+    let found = report.problems.len();
+    let repaired = report.problems.iter().filter(|p| p.repaired).count();
+    if repaired > 0 {
+        let detail = format!("{} problem(s) repaired", repaired);
+        let _ = audit::record(&session.user.username, "fsck", IMAGE_FILE_PATH, &detail);
+        let _ = syslog::record(
+            Level::Warning,
+            Facility::Fs,
+            &format!("fsck: {} in {}", detail, IMAGE_FILE_PATH),
+        );
+    }
+
+    if options.is_human() {
+        for problem in &report.problems {
+            println!(
+                "{}: {}{}",
+                problem.subject,
+                problem.description,
+                if problem.repaired { " (repaired)" } else { "" }
+            );
+        }
+    } else {
+        let mut table = Table::new(&["subject", "problem", "repaired"]);
+        for problem in report.problems {
+            table.push(vec![
+                problem.subject.into(),
+                problem.description.into(),
+                problem.repaired.into(),
+            ]);
+        }
+        match output::render(&table, &options) {
+            Ok(rendered) => print!("{}", rendered),
+            Err(e) => {
+                return Ok(CommandOutcome::failure(
+                    EXIT_USAGE,
+                    format!("{}\n{} {}", e, USAGE, OUTPUT_USAGE),
+                ))
+            }
+        }
+        return Ok(if found > repaired {
+            CommandOutcome::exited(EXIT_FAILURE)
+        } else {
+            CommandOutcome::ok()
+        });
+    }
+
+    if found == 0 {
+        Ok(CommandOutcome::success(format!(
+            "{}: clean, {} files and directories",
+            IMAGE_FILE_PATH, report.inodes
+        )))
+    } else if found > repaired {
+        Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!(
+                "{}: {} problem(s) found; run 'fsck -y' to repair them",
+                IMAGE_FILE_PATH, found
+            ),
+        ))
+    } else {
+        Ok(CommandOutcome::success(format!(
+            "{}: {} problem(s) repaired",
+            IMAGE_FILE_PATH, repaired
+        )))
+    }
+}
@@ -0,0 +1,68 @@
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::profile;
+use crate::session::Session;
+use crate::shell;
+
+const USAGE: &str = "Usage: function [NAME BODY | -d NAME]";
+
+/// Defines shell functions. The body is one or more commands separated by
+/// `;`, and can use `$1`..`$9`, `$#` and `$@`. Quote the body with single
+/// quotes so those are expanded when the function runs, not when it's
+/// defined:
+///
+///     function greet 'echo Hello, $1!; echo Bye.'
+///
+/// Definitions are saved to the user's startup file.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let rc_path = profile::user_rc_path(&session.user.username);
+
+    match args {
+        [] => {
+            if session.functions.is_empty() {
+                println!("No functions defined.");
+            }
+            for (name, body) in &session.functions {
+                println!("function {} {}", name, shell::quote(body));
+            }
+        }
+        [flag, name] if flag == "-d" => {
+            if session.functions.remove(name.as_str()).is_none() {
+                return Ok(CommandOutcome::failure(
+                    EXIT_FAILURE,
+                    format!("function '{}' not found.", name),
+                ));
+            }
+            profile::replace_definition(&session.user, &rc_path, "function", name, None)?;
+            return Ok(CommandOutcome::success(format!("Function '{}' removed.", name)));
+        }
+        [name] => match session.functions.get(name.as_str()) {
+            Some(body) => println!("function {} {}", name, shell::quote(body)),
+            None => {
+                return Ok(CommandOutcome::failure(
+                    EXIT_FAILURE,
+                    format!("function '{}' not found.", name),
+                ))
+            }
+        },
+        [name, body @ ..] => {
+            if name.starts_with('-')
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+            }
+            let body = body.join(" ");
+            if session.startup.is_none() {
+                let definition =
+                    format!("function {} {}", name, shell::quote(&body));
+                profile::replace_definition(&session.user, &rc_path, "function", name, Some(definition))?;
+            }
+            session.functions.insert(name.clone(), body);
+        }
+    }
+    Ok(CommandOutcome::ok())
+}
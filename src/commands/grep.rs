@@ -0,0 +1,154 @@
+use crate::commands::files::{parse_flags, walk, Errors};
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_SUCCESS, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path};
+use regex::{Regex, RegexBuilder};
+
+const USAGE: &str = "Usage: grep [-r] [-n] [-i] [-v] [-c] [-l] PATTERN [PATH...]";
+
+/// Name used for piped input in the output
+const STDIN_NAME: &str = "(standard input)";
+
+struct Options {
+    /// `-n`
+    line_numbers: bool,
+    /// `-v`
+    invert: bool,
+    /// `-c`
+    count: bool,
+    /// `-l`
+    names_only: bool,
+    /// Prefix lines with the file they come from
+    show_names: bool,
+}
+
+/// Prints the lines of files, or of its piped input, that match a regular
+/// expression. `-r` searches directories recursively.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let Ok((flags, operands)) = parse_flags(args, "rnivcl") else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let Some((pattern, paths)) = operands.split_first() else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let recursive = flags.contains('r');
+    let regex = match RegexBuilder::new(pattern)
+        .case_insensitive(flags.contains('i'))
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("Invalid pattern: {}", e),
+            ))
+        }
+    };
+    let options = Options {
+        line_numbers: flags.contains('n'),
+        invert: flags.contains('v'),
+        count: flags.contains('c'),
+        names_only: flags.contains('l'),
+        show_names: recursive || paths.len() > 1,
+    };
+
+    if paths.is_empty() && !recursive {
+        let Some(input) = session.stdin.take() else {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("{}\nWithout a PATH, grep searches its piped input.", USAGE),
+            ));
+        };
+        let matched = search(&regex, &input, STDIN_NAME, &options);
+        return Ok(CommandOutcome::exited(if matched {
+            EXIT_SUCCESS
+        } else {
+            EXIT_FAILURE
+        }));
+    }
+
+    let paths = if paths.is_empty() {
+        vec![".".to_string()]
+    } else {
+        paths.to_vec()
+    };
+    let mut matched = false;
+    let mut errors = Errors::default();
+    let mut fs = vfs::lock();
+    for shown in &paths {
+        let full = path::resolve(&session.cwd, shown);
+        if !recursive {
+            match fs.read_file(&session.user, &full) {
+                Ok(data) => matched |= search(&regex, &data, shown, &options),
+                Err(e) => errors.report("grep", shown, e),
+            }
+            continue;
+        }
+        walk(
+            &mut fs,
+            &session.user,
+            &full,
+            shown,
+            &mut errors,
+            "grep",
+            &mut |fs, errors, target, shown, meta| {
+                if meta.is_dir() {
+                    return;
+                }
+                match fs.read_file(&session.user, target) {
+                    Ok(data) => matched |= search(&regex, &data, shown, &options),
+                    Err(e) => errors.report("grep", shown, e),
+                }
+            },
+        );
+    }
+    if matched {
+        Ok(errors.outcome())
+    } else {
+        Ok(CommandOutcome::exited(EXIT_FAILURE))
+    }
+}
+
+/// Prints what `options` asks for about the matching lines of one file.
+/// Returns whether any line matched.
+fn search(regex: &Regex, data: &[u8], name: &str, options: &Options) -> bool {
+    let text = String::from_utf8_lossy(data);
+    let mut count = 0;
+    for (index, line) in text.lines().enumerate() {
+        if regex.is_match(line) == options.invert {
+            continue;
+        }
+        count += 1;
+        if options.count || options.names_only {
+            continue;
+        }
+        if data.contains(&0) {
+            println!("Binary file {} matches", name);
+            return true;
+        }
+        let mut prefix = String::new();
+        if options.show_names {
+            prefix.push_str(&format!("{}:", name));
+        }
+        if options.line_numbers {
+            prefix.push_str(&format!("{}:", index + 1));
+        }
+        println!("{}{}", prefix, line);
+    }
+
+    if options.names_only {
+        if count > 0 {
+            println!("{}", name);
+        }
+    } else if options.count {
+        if options.show_names {
+            println!("{}:{}", name, count);
+        } else {
+            println!("{}", count);
+        }
+    }
+    count > 0
+}
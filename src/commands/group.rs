@@ -0,0 +1,147 @@
+use crate::audit;
+use crate::auth::load_users;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_PERMISSION, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, perm};
+
+const USAGE: &str = "Usage: group [list | add NAME | del NAME | adduser NAME USER... | deluser NAME USER...]";
+
+/// Lists and manages the groups in `/etc/group`. Anyone can list them;
+/// changing them is for admins. Each user also has a private group with
+/// their own name, which isn't listed.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let action = args.first().map(|a| a.as_str());
+    if matches!(action, None | Some("list")) || action.is_some_and(|a| a.starts_with("--")) {
+        let rest = if action == Some("list") { &args[1..] } else { args };
+        return list(rest);
+    }
+    if !session.user.is_admin {
+        return Ok(CommandOutcome::failure(
+            EXIT_PERMISSION,
+            "You must be an admin to change groups.",
+        ));
+    }
+    let (Some(action), Some(name)) = (action, args.get(1)) else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let usernames = &args[2..];
+
+    let users = load_users()?;
+    let mut fs = vfs::lock();
+    let mut groups = fs.groups();
+    let index = groups.iter().position(|(n, _)| n == name);
+
+    let message = match (action, index) {
+        ("add", Some(_)) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("Group '{}' already exists.", name),
+            ))
+        }
+        ("add", None) => {
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ':' || c == ',') {
+                return Ok(CommandOutcome::failure(
+                    EXIT_USAGE,
+                    format!("'{}' is not a valid group name.", name),
+                ));
+            }
+            if users.iter().any(|u| &u.username == name) {
+                return Ok(CommandOutcome::failure(
+                    EXIT_FAILURE,
+                    format!("'{}' is already a user's private group.", name),
+                ));
+            }
+            groups.push((name.clone(), Vec::new()));
+            format!("Group '{}' created.", name)
+        }
+        ("del" | "adduser" | "deluser", None) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("Group '{}' not found.", name),
+            ))
+        }
+        ("del", Some(index)) => {
+            groups.remove(index);
+            format!("Group '{}' deleted.", name)
+        }
+        ("adduser" | "deluser", Some(_)) if usernames.is_empty() => {
+            return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE))
+        }
+        ("adduser", Some(index)) => {
+            if let Some(unknown) = usernames.iter().find(|n| !users.iter().any(|u| &u.username == *n)) {
+                return Ok(CommandOutcome::failure(
+                    EXIT_FAILURE,
+                    format!("User '{}' not found.", unknown),
+                ));
+            }
+            let members = &mut groups[index].1;
+            for username in usernames {
+                if !members.contains(username) {
+                    members.push(username.clone());
+                }
+            }
+            format!("Added {} to group '{}'.", usernames.join(", "), name)
+        }
+        ("deluser", Some(index)) => {
+            groups[index].1.retain(|m| !usernames.contains(m));
+            format!("Removed {} from group '{}'.", usernames.join(", "), name)
+        }
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+    };
+
+    fs.write_file(
+        &session.user,
+        perm::GROUP_FILE_PATH,
+        perm::format_groups(&groups).as_bytes(),
+    )?;
+    drop(fs);
+    let _ = audit::record(
+        &session.user.username,
+        &format!("group-{}", action),
+        name,
+        &usernames.join(","),
+    );
+    Ok(CommandOutcome::success(message))
+}
+
+fn list(args: &[String]) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let options = match OutputOptions::parse(args) {
+        Ok((options, rest)) if rest.is_empty() => options,
+        Ok(_) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("Usage: group [list] {}", OUTPUT_USAGE),
+            ))
+        }
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+
+    let groups = vfs::lock().groups();
+    if options.is_human() {
+        if groups.is_empty() {
+            println!("No groups defined.");
+        }
+        for (name, members) in &groups {
+            if members.is_empty() {
+                println!("{}: (no members)", name);
+            } else {
+                println!("{}: {}", name, members.join(", "));
+            }
+        }
+        return Ok(CommandOutcome::ok());
+    }
+
+    let mut table = Table::new(&["name", "members"]);
+    for (name, members) in &groups {
+        table.push(vec![name.as_str().into(), members.join(",").into()]);
+    }
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(CommandOutcome::ok())
+}
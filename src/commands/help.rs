@@ -0,0 +1,33 @@
+use crate::commands::{CommandOutcome, COMMANDS};
+use crate::session::Session;
+use crate::shell;
+
+pub fn run(
+    session: &mut Session,
+    _args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    println!("Available commands:");
+    for command in COMMANDS {
+        println!("  {:<9} - {}", command.name, command.summary);
+    }
+
+    if !session.aliases.is_empty() {
+        println!("Aliases:");
+        for (name, alias) in &session.aliases {
+            println!(
+                "  {:<9} - alias for {}{}",
+                name,
+                shell::quote(&alias.value),
+                if alias.system { " (system)" } else { "" }
+            );
+        }
+    }
+
+    if !session.functions.is_empty() {
+        println!("Functions:");
+        for (name, body) in &session.functions {
+            println!("  {:<9} - function: {}", name, body);
+        }
+    }
+    Ok(CommandOutcome::ok())
+}
@@ -0,0 +1,85 @@
+use crate::auth::CurrentUser;
+use crate::commands::files::Errors;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path, perm, Vfs, VfsError};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Copies a file or directory tree from the host into the virtual
+/// filesystem (admin only). The copies belong to the admin running it.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let [host, target] = args else {
+        return Ok(CommandOutcome::failure(
+            EXIT_USAGE,
+            "Usage: import HOSTPATH VFSPATH",
+        ));
+    };
+    let host = Path::new(host);
+    let mut target = path::resolve(&session.cwd, target);
+
+    let mut fs = vfs::lock();
+    // Like cp, an existing directory receives the copy
+    if fs.stat(&session.user, &target).is_ok_and(|m| m.is_dir()) {
+        if let Some(name) = host.file_name() {
+            target = path::join(&target, &name.to_string_lossy());
+        }
+    }
+    let mut errors = Errors::default();
+    import_tree(&mut fs, &session.user, host, &target, &mut errors);
+    Ok(errors.outcome())
+}
+
+fn import_tree(fs: &mut Vfs, who: &CurrentUser, host: &Path, target: &str, errors: &mut Errors) {
+    let shown = host.display().to_string();
+    let meta = match fs::symlink_metadata(host) {
+        Ok(meta) => meta,
+        Err(e) => return errors.report("import", &shown, e),
+    };
+    let mode = meta.permissions().mode() as u16 & perm::MODE_MASK;
+
+    if meta.is_dir() {
+        let created = match fs.mkdir(who, target) {
+            Err(VfsError::AlreadyExists) if fs.stat(who, target).is_ok_and(|m| m.is_dir()) => {
+                Ok(())
+            }
+            result => result,
+        };
+        if let Err(e) = created {
+            return errors.report("import", target, e);
+        }
+        let mut children = match fs::read_dir(host) {
+            Ok(children) => children
+                .filter_map(|c| c.ok())
+                .map(|c| c.file_name())
+                .collect::<Vec<_>>(),
+            Err(e) => return errors.report("import", &shown, e),
+        };
+        children.sort();
+        for name in children {
+            let child = path::join(target, &name.to_string_lossy());
+            import_tree(fs, who, &host.join(&name), &child, errors);
+        }
+    } else if meta.is_file() {
+        let contents = match fs::read(host) {
+            Ok(contents) => contents,
+            Err(e) => return errors.report("import", &shown, e),
+        };
+        if let Err(e) = fs.write_file(who, target, &contents) {
+            return errors.report("import", target, e);
+        }
+    } else {
+        return errors.report(
+            "import",
+            &shown,
+            "Only files and directories can be imported",
+        );
+    }
+    if let Err(e) = fs.chmod(who, target, mode) {
+        errors.report("import", target, e);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::process;
+use crate::session::Session;
+
+/// Lists the background jobs of the shell, with their PIDs given `-l`
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let usage = || format!("Usage: jobs [-l] {}", OUTPUT_USAGE);
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let long = match rest.as_slice() {
+        [] => false,
+        [flag] if flag == "-l" => true,
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, usage())),
+    };
+    let jobs = process::jobs(process::parent(session.pid));
+    let count = jobs.len();
+    // `+` marks the job `fg` and `bg` pick by default, `-` the one before
+    let mark = |i: usize| match count - i {
+        1 => Some('+'),
+        2 => Some('-'),
+        _ => None,
+    };
+
+    if options.is_human() {
+        for (i, job) in jobs.iter().enumerate() {
+            let pid = if long {
+                format!("{} ", job.pid)
+            } else {
+                String::new()
+            };
+            println!(
+                "[{}]{} {}{:<12} {} &",
+                job.job.unwrap_or_default(),
+                mark(i).unwrap_or(' '),
+                pid,
+                job.state.describe(),
+                job.command
+            );
+        }
+        return Ok(CommandOutcome::ok());
+    }
+
+    let mut table = Table::new(&["job", "mark", "pid", "state", "command"]);
+    for (i, job) in jobs.into_iter().enumerate() {
+        table.push(vec![
+            job.job.map(u64::from).into(),
+            mark(i).map(String::from).into(),
+            u64::from(job.pid).into(),
+            job.state.describe().into(),
+            job.command.into(),
+        ]);
+    }
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("{}\n{}", e, usage()),
+            ))
+        }
+    }
+    Ok(CommandOutcome::ok())
+}
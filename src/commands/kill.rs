@@ -0,0 +1,95 @@
+use crate::audit;
+use crate::commands::files::Errors;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::process::{self, Signal};
+use crate::session::Session;
+
+const USAGE: &str = "Usage: kill [-s SIGNAL | -SIGNAL] PID|%JOB... | -l [SIGNAL|STATUS]";
+
+/// Sends a signal, `TERM` unless told otherwise, to processes or jobs.
+/// Users can only signal their own processes unless they're admins. `-l`
+/// lists the signals instead.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if let [flag, rest @ ..] = args {
+        if flag == "-l" {
+            return Ok(list(rest));
+        }
+    }
+    let (signal, targets) = match args {
+        [flag, name, targets @ ..] if flag == "-s" => (Signal::parse(name), targets),
+        [flag, targets @ ..] if flag.starts_with('-') && flag.len() > 1 => {
+            (Signal::parse(&flag[1..]), targets)
+        }
+        targets => (Some(Signal::Term), targets),
+    };
+    let Some(signal) = signal else {
+        return Ok(CommandOutcome::failure(
+            EXIT_USAGE,
+            format!("invalid signal\n{}", USAGE),
+        ));
+    };
+    if targets.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    }
+
+    let shell = process::parent(session.pid);
+    let mut errors = Errors::default();
+    for target in targets {
+        let pid = if target.starts_with('%') {
+            process::find_job(shell, Some(target)).map(|job| job.pid)
+        } else {
+            target.parse().ok()
+        };
+        let Some(pid) = pid else {
+            errors.report("kill", target, "no such process or job");
+            continue;
+        };
+        let owner = process::get(pid).map(|p| p.user.username);
+        match process::signal(&session.user, pid, signal) {
+            Ok(()) => {
+                if let Some(owner) = owner.filter(|o| *o != session.user.username) {
+                    let detail = format!("SIG{} to a process of {}", signal.name(), owner);
+                    let _ = audit::record(&session.user.username, "kill", target, &detail);
+                }
+            }
+            Err(e) => errors.report("kill", target, e),
+        }
+    }
+    Ok(errors.outcome())
+}
+
+/// Lists every signal, or translates between a signal's name and number.
+/// An exit status of a process ended by a signal gives that signal's name.
+fn list(args: &[String]) -> CommandOutcome {
+    match args {
+        [] => {
+            let all: Vec<String> = Signal::ALL
+                .into_iter()
+                .map(|s| format!("{:>2}) SIG{}", s.number(), s.name()))
+                .collect();
+            println!("{}", all.join("  "));
+        }
+        [arg] => {
+            let by_status = arg
+                .parse::<i32>()
+                .ok()
+                .and_then(|n| Signal::ALL.into_iter().find(|s| s.status() == n));
+            match (by_status, Signal::parse(arg)) {
+                (Some(signal), _) => println!("{}", signal.name()),
+                (None, Some(signal)) if arg.parse::<i32>().is_ok() => println!("{}", signal.name()),
+                (None, Some(signal)) => println!("{}", signal.number()),
+                (None, None) => {
+                    return CommandOutcome::failure(
+                        EXIT_USAGE,
+                        format!("{}: invalid signal specification", arg),
+                    )
+                }
+            }
+        }
+        _ => return CommandOutcome::failure(EXIT_USAGE, USAGE),
+    }
+    CommandOutcome::ok()
+}
@@ -1,6 +1,80 @@
-use crate::auth::load_users; // Removed `self,`
+use crate::auth::{load_users, User}; // Removed `self,`
+use crate::commands::{quota, CommandOutcome, EXIT_USAGE};
+use crate::output::{self, Format, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::vfs;
 
-pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+pub fn run(
+    _session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (mut options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    for arg in &rest {
+        match arg.as_str() {
+            "-l" | "--long" => {
+                if options.format == Format::Human {
+                    options.format = Format::Columns;
+                }
+            }
+            _ => {
+                return Ok(CommandOutcome::failure(
+                    EXIT_USAGE,
+                    format!("Usage: listusr [--long] {}", OUTPUT_USAGE),
+                ))
+            }
+        }
+    }
+
+    if options.is_human() {
+        print_tree()?;
+        return Ok(CommandOutcome::ok());
+    }
+
+    let users = load_users()?;
+    match output::render(&user_table(&users), &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(CommandOutcome::ok())
+}
+
+/// One record per user, with their disk usage and quota. The first user
+/// is the root account.
+pub fn user_table(users: &[User]) -> Table {
+    let mut table = Table::new(&[
+        "name",
+        "role",
+        "admin",
+        "root",
+        "status",
+        "bytes",
+        "bytes_soft",
+        "bytes_hard",
+        "files",
+        "files_soft",
+        "files_hard",
+    ]);
+    let fs = vfs::lock();
+    for (i, user) in users.iter().enumerate() {
+        let mut row = vec![
+            user.username.as_str().into(),
+            if user.is_admin { "Admin" } else { "User" }.into(),
+            user.is_admin.into(),
+            (i == 0).into(),
+            if user.disabled { "disabled" } else { "active" }.into(),
+        ];
+        row.extend(quota::usage_cells(&fs, &user.username, false));
+        table.push(row);
+    }
+    table
+}
+
+/// Prints the user list as a tree. Also shown by `chusr` and `delusr`
+/// before they ask for a username.
+pub fn print_tree() -> Result<(), Box<dyn std::error::Error>> {
     let users = load_users()?;
 
     if users.is_empty() {
@@ -12,10 +86,11 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
     for (i, user) in users.iter().enumerate() {
         let prefix = if i == users.len() - 1 { "└──" } else { "├──" };
         println!(
-            "{} {} ({})",
+            "{} {} ({}{})",
             prefix,
             user.username,
-            if user.is_admin { "Admin" } else { "User" }
+            if user.is_admin { "Admin" } else { "User" },
+            if user.disabled { ", disabled" } else { "" }
         );
     }
     Ok(())
@@ -0,0 +1,185 @@
+use crate::clock;
+use crate::commands::files::{parse_flags, Errors};
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::tty;
+use crate::vfs::{self, path, Metadata};
+
+/// One line of a listing
+struct Listed {
+    name: String,
+    path: String,
+    meta: Metadata,
+}
+
+/// Lists directory contents. `-l` shows one entry per line with details,
+/// `-a` includes names starting with `.`.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let usage = || CommandOutcome::failure(EXIT_USAGE, format!("Usage: ls [-l] [-a] [PATH...] {}", OUTPUT_USAGE));
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let Ok((flags, mut targets)) = parse_flags(&rest, "la") else {
+        return Ok(usage());
+    };
+    if targets.is_empty() {
+        targets.push(".".to_string());
+    }
+    let long = flags.contains('l');
+    let all = flags.contains('a');
+
+    let mut errors = Errors::default();
+    // Files named on the command line are listed together first, then each
+    // directory's contents
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    {
+        let fs = vfs::lock();
+        for target in &targets {
+            let full = path::resolve(&session.cwd, target);
+            match fs.stat(&session.user, &full) {
+                Ok(meta) if meta.is_dir() => {
+                    let entries = match fs.read_dir(&session.user, &full) {
+                        Ok(entries) => entries,
+                        Err(e) => {
+                            errors.report("ls", target, e);
+                            continue;
+                        }
+                    };
+                    let mut listed = Vec::new();
+                    for entry in entries {
+                        if entry.name.starts_with('.') && !all {
+                            continue;
+                        }
+                        let child = path::join(&full, &entry.name);
+                        if let Ok(meta) = fs.stat(&session.user, &child) {
+                            listed.push(Listed {
+                                name: entry.name,
+                                path: child,
+                                meta,
+                            });
+                        }
+                    }
+                    dirs.push((target.clone(), listed));
+                }
+                Ok(meta) => files.push(Listed {
+                    name: target.clone(),
+                    path: full,
+                    meta,
+                }),
+                Err(e) => errors.report("ls", target, e),
+            }
+        }
+    }
+
+    if !options.is_human() {
+        let mut table = Table::new(&[
+            "name", "path", "type", "mode", "owner", "group", "size", "links", "inode", "modified",
+        ]);
+        for entry in files.iter().chain(dirs.iter().flat_map(|(_, listed)| listed)) {
+            table.push(vec![
+                entry.name.as_str().into(),
+                entry.path.as_str().into(),
+                if entry.meta.is_dir() { "directory" } else { "file" }.into(),
+                entry.meta.mode_string().into(),
+                entry.meta.owner.as_str().into(),
+                entry.meta.group.as_str().into(),
+                entry.meta.size.into(),
+                (entry.meta.nlink as u64).into(),
+                entry.meta.ino.into(),
+                clock::format_datetime(entry.meta.modified).into(),
+            ]);
+        }
+        match output::render(&table, &options) {
+            Ok(rendered) => print!("{}", rendered),
+            Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+        }
+        return Ok(errors.outcome());
+    }
+
+    let print = |entries: &[Listed]| {
+        if long {
+            print_long(entries)
+        } else {
+            print_short(entries)
+        }
+    };
+    let headers = files.len() + dirs.len() > 1;
+    if !files.is_empty() {
+        print(&files);
+    }
+    for (i, (target, listed)) in dirs.iter().enumerate() {
+        if headers {
+            if i > 0 || !files.is_empty() {
+                println!();
+            }
+            println!("{}:", target);
+        }
+        print(listed);
+    }
+    Ok(errors.outcome())
+}
+
+/// Directories are shown with a trailing `/`
+fn display_name(entry: &Listed) -> String {
+    if entry.meta.is_dir() {
+        format!("{}/", entry.name)
+    } else {
+        entry.name.clone()
+    }
+}
+
+/// Names in columns across the terminal, like `ls` does, or one per line
+/// when the output goes into a pipe
+fn print_short(entries: &[Listed]) {
+    if entries.is_empty() {
+        return;
+    }
+    let names: Vec<String> = entries.iter().map(display_name).collect();
+    let Some((term_width, _)) = tty::window_size() else {
+        for name in &names {
+            println!("{}", name);
+        }
+        return;
+    };
+    let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0) + 2;
+    let per_row = (term_width / width).max(1);
+    let rows = names.len().div_ceil(per_row);
+
+    for row in 0..rows {
+        let line: String = (row..names.len())
+            .step_by(rows)
+            .map(|i| format!("{:<width$}", names[i], width = width))
+            .collect();
+        println!("{}", line.trim_end());
+    }
+}
+
+fn print_long(entries: &[Listed]) {
+    let width = |field: fn(&Metadata) -> String| {
+        entries.iter().map(|e| field(&e.meta).len()).max().unwrap_or(0)
+    };
+    let owner_width = width(|m| m.owner.clone());
+    let group_width = width(|m| m.group.clone());
+    let size_width = width(|m| m.size.to_string());
+    for entry in entries {
+        println!(
+            "{} {:>3} {:<owner_width$} {:<group_width$} {:>size_width$} {} {}",
+            entry.meta.mode_string(),
+            entry.meta.nlink,
+            entry.meta.owner,
+            entry.meta.group,
+            entry.meta.size,
+            &clock::format_datetime(entry.meta.modified)[..16],
+            display_name(entry),
+            owner_width = owner_width,
+            group_width = group_width,
+            size_width = size_width
+        );
+    }
+}
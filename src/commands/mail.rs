@@ -0,0 +1,249 @@
+use crate::auth::load_users;
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_PERMISSION, EXIT_USAGE};
+use crate::console;
+use crate::mail::{self, Message};
+use crate::output::{self, Format, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::tty;
+use crate::vfs;
+use std::io::Write;
+
+const USAGE: &str =
+    "Usage: mail [list] | mail read [N] | mail delete N... | mail send [-s SUBJECT] USER|@GROUP...";
+
+/// Sends, lists, reads and deletes mail between users. Admins can send to
+/// every member of a group with `@GROUP`.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    match args.first().map(|a| a.as_str()) {
+        None | Some("list") => list(session, args.get(1..).unwrap_or_default()),
+        Some(flag) if flag.starts_with("--") => list(session, args),
+        Some("read") => read(session, &args[1..]),
+        Some("delete") => delete(session, &args[1..]),
+        Some("send") => send(session, &args[1..]),
+        Some(_) => Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+    }
+}
+
+fn list(session: &Session, args: &[String]) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let mut options = match OutputOptions::parse(args) {
+        Ok((options, rest)) if rest.is_empty() => options,
+        Ok(_) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("Usage: mail list {}", OUTPUT_USAGE),
+            ))
+        }
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let messages = mail::load(&session.user.username)?;
+    if messages.is_empty() && options.is_human() {
+        println!("No mail for {}.", session.user.username);
+        return Ok(CommandOutcome::ok());
+    }
+    if options.format == Format::Human {
+        options.format = Format::Columns;
+    }
+    let mut table = Table::new(&["n", "status", "from", "date", "subject"]);
+    for (i, message) in messages.iter().enumerate() {
+        table.push(vec![
+            (i as u64 + 1).into(),
+            (if message.read { "read" } else { "new" }).into(),
+            console::printable(&message.from).into(),
+            clock::format_datetime(message.date).into(),
+            console::printable(&message.subject).into(),
+        ]);
+    }
+    match output::render(&table, &options) {
+        Ok(text) => print!("{}", text),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(CommandOutcome::ok())
+}
+
+/// The index of message `N` of a mailbox of `count` messages
+fn message_index(text: &str, count: usize) -> Result<usize, String> {
+    match text.parse::<usize>() {
+        Ok(n) if (1..=count).contains(&n) => Ok(n - 1),
+        _ => Err(format!("{}: no such message", text)),
+    }
+}
+
+/// Shows a message, the first new one if no number is given, and marks it
+/// read
+fn read(session: &Session, args: &[String]) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if args.len() > 1 {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    }
+    let shown = mail::update(&session.user.username, |messages| {
+        let index = match args.first() {
+            Some(n) => message_index(n, messages.len())?,
+            None => messages
+                .iter()
+                .position(|m| !m.read)
+                .ok_or_else(|| "No new mail.".to_string())?,
+        };
+        messages[index].read = true;
+        Ok::<_, String>(messages[index].clone())
+    })?;
+    let message = match shown {
+        Ok(message) => message,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_FAILURE, e)),
+    };
+    // Everything but the date came from the sender
+    println!("From:    {}", console::printable(&message.from));
+    println!("To:      {}", console::printable(&message.to));
+    println!("Date:    {}", clock::format_datetime(message.date));
+    println!("Subject: {}", console::printable(&message.subject));
+    println!();
+    print!("{}", console::printable(&message.body));
+    Ok(CommandOutcome::ok())
+}
+
+fn delete(
+    session: &Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    }
+    let deleted = mail::update(&session.user.username, |messages| {
+        let mut indexes = args
+            .iter()
+            .map(|n| message_index(n, messages.len()))
+            .collect::<Result<Vec<_>, _>>()?;
+        indexes.sort_unstable();
+        indexes.dedup();
+        // From the end, so the numbers of the others don't move
+        for &index in indexes.iter().rev() {
+            messages.remove(index);
+        }
+        Ok::<_, String>(indexes.len())
+    })?;
+    match deleted {
+        Ok(count) => {
+            println!(
+                "Deleted {} message{}.",
+                count,
+                if count == 1 { "" } else { "s" }
+            );
+            Ok(CommandOutcome::ok())
+        }
+        // Nothing is deleted if any number is wrong
+        Err(e) => Ok(CommandOutcome::failure(EXIT_FAILURE, e)),
+    }
+}
+
+/// Sends a message to users and groups. The body is the piped input, or is
+/// typed in, ending with a line containing only `.`.
+fn send(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (subject, recipients) = match args {
+        [flag, subject, rest @ ..] if flag == "-s" => (Some(subject.clone()), rest),
+        rest => (None, rest),
+    };
+    if recipients.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    }
+    if subject.as_ref().is_some_and(|s| s.contains(['\r', '\n'])) {
+        return Ok(CommandOutcome::failure(
+            EXIT_USAGE,
+            "The subject must fit on one line.",
+        ));
+    }
+
+    let users = load_users()?;
+    let groups = vfs::lock().groups();
+    let mut usernames: Vec<String> = Vec::new();
+    for recipient in recipients {
+        let members = match recipient.strip_prefix('@') {
+            Some(_) if !session.user.is_admin => {
+                return Ok(CommandOutcome::failure(
+                    EXIT_PERMISSION,
+                    "You must be an admin to send mail to a group.",
+                ))
+            }
+            // Each user's private group has just them in it
+            Some(group) if users.iter().any(|u| u.username == group) => vec![group.to_string()],
+            Some(group) => match groups.iter().find(|(name, _)| name == group) {
+                Some((_, members)) => members.clone(),
+                None => {
+                    return Ok(CommandOutcome::failure(
+                        EXIT_FAILURE,
+                        format!("Group '{}' not found.", group),
+                    ))
+                }
+            },
+            None if users.iter().any(|u| &u.username == recipient) => vec![recipient.clone()],
+            None => {
+                return Ok(CommandOutcome::failure(
+                    EXIT_FAILURE,
+                    format!("User '{}' not found.", recipient),
+                ))
+            }
+        };
+        for member in members {
+            // Groups can still list accounts that were deleted
+            if !usernames.contains(&member) && users.iter().any(|u| u.username == member) {
+                usernames.push(member);
+            }
+        }
+    }
+    if usernames.is_empty() {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            "No one to send the mail to.",
+        ));
+    }
+
+    let (subject, body) = match session.stdin.take() {
+        Some(input) => (
+            subject.unwrap_or_default(),
+            String::from_utf8_lossy(&input).into_owned(),
+        ),
+        None => compose(subject)?,
+    };
+    let message = Message {
+        from: session.user.username.clone(),
+        to: recipients.join(", "),
+        date: clock::unix_now(),
+        subject,
+        body,
+        read: false,
+    };
+    for username in &usernames {
+        mail::deliver(username, message.clone())?;
+    }
+    println!("Mail sent to {}.", usernames.join(", "));
+    Ok(CommandOutcome::ok())
+}
+
+/// Prompts for the subject, unless it was given, and the body
+fn compose(subject: Option<String>) -> std::io::Result<(String, String)> {
+    let subject = match subject {
+        Some(subject) => subject,
+        None => {
+            print!("Subject: > ");
+            console::stdout().flush()?;
+            let mut subject = String::new();
+            tty::read_line(&mut subject)?;
+            subject.trim().replace('\r', " ")
+        }
+    };
+    println!("Enter the message, ending it with a line containing only '.':");
+    let mut body = String::new();
+    loop {
+        let mut line = String::new();
+        if tty::read_line(&mut line)? == 0 || line.trim_end_matches(['\r', '\n']) == "." {
+            break;
+        }
+        body.push_str(line.trim_end_matches(['\r', '\n']));
+        body.push('\n');
+    }
+    Ok((subject, body))
+}
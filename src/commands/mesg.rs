@@ -0,0 +1,18 @@
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::console;
+use crate::session::Session;
+
+/// Shows whether other users can `write` to this terminal, or allows (`y`)
+/// or refuses (`n`) their messages
+pub fn run(
+    _session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    match args {
+        [] => println!("is {}", if console::accepts_messages() { "y" } else { "n" }),
+        [flag] if flag == "y" => console::set_accepts_messages(true),
+        [flag] if flag == "n" => console::set_accepts_messages(false),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: mesg [y|n]")),
+    }
+    Ok(CommandOutcome::ok())
+}
@@ -0,0 +1,33 @@
+use crate::commands::files::{parse_flags, Errors};
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path};
+
+const USAGE: &str = "Usage: mkdir [-p] DIR...";
+
+/// Creates directories. `-p` also creates missing parents and doesn't
+/// complain about directories that already exist.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (flags, dirs) = match parse_flags(args, "p") {
+        Ok((flags, dirs)) if !dirs.is_empty() => (flags, dirs),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+    };
+
+    let mut errors = Errors::default();
+    let mut fs = vfs::lock();
+    for dir in &dirs {
+        let full = path::resolve(&session.cwd, dir);
+        let result = if flags.contains('p') {
+            fs.mkdir_all(&session.user, &full)
+        } else {
+            fs.mkdir(&session.user, &full)
+        };
+        if let Err(e) = result {
+            errors.report("mkdir", dir, e);
+        }
+    }
+    Ok(errors.outcome())
+}
@@ -1,4 +1,505 @@
 pub mod addusr;
+pub mod alias;
+pub mod apitoken;
+pub mod audit;
+pub mod bg;
+pub mod cat;
+pub mod cd;
+pub mod chgrp;
+pub mod chmod;
+pub mod chown;
 pub mod chusr;
+pub mod cp;
+pub mod crontab;
 pub mod delusr;
+pub mod dmesg;
+pub mod du;
+pub mod echo;
+pub mod edit;
+pub mod env;
+pub mod export;
+pub mod fg;
+mod files;
+pub mod find;
+pub mod fsck;
+pub mod function;
+pub mod grep;
+pub mod group;
+pub mod help;
+pub mod import;
+pub mod jobs;
+pub mod kill;
 pub mod listusr;
+pub mod ls;
+pub mod mail;
+pub mod mesg;
+pub mod mkdir;
+pub mod mount;
+pub mod mv;
+pub mod nice;
+mod outcome;
+pub mod profile;
+pub mod ps;
+pub mod pwd;
+pub mod quota;
+pub mod reboot;
+pub mod renice;
+pub mod rm;
+pub mod rmdir;
+pub mod sched;
+pub mod service;
+pub mod set;
+pub mod setquota;
+pub mod shutdown;
+pub mod sleep;
+pub mod stat;
+pub mod syslog;
+pub mod tar;
+pub mod top;
+pub mod touch;
+pub mod umount;
+pub mod unalias;
+pub mod unset;
+pub mod wall;
+pub mod watch;
+pub mod who;
+pub mod write;
+
+pub use outcome::{
+    CommandOutcome, SessionEffect, EXIT_FAILURE, EXIT_NOT_FOUND,
+    EXIT_PERMISSION, EXIT_SUCCESS, EXIT_USAGE,
+};
+
+use crate::session::Session;
+
+pub type CommandFn = fn(
+    &mut Session,
+    &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>>;
+
+/// An entry in the command registry used by the terminal dispatcher
+pub struct Command {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub admin_only: bool,
+    pub run: CommandFn,
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "addusr",
+        summary: "Add a new user (admin only)",
+        admin_only: true,
+        run: addusr::run,
+    },
+    Command {
+        name: "listusr",
+        summary: "List all users (--long, --json, --csv, --format)",
+        admin_only: false,
+        run: listusr::run,
+    },
+    Command {
+        name: "chusr",
+        summary: "Change user passwords and admin status (users can change their own password)",
+        admin_only: false,
+        run: chusr::run,
+    },
+    Command {
+        name: "delusr",
+        summary: "Delete a user (admin only)",
+        admin_only: true,
+        run: delusr::run,
+    },
+    Command {
+        name: "audit",
+        summary: "Show the account audit trail (admin only)",
+        admin_only: true,
+        run: audit::run,
+    },
+    Command {
+        name: "set",
+        summary: "Set an environment variable (set NAME=value)",
+        admin_only: false,
+        run: set::run,
+    },
+    Command {
+        name: "unset",
+        summary: "Remove an environment variable",
+        admin_only: false,
+        run: unset::run,
+    },
+    Command {
+        name: "echo",
+        summary: "Print its arguments, after variable expansion",
+        admin_only: false,
+        run: echo::run,
+    },
+    Command {
+        name: "env",
+        summary: "List environment variables",
+        admin_only: false,
+        run: env::run,
+    },
+    Command {
+        name: "profile",
+        summary: "Show or edit your startup file (--system for the system profile)",
+        admin_only: false,
+        run: profile::run,
+    },
+    Command {
+        name: "alias",
+        summary: "Define or list aliases (alias ll='listusr --long')",
+        admin_only: false,
+        run: alias::run,
+    },
+    Command {
+        name: "unalias",
+        summary: "Remove an alias",
+        admin_only: false,
+        run: unalias::run,
+    },
+    Command {
+        name: "function",
+        summary: "Define or list shell functions (function NAME 'cmd; cmd')",
+        admin_only: false,
+        run: function::run,
+    },
+    Command {
+        name: "ls",
+        summary: "List directory contents (-l for details, -a for hidden files)",
+        admin_only: false,
+        run: ls::run,
+    },
+    Command {
+        name: "cd",
+        summary: "Change the working directory (cd - goes back)",
+        admin_only: false,
+        run: cd::run,
+    },
+    Command {
+        name: "pwd",
+        summary: "Print the working directory",
+        admin_only: false,
+        run: pwd::run,
+    },
+    Command {
+        name: "mkdir",
+        summary: "Create directories (-p creates parents)",
+        admin_only: false,
+        run: mkdir::run,
+    },
+    Command {
+        name: "rmdir",
+        summary: "Remove empty directories",
+        admin_only: false,
+        run: rmdir::run,
+    },
+    Command {
+        name: "touch",
+        summary: "Create empty files or update their timestamps",
+        admin_only: false,
+        run: touch::run,
+    },
+    Command {
+        name: "cat",
+        summary: "Print the contents of files",
+        admin_only: false,
+        run: cat::run,
+    },
+    Command {
+        name: "edit",
+        summary: "Edit a text file with a line editor ('h' inside for help)",
+        admin_only: false,
+        run: edit::run,
+    },
+    Command {
+        name: "rm",
+        summary: "Remove files (-r for directories, -f ignores missing ones)",
+        admin_only: false,
+        run: rm::run,
+    },
+    Command {
+        name: "cp",
+        summary: "Copy files (-r for directories)",
+        admin_only: false,
+        run: cp::run,
+    },
+    Command {
+        name: "mv",
+        summary: "Move or rename files and directories",
+        admin_only: false,
+        run: mv::run,
+    },
+    Command {
+        name: "chmod",
+        summary: "Change permission bits (chmod [-R] 750 FILE, chmod u+x FILE)",
+        admin_only: false,
+        run: chmod::run,
+    },
+    Command {
+        name: "chown",
+        summary: "Give files to another user (admin only for the owner)",
+        admin_only: false,
+        run: chown::run,
+    },
+    Command {
+        name: "chgrp",
+        summary: "Change the group of files",
+        admin_only: false,
+        run: chgrp::run,
+    },
+    Command {
+        name: "group",
+        summary: "List groups, or manage them (admin only)",
+        admin_only: false,
+        run: group::run,
+    },
+    Command {
+        name: "tar",
+        summary: "Create (-c), extract (-x) or list (-t) ustar archives",
+        admin_only: false,
+        run: tar::run,
+    },
+    Command {
+        name: "import",
+        summary: "Copy files from the host into the filesystem (admin only)",
+        admin_only: true,
+        run: import::run,
+    },
+    Command {
+        name: "export",
+        summary: "Copy files from the filesystem to the host (admin only)",
+        admin_only: true,
+        run: export::run,
+    },
+    Command {
+        name: "mount",
+        summary: "List mounts, or mount a host directory (mount --bind-host DIR /mnt/x [--ro], admin only)",
+        admin_only: false,
+        run: mount::run,
+    },
+    Command {
+        name: "umount",
+        summary: "Unmount a mounted filesystem (admin only)",
+        admin_only: true,
+        run: umount::run,
+    },
+    Command {
+        name: "fsck",
+        summary: "Check the filesystem image for errors, -y to repair them (admin only)",
+        admin_only: true,
+        run: fsck::run,
+    },
+    Command {
+        name: "quota",
+        summary: "Show disk usage against quota (-a for all users, admins only)",
+        admin_only: false,
+        run: quota::run,
+    },
+    Command {
+        name: "setquota",
+        summary: "Set a user's disk quota or the grace period (admin only)",
+        admin_only: true,
+        run: setquota::run,
+    },
+    Command {
+        name: "stat",
+        summary: "Show file details from the virtual filesystem",
+        admin_only: false,
+        run: stat::run,
+    },
+    Command {
+        name: "find",
+        summary: "Find files by -name, -type, -user, -size and -mtime",
+        admin_only: false,
+        run: find::run,
+    },
+    Command {
+        name: "grep",
+        summary: "Print lines matching a regular expression (-r for directories, -n for line numbers)",
+        admin_only: false,
+        run: grep::run,
+    },
+    Command {
+        name: "du",
+        summary: "Show disk usage per directory (-h for readable sizes, -s for totals only)",
+        admin_only: false,
+        run: du::run,
+    },
+    Command {
+        name: "ps",
+        summary: "List processes (-e for everyone's)",
+        admin_only: false,
+        run: ps::run,
+    },
+    Command {
+        name: "jobs",
+        summary: "List background jobs started with '&'",
+        admin_only: false,
+        run: jobs::run,
+    },
+    Command {
+        name: "fg",
+        summary: "Bring a background job to the foreground",
+        admin_only: false,
+        run: fg::run,
+    },
+    Command {
+        name: "bg",
+        summary: "Continue a stopped job in the background",
+        admin_only: false,
+        run: bg::run,
+    },
+    Command {
+        name: "kill",
+        summary: "Send a signal to processes or jobs (-s SIGNAL, TERM by default; -l lists signals)",
+        admin_only: false,
+        run: kill::run,
+    },
+    Command {
+        name: "sleep",
+        summary: "Wait for a number of seconds",
+        admin_only: false,
+        run: sleep::run,
+    },
+    Command {
+        name: "nice",
+        summary: "Run a command with a lower scheduling priority (-n ADJUSTMENT, 10 by default)",
+        admin_only: false,
+        run: nice::run,
+    },
+    Command {
+        name: "renice",
+        summary: "Change the scheduling priority of running processes or jobs",
+        admin_only: false,
+        run: renice::run,
+    },
+    Command {
+        name: "top",
+        summary: "Show processes by scheduler ticks used (-n ITERATIONS, -d SECONDS)",
+        admin_only: false,
+        run: top::run,
+    },
+    Command {
+        name: "watch",
+        summary: "Run a command repeatedly (-n INTERVAL, -c COUNT)",
+        admin_only: false,
+        run: watch::run,
+    },
+    Command {
+        name: "sched",
+        summary: "Show or set the scheduling policy",
+        admin_only: false,
+        run: sched::run,
+    },
+    Command {
+        name: "dmesg",
+        summary: "Show the messages logged since boot (-l LEVEL, -f FACILITY, -c, admin only)",
+        admin_only: true,
+        run: dmesg::run,
+    },
+    Command {
+        name: "syslog",
+        summary: "Show the system log, filtered by level, facility and time (admin only)",
+        admin_only: true,
+        run: syslog::run,
+    },
+    Command {
+        name: "service",
+        summary: "Show services, or start or stop one (admin)",
+        admin_only: false,
+        run: service::run,
+    },
+    Command {
+        name: "shutdown",
+        summary: "Stop the services, save the filesystem and halt",
+        admin_only: true,
+        run: shutdown::run,
+    },
+    Command {
+        name: "reboot",
+        summary: "Shut down and boot MiniKern again",
+        admin_only: true,
+        run: reboot::run,
+    },
+    Command {
+        name: "crontab",
+        summary: "List (-l), edit (-e) or remove (-r) your scheduled jobs",
+        admin_only: false,
+        run: crontab::run,
+    },
+    Command {
+        name: "who",
+        summary: "List who is logged in, and from where",
+        admin_only: false,
+        run: who::run,
+    },
+    Command {
+        name: "write",
+        summary: "Send a message to another user's terminals",
+        admin_only: false,
+        run: write::run,
+    },
+    Command {
+        name: "wall",
+        summary: "Send a message to every logged-in terminal (admin only)",
+        admin_only: true,
+        run: wall::run,
+    },
+    Command {
+        name: "mesg",
+        summary: "Allow (y) or refuse (n) messages from other users",
+        admin_only: false,
+        run: mesg::run,
+    },
+    Command {
+        name: "mail",
+        summary: "Send, list, read and delete mail (send, read N, delete N)",
+        admin_only: false,
+        run: mail::run,
+    },
+    Command {
+        name: "apitoken",
+        summary: "List, create or revoke tokens for the HTTP admin API (admin only)",
+        admin_only: true,
+        run: apitoken::run,
+    },
+    Command {
+        name: "logout",
+        summary: "Log out and login as another user",
+        admin_only: false,
+        run: run_logout,
+    },
+    Command {
+        name: "exit",
+        summary: "Log out and exit the program",
+        admin_only: false,
+        run: run_exit,
+    },
+    Command {
+        name: "help",
+        summary: "Show this help message",
+        admin_only: false,
+        run: help::run,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+fn run_logout(
+    _session: &mut Session,
+    _args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    Ok(CommandOutcome::success("Logging out. Please log in again.")
+        .with_effect(SessionEffect::Logout))
+}
+
+fn run_exit(
+    _session: &mut Session,
+    _args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    Ok(CommandOutcome::success("Logging out. Goodbye!")
+        .with_effect(SessionEffect::Exit))
+}
@@ -0,0 +1,161 @@
+use crate::audit;
+use crate::auth::load_users;
+use crate::commands::files::group_exists;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_PERMISSION, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::syslog::{self, Facility, Level};
+use crate::vfs::backend::Backend;
+use crate::vfs::hostfs::HostFs;
+use crate::vfs::{self, path, MountOptions, IMAGE_FILE_PATH};
+use std::path::Path;
+
+const USAGE: &str = "Usage: mount --bind-host HOSTDIR MOUNTPOINT [--ro] [--owner USER[:GROUP]]";
+
+/// Lists the mount table, or mounts a host directory into the filesystem
+/// (admins only)
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    if rest.is_empty() {
+        return list(&options);
+    }
+    if !session.user.is_admin {
+        return Ok(CommandOutcome::failure(
+            EXIT_PERMISSION,
+            "You must be an admin to mount filesystems.",
+        ));
+    }
+
+    let mut host_dir = None;
+    let mut operands = Vec::new();
+    let mut read_only = false;
+    let mut owner_spec = None;
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--bind-host" => host_dir = iter.next(),
+            "--ro" => read_only = true,
+            "--rw" => read_only = false,
+            "--owner" => owner_spec = iter.next(),
+            _ if arg.starts_with('-') => return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+            _ => operands.push(arg),
+        }
+    }
+    let (Some(host_dir), [point]) = (host_dir, operands.as_slice()) else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+
+    // Mounted files belong to the admin mounting them unless told otherwise
+    let owner_spec = owner_spec.unwrap_or(&session.user.username);
+    let (owner, group) = match owner_spec.split_once(':') {
+        Some((owner, group)) => (owner, group),
+        None => (owner_spec.as_str(), owner_spec.as_str()),
+    };
+    let users = load_users()?;
+    let point = path::resolve(&session.cwd, point);
+
+    let mut fs = vfs::lock();
+    if !users.iter().any(|u| u.username == owner) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("User '{}' not found.", owner),
+        ));
+    }
+    if !group_exists(&fs, &users, group) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("Group '{}' not found.", group),
+        ));
+    }
+    let backend = match HostFs::new(Path::new(host_dir)) {
+        Ok(backend) => backend,
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("{}: {}", host_dir, e),
+            ))
+        }
+    };
+    let detail = format!(
+        "{} ({},owner={}:{})",
+        backend.source(),
+        if read_only { "ro" } else { "rw" },
+        owner,
+        group
+    );
+    let options = MountOptions {
+        read_only,
+        owner: owner.to_string(),
+        group: group.to_string(),
+    };
+    match fs.mount(&session.user, &point, Box::new(backend), options) {
+        Ok(()) => {
+            let _ = audit::record(&session.user.username, "mount", &point, &detail);
+            let _ = syslog::record(
+                Level::Info,
+                Facility::Fs,
+                &format!("mounted {} on {}", detail, point),
+            );
+            Ok(CommandOutcome::ok())
+        }
+        Err(e) => Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{}: {}", point, e),
+        )),
+    }
+}
+
+fn list(options: &OutputOptions) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let mounts = vfs::lock().mounts();
+    if options.is_human() {
+        println!("{} on / type image (rw)", IMAGE_FILE_PATH);
+        for mount in &mounts {
+            println!(
+                "{} on {} type {} ({},owner={},group={})",
+                mount.source,
+                mount.point,
+                mount.kind,
+                if mount.options.read_only { "ro" } else { "rw" },
+                mount.options.owner,
+                mount.options.group
+            );
+        }
+        return Ok(CommandOutcome::ok());
+    }
+
+    let mut table = Table::new(&["point", "type", "source", "read_only", "owner", "group"]);
+    table.push(vec![
+        "/".into(),
+        "image".into(),
+        IMAGE_FILE_PATH.into(),
+        false.into(),
+        None::<String>.into(),
+        None::<String>.into(),
+    ]);
+    for mount in mounts {
+        table.push(vec![
+            mount.point.into(),
+            mount.kind.into(),
+            mount.source.into(),
+            mount.options.read_only.into(),
+            mount.options.owner.into(),
+            mount.options.group.into(),
+        ]);
+    }
+    match output::render(&table, options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("{}\n{} {}", e, USAGE, OUTPUT_USAGE),
+            ))
+        }
+    }
+    Ok(CommandOutcome::ok())
+}
@@ -0,0 +1,61 @@
+use crate::auth::CurrentUser;
+use crate::commands::files::{copy_tree, parse_flags, Errors};
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path, Vfs, VfsError};
+
+const USAGE: &str = "Usage: mv SOURCE... DEST";
+
+/// Moves or renames files and directories. With several sources, or when
+/// DEST is an existing directory, they're moved inside DEST. Moves into or
+/// out of a mounted filesystem copy, then remove the original.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let operands = match parse_flags(args, "") {
+        Ok((_, operands)) if operands.len() >= 2 => operands,
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+    };
+    let Some((dest, sources)) = operands.split_last() else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let dest_full = path::resolve(&session.cwd, dest);
+
+    let mut fs = vfs::lock();
+    let into_dir = fs.stat(&session.user, &dest_full).map(|m| m.is_dir()).unwrap_or(false);
+    if sources.len() > 1 && !into_dir {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{}: Not a directory", dest),
+        ));
+    }
+
+    let mut errors = Errors::default();
+    for source in sources {
+        let from = path::resolve(&session.cwd, source);
+        let to = if into_dir {
+            path::join(&dest_full, path::file_name(&from))
+        } else {
+            dest_full.clone()
+        };
+        let result = match fs.rename(&session.user, &from, &to) {
+            // Between filesystems, moving is copying and removing
+            Err(VfsError::CrossDevice) => move_across(&mut fs, &session.user, &from, &to),
+            result => result,
+        };
+        if let Err(e) = result {
+            errors.report("mv", source, e);
+        }
+    }
+    Ok(errors.outcome())
+}
+
+fn move_across(fs: &mut Vfs, who: &CurrentUser, from: &str, to: &str) -> Result<(), VfsError> {
+    copy_tree(fs, who, from, to)?;
+    if fs.stat(who, from)?.is_dir() {
+        fs.remove_tree(who, from)
+    } else {
+        fs.unlink(who, from)
+    }
+}
@@ -0,0 +1,38 @@
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::process;
+use crate::session::Session;
+use crate::terminal;
+
+const USAGE: &str = "Usage: nice [-n ADJUSTMENT] [COMMAND [ARG]...]";
+
+/// How much nicer `nice` makes a command unless told otherwise
+const DEFAULT_ADJUSTMENT: i32 = 10;
+
+/// Runs a command with its niceness raised by an adjustment, 10 by
+/// default, so the scheduler favours other tasks. Only admins can lower
+/// it. Prints the current niceness given no command.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (adjustment, command) = match args {
+        [flag, n, command @ ..] if flag == "-n" => (n.parse().ok(), command),
+        command => (Some(DEFAULT_ADJUSTMENT), command),
+    };
+    let Some(adjustment) = adjustment else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let current = process::nice(session.pid);
+    let Some((name, args)) = command.split_first() else {
+        println!("{}", current);
+        return Ok(CommandOutcome::ok());
+    };
+
+    if let Err(e) = process::set_nice(&session.user, session.pid, current + adjustment) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("cannot set niceness: {}", e),
+        ));
+    }
+    Ok(terminal::run_command(session, name, args))
+}
@@ -0,0 +1,75 @@
+/// Exit status of a command that succeeded
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit status of a command that failed
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status for bad arguments or shell syntax
+pub const EXIT_USAGE: i32 = 2;
+/// Exit status when the user isn't allowed to run the command
+pub const EXIT_PERMISSION: i32 = 126;
+/// Exit status when no command by that name exists
+pub const EXIT_NOT_FOUND: i32 = 127;
+
+/// What a command did to the session, beyond its own output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEffect {
+    None,
+    /// End the session and go back to the login prompt
+    Logout,
+    /// End the session and quit MiniKern
+    Exit,
+    /// The logged-in account was changed (password, role, deleted), so the
+    /// session no longer matches it and the user has to log in again
+    IdentityChanged,
+}
+
+/// Result of running any command
+#[derive(Debug, Clone)]
+pub struct CommandOutcome {
+    pub status: i32,
+    /// Printed by the dispatcher once the command has finished
+    pub message: Option<String>,
+    pub effect: SessionEffect,
+}
+
+impl CommandOutcome {
+    pub fn ok() -> Self {
+        CommandOutcome {
+            status: EXIT_SUCCESS,
+            message: None,
+            effect: SessionEffect::None,
+        }
+    }
+
+    pub fn success(message: impl Into<String>) -> Self {
+        CommandOutcome {
+            message: Some(message.into()),
+            ..Self::ok()
+        }
+    }
+
+    pub fn failure(status: i32, message: impl Into<String>) -> Self {
+        CommandOutcome {
+            status,
+            message: Some(message.into()),
+            effect: SessionEffect::None,
+        }
+    }
+
+    /// A result with no message, for commands that already reported their
+    /// errors as they went
+    pub fn exited(status: i32) -> Self {
+        CommandOutcome {
+            status,
+            ..Self::ok()
+        }
+    }
+
+    pub fn with_effect(mut self, effect: SessionEffect) -> Self {
+        self.effect = effect;
+        self
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.status == EXIT_SUCCESS
+    }
+}
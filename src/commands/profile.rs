@@ -0,0 +1,69 @@
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_PERMISSION, EXIT_USAGE};
+use crate::profile::{self, SYSTEM_PROFILE_PATH};
+use crate::session::Session;
+
+const USAGE: &str =
+    "Usage: profile [--system] [show | add <line> | del <number> | clear]";
+
+/// Manages startup files. Without `--system` it edits the current user's
+/// `.minikernrc`; with it, the system-wide profile (admin only).
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let system = args.first().map(|a| a == "--system").unwrap_or(false);
+    let args = if system { &args[1..] } else { args };
+
+    if system && !session.user.is_admin {
+        return Ok(CommandOutcome::failure(
+            EXIT_PERMISSION,
+            "You must be an admin to change the system profile.",
+        ));
+    }
+
+    let path = if system {
+        SYSTEM_PROFILE_PATH.to_string()
+    } else {
+        profile::user_rc_path(&session.user.username)
+    };
+    let mut lines = profile::read_lines(&session.user, &path)?;
+
+    match args.first().map(|s| s.as_str()) {
+        None | Some("show") => {
+            if lines.is_empty() {
+                println!("(empty)");
+            }
+            for (i, line) in lines.iter().enumerate() {
+                println!("{:>3}  {}", i + 1, line);
+            }
+        }
+        Some("add") => {
+            if args.len() < 2 {
+                return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+            }
+            lines.push(args[1..].join(" "));
+            profile::write_lines(&session.user, &path, &lines)?;
+            println!("Line added.");
+        }
+        Some("del") => {
+            let Some(number) = args.get(1).and_then(|n| n.parse::<usize>().ok()) else {
+                return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+            };
+            if number == 0 || number > lines.len() {
+                return Ok(CommandOutcome::failure(
+                    EXIT_FAILURE,
+                    format!("No line {} in profile.", number),
+                ));
+            }
+            lines.remove(number - 1);
+            profile::write_lines(&session.user, &path, &lines)?;
+            println!("Line {} removed.", number);
+        }
+        Some("clear") => {
+            profile::write_lines(&session.user, &path, &[])?;
+            println!("Profile cleared.");
+        }
+        Some(_) => return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+    }
+    Ok(CommandOutcome::ok())
+}
@@ -0,0 +1,68 @@
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::process;
+use crate::session::Session;
+
+/// Lists the user's processes, or everyone's with `-e`
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let usage = || format!("Usage: ps [-e] {}", OUTPUT_USAGE);
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let everyone = match rest.as_slice() {
+        [] => false,
+        [flag] if flag == "-e" => true,
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, usage())),
+    };
+
+    let processes: Vec<_> = process::list()
+        .into_iter()
+        .filter(|p| everyone || p.user.username == session.user.username)
+        .collect();
+
+    if options.is_human() {
+        println!(
+            "{:>5} {:>5} {:<12} {:<4} {:<8} COMMAND",
+            "PID", "PPID", "USER", "STAT", "START"
+        );
+        for p in &processes {
+            println!(
+                "{:>5} {:>5} {:<12} {:<4} {:<8} {}",
+                p.pid,
+                p.ppid,
+                p.user.username,
+                p.state.code(),
+                clock::format_hms(p.started),
+                p.command
+            );
+        }
+        return Ok(CommandOutcome::ok());
+    }
+
+    let mut table = Table::new(&["pid", "ppid", "user", "state", "started", "command"]);
+    for p in processes {
+        table.push(vec![
+            u64::from(p.pid).into(),
+            u64::from(p.ppid).into(),
+            p.user.username.into(),
+            p.state.code().to_string().into(),
+            p.started.into(),
+            p.command.into(),
+        ]);
+    }
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("{}\n{}", e, usage()),
+            ))
+        }
+    }
+    Ok(CommandOutcome::ok())
+}
@@ -0,0 +1,9 @@
+use crate::commands::CommandOutcome;
+use crate::session::Session;
+
+pub fn run(
+    session: &mut Session,
+    _args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    Ok(CommandOutcome::success(session.cwd.clone()))
+}
@@ -0,0 +1,130 @@
+use crate::auth::load_users;
+use crate::clock;
+use crate::commands::files::Errors;
+use crate::commands::{CommandOutcome, EXIT_PERMISSION, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, Value, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::vfs::quota::{format_size, Limit, Quota, Usage};
+use crate::vfs::{self, Vfs};
+
+/// Shows disk usage against quota. Users see their own; admins can name
+/// other users or see everyone with `-a`.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let users = load_users()?;
+    let names: Vec<String> = match rest.as_slice() {
+        [] => vec![session.user.username.clone()],
+        [flag] if flag == "-a" => users.iter().map(|u| u.username.clone()).collect(),
+        names if names.iter().any(|n| n.starts_with('-')) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("Usage: quota [-a | USER...] {}", OUTPUT_USAGE),
+            ))
+        }
+        names => names.to_vec(),
+    };
+    if !session.user.is_admin && names.iter().any(|n| *n != session.user.username) {
+        return Ok(CommandOutcome::failure(
+            EXIT_PERMISSION,
+            "You can only see your own quota.",
+        ));
+    }
+
+    let mut errors = Errors::default();
+    let fs = vfs::lock();
+    let mut table = Table::new(&[
+        "user",
+        "bytes",
+        "bytes_soft",
+        "bytes_hard",
+        "bytes_grace",
+        "files",
+        "files_soft",
+        "files_hard",
+        "files_grace",
+    ]);
+    for name in &names {
+        if !users.iter().any(|u| &u.username == name) {
+            errors.report("quota", name, "No such user");
+            continue;
+        }
+        let mut row = vec![name.as_str().into()];
+        row.extend(usage_cells(&fs, name, true));
+        table.push(row);
+    }
+
+    if options.is_human() {
+        println!(
+            "Grace period: {}",
+            clock::format_duration(fs.grace_period())
+        );
+        println!(
+            "{:<12} {:>8} {:>8} {:>8} {:>8}  {:>7} {:>7} {:>7} {:>8}",
+            "USER", "USED", "SOFT", "HARD", "GRACE", "FILES", "SOFT", "HARD", "GRACE"
+        );
+        for name in names
+            .iter()
+            .filter(|n| users.iter().any(|u| &u.username == *n))
+        {
+            let (usage, quota) = (fs.usage(name), fs.quota(name));
+            let now = clock::unix_now();
+            let grace = |limit: &Limit, used| match limit.grace_left(used, now, fs.grace_period()) {
+                None => "-".to_string(),
+                Some(0) => "expired".to_string(),
+                Some(left) => clock::format_duration(left),
+            };
+            let limit = |value: u64, format: fn(u64) -> String| {
+                if value == 0 {
+                    "-".to_string()
+                } else {
+                    format(value)
+                }
+            };
+            println!(
+                "{:<12} {:>8} {:>8} {:>8} {:>8}  {:>7} {:>7} {:>7} {:>8}",
+                name,
+                format_size(usage.bytes),
+                limit(quota.bytes.soft, format_size),
+                limit(quota.bytes.hard, format_size),
+                grace(&quota.bytes, usage.bytes),
+                usage.inodes,
+                limit(quota.inodes.soft, |n| n.to_string()),
+                limit(quota.inodes.hard, |n| n.to_string()),
+                grace(&quota.inodes, usage.inodes),
+            );
+        }
+        return Ok(errors.outcome());
+    }
+
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(errors.outcome())
+}
+
+/// Usage and limits of one user, for `quota` and `listusr --long`:
+/// bytes, soft and hard byte limits, then the same for files. With
+/// `grace`, each group also gets the seconds of grace left.
+pub fn usage_cells(fs: &Vfs, username: &str, grace: bool) -> Vec<Value> {
+    let usage: Usage = fs.usage(username);
+    let quota: Quota = fs.quota(username);
+    let now = clock::unix_now();
+    let limit = |n: u64| Value::from((n != 0).then_some(n));
+    let mut cells = Vec::new();
+    for (used, limits) in [(usage.bytes, quota.bytes), (usage.inodes, quota.inodes)] {
+        cells.push(used.into());
+        cells.push(limit(limits.soft));
+        cells.push(limit(limits.hard));
+        if grace {
+            cells.push(limits.grace_left(used, now, fs.grace_period()).into());
+        }
+    }
+    cells
+}
@@ -0,0 +1,16 @@
+use crate::audit;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::init;
+use crate::session::Session;
+
+/// Shuts down like `shutdown`, then boots MiniKern again
+pub fn run(
+    session: &mut Session, // Assumed admin by the dispatcher
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if !args.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: reboot"));
+    }
+    let _ = audit::record(&session.user.username, "reboot", "system", "");
+    init::shutdown(true)
+}
@@ -0,0 +1,59 @@
+use crate::audit;
+use crate::commands::files::Errors;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::process;
+use crate::session::Session;
+
+const USAGE: &str = "Usage: renice [-n] NICENESS PID|%JOB...";
+
+/// Sets the niceness of running processes or jobs. Users can only make
+/// their own processes nicer; admins can set any value on any process.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let args = match args {
+        [flag, rest @ ..] if flag == "-n" => rest,
+        args => args,
+    };
+    let Some((nice, targets)) = args.split_first() else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let Ok(nice) = nice.parse::<i32>() else {
+        return Ok(CommandOutcome::failure(
+            EXIT_USAGE,
+            format!("invalid niceness '{}'\n{}", nice, USAGE),
+        ));
+    };
+    if targets.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    }
+
+    let shell = process::parent(session.pid);
+    let mut errors = Errors::default();
+    for target in targets {
+        let pid = if target.starts_with('%') {
+            process::find_job(shell, Some(target)).map(|job| job.pid)
+        } else {
+            target.parse().ok()
+        };
+        let Some(pid) = pid else {
+            errors.report("renice", target, "no such process or job");
+            continue;
+        };
+        let old = process::nice(pid);
+        let owner = process::get(pid).map(|p| p.user.username);
+        match process::set_nice(&session.user, pid, nice) {
+            Ok(()) => {
+                let new = process::nice(pid);
+                println!("{}: old niceness {}, new niceness {}", pid, old, new);
+                if let Some(owner) = owner.filter(|o| *o != session.user.username) {
+                    let detail = format!("niceness {} to {} of a process of {}", old, new, owner);
+                    let _ = audit::record(&session.user.username, "renice", target, &detail);
+                }
+            }
+            Err(e) => errors.report("renice", target, e),
+        }
+    }
+    Ok(errors.outcome())
+}
@@ -0,0 +1,47 @@
+use crate::commands::files::{parse_flags, Errors};
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path, VfsError};
+
+const USAGE: &str = "Usage: rm [-r] [-f] PATH...";
+
+/// Removes files. `-r` removes directories and everything in them, and
+/// `-f` ignores paths that don't exist.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (flags, targets) = match parse_flags(args, "rRf") {
+        Ok((flags, targets)) if !targets.is_empty() || flags.contains('f') => (flags, targets),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+    };
+    let recursive = flags.contains(['r', 'R']);
+    let force = flags.contains('f');
+
+    let mut errors = Errors::default();
+    let mut fs = vfs::lock();
+    for target in &targets {
+        if matches!(path::file_name(target), "." | "..") {
+            errors.report("rm", target, "Refusing to remove '.' or '..'");
+            continue;
+        }
+        let full = path::resolve(&session.cwd, target);
+        if full == "/" {
+            errors.report("rm", target, "Refusing to remove the root directory");
+            continue;
+        }
+
+        let result = match fs.stat(&session.user, &full) {
+            Ok(meta) if meta.is_dir() && !recursive => Err(VfsError::IsADirectory),
+            Ok(meta) if meta.is_dir() => fs.remove_tree(&session.user, &full),
+            Ok(_) => fs.unlink(&session.user, &full),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {}
+            Err(VfsError::NotFound) if force => {}
+            Err(e) => errors.report("rm", target, e),
+        }
+    }
+    Ok(errors.outcome())
+}
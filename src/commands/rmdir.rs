@@ -0,0 +1,23 @@
+use crate::commands::files::Errors;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path};
+
+/// Removes empty directories
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: rmdir DIR..."));
+    }
+
+    let mut errors = Errors::default();
+    let mut fs = vfs::lock();
+    for dir in args {
+        if let Err(e) = fs.rmdir(&session.user, &path::resolve(&session.cwd, dir)) {
+            errors.report("rmdir", dir, e);
+        }
+    }
+    Ok(errors.outcome())
+}
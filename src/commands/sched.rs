@@ -0,0 +1,73 @@
+use crate::audit;
+use crate::commands::{CommandOutcome, EXIT_PERMISSION, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::sched;
+use crate::session::Session;
+
+/// Shows the scheduling policy, or lets an admin switch to another. The
+/// output flags list every policy, marking the one in use.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let usage = || {
+        format!(
+            "Usage: sched [{}] | sched {}",
+            sched::POLICIES.join("|"),
+            OUTPUT_USAGE
+        )
+    };
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let name = match rest.as_slice() {
+        [] if options.is_human() => {
+            println!("{}", sched::policy());
+            return Ok(CommandOutcome::ok());
+        }
+        [] => return Ok(list(&options, &usage())),
+        [name] if options.is_human() => name,
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, usage())),
+    };
+    let Some(policy) = sched::policy_named(name) else {
+        return Ok(CommandOutcome::failure(
+            EXIT_USAGE,
+            format!("unknown policy '{}'\n{}", name, usage()),
+        ));
+    };
+    if !session.user.is_admin {
+        return Ok(CommandOutcome::failure(
+            EXIT_PERMISSION,
+            "You must be an admin to change the scheduling policy.",
+        ));
+    }
+
+    let old = sched::policy();
+    sched::set_policy(policy);
+    let _ = audit::record(
+        &session.user.username,
+        "sched",
+        name,
+        &format!("policy was {}", old),
+    );
+    Ok(CommandOutcome::success(format!(
+        "Scheduling policy set to {}.",
+        name
+    )))
+}
+
+fn list(options: &OutputOptions, usage: &str) -> CommandOutcome {
+    let current = sched::policy();
+    let mut table = Table::new(&["policy", "current"]);
+    for &name in sched::POLICIES {
+        table.push(vec![name.into(), (name == current).into()]);
+    }
+    match output::render(&table, options) {
+        Ok(rendered) => {
+            print!("{}", rendered);
+            CommandOutcome::ok()
+        }
+        Err(e) => CommandOutcome::failure(EXIT_USAGE, format!("{}\n{}", e, usage)),
+    }
+}
@@ -0,0 +1,117 @@
+use crate::audit;
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_PERMISSION, EXIT_USAGE};
+use crate::init::{self, Status};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+
+/// Shows what the services declared in the boot configuration are doing,
+/// or lets an admin start or stop one. Starting a service starts what it
+/// comes after first; stopping one stops what comes after it first.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let usage = || {
+        format!(
+            "Usage: service start|stop NAME | status [NAME] {}",
+            OUTPUT_USAGE
+        )
+    };
+    let (action, rest) = match args {
+        [action, rest @ ..] => (action.as_str(), rest),
+        [] => ("status", args),
+    };
+    let (starting, name) = match (action, rest) {
+        ("status", rest) => return status(rest, usage),
+        ("start", [name]) => (true, name),
+        ("stop", [name]) => (false, name),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, usage())),
+    };
+    if !session.user.is_admin {
+        return Ok(CommandOutcome::failure(
+            EXIT_PERMISSION,
+            "You must be an admin to start or stop services.",
+        ));
+    }
+
+    let result = if starting {
+        init::start(name)
+    } else {
+        init::stop(name)
+    };
+    let done = match result {
+        Ok(done) => done,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_FAILURE, e)),
+    };
+    for service in &done {
+        println!(
+            "{} {}.",
+            if starting { "Started" } else { "Stopped" },
+            service
+        );
+        let _ = audit::record(&session.user.username, action, service, "service");
+    }
+    Ok(CommandOutcome::ok())
+}
+
+fn status(
+    args: &[String],
+    usage: impl Fn() -> String,
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let name = match rest.as_slice() {
+        [] => None,
+        [name] => Some(name.as_str()),
+        _ => return Ok(CommandOutcome::failure(EXIT_USAGE, usage())),
+    };
+    let services: Vec<_> = init::status()
+        .into_iter()
+        .filter(|(service, _)| name.is_none_or(|name| service.name == name))
+        .collect();
+    if let (Some(name), true) = (name, services.is_empty()) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{}: no such service", name),
+        ));
+    }
+
+    let mut table = Table::new(&[
+        "SERVICE", "ENABLED", "STATUS", "PID", "SINCE", "AFTER", "COMMAND",
+    ]);
+    for (service, status) in services {
+        let (pid, since) = match status {
+            Status::Running(since, pid) => (
+                pid.map(|pid| pid.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                clock::format_datetime(since),
+            ),
+            _ => ("-".to_string(), "-".to_string()),
+        };
+        table.push(vec![
+            service.name.into(),
+            service.enabled.into(),
+            status.describe().into(),
+            pid.into(),
+            since.into(),
+            service.after.join(",").into(),
+            service
+                .command
+                .unwrap_or_else(|| "(built-in)".to_string())
+                .into(),
+        ]);
+    }
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("{}\n{}", e, usage()),
+            ))
+        }
+    }
+    Ok(CommandOutcome::ok())
+}
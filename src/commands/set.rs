@@ -0,0 +1,23 @@
+use crate::commands::{self, CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+
+/// `set NAME=value` or `set NAME value`. With no arguments it lists the
+/// environment, like `env`.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (name, value) = match args {
+        [] => return commands::env::run(session, args),
+        [assignment] => match assignment.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => (assignment.clone(), String::new()),
+        },
+        [name, rest @ ..] => (name.clone(), rest.join(" ")),
+    };
+
+    if let Err(e) = session.set_var(&name, &value) {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, e));
+    }
+    Ok(CommandOutcome::ok())
+}
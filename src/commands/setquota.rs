@@ -0,0 +1,94 @@
+use crate::audit;
+use crate::auth::load_users;
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs;
+use crate::vfs::quota::{format_size, parse_size, Limit, Quota};
+
+const USAGE: &str = "Usage: setquota USER BYTES_SOFT BYTES_HARD FILES_SOFT FILES_HARD\n       \
+                     setquota -t GRACE_PERIOD";
+
+/// Sets a user's disk quota, or the grace period for soft limits (admin
+/// only). Sizes take K, M, G suffixes, periods s, m, h, d; 0 is no limit.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if let [flag, period] = args {
+        if flag != "-t" {
+            return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+        }
+        let seconds = match clock::parse_duration(period) {
+            Ok(seconds) => seconds,
+            Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+        };
+        vfs::lock().set_grace_period(&session.user, seconds)?;
+        let period = clock::format_duration(seconds);
+        let _ = audit::record(&session.user.username, "setquota-grace", "", &period);
+        return Ok(CommandOutcome::success(format!(
+            "Grace period set to {}.",
+            period
+        )));
+    }
+
+    let [username, limits @ ..] = args else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let [bytes_soft, bytes_hard, files_soft, files_hard] = limits else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    if !load_users()?.iter().any(|u| &u.username == username) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("User '{}' not found.", username),
+        ));
+    }
+    let quota = match parse_quota(bytes_soft, bytes_hard, files_soft, files_hard) {
+        Ok(quota) => quota,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    for limit in [quota.bytes, quota.inodes] {
+        if limit.hard != 0 && limit.soft > limit.hard {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                "A soft limit can't be above its hard limit.",
+            ));
+        }
+    }
+
+    vfs::lock().set_quota(&session.user, username, quota)?;
+    let detail = if quota.is_unlimited() {
+        "none".to_string()
+    } else {
+        format!(
+            "bytes {}/{} files {}/{}",
+            format_size(quota.bytes.soft),
+            format_size(quota.bytes.hard),
+            quota.inodes.soft,
+            quota.inodes.hard
+        )
+    };
+    let _ = audit::record(&session.user.username, "setquota", username, &detail);
+    Ok(CommandOutcome::success(if quota.is_unlimited() {
+        format!("Quota for '{}' removed.", username)
+    } else {
+        format!("Quota for '{}' set.", username)
+    }))
+}
+
+fn parse_quota(
+    bytes_soft: &str,
+    bytes_hard: &str,
+    files_soft: &str,
+    files_hard: &str,
+) -> Result<Quota, String> {
+    let parse_count = |text: &str| {
+        text.parse::<u64>()
+            .map_err(|_| format!("'{}' is not a valid file count.", text))
+    };
+    Ok(Quota {
+        bytes: Limit::new(parse_size(bytes_soft)?, parse_size(bytes_hard)?),
+        inodes: Limit::new(parse_count(files_soft)?, parse_count(files_hard)?),
+    })
+}
@@ -0,0 +1,24 @@
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::sched;
+use crate::session::Session;
+
+/// Waits for a duration such as `5`, `30s` or `2m` of the virtual clock,
+/// giving up the CPU to other tasks meanwhile
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let [duration] = args else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: sleep DURATION"));
+    };
+    let seconds = match clock::parse_duration(duration) {
+        Ok(seconds) => seconds,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+
+    match sched::sleep(session.pid, seconds * sched::TICKS_PER_SECOND) {
+        Ok(()) => Ok(CommandOutcome::ok()),
+        Err(signal) => Ok(CommandOutcome::exited(signal.status())),
+    }
+}
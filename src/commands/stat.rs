@@ -0,0 +1,95 @@
+use crate::clock;
+use crate::commands::files::Errors;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path, FileType, Metadata};
+
+/// Shows inode details of files and directories in the virtual filesystem.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (options, paths) = match OutputOptions::parse(args) {
+        Ok((_, rest)) if rest.is_empty() => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("Usage: stat PATH... {}", OUTPUT_USAGE),
+            ))
+        }
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+
+    let mut found = Vec::new();
+    let mut errors = Errors::default();
+    for arg in &paths {
+        let full = path::resolve(&session.cwd, arg);
+        match vfs::lock().stat(&session.user, &full) {
+            Ok(meta) => found.push((full, meta)),
+            Err(e) => errors.report("stat", arg, e),
+        }
+    }
+
+    if options.is_human() {
+        for (full, meta) in &found {
+            print_stat(full, meta);
+        }
+        return Ok(errors.outcome());
+    }
+
+    let mut table = Table::new(&[
+        "path", "type", "mode", "owner", "group", "size", "inode", "links", "accessed",
+        "modified", "changed", "created",
+    ]);
+    for (full, meta) in &found {
+        table.push(vec![
+            full.as_str().into(),
+            type_name(meta.file_type).into(),
+            format!("{:04o}", meta.mode).into(),
+            meta.owner.as_str().into(),
+            meta.group.as_str().into(),
+            meta.size.into(),
+            meta.ino.into(),
+            (meta.nlink as u64).into(),
+            clock::format_datetime(meta.accessed).into(),
+            clock::format_datetime(meta.modified).into(),
+            clock::format_datetime(meta.changed).into(),
+            clock::format_datetime(meta.created).into(),
+        ]);
+    }
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(errors.outcome())
+}
+
+fn type_name(file_type: FileType) -> &'static str {
+    match file_type {
+        FileType::Regular => "file",
+        FileType::Directory => "directory",
+    }
+}
+
+fn print_stat(full: &str, meta: &Metadata) {
+    println!("  File: {}", full);
+    println!(
+        "  Type: {:<12} Size: {:<10} Inode: {:<6} Links: {}",
+        type_name(meta.file_type),
+        meta.size,
+        meta.ino,
+        meta.nlink
+    );
+    println!(
+        "  Mode: ({:04o}/{})  Owner: {}  Group: {}",
+        meta.mode,
+        meta.mode_string(),
+        meta.owner,
+        meta.group
+    );
+    println!("Access: {} UTC", clock::format_datetime(meta.accessed));
+    println!("Modify: {} UTC", clock::format_datetime(meta.modified));
+    println!("Change: {} UTC", clock::format_datetime(meta.changed));
+    println!(" Birth: {} UTC", clock::format_datetime(meta.created));
+}
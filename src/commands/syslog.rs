@@ -0,0 +1,100 @@
+use crate::clock;
+use crate::commands::dmesg::unknown;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, Format, OutputOptions, Table, OUTPUT_USAGE};
+use crate::session::Session;
+use crate::syslog::{self, Entry, Facility, Level};
+
+const USAGE: &str =
+    "Usage: syslog [-l LEVEL] [-f FACILITY] [--since TIME] [--until TIME] [-n COUNT]";
+
+/// Takes a date (`YYYY-MM-DD [HH:MM[:SS]]`) or how long ago (`30m`)
+fn parse_time(text: &str) -> Result<u64, String> {
+    match clock::parse_duration(text) {
+        Ok(ago) => Ok(clock::unix_now().saturating_sub(ago)),
+        Err(_) => clock::parse_datetime(text),
+    }
+}
+
+/// Shows the system log, rotated files included, newest messages last.
+/// `-l` leaves out messages less serious than LEVEL.
+pub fn run(
+    _session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let (mut options, rest) = match OutputOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+
+    let mut level = None;
+    let mut facility = None;
+    let mut since = None;
+    let mut until = None;
+    let mut count: Option<usize> = None;
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match (arg.as_str(), iter.next()) {
+            ("-l", Some(value)) => match Level::parse(value) {
+                Some(parsed) => level = Some(parsed),
+                None => return Ok(unknown("level", value)),
+            },
+            ("-f", Some(value)) => match Facility::parse(value) {
+                Some(parsed) => facility = Some(parsed),
+                None => return Ok(unknown("facility", value)),
+            },
+            ("--since", Some(value)) => match parse_time(value) {
+                Ok(time) => since = Some(time),
+                Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+            },
+            ("--until", Some(value)) => match parse_time(value) {
+                Ok(time) => until = Some(time),
+                Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+            },
+            ("-n", Some(value)) => match value.parse() {
+                Ok(n) => count = Some(n),
+                Err(_) => return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE)),
+            },
+            _ => {
+                return Ok(CommandOutcome::failure(
+                    EXIT_USAGE,
+                    format!("{} {}", USAGE, OUTPUT_USAGE),
+                ))
+            }
+        }
+    }
+
+    let mut entries: Vec<Entry> = syslog::load_entries()?
+        .into_iter()
+        .filter(|e| level.is_none_or(|level| e.level <= level))
+        .filter(|e| facility.is_none_or(|facility| e.facility == facility))
+        .filter(|e| since.is_none_or(|since| e.time >= since))
+        .filter(|e| until.is_none_or(|until| e.time <= until))
+        .collect();
+    if let Some(count) = count {
+        let skip = entries.len().saturating_sub(count);
+        entries.drain(..skip);
+    }
+
+    if entries.is_empty() && options.is_human() {
+        println!("No log entries.");
+        return Ok(CommandOutcome::ok());
+    }
+    if options.format == Format::Human {
+        options.format = Format::Columns;
+    }
+    let mut table = Table::new(&["time", "level", "facility", "message"]);
+    for entry in &entries {
+        table.push(vec![
+            clock::format_datetime(entry.time).into(),
+            entry.level.name().into(),
+            entry.facility.name().into(),
+            entry.message.as_str().into(),
+        ]);
+    }
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    }
+    Ok(CommandOutcome::ok())
+}
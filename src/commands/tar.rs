@@ -0,0 +1,329 @@
+use crate::auth::{load_users, CurrentUser, User};
+use crate::commands::files::{group_exists, Errors};
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::session::Session;
+use crate::tar::{self, Builder, Entry, EntryKind};
+use crate::vfs::{self, path, perm, FileType, Vfs, VfsError};
+
+const USAGE: &str = "Usage: tar -c [-v] -f ARCHIVE [-C DIR] PATH...\n       \
+                     tar -x [-v] -f ARCHIVE [-C DIR]\n       \
+                     tar -t [-v] -f ARCHIVE";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Create,
+    Extract,
+    List,
+}
+
+struct Options {
+    mode: Mode,
+    verbose: bool,
+    archive: String,
+    /// `-C`: where paths are taken from or extracted to
+    dir: Option<String>,
+    paths: Vec<String>,
+}
+
+fn parse_options(args: &[String]) -> Option<Options> {
+    let mut mode = None;
+    let mut verbose = false;
+    let mut archive = None;
+    let mut dir = None;
+    let mut paths = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let letters = match arg.strip_prefix('-') {
+            Some(letters) if !letters.is_empty() && arg != "--" => letters,
+            _ if arg == "--" => {
+                paths.extend(iter.by_ref().cloned());
+                break;
+            }
+            _ => {
+                paths.push(arg.clone());
+                continue;
+            }
+        };
+        for c in letters.chars() {
+            match c {
+                'c' | 'x' | 't' if mode.is_some() => return None,
+                'c' => mode = Some(Mode::Create),
+                'x' => mode = Some(Mode::Extract),
+                't' => mode = Some(Mode::List),
+                'v' => verbose = true,
+                // These take the next argument
+                'f' => archive = Some(iter.next()?.clone()),
+                'C' => dir = Some(iter.next()?.clone()),
+                _ => return None,
+            }
+        }
+    }
+    let mode = mode?;
+    if (mode == Mode::Create) == paths.is_empty() {
+        return None;
+    }
+    Some(Options {
+        mode,
+        verbose,
+        archive: archive?,
+        dir,
+        paths,
+    })
+}
+
+/// Creates, extracts and lists ustar archives in the virtual filesystem
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let Some(options) = parse_options(args) else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let archive = path::resolve(&session.cwd, &options.archive);
+    let dir = match &options.dir {
+        Some(dir) => path::resolve(&session.cwd, dir),
+        None => session.cwd.clone(),
+    };
+
+    let mut fs = vfs::lock();
+    let who = &session.user;
+    if options.mode == Mode::Create {
+        return create(&mut fs, who, &archive, &dir, &options);
+    }
+
+    let entries = match fs.read_file(who, &archive).map_err(|e| e.to_string()) {
+        Ok(bytes) => tar::parse(&bytes),
+        Err(e) => Err(e),
+    };
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_FAILURE,
+                format!("tar: {}: {}", options.archive, e),
+            ))
+        }
+    };
+    if options.mode == Mode::List {
+        for entry in &entries {
+            list(entry, options.verbose);
+        }
+        return Ok(CommandOutcome::ok());
+    }
+    extract(&mut fs, who, &entries, &dir, options.verbose)
+}
+
+fn create(
+    fs: &mut Vfs,
+    who: &CurrentUser,
+    archive: &str,
+    dir: &str,
+    options: &Options,
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let mut errors = Errors::default();
+    let mut builder = Builder::default();
+    for name in &options.paths {
+        // Like other tars, absolute paths are stored without the leading `/`
+        let base = if name.starts_with('/') { "/" } else { dir };
+        let Some(member) = member_name(name) else {
+            errors.report("tar", name, "Refusing to archive a path containing '..'");
+            continue;
+        };
+        let members = if member.is_empty() {
+            // `.` stands for everything in the directory
+            match fs.read_dir(who, base) {
+                Ok(entries) => entries.into_iter().map(|e| e.name).collect(),
+                Err(e) => {
+                    errors.report("tar", name, e);
+                    continue;
+                }
+            }
+        } else {
+            vec![member]
+        };
+        for member in members {
+            if let Err(e) = builder.append_tree(fs, who, base, &member) {
+                errors.report("tar", name, e);
+            }
+        }
+    }
+
+    let bytes = builder.finish();
+    if options.verbose {
+        for entry in tar::parse(&bytes).unwrap_or_default() {
+            println!("{}", entry.name);
+        }
+    }
+    if let Err(e) = fs.write_file(who, archive, &bytes) {
+        errors.report("tar", &options.archive, e);
+    }
+    Ok(errors.outcome())
+}
+
+fn list(entry: &Entry, verbose: bool) {
+    if !verbose {
+        println!("{}", entry.name);
+        return;
+    }
+    let (file_type, size) = match &entry.kind {
+        EntryKind::File(data) => (FileType::Regular, data.len()),
+        EntryKind::Directory => (FileType::Directory, 0),
+        EntryKind::Other(flag) => {
+            println!("?{:<9} {}  (type '{}')", "", entry.name, flag);
+            return;
+        }
+    };
+    println!(
+        "{} {}/{} {:>8} {}",
+        perm::mode_string(file_type, entry.mode & perm::MODE_MASK),
+        entry.owner,
+        entry.group,
+        size,
+        entry.name
+    );
+}
+
+fn extract(
+    fs: &mut Vfs,
+    who: &CurrentUser,
+    entries: &[Entry],
+    dir: &str,
+    verbose: bool,
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let users = load_users()?;
+    let mut errors = Errors::default();
+    // Directory modes are set last, so a read-only directory can still
+    // be filled
+    let mut directories = Vec::new();
+    for entry in entries {
+        // Nothing may land outside the target directory
+        if entry.name.starts_with('/') {
+            errors.report("tar", &entry.name, "Refusing to extract an absolute path");
+            continue;
+        }
+        let Some(member) = member_name(&entry.name).filter(|m| !m.is_empty()) else {
+            errors.report("tar", &entry.name, "Refusing to extract an unsafe path");
+            continue;
+        };
+        let target = path::resolve(dir, &member);
+
+        let result = match &entry.kind {
+            EntryKind::Directory => fs.mkdir_all(who, &target).map(|()| {
+                directories.push((entry, target.clone()));
+            }),
+            EntryKind::File(data) => path::split_parent(&target)
+                .map_or(Ok(()), |(parent, _)| fs.mkdir_all(who, &parent))
+                .and_then(|()| fs.write_file(who, &target, data))
+                .and_then(|()| set_attributes(fs, who, &users, entry, &target)),
+            EntryKind::Other(flag) => {
+                errors.report(
+                    "tar",
+                    &entry.name,
+                    format!("Unsupported entry type '{}'", flag),
+                );
+                continue;
+            }
+        };
+        match result {
+            Ok(()) if verbose => println!("{}", member),
+            Ok(()) => {}
+            Err(e) => errors.report("tar", &entry.name, e),
+        }
+    }
+    for (entry, target) in directories.into_iter().rev() {
+        if let Err(e) = set_attributes(fs, who, &users, entry, &target) {
+            errors.report("tar", &entry.name, e);
+        }
+    }
+    Ok(errors.outcome())
+}
+
+/// Applies the archived mode, and for admins the archived owner and group
+/// when they exist here. Other users' directories that were already there
+/// are left alone.
+fn set_attributes(
+    fs: &mut Vfs,
+    who: &CurrentUser,
+    users: &[User],
+    entry: &Entry,
+    target: &str,
+) -> Result<(), VfsError> {
+    if !who.is_admin {
+        if fs.stat(who, target)?.owner != who.username {
+            return Ok(());
+        }
+        return fs.chmod(who, target, entry.mode & perm::MODE_MASK);
+    }
+    fs.chmod(who, target, entry.mode & perm::MODE_MASK)?;
+    let owner = if users.iter().any(|u| u.username == entry.owner) {
+        entry.owner.as_str()
+    } else {
+        who.username.as_str()
+    };
+    let group = if group_exists(fs, users, &entry.group) {
+        entry.group.as_str()
+    } else {
+        owner
+    };
+    fs.chown(who, target, Some(owner), Some(group))
+}
+
+/// The path as stored in an archive: relative, without `.` components.
+/// `None` if it climbs out with `..`.
+fn member_name(name: &str) -> Option<String> {
+    let parts: Vec<&str> = path::components(name)
+        .into_iter()
+        .filter(|part| *part != ".")
+        .collect();
+    if parts.contains(&"..") {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn file(name: &str, data: &[u8]) -> Entry {
+        Entry {
+            name: name.to_string(),
+            mode: 0o644,
+            owner: "root".to_string(),
+            group: "root".to_string(),
+            kind: EntryKind::File(data.to_vec()),
+        }
+    }
+
+    #[test]
+    fn member_names_cant_climb_out() {
+        assert_eq!(member_name("a/b"), Some("a/b".to_string()));
+        assert_eq!(member_name("./a/./b/"), Some("a/b".to_string()));
+        assert_eq!(member_name("."), Some(String::new()));
+        assert_eq!(member_name("../x"), None);
+        assert_eq!(member_name("a/../../x"), None);
+        assert_eq!(member_name("a/.."), None);
+    }
+
+    #[test]
+    fn extraction_stays_inside_the_target_directory() {
+        let mut fs = Vfs::new(Path::new("/nonexistent/minikern.img"), "root");
+        let root = fs.root_user();
+        fs.mkdir(&root, "/tmp/x").unwrap();
+        let entries = [
+            file("../x", b"escaped"),
+            file("/etc/passwd", b"escaped"),
+            file("a/../../y", b"escaped"),
+            file("ok/kept.txt", b"kept"),
+        ];
+
+        let outcome = extract(&mut fs, &root, &entries, "/tmp/x", false).unwrap();
+        assert!(!outcome.succeeded());
+        assert!(!fs.exists(&root, "/etc/passwd"));
+        assert!(!fs.exists(&root, "/tmp/y"));
+        assert!(!fs.exists(&root, "/y"));
+        assert!(fs.stat(&root, "/tmp/x").unwrap().is_dir());
+        assert_eq!(fs.read_file(&root, "/tmp/x/ok/kept.txt").unwrap(), b"kept");
+    }
+}
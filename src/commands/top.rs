@@ -0,0 +1,127 @@
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::process::{self, Pid, State};
+use crate::sched::{self, TaskState};
+use crate::session::Session;
+use std::collections::BTreeMap;
+
+/// Shows every process with the scheduler time slices it's had, busiest
+/// first, `-n` times `-d` seconds of the virtual clock apart. `%CPU` is
+/// each process's share of the slices handed out since the last update.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let usage = || format!("Usage: top [-n ITERATIONS] [-d SECONDS] {}", OUTPUT_USAGE);
+    let (options, mut rest) = match OutputOptions::parse(args) {
+        Ok((options, rest)) => (options, rest.as_slice().to_vec()),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+    let mut iterations = 1;
+    let mut delay = 3;
+    while !rest.is_empty() {
+        match rest.as_slice() {
+            [flag, value, ..] if flag == "-n" => match value.parse::<u64>() {
+                Ok(n) if n > 0 => iterations = n,
+                _ => return Ok(CommandOutcome::failure(EXIT_USAGE, usage())),
+            },
+            [flag, value, ..] if flag == "-d" => match clock::parse_duration(value) {
+                Ok(seconds) => delay = seconds,
+                Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+            },
+            _ => return Ok(CommandOutcome::failure(EXIT_USAGE, usage())),
+        }
+        rest.drain(..2);
+    }
+
+    let mut previous: BTreeMap<Pid, u64> = BTreeMap::new();
+    for iteration in 0..iterations {
+        if iteration > 0 {
+            if let Err(signal) = sched::sleep(session.pid, delay * sched::TICKS_PER_SECOND) {
+                return Ok(CommandOutcome::exited(signal.status()));
+            }
+            if options.is_human() {
+                println!();
+            }
+        }
+
+        let mut processes = process::list();
+        processes.sort_by(|a, b| b.ticks.cmp(&a.ticks).then(a.pid.cmp(&b.pid)));
+        let used =
+            |pid: Pid, ticks: u64| ticks - previous.get(&pid).copied().unwrap_or(0).min(ticks);
+        let total: u64 = processes.iter().map(|p| used(p.pid, p.ticks)).sum();
+        let rows: Vec<_> = processes
+            .iter()
+            .map(|p| {
+                let share = match total {
+                    0 => 0.0,
+                    total => used(p.pid, p.ticks) as f64 * 100.0 / total as f64,
+                };
+                (p, status(p.pid, p.state), share)
+            })
+            .collect();
+
+        if options.is_human() {
+            let count = |code: char| rows.iter().filter(|(_, s, _)| *s == code).count();
+            let now = sched::now();
+            println!(
+                "top - up {}, {} ticks, policy {}",
+                clock::format_duration(now / sched::TICKS_PER_SECOND),
+                now,
+                sched::policy()
+            );
+            println!(
+                "Tasks: {} total, {} running, {} sleeping, {} stopped, {} done",
+                rows.len(),
+                count('R'),
+                count('S'),
+                count('T'),
+                count('Z')
+            );
+            println!(
+                "{:>5} {:<12} {:>3} {:<4} {:>7} {:>5} COMMAND",
+                "PID", "USER", "NI", "STAT", "TICKS", "%CPU"
+            );
+            for (p, status, share) in &rows {
+                println!(
+                    "{:>5} {:<12} {:>3} {:<4} {:>7} {:>5.1} {}",
+                    p.pid, p.user.username, p.nice, status, p.ticks, share, p.command
+                );
+            }
+        } else {
+            let mut table =
+                Table::new(&["pid", "user", "nice", "state", "ticks", "cpu", "command"]);
+            for (p, status, share) in &rows {
+                table.push(vec![
+                    u64::from(p.pid).into(),
+                    p.user.username.clone().into(),
+                    i64::from(p.nice).into(),
+                    status.to_string().into(),
+                    p.ticks.into(),
+                    format!("{:.1}", share).into(),
+                    p.command.clone().into(),
+                ]);
+            }
+            match output::render(&table, &options) {
+                Ok(rendered) => print!("{}", rendered),
+                Err(e) => {
+                    return Ok(CommandOutcome::failure(
+                        EXIT_USAGE,
+                        format!("{}\n{}", e, usage()),
+                    ))
+                }
+            }
+        }
+        previous = processes.iter().map(|p| (p.pid, p.ticks)).collect();
+    }
+    Ok(CommandOutcome::ok())
+}
+
+/// The process's state, with `S` for tasks waiting on the virtual clock
+fn status(pid: Pid, state: State) -> char {
+    match (state, sched::state(pid)) {
+        (State::Running, Some(TaskState::Sleeping(_))) => 'S',
+        _ => state.code(),
+    }
+}
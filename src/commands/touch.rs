@@ -0,0 +1,23 @@
+use crate::commands::files::Errors;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+use crate::vfs::{self, path};
+
+/// Creates empty files, or updates the timestamps of existing ones
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: touch FILE..."));
+    }
+
+    let mut errors = Errors::default();
+    let mut fs = vfs::lock();
+    for file in args {
+        if let Err(e) = fs.touch(&session.user, &path::resolve(&session.cwd, file)) {
+            errors.report("touch", file, e);
+        }
+    }
+    Ok(errors.outcome())
+}
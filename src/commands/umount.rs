@@ -0,0 +1,34 @@
+use crate::audit;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::session::Session;
+use crate::syslog::{self, Facility, Level};
+use crate::vfs::{self, path, VfsError};
+
+/// Removes a mounted filesystem from the tree
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let [point] = args else {
+        return Ok(CommandOutcome::failure(
+            EXIT_USAGE,
+            "Usage: umount MOUNTPOINT",
+        ));
+    };
+    let full = path::resolve(&session.cwd, point);
+    match vfs::lock().umount(&session.user, &full) {
+        Ok(()) => {
+            let _ = audit::record(&session.user.username, "umount", &full, "");
+            let _ = syslog::record(Level::Info, Facility::Fs, &format!("unmounted {}", full));
+            Ok(CommandOutcome::ok())
+        }
+        Err(VfsError::InvalidArgument) => Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{}: Not mounted", point),
+        )),
+        Err(e) => Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{}: {}", point, e),
+        )),
+    }
+}
@@ -0,0 +1,52 @@
+use crate::commands::{CommandOutcome, EXIT_PERMISSION, EXIT_USAGE};
+use crate::profile::{self, SYSTEM_PROFILE_PATH};
+use crate::session::Session;
+
+/// Removes aliases from the session and from the startup file they were
+/// saved in. System aliases can only be removed by admins with `--system`.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let system = args.first().map(|a| a == "--system").unwrap_or(false);
+    let args = if system { &args[1..] } else { args };
+
+    if args.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: unalias [--system] NAME..."));
+    }
+    if system && !session.user.is_admin {
+        return Ok(CommandOutcome::failure(
+            EXIT_PERMISSION,
+            "You must be an admin to remove system aliases.",
+        ));
+    }
+
+    for name in args {
+        let Some(alias) = session.aliases.get(name.as_str()) else {
+            println!("unalias: '{}' not found.", name);
+            continue;
+        };
+        if alias.system != system {
+            if alias.system {
+                println!(
+                    "unalias: '{}' is a system alias. Use 'unalias --system {}' (admin only).",
+                    name, name
+                );
+            } else {
+                println!("unalias: '{}' is not a system alias.", name);
+            }
+            continue;
+        }
+
+        let rc_path = profile::user_rc_path(&session.user.username);
+        let path = if system {
+            SYSTEM_PROFILE_PATH
+        } else {
+            rc_path.as_str()
+        };
+        profile::replace_definition(&session.user, path, "alias", name, None)?;
+        session.aliases.remove(name.as_str());
+        println!("Alias '{}' removed.", name);
+    }
+    Ok(CommandOutcome::ok())
+}
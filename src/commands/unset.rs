@@ -0,0 +1,17 @@
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::session::Session;
+
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: unset NAME..."));
+    }
+    for name in args {
+        if !session.unset_var(name) {
+            println!("Variable '{}' is not set.", name);
+        }
+    }
+    Ok(CommandOutcome::ok())
+}
@@ -0,0 +1,31 @@
+use crate::audit;
+use crate::clock;
+use crate::commands::write::message_text;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::console;
+use crate::session::Session;
+
+/// Sends a message to every logged-in terminal, for example before a
+/// shutdown. It reaches terminals with messages turned off too.
+pub fn run(
+    session: &mut Session, // Assumed admin by the dispatcher
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let Some(text) = message_text(session, args) else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, "Usage: wall [MESSAGE...]"));
+    };
+    let message = format!(
+        "Broadcast message from {} at {}:\n{}",
+        session.user.username,
+        clock::format_hms(clock::unix_now()),
+        text
+    );
+    let reached = console::send_message(None, &message, true);
+    let _ = audit::record(
+        &session.user.username,
+        "wall",
+        "all",
+        &format!("{} terminal{}", reached, if reached == 1 { "" } else { "s" }),
+    );
+    Ok(CommandOutcome::ok())
+}
@@ -0,0 +1,65 @@
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::sched;
+use crate::session::Session;
+use crate::terminal;
+
+const USAGE: &str = "Usage: watch [-n INTERVAL] [-c COUNT] COMMAND...";
+
+/// Runs a command line over and over, `-n` apart of the virtual clock (2
+/// seconds by default), until it's been run `-c` times or the process is
+/// killed. Exits with the status of the last run.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let mut interval = 2;
+    let mut count = None;
+    let mut rest = args;
+    loop {
+        match rest {
+            [flag, value, tail @ ..] if flag == "-n" => {
+                interval = match clock::parse_duration(value) {
+                    Ok(seconds) => seconds,
+                    Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+                };
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "-c" => {
+                count = match value.parse::<u64>() {
+                    Ok(count) if count > 0 => Some(count),
+                    _ => {
+                        return Ok(CommandOutcome::failure(
+                            EXIT_USAGE,
+                            format!("invalid count '{}'\n{}", value, USAGE),
+                        ))
+                    }
+                };
+                rest = tail;
+            }
+            _ => break,
+        }
+    }
+    if rest.is_empty() {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    }
+    let line = rest.join(" ");
+
+    let mut runs = 0;
+    loop {
+        println!(
+            "Every {}: {}    {}",
+            clock::format_duration(interval),
+            line,
+            clock::format_hms(clock::unix_now())
+        );
+        terminal::execute_line(session, &line)?;
+        runs += 1;
+        if count.is_some_and(|count| runs >= count) {
+            return Ok(CommandOutcome::exited(session.last_status));
+        }
+        if let Err(signal) = sched::sleep(session.pid, interval * sched::TICKS_PER_SECOND) {
+            return Ok(CommandOutcome::exited(signal.status()));
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_USAGE};
+use crate::console;
+use crate::output::{self, OutputOptions, Table, OUTPUT_USAGE};
+use crate::process;
+use crate::session::Session;
+
+/// Lists the login shells, with where each one connected from and when
+pub fn run(
+    _session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let usage = || format!("Usage: who {}", OUTPUT_USAGE);
+    let options = match OutputOptions::parse(args) {
+        Ok((options, rest)) if rest.is_empty() => options,
+        Ok(_) => return Ok(CommandOutcome::failure(EXIT_USAGE, usage())),
+        Err(e) => return Ok(CommandOutcome::failure(EXIT_USAGE, e)),
+    };
+
+    let logins = console::logged_in();
+    let login_time = |shell| {
+        process::get(shell)
+            .map(|p| clock::format_datetime(p.started))
+            .unwrap_or_default()
+    };
+
+    if options.is_human() {
+        for login in &logins {
+            // `console` is the host's own terminal
+            let from = login.peer.as_deref().unwrap_or("console");
+            println!(
+                "{:<12} {:<24} {}",
+                login.username,
+                from,
+                login_time(login.shell)
+            );
+        }
+        return Ok(CommandOutcome::ok());
+    }
+
+    let mut table = Table::new(&["user", "pid", "from", "login", "messages"]);
+    for login in logins {
+        table.push(vec![
+            login.username.into(),
+            u64::from(login.shell).into(),
+            login.peer.into(),
+            login_time(login.shell).into(),
+            login.accepts_messages.into(),
+        ]);
+    }
+    match output::render(&table, &options) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => {
+            return Ok(CommandOutcome::failure(
+                EXIT_USAGE,
+                format!("{}\n{}", e, usage()),
+            ))
+        }
+    }
+    Ok(CommandOutcome::ok())
+}
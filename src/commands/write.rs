@@ -0,0 +1,52 @@
+use crate::clock;
+use crate::commands::{CommandOutcome, EXIT_FAILURE, EXIT_USAGE};
+use crate::console;
+use crate::session::Session;
+
+const USAGE: &str = "Usage: write USER [MESSAGE...]";
+
+/// Sends a message to the terminals a user is logged in on, unless they
+/// turned messages off with `mesg n`. The message is the rest of the
+/// arguments, or the piped input when there are none.
+pub fn run(
+    session: &mut Session,
+    args: &[String],
+) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let Some((recipient, words)) = args.split_first() else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    let Some(text) = message_text(session, words) else {
+        return Ok(CommandOutcome::failure(EXIT_USAGE, USAGE));
+    };
+    if !console::is_logged_in(recipient) {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{} is not logged in", recipient),
+        ));
+    }
+    let message = format!(
+        "Message from {} at {}:\n{}",
+        session.user.username,
+        clock::format_hms(clock::unix_now()),
+        text
+    );
+    if console::send_message(Some(recipient), &message, false) == 0 {
+        return Ok(CommandOutcome::failure(
+            EXIT_FAILURE,
+            format!("{} has messages disabled", recipient),
+        ));
+    }
+    Ok(CommandOutcome::ok())
+}
+
+/// The text of a message: its words, or the piped input when there are
+/// none, with control characters made visible. `None` if that's empty.
+pub fn message_text(session: &mut Session, words: &[String]) -> Option<String> {
+    let text = match words.is_empty() {
+        false => words.join(" "),
+        true => String::from_utf8_lossy(&session.stdin.take()?)
+            .trim_end()
+            .to_string(),
+    };
+    (!text.is_empty()).then(|| console::printable(&text))
+}
@@ -0,0 +1,507 @@
+use crate::process::{self, Pid};
+use crate::remote::Stream;
+use crate::syslog::{self, Facility, Level};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+/// How often a remote prompt waiting for input looks for an interrupt
+const INTERRUPT_POLL: Duration = Duration::from_millis(100);
+
+/// The terminal a session runs on: the host's own, a client connected to
+/// `minikern serve`, or none at all for cron jobs and services. Every
+/// thread prints to and reads from its console, the host's unless it was
+/// given another with `attach`.
+pub struct Console {
+    remote: Option<Remote>,
+    detached: Option<Detached>,
+    /// The command Ctrl-C interrupts, if one is running in the foreground
+    foreground: Mutex<Option<Pid>>,
+    /// Messages from `write` and `wall` not shown yet
+    messages: Mutex<Vec<String>>,
+    /// Whether other users may `write` to it, as set by `mesg`
+    accepts_messages: AtomicBool,
+}
+
+/// A connected client's end: what it sends is queued for prompts to read,
+/// and Ctrl-C arrives as a byte rather than a signal
+struct Remote {
+    peer: String,
+    output: Mutex<Stream>,
+    input: Mutex<Input>,
+    input_changed: Condvar,
+    /// Set when Ctrl-C is received, until a prompt gives up because of it
+    interrupted: AtomicBool,
+}
+
+/// A console with no terminal behind it: reading from it gets end of
+/// input, and what's printed to it is logged a line at a time
+struct Detached {
+    facility: Facility,
+    /// Who the lines are logged as coming from, like a cron job's owner
+    owner: String,
+    /// The unfinished last lines of standard output and standard error
+    pending: Mutex<[Vec<u8>; 2]>,
+}
+
+#[derive(Default)]
+struct Input {
+    bytes: VecDeque<u8>,
+    /// The client has disconnected
+    closed: bool,
+}
+
+/// A login shell and the console it runs on
+struct Login {
+    shell: Pid,
+    username: String,
+    console: Arc<Console>,
+}
+
+static HOST: OnceLock<Arc<Console>> = OnceLock::new();
+
+/// The login shells running on a console, which messages can reach
+static LOGINS: Mutex<Vec<Login>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Console>>> = const { RefCell::new(None) };
+    /// Output being captured for the next command of a pipeline, innermost
+    /// pipeline last
+    static CAPTURED: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Console {
+    fn new(remote: Option<Remote>) -> Console {
+        Console {
+            remote,
+            detached: None,
+            foreground: Mutex::new(None),
+            messages: Mutex::new(Vec::new()),
+            accepts_messages: AtomicBool::new(true),
+        }
+    }
+
+    /// Makes a console for a client, reading what it sends on a thread of
+    /// its own until it disconnects
+    pub fn remote(stream: Stream, peer: String) -> io::Result<Arc<Console>> {
+        let mut reader = stream.try_clone()?;
+        let console = Arc::new(Console::new(Some(Remote {
+            peer,
+            output: Mutex::new(stream),
+            input: Mutex::new(Input::default()),
+            input_changed: Condvar::new(),
+            interrupted: AtomicBool::new(false),
+        })));
+        let receiving = Arc::clone(&console);
+        std::thread::spawn(move || {
+            let Some(remote) = &receiving.remote else {
+                return;
+            };
+            let mut buf = [0u8; 1024];
+            loop {
+                let n = match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                let mut bytes = buf[..n].to_vec();
+                if bytes.contains(&0x03) {
+                    remote.interrupted.store(true, Ordering::SeqCst);
+                    // Ctrl-C is left queued only when there's no command to
+                    // interrupt, for the line editor, which reads it as a key
+                    if receiving.foreground_slot().is_some() {
+                        receiving.interrupt_foreground();
+                        bytes.retain(|&b| b != 0x03);
+                    }
+                }
+                remote.input().bytes.extend(bytes);
+                remote.input_changed.notify_all();
+            }
+            remote.input().closed = true;
+            remote.input_changed.notify_all();
+            // Hanging up interrupts whatever was running
+            receiving.interrupt_foreground();
+        });
+        Ok(console)
+    }
+
+    /// Makes a console with no terminal for a cron job or service, whose
+    /// output goes to the system log under `facility`, tagged with `owner`
+    pub fn detached(facility: Facility, owner: &str) -> Arc<Console> {
+        let mut console = Console::new(None);
+        console.detached = Some(Detached {
+            facility,
+            owner: owner.to_string(),
+            pending: Mutex::new([Vec::new(), Vec::new()]),
+        });
+        Arc::new(console)
+    }
+
+    /// Disconnects a remote client
+    pub fn hang_up(&self) {
+        if let Some(remote) = &self.remote {
+            let output = remote.output.lock().unwrap_or_else(|p| p.into_inner());
+            let _ = output.shutdown(Shutdown::Both);
+        }
+    }
+
+    pub fn is_remote(&self) -> bool {
+        self.remote.is_some()
+    }
+
+    /// Whether it's the host's own terminal
+    pub fn is_host(&self) -> bool {
+        self.remote.is_none() && self.detached.is_none()
+    }
+
+    /// Where the client connected from, for remote consoles
+    pub fn peer(&self) -> Option<&str> {
+        self.remote.as_ref().map(|r| r.peer.as_str())
+    }
+
+    fn messages(&self) -> MutexGuard<'_, Vec<String>> {
+        self.messages.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn foreground_slot(&self) -> MutexGuard<'_, Option<Pid>> {
+        self.foreground.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Sends `SIGINT` to the command in the foreground, if there is one
+    pub fn interrupt_foreground(&self) {
+        if let Some(pid) = *self.foreground_slot() {
+            process::interrupt(pid);
+        }
+    }
+
+    fn write(&self, buf: &[u8], error: bool) -> io::Result<()> {
+        if let Some(detached) = &self.detached {
+            detached.write(buf, error);
+            return Ok(());
+        }
+        match (&self.remote, error) {
+            (Some(remote), _) => {
+                let mut output = remote.output.lock().unwrap_or_else(|p| p.into_inner());
+                output.write_all(buf)?;
+                output.flush()
+            }
+            (None, false) => io::stdout().write_all(buf),
+            (None, true) => io::stderr().write_all(buf),
+        }
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        match &self.remote {
+            Some(_) => Ok(()),
+            None => io::stdout().flush(),
+        }
+    }
+}
+
+impl Drop for Console {
+    fn drop(&mut self) {
+        if let Some(detached) = &self.detached {
+            detached.finish();
+        }
+    }
+}
+
+impl Detached {
+    fn pending(&self) -> MutexGuard<'_, [Vec<u8>; 2]> {
+        self.pending.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Logs the lines `buf` finishes, keeping the rest for later
+    fn write(&self, buf: &[u8], error: bool) {
+        let mut pending = self.pending();
+        let stream = &mut pending[usize::from(error)];
+        stream.extend_from_slice(buf);
+        while let Some(end) = stream.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = stream.drain(..=end).collect();
+            self.log(&line[..end], error);
+        }
+    }
+
+    /// Logs what's left of lines not ended with a newline
+    fn finish(&self) {
+        let pending = std::mem::take(&mut *self.pending());
+        for (stream, line) in pending.iter().enumerate() {
+            if !line.is_empty() {
+                self.log(line, stream == 1);
+            }
+        }
+    }
+
+    fn log(&self, line: &[u8], error: bool) {
+        let level = if error { Level::Notice } else { Level::Info };
+        let line = printable(&String::from_utf8_lossy(line));
+        let _ = syslog::record(level, self.facility, &format!("({}) {}", self.owner, line));
+    }
+}
+
+impl Remote {
+    fn input(&self) -> MutexGuard<'_, Input> {
+        self.input.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+/// The host's own terminal
+pub fn host() -> Arc<Console> {
+    Arc::clone(HOST.get_or_init(|| Arc::new(Console::new(None))))
+}
+
+/// The calling thread's console
+pub fn current() -> Arc<Console> {
+    CURRENT
+        .with(|current| current.borrow().clone())
+        .unwrap_or_else(host)
+}
+
+/// Makes `console` the calling thread's console
+pub fn attach(console: Arc<Console>) {
+    CURRENT.with(|current| *current.borrow_mut() = Some(console));
+}
+
+/// Makes `pid` the command in the foreground of the calling thread's
+/// console, the one Ctrl-C interrupts, or leaves the foreground empty
+/// given `None`. Returns the command that was there before.
+pub fn set_foreground(pid: Option<Pid>) -> Option<Pid> {
+    std::mem::replace(&mut *current().foreground_slot(), pid)
+}
+
+pub fn foreground() -> Option<Pid> {
+    *current().foreground_slot()
+}
+
+/// Forgets a Ctrl-C received by the calling thread's remote console.
+/// Returns whether there was one; `None` for the host's terminal.
+pub fn clear_remote_interrupt() -> Option<bool> {
+    let console = current();
+    if console.detached.is_some() {
+        return Some(false);
+    }
+    console
+        .remote
+        .as_ref()
+        .map(|remote| remote.interrupted.swap(false, Ordering::SeqCst))
+}
+
+/// Reads a byte sent by the calling thread's remote client, or `None`
+/// once it has disconnected. An interruptible read fails with
+/// `ErrorKind::Interrupted` if Ctrl-C is received while it waits, dropping
+/// whatever was typed ahead. Consoles with no terminal are always at end
+/// of input. Returns `Err(ErrorKind::Unsupported)` on the host's terminal.
+pub fn read_remote_byte(interruptible: bool) -> io::Result<Option<u8>> {
+    let console = current();
+    if console.detached.is_some() {
+        return Ok(None);
+    }
+    let Some(remote) = &console.remote else {
+        return Err(io::ErrorKind::Unsupported.into());
+    };
+    let mut input = remote.input();
+    loop {
+        if interruptible && remote.interrupted.swap(false, Ordering::SeqCst) {
+            input.bytes.clear();
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+        }
+        if let Some(byte) = input.bytes.pop_front() {
+            return Ok(Some(byte));
+        }
+        if input.closed {
+            return Ok(None);
+        }
+        input = remote
+            .input_changed
+            .wait_timeout(input, INTERRUPT_POLL)
+            .unwrap_or_else(|p| p.into_inner())
+            .0;
+    }
+}
+
+/// Waits up to `timeout` for the calling thread's remote client to send
+/// something, returning whether there's input to read (or the client has
+/// gone). Returns `Err(ErrorKind::Unsupported)` on the host's terminal.
+pub fn wait_remote_input(timeout: Duration) -> io::Result<bool> {
+    let console = current();
+    if console.detached.is_some() {
+        return Ok(true);
+    }
+    let Some(remote) = &console.remote else {
+        return Err(io::ErrorKind::Unsupported.into());
+    };
+    let ready = |input: &Input| !input.bytes.is_empty() || input.closed;
+    let input = remote.input();
+    if ready(&input) {
+        return Ok(true);
+    }
+    let input = remote
+        .input_changed
+        .wait_timeout(input, timeout)
+        .unwrap_or_else(|p| p.into_inner())
+        .0;
+    Ok(ready(&input))
+}
+
+fn logins() -> MutexGuard<'static, Vec<Login>> {
+    LOGINS.lock().unwrap_or_else(|p| p.into_inner())
+}
+
+/// Records that `username` logged in to the calling thread's console with
+/// the shell `shell`, so messages can reach it until `log_out`
+pub fn log_in(shell: Pid, username: &str) {
+    let console = current();
+    console.accepts_messages.store(true, Ordering::SeqCst);
+    logins().push(Login {
+        shell,
+        username: username.to_string(),
+        console,
+    });
+}
+
+pub fn log_out(shell: Pid) {
+    let mut logins = logins();
+    if let Some(i) = logins.iter().position(|login| login.shell == shell) {
+        let login = logins.remove(i);
+        login.console.messages().clear();
+    }
+}
+
+/// A login shell, as `who` lists it
+pub struct LoginInfo {
+    pub shell: Pid,
+    pub username: String,
+    /// Where a remote client connected from; `None` on the host's terminal
+    pub peer: Option<String>,
+    pub accepts_messages: bool,
+}
+
+/// The login shells running, in the order they logged in
+pub fn logged_in() -> Vec<LoginInfo> {
+    logins()
+        .iter()
+        .map(|login| LoginInfo {
+            shell: login.shell,
+            username: login.username.clone(),
+            peer: login.console.peer().map(str::to_string),
+            accepts_messages: login.console.accepts_messages.load(Ordering::SeqCst),
+        })
+        .collect()
+}
+
+/// Whether `username` has a login shell on any console
+pub fn is_logged_in(username: &str) -> bool {
+    logins().iter().any(|login| login.username == username)
+}
+
+/// Queues `message` for the consoles `username` is logged in on, or for
+/// every login console given `None`. Consoles that refused messages with
+/// `mesg n` are skipped unless `force` is set. Returns how many consoles
+/// it was queued for.
+pub fn send_message(username: Option<&str>, message: &str, force: bool) -> usize {
+    let logins = logins();
+    let mut sent: Vec<&Arc<Console>> = Vec::new();
+    for login in logins.iter() {
+        if username.is_some_and(|name| name != login.username)
+            || !(force || login.console.accepts_messages.load(Ordering::SeqCst))
+            || sent.iter().any(|console| Arc::ptr_eq(console, &login.console))
+        {
+            continue;
+        }
+        login.console.messages().push(message.to_string());
+        if let Some(remote) = &login.console.remote {
+            remote.input_changed.notify_all();
+        }
+        sent.push(&login.console);
+    }
+    sent.len()
+}
+
+/// `text` with the control characters other than newlines and tabs made
+/// visible, like `^[` for ESC, so text from other users can't send escape
+/// sequences to the terminal it's shown on
+pub fn printable(text: &str) -> String {
+    let mut shown = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\n' | '\t' => shown.push(c),
+            '\x00'..='\x1f' => {
+                shown.push('^');
+                shown.push((c as u8 + b'@') as char);
+            }
+            '\x7f' => shown.push_str("^?"),
+            c if c.is_control() => shown.push_str(&c.escape_unicode().to_string()),
+            c => shown.push(c),
+        }
+    }
+    shown
+}
+
+/// Takes the messages waiting to be shown on the calling thread's console
+pub fn take_messages() -> Vec<String> {
+    std::mem::take(&mut *current().messages())
+}
+
+/// Whether the calling thread's console accepts messages
+pub fn accepts_messages() -> bool {
+    current().accepts_messages.load(Ordering::SeqCst)
+}
+
+pub fn set_accepts_messages(accept: bool) {
+    current().accepts_messages.store(accept, Ordering::SeqCst);
+}
+
+/// Runs `f` with the calling thread's standard output going into a buffer
+/// instead of its console, and returns what it printed. Standard error
+/// isn't captured.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<u8>) {
+    CAPTURED.with(|captured| captured.borrow_mut().push(Vec::new()));
+    let result = f();
+    let output = CAPTURED.with(|captured| captured.borrow_mut().pop().unwrap_or_default());
+    (result, output)
+}
+
+/// The calling thread's standard output: the pipe capturing it, if any,
+/// otherwise its console
+pub struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let captured = CAPTURED.with(|captured| match captured.borrow_mut().last_mut() {
+            Some(pipe) => {
+                pipe.extend_from_slice(buf);
+                true
+            }
+            None => false,
+        });
+        if !captured {
+            current().write(buf, false)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        current().flush()
+    }
+}
+
+pub fn stdout() -> Stdout {
+    Stdout
+}
+
+/// What `print!` and `println!` do. A client that has gone away isn't an
+/// error worth stopping for.
+pub fn print(args: fmt::Arguments) {
+    let _ = Stdout.write_fmt(args);
+}
+
+/// What `eprint!` and `eprintln!` do: standard error is never captured
+pub fn eprint(args: fmt::Arguments) {
+    let console = current();
+    let _ = console.flush();
+    let _ = console.write(args.to_string().as_bytes(), true);
+}
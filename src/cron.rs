@@ -0,0 +1,533 @@
+use crate::auth::{self, CurrentUser};
+use crate::clock;
+use crate::commands::EXIT_SUCCESS;
+use crate::console::{self, Console};
+use crate::process;
+use crate::profile;
+use crate::session::Session;
+use crate::syslog::{self, Facility, Level};
+use crate::terminal;
+use crate::vfs::{self, path, VfsError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// Where each user's crontab is kept, as a file named after them. Only
+/// root can read it; users change theirs with `crontab`.
+pub const CRONTAB_DIR_PATH: &str = "/var/spool/cron";
+
+/// Most missed minutes the daemon catches up on, if it falls behind
+const MAX_CATCH_UP: u64 = 60;
+
+/// Names allowed for months and weekdays, in order from their first value
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// The five time fields of a crontab line, each a set of the values it
+/// matches as a bit mask
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    /// 0 is Sunday
+    weekdays: u64,
+    /// Whether the day of the month or of the week was `*`. When neither
+    /// was, a day matching either one will do.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// A broken-down UTC time, to the minute
+#[derive(Debug, Clone, Copy)]
+struct Minute {
+    minute: u32,
+    hour: u32,
+    day: u32,
+    month: u32,
+    weekday: u32,
+}
+
+impl Minute {
+    fn at(secs: u64) -> Self {
+        let days = secs / 86_400;
+        let (_, month, day) = clock::civil_from_days(days as i64);
+        Minute {
+            minute: (secs / 60 % 60) as u32,
+            hour: (secs / 3600 % 24) as u32,
+            day,
+            month,
+            // The epoch was a Thursday
+            weekday: ((days + 4) % 7) as u32,
+        }
+    }
+}
+
+impl Schedule {
+    /// Parses the five fields `minute hour day month weekday`, each `*`, a
+    /// number, a range `A-B` or a list of those separated by commas, with an
+    /// optional step such as `*/15`. Months and weekdays can be given by
+    /// their first three letters, and Sunday is 0 or 7. `@hourly`,
+    /// `@daily`, `@weekly`, `@monthly` and `@yearly` stand for the usual
+    /// schedules.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let expanded = match text {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            text if text.starts_with('@') => {
+                return Err(format!("unknown schedule '{}'", text));
+            }
+            text => text,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+            return Err(format!("expected 5 time fields, found {}", fields.len()));
+        };
+        // Sunday may be written as 7, so weekdays are parsed as 0-7 and 7
+        // folded onto 0
+        let weekdays = parse_field(weekday, 0, 7, WEEKDAYS)?;
+        Ok(Schedule {
+            minutes: parse_field(minute, 0, 59, &[])?,
+            hours: parse_field(hour, 0, 23, &[])?,
+            days: parse_field(day, 1, 31, &[])?,
+            months: parse_field(month, 1, 12, MONTHS)?,
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// Whether the schedule fires in the minute containing `secs`
+    pub fn matches(&self, secs: u64) -> bool {
+        let t = Minute::at(secs);
+        let has = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day = has(self.days, t.day);
+        let weekday = has(self.weekdays, t.weekday);
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        has(self.minutes, t.minute)
+            && has(self.hours, t.hour)
+            && has(self.months, t.month)
+            && day_matches
+    }
+}
+
+/// Parses one time field into a bit mask of the values from `min` to `max`
+/// it matches. `names` are accepted for the values from `min` on.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let lower = text.to_ascii_lowercase();
+        let number = match names.iter().position(|name| *name == lower) {
+            Some(index) => min + index as u32,
+            None => text
+                .parse()
+                .map_err(|_| format!("'{}' is not a valid value", text))?,
+        };
+        if !(min..=max).contains(&number) {
+            return Err(format!("{} is out of range {}-{}", number, min, max));
+        }
+        Ok(number)
+    };
+
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("'{}' is not a valid step", step)),
+            },
+            None => (part, 1),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (value(first)?, value(last)?),
+            // `5/10` means from 5 to the end, every 10
+            None if part.contains('/') => (value(range)?, max),
+            None => {
+                let single = value(range)?;
+                (single, single)
+            }
+        };
+        if first > last {
+            return Err(format!("'{}' is a backwards range", range));
+        }
+        for v in (first..=last).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+/// A job from a crontab
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub schedule: Schedule,
+    /// The schedule as it was written
+    pub when: String,
+    pub command: String,
+}
+
+/// Parses a crontab: one job per line, its schedule followed by the
+/// command line to run. Blank lines and lines starting with `#` are
+/// ignored. Errors name the line they're on.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |e: String| format!("line {}: {}", number + 1, e);
+        // Everything after the fifth field, or after an `@` schedule, is
+        // the command
+        let count = if line.starts_with('@') { 1 } else { 5 };
+        let mut rest = line;
+        for _ in 0..count {
+            rest = rest.trim_start();
+            rest = rest.find(char::is_whitespace).map_or("", |i| &rest[i..]);
+        }
+        let when = line[..line.len() - rest.len()].trim();
+        let schedule = Schedule::parse(when).map_err(error)?;
+        let command = rest.trim();
+        if command.is_empty() {
+            return Err(error("missing command".to_string()));
+        }
+        entries.push(Entry {
+            schedule,
+            when: when.to_string(),
+            command: command.to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+pub fn crontab_path(username: &str) -> String {
+    path::join(CRONTAB_DIR_PATH, username)
+}
+
+/// A user's crontab, if they have one
+pub fn load(username: &str) -> Result<Option<String>, VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    match fs.read_file(&root, &crontab_path(username)) {
+        Ok(bytes) => Ok(Some(String::from_utf8_lossy(&bytes).into_owned())),
+        Err(VfsError::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replaces a user's crontab. Callers check it parses first.
+pub fn install(username: &str, text: &str) -> Result<(), VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    if !fs.exists(&root, CRONTAB_DIR_PATH) {
+        fs.mkdir_all(&root, CRONTAB_DIR_PATH)?;
+        fs.chmod(&root, CRONTAB_DIR_PATH, 0o700)?;
+    }
+    let path = crontab_path(username);
+    fs.write_file(&root, &path, text.as_bytes())?;
+    fs.chmod(&root, &path, 0o600)
+}
+
+/// Deletes a user's crontab, returning whether they had one
+pub fn remove(username: &str) -> Result<bool, VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    match fs.unlink(&root, &crontab_path(username)) {
+        Ok(()) => Ok(true),
+        Err(VfsError::NotFound) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Moves a renamed user's crontab along with them
+pub fn rename(old: &str, new: &str) -> Result<(), VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    match fs.rename(&root, &crontab_path(old), &crontab_path(new)) {
+        Err(VfsError::NotFound) => Ok(()),
+        result => result,
+    }
+}
+
+/// Which run of the daemon is current, if it's running. A daemon thread
+/// that finds another run current, or none, stops.
+static DAEMON: Mutex<Option<u64>> = Mutex::new(None);
+static DAEMON_CHANGED: Condvar = Condvar::new();
+static NEXT_RUN: AtomicU64 = AtomicU64::new(0);
+
+/// Starts the daemon that runs crontab jobs at the start of each minute
+/// they're scheduled for, until `stop`. Returns false if it was already
+/// running.
+pub fn start() -> bool {
+    let mut daemon = DAEMON.lock().unwrap_or_else(|p| p.into_inner());
+    if daemon.is_some() {
+        return false;
+    }
+    let run = NEXT_RUN.fetch_add(1, Ordering::SeqCst);
+    *daemon = Some(run);
+    std::thread::spawn(move || {
+        let mut last = clock::unix_now() / 60;
+        loop {
+            let now = clock::unix_now();
+            let wait = Duration::from_secs(60 - now % 60);
+            let daemon = DAEMON.lock().unwrap_or_else(|p| p.into_inner());
+            let (daemon, _) = DAEMON_CHANGED
+                .wait_timeout_while(daemon, wait, |current| *current == Some(run))
+                .unwrap_or_else(|p| p.into_inner());
+            if *daemon != Some(run) {
+                return;
+            }
+            drop(daemon);
+            let minute = clock::unix_now() / 60;
+            for due in (last + 1).max(minute.saturating_sub(MAX_CATCH_UP))..=minute {
+                run_due(due * 60);
+            }
+            last = last.max(minute);
+        }
+    });
+    true
+}
+
+/// Stops the daemon. Jobs it already started run to the end. Returns
+/// false if it wasn't running.
+pub fn stop() -> bool {
+    let stopped = DAEMON
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .take()
+        .is_some();
+    DAEMON_CHANGED.notify_all();
+    stopped
+}
+
+/// Starts the jobs of every crontab scheduled for the minute at `secs`
+fn run_due(secs: u64) {
+    let users = match auth::load_users() {
+        Ok(users) => users,
+        Err(e) => {
+            let _ = syslog::record(Level::Err, Facility::Cron, &format!("could not load users: {}", e));
+            return;
+        }
+    };
+    let names = {
+        let fs = vfs::lock();
+        match fs.read_dir(&fs.root_user(), CRONTAB_DIR_PATH) {
+            Ok(entries) => entries.into_iter().map(|e| e.name).collect(),
+            Err(_) => Vec::new(),
+        }
+    };
+    for name in names {
+        let text = match load(&name) {
+            Ok(Some(text)) => text,
+            Ok(None) => continue,
+            Err(e) => {
+                let _ =
+                    syslog::record(
+                    Level::Err,
+                    Facility::Cron,
+                    &format!("({}) could not read crontab: {}", name, e),
+                );
+                continue;
+            }
+        };
+        let entries = match parse(&text) {
+            Ok(entries) => entries,
+            Err(e) => {
+                let _ = syslog::record(
+                    Level::Err,
+                    Facility::Cron,
+                    &format!("({}) bad crontab: {}", name, e),
+                );
+                continue;
+            }
+        };
+        let due: Vec<Entry> = entries
+            .into_iter()
+            .filter(|entry| entry.schedule.matches(secs))
+            .collect();
+        if due.is_empty() {
+            continue;
+        }
+        let Some(user) = users.iter().find(|u| u.username == name) else {
+            let _ = syslog::record(
+                Level::Warning,
+                Facility::Cron,
+                &format!("({}) skipped: no such user", name),
+            );
+            continue;
+        };
+        if user.disabled {
+            let _ = syslog::record(
+                Level::Warning,
+                Facility::Cron,
+                &format!("({}) skipped: account disabled", name),
+            );
+            continue;
+        }
+        let user = CurrentUser {
+            username: user.username.clone(),
+            is_admin: user.is_admin,
+        };
+        for entry in due {
+            let user = user.clone();
+            std::thread::spawn(move || run_job(user, &entry.command));
+        }
+    }
+}
+
+/// Runs one job in a shell of its own, as its owner, logging how it ended
+/// and what it printed. It has no terminal to read from.
+fn run_job(user: CurrentUser, command: &str) {
+    let username = user.username.clone();
+    console::attach(Console::detached(Facility::Cron, &username));
+    let _ = syslog::record(
+        Level::Info,
+        Facility::Cron,
+        &format!("({}) CMD ({})", username, command),
+    );
+    if let Err(e) = profile::ensure_home(&username) {
+        let _ = syslog::record(
+            Level::Err,
+            Facility::Cron,
+            &format!("({}) no home directory: {}", username, e),
+        );
+        return;
+    }
+    let mut session = Session::new(user);
+    session.enter_home();
+    let result = terminal::execute_line(&mut session, command);
+    process::end_session(session.pid);
+    if let Err(e) = vfs::sync() {
+        let _ = syslog::record(
+            Level::Err,
+            Facility::Fs,
+            &format!("could not save the filesystem: {}", e),
+        );
+    }
+    let (level, message) = match result {
+        Ok(_) if session.last_status == EXIT_SUCCESS => (
+            Level::Info,
+            format!("({}) exit {} ({})", username, session.last_status, command),
+        ),
+        Ok(_) => (
+            Level::Notice,
+            format!("({}) exit {} ({})", username, session.last_status, command),
+        ),
+        Err(e) => (
+            Level::Err,
+            format!("({}) failed ({}): {}", username, command, e),
+        ),
+    };
+    let _ = syslog::record(level, Facility::Cron, &message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds since the epoch at a UTC date and time
+    fn at(year: i64, month: u32, day: u32, hour: u64, minute: u64) -> u64 {
+        clock::days_from_civil(year, month, day) as u64 * 86_400 + hour * 3600 + minute * 60
+    }
+
+    #[test]
+    fn parses_the_five_fields() {
+        let schedule = Schedule::parse("*/15 9-17 * * mon-fri").unwrap();
+        assert_eq!(schedule.minutes, 1 << 0 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(schedule.hours, 0b11_1111_1110_0000_0000);
+        assert_eq!(schedule.weekdays, 0b011_1110);
+        assert!(schedule.any_day && !schedule.any_weekday);
+
+        let schedule = Schedule::parse("5,10-20/5 0 1,15 JAN,jul *").unwrap();
+        assert_eq!(schedule.minutes, 1 << 5 | 1 << 10 | 1 << 15 | 1 << 20);
+        assert_eq!(schedule.days, 1 << 1 | 1 << 15);
+        assert_eq!(schedule.months, 1 << 1 | 1 << 7);
+        assert_eq!(Schedule::parse("0 0 * * 7"), Schedule::parse("0 0 * * sun"));
+        assert_eq!(Schedule::parse("0 0 * * 7"), Schedule::parse("0 0 * * 0"));
+    }
+
+    #[test]
+    fn shortcuts_stand_for_their_schedules() {
+        for (shortcut, fields) in [
+            ("@hourly", "0 * * * *"),
+            ("@daily", "0 0 * * *"),
+            ("@midnight", "0 0 * * *"),
+            ("@weekly", "0 0 * * 0"),
+            ("@monthly", "0 0 1 * *"),
+            ("@yearly", "0 0 1 1 *"),
+            ("@annually", "0 0 1 1 *"),
+        ] {
+            let expanded = Schedule::parse(fields);
+            assert_eq!(Schedule::parse(shortcut), expanded, "{}", shortcut);
+        }
+        assert!(Schedule::parse("@reboot").is_err());
+    }
+
+    #[test]
+    fn bad_fields_are_refused() {
+        for bad in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "10-5 * * * *",
+            "x * * * *",
+            "* * * foo *",
+        ] {
+            assert!(Schedule::parse(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn matches_in_utc() {
+        // 2024-01-01 was a Monday
+        let schedule = Schedule::parse("30 9 * * mon").unwrap();
+        assert!(schedule.matches(at(2024, 1, 1, 9, 30)));
+        assert!(schedule.matches(at(2024, 1, 1, 9, 30) + 59));
+        assert!(!schedule.matches(at(2024, 1, 1, 9, 31)));
+        assert!(!schedule.matches(at(2024, 1, 2, 9, 30)));
+        assert!(schedule.matches(at(2024, 1, 8, 9, 30)));
+
+        // With both days restricted, either one will do
+        let schedule = Schedule::parse("0 0 13 * fri").unwrap();
+        assert!(schedule.matches(at(2024, 1, 13, 0, 0)));
+        assert!(schedule.matches(at(2024, 1, 5, 0, 0)));
+        assert!(!schedule.matches(at(2024, 1, 6, 0, 0)));
+
+        let schedule = Schedule::parse("@yearly").unwrap();
+        assert!(schedule.matches(at(2025, 1, 1, 0, 0)));
+        assert!(!schedule.matches(at(2025, 2, 1, 0, 0)));
+    }
+
+    #[test]
+    fn parses_a_crontab() {
+        let text = "# backups\n\n0 3 * * *  backup /home  \n@hourly echo  tick\n";
+        let entries = parse(text).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].when, "0 3 * * *");
+        assert_eq!(entries[0].command, "backup /home");
+        assert_eq!(entries[1].when, "@hourly");
+        assert_eq!(entries[1].command, "echo  tick");
+        assert_eq!(entries[1].schedule, Schedule::parse("0 * * * *").unwrap());
+
+        let missing = parse("# ok\n0 3 * * *\n").unwrap_err();
+        assert_eq!(missing, "line 2: missing command");
+        assert_eq!(parse("@daily").unwrap_err(), "line 1: missing command");
+        let bad = parse("* * * * * ok\n61 * * * * bad\n").unwrap_err();
+        assert!(bad.starts_with("line 2: "), "{}", bad);
+    }
+}
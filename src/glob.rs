@@ -0,0 +1,156 @@
+use crate::auth::CurrentUser;
+use crate::vfs::{self, path};
+
+/// Characters that make a word a glob pattern, unless escaped
+const MAGIC: &[char] = &['*', '?', '['];
+
+/// Whether `pattern` has an unescaped `*`, `?` or `[`.
+pub fn has_magic(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if MAGIC.contains(&c) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Removes the backslash escapes from a pattern
+pub fn unescape(pattern: &str) -> String {
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escapes `text` so it only matches itself
+pub fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if c == '\\' || MAGIC.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Matches a single file name against a pattern: `*` matches any run of
+/// characters, `?` any one character, `[abc]`, `[a-z]` and `[!abc]` a
+/// character class, and `\` escapes the next character.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_from(&pattern, &name)
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    let Some((&p, rest)) = pattern.split_first() else {
+        return name.is_empty();
+    };
+    match p {
+        '*' => (0..=name.len()).any(|skip| match_from(rest, &name[skip..])),
+        '?' => !name.is_empty() && match_from(rest, &name[1..]),
+        '[' => match (name.first(), parse_class(rest)) {
+            (Some(&c), Some((matched, after))) => matched(c) && match_from(after, &name[1..]),
+            (Some(&c), None) => c == '[' && match_from(rest, &name[1..]),
+            (None, _) => false,
+        },
+        '\\' => match rest.split_first() {
+            Some((&escaped, rest)) => name.first() == Some(&escaped) && match_from(rest, &name[1..]),
+            None => name == ['\\'],
+        },
+        p => name.first() == Some(&p) && match_from(rest, &name[1..]),
+    }
+}
+
+/// Parses a character class after its `[`. Returns a matcher and the rest
+/// of the pattern, or `None` if there's no closing `]`.
+fn parse_class(pattern: &[char]) -> Option<(impl Fn(char) -> bool, &[char])> {
+    let (negated, mut i) = match pattern.first() {
+        Some('!' | '^') => (true, 1),
+        _ => (false, 0),
+    };
+    let mut ranges = Vec::new();
+    // A `]` right after the `[` is part of the class
+    let mut first = true;
+    loop {
+        let c = *pattern.get(i)?;
+        if c == ']' && !first {
+            break;
+        }
+        first = false;
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&e| e != ']') {
+            ranges.push((c, pattern[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    let matcher = move |c: char| ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != negated;
+    Some((matcher, &pattern[i + 1..]))
+}
+
+/// Expands a pattern against the virtual filesystem. Relative patterns are
+/// matched from `cwd` and give relative results. Names starting with `.`
+/// are only matched by a pattern that starts with `.` too, and directories
+/// `who` can't read are skipped. Returns the matches sorted, or nothing if
+/// there are none.
+pub fn expand(pattern: &str, cwd: &str, who: &CurrentUser) -> Vec<String> {
+    // Pairs of (absolute path, path as the user wrote it)
+    let mut candidates = if pattern.starts_with('/') {
+        vec![("/".to_string(), "/".to_string())]
+    } else {
+        vec![(cwd.to_string(), String::new())]
+    };
+
+    let fs = vfs::lock();
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        for (full, shown) in &candidates {
+            if !has_magic(component) {
+                let name = unescape(component);
+                next.push((path::resolve(full, &name), display_join(shown, &name)));
+                continue;
+            }
+            let Ok(entries) = fs.read_dir(who, full) else {
+                continue;
+            };
+            for entry in entries {
+                if entry.name.starts_with('.') && !component.starts_with('.') {
+                    continue;
+                }
+                if matches(component, &entry.name) {
+                    next.push((path::join(full, &entry.name), display_join(shown, &entry.name)));
+                }
+            }
+        }
+        candidates = next;
+    }
+
+    let mut found: Vec<String> = candidates
+        .into_iter()
+        .filter(|(full, _)| fs.exists(who, full))
+        .map(|(_, shown)| shown)
+        .collect();
+    found.sort();
+    found
+}
+
+fn display_join(shown: &str, name: &str) -> String {
+    if shown.is_empty() || shown.ends_with('/') {
+        format!("{}{}", shown, name)
+    } else {
+        format!("{}/{}", shown, name)
+    }
+}
@@ -0,0 +1,562 @@
+use crate::api;
+use crate::auth::{self, CurrentUser};
+use crate::clock;
+use crate::commands::{EXIT_FAILURE, EXIT_SUCCESS};
+use crate::console::{self, Console};
+use crate::cron;
+use crate::process::{self, Pid, Signal};
+use crate::profile;
+use crate::remote::Address;
+use crate::session::Session;
+use crate::syslog::{self, Facility, Level};
+use crate::terminal;
+use crate::vfs;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Boot configuration, next to users.xml. Declares the services started
+/// at boot.
+pub const CONFIG_FILE_PATH: &str = "minikern.conf";
+
+/// Written on the first boot
+const DEFAULT_CONFIG: &str = "\
+# MiniKern boot configuration
+#
+# Each [name] section declares a service, started at boot after the
+# services listed in `after`. Services other than the built-in ones run
+# `command` in a shell of their own, as `user` (root by default), until
+# it ends or the service is stopped.
+#
+# [ticker]
+# command = watch -n 60 echo tick
+# user = root
+# after = cron
+# enabled = yes
+#
+# The HTTP admin API is a built-in service too, off unless declared. It
+# listens on HOST:PORT or unix:PATH; tokens come from `apitoken`.
+#
+# [api]
+# listen = 127.0.0.1:8023
+
+[cron]
+";
+
+/// Services MiniKern provides itself, which don't take a command
+const BUILTIN_SERVICES: &[&str] = &["cron", "api"];
+
+/// How long a stopping service has to end after TERM before it's killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A service declared in the boot configuration
+#[derive(Debug, Clone)]
+pub struct Service {
+    pub name: String,
+    /// `None` for built-in services
+    pub command: Option<String>,
+    /// Who the command runs as; root if not given
+    pub user: Option<String>,
+    /// Services that have to be running before this one starts
+    pub after: Vec<String>,
+    /// Whether it's started at boot
+    pub enabled: bool,
+    /// Where the `api` service listens
+    pub listen: Option<String>,
+}
+
+/// What a service is doing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Stopped,
+    /// Since when, and the shell running its command
+    Running(u64, Option<Pid>),
+    /// Its command ended on its own with this status
+    Exited(i32),
+    Failed(String),
+}
+
+impl Status {
+    pub fn describe(&self) -> String {
+        match self {
+            Status::Stopped => "stopped".to_string(),
+            Status::Running(..) => "running".to_string(),
+            Status::Exited(status) => format!("exited {}", status),
+            Status::Failed(reason) => format!("failed: {}", reason),
+        }
+    }
+}
+
+/// A started service: since when, and for services with a command the
+/// shell running it and the thread that returns its exit status
+struct Running {
+    since: u64,
+    shell: Option<(Pid, JoinHandle<i32>)>,
+}
+
+struct Init {
+    /// Declared services, in the order they start
+    services: Vec<Service>,
+    /// Running services, in the order they started
+    running: Vec<(String, Running)>,
+    /// How services that aren't running last ended
+    ended: BTreeMap<String, Status>,
+}
+
+static INIT: Mutex<Init> = Mutex::new(Init {
+    services: Vec::new(),
+    running: Vec::new(),
+    ended: BTreeMap::new(),
+});
+
+fn lock() -> MutexGuard<'static, Init> {
+    let mut init = INIT.lock().unwrap_or_else(|p| p.into_inner());
+    init.reap();
+    init
+}
+
+impl Init {
+    /// Notes services whose commands have ended on their own
+    fn reap(&mut self) {
+        let mut i = 0;
+        while i < self.running.len() {
+            let finished = matches!(
+                &self.running[i].1.shell,
+                Some((_, handle)) if handle.is_finished()
+            );
+            if !finished {
+                i += 1;
+                continue;
+            }
+            let (name, running) = self.running.remove(i);
+            let status = match running.shell.map(|(_, handle)| handle.join()) {
+                Some(Ok(status)) => Status::Exited(status),
+                _ => Status::Failed("crashed".to_string()),
+            };
+            let level = match status {
+                Status::Exited(EXIT_SUCCESS) => Level::Info,
+                Status::Exited(_) => Level::Warning,
+                _ => Level::Err,
+            };
+            let _ = syslog::record(
+                level,
+                Facility::Kern,
+                &format!("{}: {}", name, status.describe()),
+            );
+            self.ended.insert(name, status);
+        }
+    }
+
+    fn service(&self, name: &str) -> Result<&Service, String> {
+        self.services
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| format!("{}: no such service", name))
+    }
+
+    fn is_running(&self, name: &str) -> bool {
+        self.running.iter().any(|(n, _)| n == name)
+    }
+}
+
+/// Prints how a boot or shutdown step went, and logs it
+pub fn report(result: Result<String, String>) {
+    let _ = match &result {
+        Ok(message) => syslog::record(Level::Info, Facility::Kern, message),
+        Err(message) => syslog::record(Level::Err, Facility::Kern, message),
+    };
+    match result {
+        Ok(message) => println!("[  OK  ] {}", message),
+        Err(message) => println!("[FAILED] {}", message),
+    }
+}
+
+/// Parses a boot configuration: `[name]` sections of `key = value` lines.
+/// Errors say which line is wrong.
+pub fn parse(text: &str) -> Result<Vec<Service>, String> {
+    let mut services: Vec<Service> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(error(format!("bad service name '{}'", name)));
+            }
+            if services.iter().any(|s| s.name == name) {
+                return Err(error(format!("service '{}' is declared twice", name)));
+            }
+            services.push(Service {
+                name: name.to_string(),
+                command: None,
+                user: None,
+                after: Vec::new(),
+                enabled: true,
+                listen: None,
+            });
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error(format!("expected 'key = value', found '{}'", line)));
+        };
+        let Some(service) = services.last_mut() else {
+            return Err(error("setting outside of a [service] section".to_string()));
+        };
+        let value = value.trim();
+        match key.trim() {
+            "command" => service.command = Some(value.to_string()),
+            "user" => service.user = Some(value.to_string()),
+            "listen" => service.listen = Some(value.to_string()),
+            "after" => {
+                service.after = value
+                    .split([',', ' '])
+                    .filter(|name| !name.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            "enabled" => {
+                service.enabled = match value {
+                    "yes" | "true" => true,
+                    "no" | "false" => false,
+                    _ => return Err(error(format!("enabled must be yes or no, not '{}'", value))),
+                }
+            }
+            key => return Err(error(format!("unknown setting '{}'", key))),
+        }
+    }
+    for service in &services {
+        let builtin = BUILTIN_SERVICES.contains(&service.name.as_str());
+        match (&service.command, builtin) {
+            (Some(_), true) => {
+                return Err(format!(
+                    "{}: built-in services don't take a command",
+                    service.name
+                ))
+            }
+            (None, false) => return Err(format!("{}: no command", service.name)),
+            _ => {}
+        }
+        match (service.name == "api", &service.listen) {
+            (true, None) => return Err("api: no listen address".to_string()),
+            (false, Some(_)) => {
+                return Err(format!("{}: only the api service takes listen", service.name))
+            }
+            _ => {}
+        }
+    }
+    start_order(services)
+}
+
+/// Sorts services so each comes after the ones it names in `after`,
+/// otherwise keeping the order they were declared in
+fn start_order(mut services: Vec<Service>) -> Result<Vec<Service>, String> {
+    for service in &services {
+        if let Some(unknown) = service
+            .after
+            .iter()
+            .find(|name| !services.iter().any(|s| &s.name == *name))
+        {
+            return Err(format!(
+                "{}: comes after unknown service '{}'",
+                service.name, unknown
+            ));
+        }
+    }
+    let mut ordered: Vec<Service> = Vec::new();
+    while !services.is_empty() {
+        let Some(next) = services.iter().position(|service| {
+            service
+                .after
+                .iter()
+                .all(|name| ordered.iter().any(|s| &s.name == name))
+        }) else {
+            let names: Vec<&str> = services.iter().map(|s| s.name.as_str()).collect();
+            return Err(format!("services wait on each other: {}", names.join(", ")));
+        };
+        ordered.push(services.remove(next));
+    }
+    Ok(ordered)
+}
+
+/// Reads the boot configuration, writing the default one on the first
+/// boot. Returns how many services it declares.
+pub fn load_config() -> Result<usize, String> {
+    let text = match std::fs::read_to_string(CONFIG_FILE_PATH) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            std::fs::write(CONFIG_FILE_PATH, DEFAULT_CONFIG)
+                .map_err(|e| format!("could not write {}: {}", CONFIG_FILE_PATH, e))?;
+            DEFAULT_CONFIG.to_string()
+        }
+        Err(e) => return Err(format!("could not read {}: {}", CONFIG_FILE_PATH, e)),
+    };
+    let services = parse(&text).map_err(|e| format!("{}: {}", CONFIG_FILE_PATH, e))?;
+    let count = services.len();
+    lock().services = services;
+    Ok(count)
+}
+
+/// Starts the enabled services in dependency order, reporting each. A
+/// service whose dependencies didn't start isn't started either.
+pub fn start_services() {
+    let services = lock().services.clone();
+    for service in services.iter().filter(|s| s.enabled) {
+        let result = match start_one(&service.name) {
+            Ok(()) => Ok(format!("Started {}.", service.name)),
+            Err(e) => Err(format!("Failed to start {}: {}", service.name, e)),
+        };
+        report(result);
+    }
+}
+
+/// Stops every running service, last started first, reporting each
+pub fn stop_services() {
+    loop {
+        let Some(name) = lock().running.last().map(|(name, _)| name.clone()) else {
+            return;
+        };
+        report(stop_one(&name).map(|()| format!("Stopped {}.", name)));
+    }
+}
+
+/// Starts a service, and first the services it comes after. Returns the
+/// services started, in order.
+pub fn start(name: &str) -> Result<Vec<String>, String> {
+    let mut order = Vec::new();
+    {
+        let init = lock();
+        let mut pending = vec![init.service(name)?.name.clone()];
+        while let Some(next) = pending.pop() {
+            if init.is_running(&next) || order.contains(&next) {
+                continue;
+            }
+            order.push(next.clone());
+            pending.extend(init.service(&next)?.after.iter().cloned());
+        }
+        // Services start after everything they come after
+        order.sort_by_key(|n| init.services.iter().position(|s| &s.name == n));
+    }
+    if order.is_empty() {
+        return Err(format!("{}: already running", name));
+    }
+    for name in &order {
+        start_one(name).map_err(|e| format!("{}: {}", name, e))?;
+    }
+    Ok(order)
+}
+
+/// Stops a service, and first the running services that come after it.
+/// Returns the services stopped, in order.
+pub fn stop(name: &str) -> Result<Vec<String>, String> {
+    let order: Vec<String> = {
+        let init = lock();
+        init.service(name)?;
+        if !init.is_running(name) {
+            return Err(format!("{}: not running", name));
+        }
+        let mut stopping = vec![name.to_string()];
+        // Services start after what they depend on, so anything depending
+        // on a stopping service is later in the list
+        for service in &init.services {
+            if service.after.iter().any(|n| stopping.contains(n)) {
+                stopping.push(service.name.clone());
+            }
+        }
+        init.running
+            .iter()
+            .rev()
+            .map(|(n, _)| n.clone())
+            .filter(|n| stopping.contains(n))
+            .collect()
+    };
+    for name in &order {
+        stop_one(name).map_err(|e| format!("{}: {}", name, e))?;
+    }
+    Ok(order)
+}
+
+/// Every declared service and what it's doing
+pub fn status() -> Vec<(Service, Status)> {
+    let init = lock();
+    init.services
+        .iter()
+        .map(|service| {
+            let status = match init.running.iter().find(|(n, _)| *n == service.name) {
+                Some((_, running)) => {
+                    Status::Running(running.since, running.shell.as_ref().map(|(pid, _)| *pid))
+                }
+                None => init
+                    .ended
+                    .get(&service.name)
+                    .cloned()
+                    .unwrap_or(Status::Stopped),
+            };
+            (service.clone(), status)
+        })
+        .collect()
+}
+
+fn start_one(name: &str) -> Result<(), String> {
+    let mut init = lock();
+    let service = init.service(name)?.clone();
+    if init.is_running(name) {
+        return Ok(());
+    }
+    if let Some(missing) = service.after.iter().find(|n| !init.is_running(n)) {
+        return Err(format!("{} isn't running", missing));
+    }
+    let shell = match &service.command {
+        None => {
+            let started = match name {
+                "cron" => {
+                    cron::start();
+                    Ok(())
+                }
+                "api" => api::start(&Address::parse(service.listen.as_deref().unwrap_or_default())),
+                _ => Err("no such built-in service".to_string()),
+            };
+            if let Err(e) = started {
+                init.ended.insert(name.to_string(), Status::Failed(e.clone()));
+                return Err(e);
+            }
+            None
+        }
+        Some(command) => match spawn(&service, command) {
+            Ok(shell) => Some(shell),
+            Err(e) => {
+                init.ended
+                    .insert(name.to_string(), Status::Failed(e.clone()));
+                return Err(e);
+            }
+        },
+    };
+    init.running.push((
+        name.to_string(),
+        Running {
+            since: clock::unix_now(),
+            shell,
+        },
+    ));
+    init.ended.remove(name);
+    Ok(())
+}
+
+/// Runs a service's command in a new shell of its user
+fn spawn(service: &Service, command: &str) -> Result<(Pid, JoinHandle<i32>), String> {
+    let users = auth::load_users().map_err(|e| format!("could not load users: {}", e))?;
+    let user = match &service.user {
+        Some(name) => users.iter().find(|u| &u.username == name),
+        None => users.first(),
+    }
+    .ok_or_else(|| format!("no such user '{}'", service.user.as_deref().unwrap_or("")))?;
+    if user.disabled {
+        return Err(format!("account '{}' is disabled", user.username));
+    }
+    profile::ensure_home(&user.username)
+        .map_err(|e| format!("no home directory for '{}': {}", user.username, e))?;
+    let mut session = Session::new(CurrentUser {
+        username: user.username.clone(),
+        is_admin: user.is_admin,
+    });
+    session.enter_home();
+    let pid = session.pid;
+    let command = command.to_string();
+    // Services have no terminal; what they print is logged
+    let console = Console::detached(
+        Facility::Kern,
+        &format!("{} as {}", service.name, user.username),
+    );
+    let handle = std::thread::spawn(move || {
+        console::attach(console);
+        let status = match terminal::execute_line(&mut session, &command) {
+            Ok(_) => session.last_status,
+            Err(_) => EXIT_FAILURE,
+        };
+        process::end_session(session.pid);
+        let _ = vfs::sync();
+        status
+    });
+    Ok((pid, handle))
+}
+
+/// Stops one service: built-ins at once, commands by sending their shell
+/// TERM, and KILL if it hasn't ended in time
+fn stop_one(name: &str) -> Result<(), String> {
+    let running = {
+        let mut init = lock();
+        let Some(i) = init.running.iter().position(|(n, _)| n == name) else {
+            return Ok(());
+        };
+        init.running.remove(i).1
+    };
+    let status = match running.shell {
+        None => {
+            match name {
+                "cron" => cron::stop(),
+                "api" => api::stop(),
+                _ => false,
+            };
+            Status::Stopped
+        }
+        Some((pid, handle)) => {
+            let root = vfs::lock().root_user();
+            let _ = process::signal(&root, pid, Signal::Term);
+            let deadline = Instant::now() + STOP_TIMEOUT;
+            while !handle.is_finished() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            if handle.is_finished() {
+                let _ = handle.join();
+                Status::Stopped
+            } else {
+                // The thread is left to notice on its own
+                let _ = process::signal(&root, pid, Signal::Kill);
+                Status::Failed(format!("killed after {}s", STOP_TIMEOUT.as_secs()))
+            }
+        }
+    };
+    let mut init = lock();
+    init.ended.insert(name.to_string(), status.clone());
+    match status {
+        Status::Failed(reason) => Err(reason),
+        _ => Ok(()),
+    }
+}
+
+/// Stops the services in reverse order, saves the filesystem and ends
+/// MiniKern, starting it again from the top for a reboot
+pub fn shutdown(reboot: bool) -> ! {
+    println!(
+        "System is going down for {} NOW.",
+        if reboot { "reboot" } else { "halt" }
+    );
+    stop_services();
+    report(
+        vfs::checkpoint()
+            .map(|()| format!("Saved {}.", vfs::IMAGE_FILE_PATH))
+            .map_err(|e| format!("Could not save {}: {}", vfs::IMAGE_FILE_PATH, e)),
+    );
+    if reboot {
+        report(Ok("Rebooting.".to_string()));
+        use std::os::unix::process::CommandExt;
+        let error = std::env::current_exe()
+            .map(|exe| {
+                std::process::Command::new(exe)
+                    .args(std::env::args_os().skip(1))
+                    .exec()
+            })
+            .unwrap_or_else(|e| e);
+        syslog::console(
+            Level::Crit,
+            Facility::Kern,
+            &format!("could not reboot: {}", error),
+        );
+        std::process::exit(EXIT_FAILURE);
+    }
+    report(Ok("Halted.".to_string()));
+    std::process::exit(0);
+}
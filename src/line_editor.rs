@@ -0,0 +1,241 @@
+use crate::console;
+use crate::tty::{self, read_key, Key, RawMode};
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// How often a prompt waiting for a key looks for messages to show
+const MESSAGE_POLL: Duration = Duration::from_millis(200);
+
+/// A tab-completion candidate. `note` is shown next to the value when the
+/// candidates are listed, for example "(alias)".
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub value: String,
+    pub note: Option<String>,
+}
+
+/// Minimal line editor with history and tab completion. Falls back to plain
+/// `read_line` when stdin isn't a terminal.
+#[derive(Default)]
+pub struct LineEditor {
+    history: Vec<String>,
+}
+
+impl LineEditor {
+    /// Reads one line. Returns `None` at end of input.
+    ///
+    /// `complete` is called with the word under the cursor and whether it's
+    /// the first word of the line, and returns the possible completions.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        complete: &dyn Fn(&str, bool) -> Vec<Completion>,
+    ) -> io::Result<Option<String>> {
+        print_messages();
+        print!("{}", prompt);
+        console::stdout().flush()?;
+
+        let Some(_raw) = RawMode::enable() else {
+            let mut input = String::new();
+            loop {
+                match tty::read_line(&mut input) {
+                    Ok(0) => return Ok(None),
+                    Ok(_) => return Ok(Some(input)),
+                    // Start over on a fresh prompt, as in raw mode
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                        input.clear();
+                        print!("{}", prompt);
+                        console::stdout().flush()?;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+
+        // Redraws only touch the last line of a multi-line prompt
+        let prompt_tail = prompt.rsplit('\n').next().unwrap_or(prompt);
+        let mut line: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        let mut history_index = self.history.len();
+        let mut last_was_tab = false;
+
+        loop {
+            // Messages arriving meanwhile go above the line being typed
+            while !tty::wait_for_input(MESSAGE_POLL)? {
+                if print_messages() {
+                    redraw(prompt_tail, &line, cursor)?;
+                }
+            }
+            let key = read_key()?;
+            let is_tab = matches!(key, Key::Tab);
+            match key {
+                Key::Char(c) => {
+                    line.insert(cursor, c);
+                    cursor += 1;
+                    if cursor == line.len() {
+                        // Typing at the end of the line needs no redraw
+                        print!("{}", c);
+                        console::stdout().flush()?;
+                        last_was_tab = false;
+                        continue;
+                    }
+                }
+                Key::Enter => {
+                    println!();
+                    let line: String = line.into_iter().collect();
+                    if !line.trim().is_empty()
+                        && self.history.last() != Some(&line)
+                    {
+                        self.history.push(line.clone());
+                    }
+                    return Ok(Some(line));
+                }
+                Key::Backspace => {
+                    if cursor > 0 {
+                        cursor -= 1;
+                        line.remove(cursor);
+                    }
+                }
+                Key::Delete => {
+                    if cursor < line.len() {
+                        line.remove(cursor);
+                    }
+                }
+                Key::Left => cursor = cursor.saturating_sub(1),
+                Key::Right => cursor = (cursor + 1).min(line.len()),
+                Key::Home => cursor = 0,
+                Key::End => cursor = line.len(),
+                Key::Up => {
+                    if history_index > 0 {
+                        history_index -= 1;
+                        line = self.history[history_index].chars().collect();
+                        cursor = line.len();
+                    }
+                }
+                Key::Down => {
+                    if history_index < self.history.len() {
+                        history_index += 1;
+                        line = self
+                            .history
+                            .get(history_index)
+                            .map(|h| h.chars().collect())
+                            .unwrap_or_default();
+                        cursor = line.len();
+                    }
+                }
+                Key::KillLine => {
+                    line.clear();
+                    cursor = 0;
+                }
+                Key::Interrupt => {
+                    // Abandon the line and start over on a fresh prompt
+                    println!("^C");
+                    line.clear();
+                    cursor = 0;
+                    print!("{}", prompt);
+                }
+                Key::Eof => {
+                    if line.is_empty() {
+                        println!();
+                        return Ok(None);
+                    }
+                }
+                Key::Tab => {
+                    let start = line[..cursor]
+                        .iter()
+                        .rposition(|c| c.is_whitespace())
+                        .map(|i| i + 1)
+                        .unwrap_or(0);
+                    let word: String = line[start..cursor].iter().collect();
+                    let first_word =
+                        line[..start].iter().all(|c| c.is_whitespace());
+                    let candidates = complete(&word, first_word);
+
+                    match candidates.len() {
+                        0 => {}
+                        1 => {
+                            // A completed directory is followed by its
+                            // contents rather than the next word
+                            let value = &candidates[0].value;
+                            let separator = (!value.ends_with('/')).then_some(' ');
+                            let rest: Vec<char> = value[word.len()..]
+                                .chars()
+                                .chain(separator)
+                                .collect();
+                            for c in rest {
+                                line.insert(cursor, c);
+                                cursor += 1;
+                            }
+                        }
+                        _ => {
+                            let common = common_prefix(&candidates);
+                            if common.len() > word.len() {
+                                for c in common[word.len()..].chars() {
+                                    line.insert(cursor, c);
+                                    cursor += 1;
+                                }
+                            } else if last_was_tab {
+                                println!();
+                                print_candidates(&candidates);
+                                print!("{}", prompt);
+                            }
+                        }
+                    }
+                }
+                Key::PageUp | Key::PageDown | Key::Unknown => {}
+            }
+            last_was_tab = is_tab;
+            redraw(prompt_tail, &line, cursor)?;
+        }
+    }
+}
+
+fn redraw(prompt: &str, line: &[char], cursor: usize) -> io::Result<()> {
+    let text: String = line.iter().collect();
+    let mut out = console::stdout();
+    write!(out, "\r{}{}\x1b[K", prompt, text)?;
+    let back = line.len() - cursor;
+    if back > 0 {
+        write!(out, "\x1b[{}D", back)?;
+    }
+    out.flush()
+}
+
+/// Prints the messages other users sent to the console on lines of their
+/// own, returning whether there were any
+fn print_messages() -> bool {
+    let messages = console::take_messages();
+    if messages.is_empty() {
+        return false;
+    }
+    print!("\r\x1b[K");
+    for message in messages {
+        println!("{}", message);
+    }
+    true
+}
+
+fn common_prefix(candidates: &[Completion]) -> String {
+    let first = &candidates[0].value;
+    let mut len = first.len();
+    for candidate in &candidates[1..] {
+        len = first
+            .char_indices()
+            .zip(candidate.value.chars())
+            .take_while(|((_, a), b)| a == b)
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+fn print_candidates(candidates: &[Completion]) {
+    for candidate in candidates {
+        match &candidate.note {
+            Some(note) => println!("  {:<12} {}", candidate.value, note),
+            None => println!("  {}", candidate.value),
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use crate::clock;
+use crate::vfs::{self, path, VfsError};
+
+/// Where each user's mailbox is kept, as an mbox file named after them.
+/// Only root can read it; users get at theirs with `mail`.
+pub const MAIL_DIR_PATH: &str = "/var/mail";
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A message in a user's mailbox
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub from: String,
+    /// The recipients as the sender gave them, groups included
+    pub to: String,
+    /// Unix timestamp
+    pub date: u64,
+    pub subject: String,
+    pub body: String,
+    /// Whether it has been read with `mail read`
+    pub read: bool,
+}
+
+pub fn mailbox_path(username: &str) -> String {
+    path::join(MAIL_DIR_PATH, username)
+}
+
+/// The date of an mbox `From ` line, like `Mon Oct 19 14:02:00 2026`
+fn asctime(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let (year, month, day) = clock::civil_from_days(days);
+    format!(
+        "{} {} {:2} {} {}",
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+        MONTHS[month as usize - 1],
+        day,
+        clock::format_hms(secs),
+        year
+    )
+}
+
+/// Parses a mailbox in mbox format: each message starts with a `From `
+/// line after a blank line, then has its headers, a blank line and the
+/// body. Body lines that started with `From ` were quoted with `>`.
+pub fn parse(text: &str) -> Vec<Message> {
+    let mut messages = Vec::new();
+    let mut lines = text.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(envelope) = line.strip_prefix("From ") else {
+            continue;
+        };
+        let mut message = Message {
+            from: envelope
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            to: String::new(),
+            date: 0,
+            subject: String::new(),
+            body: String::new(),
+            read: false,
+        };
+        for header in lines.by_ref() {
+            let Some((name, value)) = header.split_once(": ") else {
+                break;
+            };
+            match name {
+                "From" => message.from = value.to_string(),
+                "To" => message.to = value.to_string(),
+                "Date" => message.date = clock::parse_datetime(value).unwrap_or(0),
+                "Subject" => message.subject = value.to_string(),
+                "Status" => message.read = value.contains('R'),
+                _ => {}
+            }
+        }
+        let mut body: Vec<&str> = Vec::new();
+        while let Some(line) = lines.next_if(|line| !line.starts_with("From ")) {
+            body.push(line);
+        }
+        // The blank line before the next message isn't part of the body
+        if body.last() == Some(&"") {
+            body.pop();
+        }
+        for line in body {
+            let unquoted = match line.trim_start_matches('>').starts_with("From ") {
+                true => &line[1..],
+                false => line,
+            };
+            message.body.push_str(unquoted);
+            message.body.push('\n');
+        }
+        messages.push(message);
+    }
+    messages
+}
+
+/// A header value on one line, so it can't start headers or a message of
+/// its own
+fn one_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+/// Writes messages in the format `parse` reads
+pub fn format(messages: &[Message]) -> String {
+    let mut text = String::new();
+    for message in messages {
+        let from = one_line(&message.from);
+        text.push_str(&format!("From {} {}\n", from, asctime(message.date)));
+        text.push_str(&format!("From: {}\n", from));
+        text.push_str(&format!("To: {}\n", one_line(&message.to)));
+        text.push_str(&format!("Date: {}\n", clock::format_datetime(message.date)));
+        text.push_str(&format!("Subject: {}\n", one_line(&message.subject)));
+        if message.read {
+            text.push_str("Status: RO\n");
+        }
+        text.push('\n');
+        for line in message.body.lines() {
+            if line.trim_start_matches('>').starts_with("From ") {
+                text.push('>');
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Runs `f` on a user's messages, oldest first, and saves what it leaves.
+/// The filesystem stays locked meanwhile, so mail delivered at the same
+/// time isn't lost.
+pub fn update<T>(username: &str, f: impl FnOnce(&mut Vec<Message>) -> T) -> Result<T, VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    let path = mailbox_path(username);
+    let mut messages = match fs.read_file(&root, &path) {
+        Ok(bytes) => parse(&String::from_utf8_lossy(&bytes)),
+        Err(VfsError::NotFound) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    let result = f(&mut messages);
+    if !fs.exists(&root, MAIL_DIR_PATH) {
+        fs.mkdir_all(&root, MAIL_DIR_PATH)?;
+        fs.chmod(&root, MAIL_DIR_PATH, 0o700)?;
+    }
+    fs.write_file(&root, &path, format(&messages).as_bytes())?;
+    fs.chmod(&root, &path, 0o600)?;
+    Ok(result)
+}
+
+/// A user's messages, oldest first
+pub fn load(username: &str) -> Result<Vec<Message>, VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    match fs.read_file(&root, &mailbox_path(username)) {
+        Ok(bytes) => Ok(parse(&String::from_utf8_lossy(&bytes))),
+        Err(VfsError::NotFound) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Adds a message to the end of a user's mailbox
+pub fn deliver(username: &str, message: Message) -> Result<(), VfsError> {
+    update(username, |messages| messages.push(message))
+}
+
+/// Whether a user has mail they haven't read
+pub fn has_new(username: &str) -> bool {
+    load(username).is_ok_and(|messages| messages.iter().any(|m| !m.read))
+}
+
+/// Deletes a user's mailbox, returning whether they had one
+pub fn remove(username: &str) -> Result<bool, VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    match fs.unlink(&root, &mailbox_path(username)) {
+        Ok(()) => Ok(true),
+        Err(VfsError::NotFound) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Moves a renamed user's mailbox along with them
+pub fn rename(old: &str, new: &str) -> Result<(), VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    match fs.rename(&root, &mailbox_path(old), &mailbox_path(new)) {
+        Err(VfsError::NotFound) => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(subject: &str, body: &str) -> Message {
+        Message {
+            from: "alice".to_string(),
+            to: "bob, @staff".to_string(),
+            date: 1_792_418_520,
+            subject: subject.to_string(),
+            body: body.to_string(),
+            read: false,
+        }
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        let mut second = message("Re: lunch", "Fine.\n");
+        second.read = true;
+        let messages = [message("lunch", "Noon?\n\nBring a friend.\n"), second];
+        let parsed = parse(&format(&messages));
+        assert_eq!(parsed.len(), 2);
+        for (parsed, sent) in parsed.iter().zip(&messages) {
+            assert_eq!(parsed.from, sent.from);
+            assert_eq!(parsed.to, sent.to);
+            assert_eq!(parsed.date, sent.date);
+            assert_eq!(parsed.subject, sent.subject);
+            assert_eq!(parsed.body, sent.body);
+            assert_eq!(parsed.read, sent.read);
+        }
+    }
+
+    #[test]
+    fn from_lines_in_the_body_stay_in_the_body() {
+        let body = "From here on\n>From quoted\n\nFrom alice Mon Oct 19 14:02:00 2026\n";
+        let parsed = parse(&format(&[message("s", body)]));
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].body, body);
+    }
+
+    #[test]
+    fn a_subject_cant_add_headers_or_messages() {
+        let subject =
+            "hi\nStatus: RO\nFrom: root\n\nFrom root Mon Oct 19 14:02:00 2026\r\nSubject: forged";
+        let text = format(&[message(subject, "Body\n")]);
+        assert_eq!(text.lines().filter(|l| l.starts_with("From ")).count(), 1);
+
+        let parsed = parse(&text);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].from, "alice");
+        assert!(!parsed[0].read);
+        assert_eq!(parsed[0].subject, subject.replace(['\r', '\n'], " "));
+        assert_eq!(parsed[0].body, "Body\n");
+    }
+}
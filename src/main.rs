@@ -1,11 +1,59 @@
+// Output goes to the calling thread's console, which is a network client's
+// in a served session, or a buffer for a pipeline, so these stand in for
+// the standard macros in every module
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::console::print(format_args!($($arg)*))
+    };
+}
+
+macro_rules! println {
+    () => {
+        $crate::console::print(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::console::print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+macro_rules! eprintln {
+    ($($arg:tt)*) => {
+        $crate::console::eprint(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+mod accounts;
+mod api;
+mod audit;
 mod auth;
+mod clock;
 mod commands;
+mod console;
+mod cron;
+mod glob;
+mod init;
+mod line_editor;
+mod mail;
+mod output;
+mod process;
+mod profile;
+mod remote;
+mod sched;
+mod session;
+mod shell;
+mod syslog;
 mod terminal;
+mod tar;
+mod tui;
+mod tty;
+mod vfs;
 
 use auth::{
     hash_password, load_users, save_users, CurrentUser, User,
     USERS_FILE_PATH,
 };
+use session::Session;
+use syslog::{Facility, Level};
 use std::io::{self, Write};
 use std::path::Path;
 
@@ -18,19 +66,12 @@ fn initial_user_setup() -> Result<(), Box<dyn std::error::Error>> {
 
     let username = loop {
         print!("Username: > ");
-        io::stdout().flush()?;
+        console::stdout().flush()?;
         let mut buffer = String::new();
-        io::stdin().read_line(&mut buffer)?;
+        tty::read_line(&mut buffer)?;
         let name = buffer.trim().to_string();
-        if name.is_empty() {
-            println!("Username cannot be empty.");
-            continue;
-        }
-        if name.contains(|c: char| c.is_whitespace() || "<>&\"'".contains(c))
-        {
-            println!(
-                "Username cannot contain spaces or XML special characters."
-            );
+        if let Err(e) = accounts::validate_username(&name) {
+            println!("{}", e);
             continue;
         }
         break name;
@@ -43,9 +84,11 @@ fn initial_user_setup() -> Result<(), Box<dyn std::error::Error>> {
         username: username.clone(),
         password_hash,
         is_admin: true, // First user is always admin
+        disabled: false,
     };
 
     save_users(&[admin_user])?;
+    let _ = audit::record(&username, "create", &username, "admin (initial setup)");
     println!("Admin user '{}' created successfully.", username);
     Ok(())
 }
@@ -55,42 +98,128 @@ fn login_procedure(
 ) -> Result<CurrentUser, Box<dyn std::error::Error>> {
     println!("-----------------------------");
     println!("Login");
-    for _attempt in 0..3 { // Allow 3 login attempts
-        print!("Username: > ");
-        io::stdout().flush()?;
-        let mut username_input = String::new();
-        io::stdin().read_line(&mut username_input)?;
+    let mut attempts = 0;
+    while attempts < 3 { // Allow 3 login attempts
+        // Ctrl-C starts the attempt over rather than using it up
+        let (username_input, password_input) = match read_credentials() {
+            Err(e) if tty::is_interrupt(&e) => continue,
+            credentials => credentials?,
+        };
         let username_input = username_input.trim();
-
-        let password_input =
-            auth::prompt_password_hidden("Password: > ")?;
+        attempts += 1;
 
         if let Some(user) =
-            users.iter().find(|u| u.username == username_input)
+            auth::verify_login(users, username_input, &password_input)
         {
-            if user.password_hash == hash_password(&password_input) {
-                println!("Login successful!");
-                return Ok(CurrentUser {
-                    username: user.username.clone(),
-                    is_admin: user.is_admin,
-                });
-            }
+            let _ = audit::record(&user.username, "login", &user.username, "");
+            let _ = syslog::record(
+                Level::Info,
+                Facility::Auth,
+                &format!("{} logged in{}", user.username, login_origin()),
+            );
+            println!("Login successful!");
+            return Ok(user);
         }
+        let _ = audit::record(username_input, "login-failed", username_input, "");
+        let _ = syslog::record(
+            Level::Notice,
+            Facility::Auth,
+            &format!("failed login for '{}'{}", username_input, login_origin()),
+        );
         println!("Invalid username or password. Please try again.");
     }
+    let _ = syslog::record(Level::Warning, Facility::Auth, "too many failed login attempts");
     Err("Too many failed login attempts.".into())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Where a login came from, for the log: nothing on the host's terminal
+fn login_origin() -> String {
+    match console::current().peer() {
+        Some(peer) => format!(" from {}", peer),
+        None => String::new(),
+    }
+}
+
+fn read_credentials() -> io::Result<(String, String)> {
+    print!("Username: > ");
+    console::stdout().flush()?;
+    let mut username = String::new();
+    if tty::read_line(&mut username)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of input"));
+    }
+    let password = auth::prompt_password_hidden("Password: > ")?;
+    Ok((username, password))
+}
+
+/// `minikern exec [-u USER] COMMAND...` runs one command line without the
+/// interactive terminal and exits with its status. The user defaults to
+/// `MINIKERN_USER`, and the password is taken from `MINIKERN_PASSWORD` if
+/// set, otherwise prompted for.
+fn exec_command(args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+    let (username, command) = match args {
+        [flag, user, rest @ ..] if flag == "-u" || flag == "--user" => {
+            (user.clone(), rest)
+        }
+        _ => (
+            std::env::var("MINIKERN_USER").unwrap_or_default(),
+            args,
+        ),
+    };
+    if username.is_empty() || command.is_empty() {
+        eprintln!("Usage: minikern exec [-u USER] COMMAND [ARGS...]");
+        return Ok(commands::EXIT_USAGE);
+    }
+
+    let users = load_users()?;
+    let password = match std::env::var("MINIKERN_PASSWORD") {
+        Ok(password) => password,
+        Err(_) => auth::prompt_password_hidden(&format!("Password for {}: > ", username))?,
+    };
+    let Some(user) = auth::verify_login(&users, &username, &password) else {
+        let _ = syslog::record(
+            Level::Notice,
+            Facility::Auth,
+            &format!("failed exec login for '{}'", username),
+        );
+        eprintln!("Invalid username or password.");
+        return Ok(commands::EXIT_PERMISSION);
+    };
+
+    // A single argument is a whole command line (so `&&`, `;` etc. work);
+    // several are quoted again so the shell sees the words we were given
+    let line = match command {
+        [line] => line.clone(),
+        words => words
+            .iter()
+            .map(|word| shell::quote(word))
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    profile::ensure_home(&user.username)?;
+    let mut session = Session::new(user);
+    session.terminal = true;
+    session.enter_home();
+    terminal::execute_line(&mut session, &line)?;
+    process::end_session(session.pid);
+    vfs::checkpoint()?;
+    Ok(session.last_status)
+}
+
+/// Loads the user list, running the first-time setup if there are no users.
+fn ensure_users() -> Result<(), Box<dyn std::error::Error>> {
     // Check for users.xml and load users
     let initial_users = match load_users() {
         Ok(users_vec) => users_vec,
         Err(e) => {
             // If loading fails (e.g., malformed XML), treat as no users.
             // A more robust solution might offer to repair or backup.
-            eprintln!(
-                "Warning: Could not load users from {}: {}. Proceeding with initial setup if needed.",
-                USERS_FILE_PATH, e
+            syslog::console(
+                Level::Warning,
+                Facility::Auth,
+                &format!(
+                    "could not load users from {}: {}. Proceeding with initial setup if needed.",
+                    USERS_FILE_PATH, e
+                ),
             );
             // Attempt to delete potentially corrupt file before creating a new one
             if Path::new(USERS_FILE_PATH).exists() {
@@ -104,6 +233,225 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     if initial_users.is_empty() {
         initial_user_setup()?;
     }
+    Ok(())
+}
+
+/// Loads the virtual filesystem from its disk image. The first boot with
+/// no image creates a fresh filesystem and brings over startup files kept on
+/// the host by older versions. The root account owns the system
+/// directories, and the kernel process, so this runs once there are users.
+fn mount_filesystem() -> Result<(), Box<dyn std::error::Error>> {
+    let users = load_users()?;
+    let root = users.first().ok_or("No users found in system.")?;
+    let image = Path::new(vfs::IMAGE_FILE_PATH);
+    let fresh = !image.exists();
+    let replayed = vfs::mount_root(image, &root.username)
+        .map_err(|e| format!("Could not load {}: {}", vfs::IMAGE_FILE_PATH, e))?;
+    if replayed > 0 {
+        syslog::console(
+            Level::Notice,
+            Facility::Fs,
+            &format!(
+                "recovered {} journal record{} into {}",
+                replayed,
+                if replayed == 1 { "" } else { "s" },
+                vfs::IMAGE_FILE_PATH
+            ),
+        );
+    }
+    if fresh {
+        profile::import_host_files()?;
+        vfs::sync()?;
+    }
+    process::boot(&vfs::lock().root_user());
+    sched::start();
+    Ok(())
+}
+
+/// Checks the user store is fit to log in with: the first account, root,
+/// has to be an admin, and no name may be used twice
+fn check_user_store() -> Result<String, String> {
+    let users = load_users().map_err(|e| format!("{}: {}", USERS_FILE_PATH, e))?;
+    let root = users
+        .first()
+        .ok_or_else(|| format!("{}: no users", USERS_FILE_PATH))?;
+    if !root.is_admin {
+        return Err(format!(
+            "{}: root account '{}' isn't an admin",
+            USERS_FILE_PATH, root.username
+        ));
+    }
+    if let Some((i, user)) = users
+        .iter()
+        .enumerate()
+        .find(|(i, user)| users[..*i].iter().any(|u| u.username == user.username))
+    {
+        return Err(format!(
+            "{}: account {} reuses the name '{}'",
+            USERS_FILE_PATH,
+            i + 1,
+            user.username
+        ));
+    }
+    Ok(format!(
+        "Checked {} ({} account{}).",
+        USERS_FILE_PATH,
+        users.len(),
+        if users.len() == 1 { "" } else { "s" }
+    ))
+}
+
+/// Brings MiniKern up for interactive use, reporting each step: reads the
+/// boot configuration, checks the user store, mounts the filesystem,
+/// replaying its journal, and starts the services in dependency order
+fn boot() -> Result<(), Box<dyn std::error::Error>> {
+    println!("MiniKern OS booting");
+    let _ = syslog::record(Level::Info, Facility::Kern, "MiniKern booting");
+    init::report(init::load_config().map(|count| {
+        format!(
+            "Loaded {} ({} service{}).",
+            init::CONFIG_FILE_PATH,
+            count,
+            if count == 1 { "" } else { "s" }
+        )
+    }));
+    ensure_users()?;
+    let users = check_user_store();
+    let users_ok = users.is_ok();
+    init::report(users);
+    if !users_ok {
+        return Err("The user store is damaged.".into());
+    }
+    mount_filesystem()?;
+    init::report(Ok(format!("Mounted {}.", vfs::IMAGE_FILE_PATH)));
+    init::start_services();
+    Ok(())
+}
+
+/// `minikern serve --listen ADDRESS` boots MiniKern and lets any number of
+/// clients log in at once over TCP or a Unix socket, each in a session of
+/// its own. Ctrl-C on the host's terminal shuts it down.
+fn serve_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let address = match args {
+        [flag, address] if flag == "--listen" || flag == "-l" => remote::Address::parse(address),
+        _ => {
+            eprintln!("Usage: minikern serve --listen HOST:PORT|unix:PATH");
+            std::process::exit(commands::EXIT_USAGE);
+        }
+    };
+    tty::catch_interrupts()?;
+    std::thread::spawn(|| {
+        if tty::wait_for_interrupt().is_ok() {
+            println!();
+            init::shutdown(false);
+        }
+    });
+
+    boot()?;
+    let listener = remote::Listener::bind(&address)
+        .map_err(|e| format!("Could not listen on {}: {}", address, e))?;
+    let address = listener.local_addr().unwrap_or(address);
+    init::report(Ok(format!("Listening on {}.", address)));
+    loop {
+        let (stream, peer) = match listener.accept() {
+            Ok(client) => client,
+            Err(e) => {
+                syslog::console(
+                    Level::Err,
+                    Facility::Auth,
+                    &format!("could not accept a connection: {}", e),
+                );
+                continue;
+            }
+        };
+        let _ = syslog::record(
+            Level::Info,
+            Facility::Auth,
+            &format!("connection from {}", peer),
+        );
+        let console = match console::Console::remote(stream, peer.clone()) {
+            Ok(console) => console,
+            Err(e) => {
+                let _ = syslog::record(
+                    Level::Err,
+                    Facility::Auth,
+                    &format!("could not set up a console for {}: {}", peer, e),
+                );
+                continue;
+            }
+        };
+        std::thread::spawn(move || {
+            console::attach(console.clone());
+            if let Err(e) = serve_client() {
+                let _ = syslog::record(
+                    Level::Notice,
+                    Facility::Auth,
+                    &format!("session from {} ended: {}", peer, e),
+                );
+            }
+            console.hang_up();
+            let _ = syslog::record(
+                Level::Info,
+                Facility::Auth,
+                &format!("{} disconnected", peer),
+            );
+        });
+    }
+}
+
+/// What a client connected to `minikern serve` gets: the login prompt, and
+/// a terminal session, until it exits the system or hangs up
+fn serve_client() -> Result<(), Box<dyn std::error::Error>> {
+    loop {
+        println!("MiniKern OS Loaded");
+        let current_user = login_procedure(&load_users()?)?;
+        let exit = terminal::run_terminal(current_user)?;
+        vfs::checkpoint()?;
+        if exit {
+            return Ok(());
+        }
+    }
+}
+
+/// `minikern connect ADDRESS` is the client for `minikern serve`
+fn connect_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let [address] = args else {
+        eprintln!("Usage: minikern connect HOST:PORT|unix:PATH");
+        std::process::exit(commands::EXIT_USAGE);
+    };
+    let address = remote::Address::parse(address);
+    remote::connect(&address)
+        .map_err(|e| format!("Could not connect to {}: {}", address, e))?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|a| a.as_str()) {
+        Some("serve") => return serve_command(&args[1..]),
+        Some("connect") => return connect_command(&args[1..]),
+        _ => {}
+    }
+    terminal::forward_interrupts()?;
+    match args.first().map(|a| a.as_str()) {
+        Some("exec") => {
+            mount_filesystem()?;
+            let status = exec_command(&args[1..])?;
+            std::process::exit(status);
+        }
+        Some("tui") => {
+            ensure_users()?;
+            mount_filesystem()?;
+            println!("MiniKern admin console");
+            let current_user = login_procedure(&load_users()?)?;
+            tui::run(current_user)?;
+            vfs::checkpoint()?;
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    boot()?;
 
     let mut should_exit = false;
     
@@ -123,7 +471,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // Run terminal and check if user wants to exit completely
         should_exit = terminal::run_terminal(current_user)?;
+        vfs::checkpoint()?;
     }
 
+    init::stop_services();
     Ok(())
 }
@@ -0,0 +1,357 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Null,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", if *b { "yes" } else { "no" }),
+            Value::Null => write!(f, "-"),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Int(n)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Self {
+        Value::Int(n as i64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Int(x), Value::Int(y)) => x.cmp(y),
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+/// Records produced by an informational command. `render` turns them into
+/// aligned columns, JSON, CSV or a `--format` template.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: &[&'static str]) -> Self {
+        Table {
+            columns: columns.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    fn column_index(&self, name: &str) -> Result<usize, String> {
+        self.columns.iter().position(|c| *c == name).ok_or_else(|| {
+            format!(
+                "Unknown column '{}'. Available columns: {}",
+                name,
+                self.columns.join(", ")
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    /// The command's own human-readable view
+    Human,
+    /// Aligned columns with a header
+    Columns,
+    Json,
+    Csv,
+    /// `--format '{name} {role}'`: one line per record
+    Template(String),
+}
+
+/// Output flags shared by all informational commands
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    pub format: Format,
+    pub columns: Option<Vec<String>>,
+    /// Column to sort by, and whether to sort descending
+    pub sort: Option<(String, bool)>,
+}
+
+pub const OUTPUT_USAGE: &str = "[--json | --csv | --table | --format TEMPLATE] [--columns a,b] [--sort [-]column]";
+
+impl OutputOptions {
+    /// Pulls the output flags out of `args` and returns the remaining ones.
+    pub fn parse(args: &[String]) -> Result<(OutputOptions, Vec<String>), String> {
+        let mut options = OutputOptions {
+            format: Format::Human,
+            columns: None,
+            sort: None,
+        };
+        let mut rest = Vec::new();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            let mut value = |flag: &str| {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} needs a value.", flag))
+            };
+            match arg.as_str() {
+                "--json" => options.format = Format::Json,
+                "--csv" => options.format = Format::Csv,
+                "--table" => options.format = Format::Columns,
+                "--format" => options.format = Format::Template(value("--format")?),
+                "--columns" => {
+                    options.columns = Some(
+                        value("--columns")?
+                            .split(',')
+                            .map(|c| c.trim().to_string())
+                            .filter(|c| !c.is_empty())
+                            .collect(),
+                    )
+                }
+                "--sort" => {
+                    let column = value("--sort")?;
+                    options.sort = Some(match column.strip_prefix('-') {
+                        Some(column) => (column.to_string(), true),
+                        None => (column, false),
+                    });
+                }
+                _ => rest.push(arg.clone()),
+            }
+        }
+        Ok((options, rest))
+    }
+
+    /// Whether the command should use its own human-readable view. That
+    /// view can't honour column selection or sorting, so those switch to
+    /// the plain column layout.
+    pub fn is_human(&self) -> bool {
+        self.format == Format::Human && self.columns.is_none() && self.sort.is_none()
+    }
+}
+
+/// Applies sorting and column selection, then renders the table in the
+/// requested format. `Format::Human` falls back to aligned columns; commands
+/// with their own view should check `OutputOptions::is_human` first.
+pub fn render(table: &Table, options: &OutputOptions) -> Result<String, String> {
+    let mut rows = table.rows.clone();
+    if let Some((column, descending)) = &options.sort {
+        let index = table.column_index(column)?;
+        rows.sort_by(|a, b| {
+            let ordering = compare(&a[index], &b[index]);
+            if *descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    let selected: Vec<usize> = match &options.columns {
+        Some(columns) => columns
+            .iter()
+            .map(|c| table.column_index(c))
+            .collect::<Result<_, _>>()?,
+        None => (0..table.columns.len()).collect(),
+    };
+
+    Ok(match &options.format {
+        Format::Human | Format::Columns => render_columns(table, &rows, &selected),
+        Format::Json => render_json(table, &rows, &selected),
+        Format::Csv => render_csv(table, &rows, &selected),
+        Format::Template(template) => render_template(table, &rows, template)?,
+    })
+}
+
+fn render_columns(table: &Table, rows: &[Vec<Value>], selected: &[usize]) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| selected.iter().map(|&i| row[i].to_string()).collect())
+        .collect();
+    let headers: Vec<String> = selected
+        .iter()
+        .map(|&i| table.columns[i].to_uppercase())
+        .collect();
+    let widths: Vec<usize> = (0..selected.len())
+        .map(|col| {
+            cells
+                .iter()
+                .map(|row| row[col].chars().count())
+                .chain(std::iter::once(headers[col].len()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut out = String::new();
+    for row in std::iter::once(&headers).chain(cells.iter()) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        out.push_str(line.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn json_value(value: &Value) -> String {
+    match value {
+        Value::Str(s) => json_string(s),
+        Value::Int(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => "null".to_string(),
+    }
+}
+
+fn json_object(table: &Table, row: &[Value], selected: &[usize]) -> String {
+    let fields: Vec<String> = selected
+        .iter()
+        .map(|&i| format!("{}: {}", json_string(table.columns[i]), json_value(&row[i])))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+/// One row of a table as a JSON object, for a record on its own
+pub fn json_record(table: &Table, row: &[Value]) -> String {
+    json_object(table, row, &(0..table.columns.len()).collect::<Vec<_>>())
+}
+
+fn render_json(table: &Table, rows: &[Vec<Value>], selected: &[usize]) -> String {
+    let objects: Vec<String> = rows
+        .iter()
+        .map(|row| format!("  {}", json_object(table, row, selected)))
+        .collect();
+    if objects.is_empty() {
+        "[]\n".to_string()
+    } else {
+        format!("[\n{}\n]\n", objects.join(",\n"))
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn render_csv(table: &Table, rows: &[Vec<Value>], selected: &[usize]) -> String {
+    let mut out = String::new();
+    let header: Vec<&str> = selected.iter().map(|&i| table.columns[i]).collect();
+    out.push_str(&header.join(","));
+    out.push('\n');
+    for row in rows {
+        let fields: Vec<String> = selected
+            .iter()
+            .map(|&i| match &row[i] {
+                Value::Null => String::new(),
+                Value::Bool(b) => b.to_string(),
+                value => csv_field(&value.to_string()),
+            })
+            .collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Fills `{column}` placeholders for each row. `{{` and `}}` are literal
+/// braces, and `\t`/`\n` are accepted for tabs and newlines.
+fn render_template(table: &Table, rows: &[Vec<Value>], template: &str) -> Result<String, String> {
+    let template = template.replace("\\t", "\t").replace("\\n", "\n");
+    let mut out = String::new();
+    for row in rows {
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    out.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    out.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(ch) => name.push(ch),
+                            None => return Err("Unclosed '{' in format.".into()),
+                        }
+                    }
+                    let index = table.column_index(name.trim())?;
+                    out.push_str(&row[index].to_string());
+                }
+                c => out.push(c),
+            }
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
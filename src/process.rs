@@ -0,0 +1,492 @@
+use crate::auth::CurrentUser;
+use crate::clock;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+
+pub type Pid = u32;
+
+/// The MiniKern kernel itself, parent of every login shell
+pub const INIT_PID: Pid = 1;
+
+/// Range of niceness values
+pub const MIN_NICE: i32 = -20;
+pub const MAX_NICE: i32 = 19;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,
+    /// Stopped by `SIGSTOP` until it gets `SIGCONT`
+    Stopped,
+    /// A background job that finished with this status, kept until its
+    /// shell has reported it
+    Done(i32),
+}
+
+impl State {
+    /// The letter `ps` shows
+    pub fn code(&self) -> char {
+        match self {
+            State::Running => 'R',
+            State::Stopped => 'T',
+            State::Done(_) => 'Z',
+        }
+    }
+
+    /// How `jobs` describes a job in this state
+    pub fn describe(&self) -> String {
+        match *self {
+            State::Running => "Running".to_string(),
+            State::Stopped => "Stopped".to_string(),
+            State::Done(0) => "Done".to_string(),
+            State::Done(status) => match Signal::ALL.into_iter().find(|s| s.status() == status) {
+                Some(Signal::Int) => "Interrupt".to_string(),
+                Some(Signal::Kill) => "Killed".to_string(),
+                Some(Signal::Term) => "Terminated".to_string(),
+                _ => format!("Exit {}", status),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Int,
+    Kill,
+    Term,
+    Cont,
+    Stop,
+}
+
+impl Signal {
+    pub const ALL: [Signal; 5] = [
+        Signal::Int,
+        Signal::Kill,
+        Signal::Term,
+        Signal::Cont,
+        Signal::Stop,
+    ];
+
+    pub fn number(self) -> i32 {
+        match self {
+            Signal::Int => 2,
+            Signal::Kill => 9,
+            Signal::Term => 15,
+            Signal::Cont => 18,
+            Signal::Stop => 19,
+        }
+    }
+
+    /// Name without the `SIG` prefix
+    pub fn name(self) -> &'static str {
+        match self {
+            Signal::Int => "INT",
+            Signal::Kill => "KILL",
+            Signal::Term => "TERM",
+            Signal::Cont => "CONT",
+            Signal::Stop => "STOP",
+        }
+    }
+
+    /// Parses `TERM`, `SIGTERM` or `15`
+    pub fn parse(text: &str) -> Option<Signal> {
+        let name = text.to_ascii_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        Signal::ALL
+            .into_iter()
+            .find(|s| s.name() == name || s.number().to_string() == name)
+    }
+
+    /// Exit status of a process ended by this signal
+    pub fn status(self) -> i32 {
+        128 + self.number()
+    }
+}
+
+#[derive(Debug)]
+pub enum ProcessError {
+    NoSuchProcess,
+    /// Signalling someone else's process without being an admin, or the
+    /// kernel itself
+    NotPermitted,
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessError::NoSuchProcess => write!(f, "No such process"),
+            ProcessError::NotPermitted => write!(f, "Operation not permitted"),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+/// An entry in the process table. Every command a shell runs is a
+/// process, and so is each login shell and background job.
+#[derive(Debug, Clone)]
+pub struct Process {
+    pub pid: Pid,
+    pub ppid: Pid,
+    pub user: CurrentUser,
+    pub state: State,
+    /// Unix timestamp
+    pub started: u64,
+    pub command: String,
+    /// Number in its shell's job table, for background jobs
+    pub job: Option<u32>,
+    /// From -20, scheduled first, to 19, scheduled last
+    pub nice: i32,
+    /// Scheduler time slices it has had, its finished children's included
+    pub ticks: u64,
+    /// `SIGINT`, `SIGTERM` or `SIGKILL`, until the process notices it
+    pending: Option<Signal>,
+}
+
+struct Table {
+    processes: BTreeMap<Pid, Process>,
+    next_pid: Pid,
+}
+
+impl Table {
+    /// Whether `pid` is `ancestor` or one of its descendants
+    fn descends_from(&self, mut pid: Pid, ancestor: Pid) -> bool {
+        loop {
+            if pid == ancestor {
+                return true;
+            }
+            match self.processes.get(&pid) {
+                Some(p) if p.ppid != pid => pid = p.ppid,
+                _ => return false,
+            }
+        }
+    }
+
+    fn insert(&mut self, ppid: Pid, user: &CurrentUser, command: &str, job: Option<u32>) -> Pid {
+        let pid = self.next_pid;
+        self.next_pid += 1;
+        // Children start as nice as their parent
+        let nice = self.processes.get(&ppid).map_or(0, |p| p.nice);
+        self.processes.insert(
+            pid,
+            Process {
+                pid,
+                ppid,
+                user: user.clone(),
+                state: State::Running,
+                started: clock::unix_now(),
+                command: command.to_string(),
+                job,
+                nice,
+                ticks: 0,
+                pending: None,
+            },
+        );
+        pid
+    }
+
+    /// Removes a process along with its descendants, which notice they're
+    /// gone at their next `checkpoint`
+    fn remove_tree(&mut self, pid: Pid) -> Option<Process> {
+        let descendants: Vec<Pid> = self
+            .processes
+            .keys()
+            .copied()
+            .filter(|&other| other != pid && self.descends_from(other, pid))
+            .collect();
+        for other in descendants {
+            self.processes.remove(&other);
+        }
+        self.processes.remove(&pid)
+    }
+}
+
+struct ProcessTable {
+    table: Mutex<Table>,
+    /// Woken whenever a process stops, continues or ends
+    changed: Condvar,
+}
+
+static PROCESSES: OnceLock<ProcessTable> = OnceLock::new();
+
+fn processes() -> &'static ProcessTable {
+    PROCESSES.get_or_init(|| ProcessTable {
+        table: Mutex::new(Table {
+            processes: BTreeMap::new(),
+            next_pid: INIT_PID,
+        }),
+        changed: Condvar::new(),
+    })
+}
+
+fn lock() -> MutexGuard<'static, Table> {
+    processes()
+        .table
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Starts the kernel process, PID 1, owned by the root account
+pub fn boot(root: &CurrentUser) {
+    spawn(0, root, "init");
+}
+
+/// Adds a running process and returns its PID
+pub fn spawn(ppid: Pid, user: &CurrentUser, command: &str) -> Pid {
+    lock().insert(ppid, user, command, None)
+}
+
+/// Adds a background job of the shell `ppid`, returning its PID and job
+/// number
+pub fn spawn_job(ppid: Pid, user: &CurrentUser, command: &str) -> (Pid, u32) {
+    let mut table = lock();
+    let job = table
+        .processes
+        .values()
+        .filter(|p| p.ppid == ppid)
+        .filter_map(|p| p.job)
+        .max()
+        .unwrap_or(0)
+        + 1;
+    (table.insert(ppid, user, command, Some(job)), job)
+}
+
+/// Ends a process. Background jobs are kept as `Done` until their shell
+/// reports them; anything else leaves the table at once.
+pub fn exit(pid: Pid, status: i32) {
+    let mut table = lock();
+    match table.processes.get_mut(&pid) {
+        // A job that was killed keeps the status of the signal
+        Some(p) if p.job.is_some() && !matches!(p.state, State::Done(_)) => {
+            p.state = State::Done(status);
+        }
+        Some(p) if p.job.is_some() => {}
+        Some(_) => {
+            if let Some(child) = table.processes.remove(&pid) {
+                if let Some(parent) = table.processes.get_mut(&child.ppid) {
+                    parent.ticks += child.ticks;
+                }
+            }
+        }
+        None => {}
+    }
+    processes().changed.notify_all();
+}
+
+/// Ends a login shell, killing whatever it still has running
+pub fn end_session(shell: Pid) {
+    lock().remove_tree(shell);
+    processes().changed.notify_all();
+}
+
+pub fn list() -> Vec<Process> {
+    lock().processes.values().cloned().collect()
+}
+
+pub fn get(pid: Pid) -> Option<Process> {
+    lock().processes.get(&pid).cloned()
+}
+
+/// Background jobs of a shell, by job number
+pub fn jobs(shell: Pid) -> Vec<Process> {
+    let mut jobs: Vec<Process> = lock()
+        .processes
+        .values()
+        .filter(|p| p.ppid == shell && p.job.is_some())
+        .cloned()
+        .collect();
+    jobs.sort_by_key(|p| p.job);
+    jobs
+}
+
+/// The job of a shell a job spec names: `%N` or `N` for job N, and
+/// `%+`, `%%` or no spec at all for the most recent one
+pub fn find_job(shell: Pid, spec: Option<&str>) -> Option<Process> {
+    let jobs = jobs(shell);
+    match spec.map(|s| s.strip_prefix('%').unwrap_or(s)) {
+        None | Some("+" | "%") => jobs.into_iter().last(),
+        Some(number) => {
+            let number = number.parse().ok()?;
+            jobs.into_iter().find(|p| p.job == Some(number))
+        }
+    }
+}
+
+/// Whether a process is part of a background job
+pub fn in_background(pid: Pid) -> bool {
+    let table = lock();
+    let mut current = table.processes.get(&pid);
+    while let Some(process) = current {
+        if process.job.is_some() {
+            return true;
+        }
+        current = table.processes.get(&process.ppid).filter(|_| process.ppid != process.pid);
+    }
+    false
+}
+
+pub fn nice(pid: Pid) -> i32 {
+    lock().processes.get(&pid).map_or(0, |p| p.nice)
+}
+
+/// Changes the niceness of a process and of the processes it started, so
+/// renicing a job reaches the command it's running. Users may only make
+/// their own processes nicer; admins may set any value.
+pub fn set_nice(who: &CurrentUser, pid: Pid, nice: i32) -> Result<(), ProcessError> {
+    let mut table = lock();
+    let process = table
+        .processes
+        .get(&pid)
+        .ok_or(ProcessError::NoSuchProcess)?;
+    if !who.is_admin && (process.user.username != who.username || nice < process.nice) {
+        return Err(ProcessError::NotPermitted);
+    }
+    let nice = nice.clamp(MIN_NICE, MAX_NICE);
+    let tree: Vec<Pid> = table
+        .processes
+        .keys()
+        .copied()
+        .filter(|&other| table.descends_from(other, pid))
+        .collect();
+    for other in tree {
+        if let Some(process) = table.processes.get_mut(&other) {
+            process.nice = nice;
+        }
+    }
+    Ok(())
+}
+
+/// Counts time slices a process was given by the scheduler
+pub fn charge(pid: Pid, ticks: u64) {
+    if let Some(process) = lock().processes.get_mut(&pid) {
+        process.ticks += ticks;
+    }
+}
+
+/// The shell or job a command is running in
+pub fn parent(pid: Pid) -> Pid {
+    get(pid).map_or(INIT_PID, |p| p.ppid)
+}
+
+/// Removes the finished jobs of a shell from the table, returning them so
+/// the shell can report them
+pub fn take_finished(shell: Pid) -> Vec<Process> {
+    let mut table = lock();
+    let done: Vec<Pid> = table
+        .processes
+        .values()
+        .filter(|p| p.ppid == shell && p.job.is_some())
+        .filter(|p| matches!(p.state, State::Done(_)))
+        .map(|p| p.pid)
+        .collect();
+    let mut finished: Vec<Process> = done
+        .iter()
+        .filter_map(|&pid| table.remove_tree(pid))
+        .collect();
+    finished.sort_by_key(|p| p.job);
+    finished
+}
+
+/// Sends a signal. Users may only signal their own processes, admins any
+/// but the kernel's.
+pub fn signal(who: &CurrentUser, pid: Pid, signal: Signal) -> Result<(), ProcessError> {
+    let mut table = lock();
+    let process = table
+        .processes
+        .get_mut(&pid)
+        .ok_or(ProcessError::NoSuchProcess)?;
+    if pid == INIT_PID || (!who.is_admin && process.user.username != who.username) {
+        return Err(ProcessError::NotPermitted);
+    }
+    match (signal, process.state) {
+        (_, State::Done(_)) => {}
+        (Signal::Stop, _) => process.state = State::Stopped,
+        (Signal::Cont, _) => process.state = State::Running,
+        (Signal::Int | Signal::Term, _) => process.pending = Some(signal),
+        (Signal::Kill, _) => {
+            process.pending = Some(Signal::Kill);
+            // Nothing waits for a killed job to notice
+            process.state = match process.job {
+                Some(_) => State::Done(Signal::Kill.status()),
+                None => State::Running,
+            };
+        }
+    }
+    processes().changed.notify_all();
+    Ok(())
+}
+
+/// Sends `SIGINT` to a process, as Ctrl-C does to the one in the
+/// foreground of a terminal. It reaches the commands it's running as well.
+pub fn interrupt(pid: Pid) {
+    let mut table = lock();
+    if let Some(process) = table.processes.get_mut(&pid) {
+        if !matches!(process.state, State::Done(_)) {
+            process.pending = Some(Signal::Int);
+            processes().changed.notify_all();
+        }
+    }
+}
+
+/// A termination signal sent to a process that hasn't acted on it yet,
+/// without waiting like `checkpoint`
+pub fn pending(pid: Pid) -> Option<Signal> {
+    lock().processes.get(&pid).and_then(|p| p.pending)
+}
+
+/// Where long-running commands let signals reach them: waits while the
+/// process or one of its parents is stopped, and returns the signal if one
+/// of them has been told to terminate. A process that has left the table
+/// was killed.
+pub fn checkpoint(pid: Pid) -> Result<(), Signal> {
+    let shared = processes();
+    let mut table = lock();
+    loop {
+        if !table.processes.contains_key(&pid) {
+            return Err(Signal::Kill);
+        }
+        let mut stopped = false;
+        let mut current = pid;
+        while let Some(process) = table.processes.get(&current) {
+            if let Some(signal) = process.pending {
+                return Err(signal);
+            }
+            if let State::Done(_) = process.state {
+                return Err(Signal::Kill);
+            }
+            stopped |= process.state == State::Stopped;
+            if process.ppid == current {
+                break;
+            }
+            current = process.ppid;
+        }
+        if !stopped {
+            return Ok(());
+        }
+        table = shared
+            .changed
+            .wait(table)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+}
+
+/// Waits for a background job to finish and removes it from the table.
+/// Returns its exit status, or `None` if there's no such job.
+pub fn wait(pid: Pid) -> Option<i32> {
+    let shared = processes();
+    let mut table = lock();
+    loop {
+        match table.processes.get(&pid)?.state {
+            State::Done(status) => {
+                table.remove_tree(pid);
+                return Some(status);
+            }
+            _ => {
+                table = shared
+                    .changed
+                    .wait(table)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+        }
+    }
+}
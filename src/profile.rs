@@ -0,0 +1,225 @@
+use crate::auth::CurrentUser;
+use crate::clock;
+use crate::tar;
+use crate::vfs::{self, path, FileType, Vfs, VfsError};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// System-wide startup file, run for every user before their own rc file
+pub const SYSTEM_PROFILE_PATH: &str = "/etc/profile";
+
+/// Directory holding the users' home directories
+pub const HOME_DIR_PATH: &str = "/home";
+
+pub const RC_FILE_NAME: &str = ".minikernrc";
+
+/// Files copied into every new home directory
+pub const SKEL_DIR_PATH: &str = "/etc/skel";
+
+/// Where `delusr` keeps archived home directories
+pub const HOME_ARCHIVE_DIR_PATH: &str = "/var/backups";
+
+/// Where the startup files lived on the host before the virtual filesystem
+const LEGACY_PROFILE_PATH: &str = "profile";
+const LEGACY_HOME_DIR_PATH: &str = "home";
+
+pub fn user_home(username: &str) -> String {
+    path::join(HOME_DIR_PATH, username)
+}
+
+pub fn user_rc_path(username: &str) -> String {
+    path::join(&user_home(username), RC_FILE_NAME)
+}
+
+/// Makes sure a user has a home directory they own. A new one gets a copy
+/// of the skeleton files.
+pub fn ensure_home(username: &str) -> Result<(), VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    let home = user_home(username);
+    match fs.mkdir(&root, &home) {
+        Ok(()) => {}
+        Err(VfsError::AlreadyExists) => return Ok(()),
+        Err(e) => return Err(e),
+    }
+    fs.chown(&root, &home, Some(username), Some(username))?;
+    match fs.read_dir(&root, SKEL_DIR_PATH) {
+        Ok(_) => copy_skel(&mut fs, SKEL_DIR_PATH, &home, username),
+        Err(VfsError::NotFound) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn copy_skel(fs: &mut Vfs, from: &str, to: &str, username: &str) -> Result<(), VfsError> {
+    let root = fs.root_user();
+    for entry in fs.read_dir(&root, from)? {
+        let source = path::join(from, &entry.name);
+        let target = path::join(to, &entry.name);
+        match entry.file_type {
+            FileType::Directory => fs.mkdir(&root, &target)?,
+            FileType::Regular => {
+                let contents = fs.read_file(&root, &source)?;
+                fs.write_file(&root, &target, &contents)?;
+            }
+        }
+        let mode = fs.stat(&root, &source)?.mode;
+        fs.chmod(&root, &target, mode)?;
+        fs.chown(&root, &target, Some(username), Some(username))?;
+        if entry.file_type == FileType::Directory {
+            copy_skel(fs, &source, &target, username)?;
+        }
+    }
+    Ok(())
+}
+
+/// Packs a user's home directory into a tarball under
+/// `HOME_ARCHIVE_DIR_PATH` that only admins can read. Returns its path, or
+/// `None` if the user has no home directory.
+pub fn archive_home(username: &str) -> Result<Option<String>, VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    let data = match tar::create(&mut fs, &root, HOME_DIR_PATH, &[username.to_string()]) {
+        Ok(data) => data,
+        Err(VfsError::NotFound) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if !fs.exists(&root, HOME_ARCHIVE_DIR_PATH) {
+        fs.mkdir_all(&root, HOME_ARCHIVE_DIR_PATH)?;
+        fs.chmod(&root, HOME_ARCHIVE_DIR_PATH, 0o700)?;
+    }
+    // 2026-10-19 02:15:21 becomes 20261019-021521
+    let stamp: String = clock::format_datetime(clock::unix_now())
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_ascii_digit() => Some(c),
+            _ => None,
+        })
+        .collect();
+    let archive = path::join(
+        HOME_ARCHIVE_DIR_PATH,
+        &format!("home-{}-{}.tar", username, stamp),
+    );
+    fs.write_file(&root, &archive, &data)?;
+    fs.chmod(&root, &archive, 0o600)?;
+    Ok(Some(archive))
+}
+
+/// Moves a renamed user's home directory along with them
+pub fn rename_home(old: &str, new: &str) -> Result<(), VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    match fs.rename(&root, &user_home(old), &user_home(new)) {
+        Ok(()) | Err(VfsError::NotFound) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads a startup file line by line. A missing file is treated as empty.
+pub fn read_lines(who: &CurrentUser, path: &str) -> Result<Vec<String>, VfsError> {
+    match vfs::lock().read_file(who, path) {
+        Ok(bytes) => Ok(String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(|l| l.to_string())
+            .collect()),
+        Err(VfsError::NotFound) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_lines(who: &CurrentUser, path: &str, lines: &[String]) -> Result<(), VfsError> {
+    let mut contents = lines.join("\n");
+    if !lines.is_empty() {
+        contents.push('\n');
+    }
+    vfs::lock().write_file(who, path, contents.as_bytes())
+}
+
+/// Removes a user's home directory and everything in it
+pub fn remove_home(username: &str) -> Result<(), VfsError> {
+    let mut fs = vfs::lock();
+    let root = fs.root_user();
+    match fs.remove_tree(&root, &user_home(username)) {
+        Ok(()) | Err(VfsError::NotFound) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Copies startup files kept on the host by older versions into a freshly
+/// created filesystem.
+pub fn import_host_files() -> Result<(), Box<dyn std::error::Error>> {
+    let root = vfs::lock().root_user();
+    let mut files = vec![(
+        Path::new(LEGACY_PROFILE_PATH).to_path_buf(),
+        SYSTEM_PROFILE_PATH.to_string(),
+        None,
+    )];
+    match fs::read_dir(LEGACY_HOME_DIR_PATH) {
+        Ok(dirs) => {
+            for dir in dirs {
+                let dir = dir?;
+                let username = dir.file_name().to_string_lossy().into_owned();
+                files.push((
+                    dir.path().join(RC_FILE_NAME),
+                    user_rc_path(&username),
+                    Some(username),
+                ));
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    for (host_path, vfs_path, owner) in files {
+        let contents = match fs::read_to_string(&host_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let lines: Vec<String> = contents.lines().map(|l| l.to_string()).collect();
+        if let Some(owner) = &owner {
+            ensure_home(owner)?;
+        }
+        write_lines(&root, &vfs_path, &lines)?;
+        if let Some(owner) = &owner {
+            vfs::lock().chown(&root, &vfs_path, Some(owner), Some(owner))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the line that defines `name` with `keyword` (for example
+/// `alias ll=...` or `function greet ...`) by `definition`, or just removes
+/// it when `definition` is `None`. Returns whether a line was removed.
+pub fn replace_definition(
+    who: &CurrentUser,
+    path: &str,
+    keyword: &str,
+    name: &str,
+    definition: Option<String>,
+) -> Result<bool, VfsError> {
+    let mut lines = read_lines(who, path)?;
+    let before = lines.len();
+    lines.retain(|line| !defines(line, keyword, name));
+    let removed = lines.len() != before;
+    if let Some(definition) = definition {
+        lines.push(definition);
+    }
+    write_lines(who, path, &lines)?;
+    Ok(removed)
+}
+
+fn defines(line: &str, keyword: &str, name: &str) -> bool {
+    let Some(rest) = line.trim_start().strip_prefix(keyword) else {
+        return false;
+    };
+    if !rest.starts_with(char::is_whitespace) {
+        return false;
+    }
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix("--system").unwrap_or(rest).trim_start();
+    rest.strip_prefix(name)
+        .map(|after| after.starts_with('=') || after.starts_with(char::is_whitespace))
+        .unwrap_or(false)
+}
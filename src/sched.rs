@@ -0,0 +1,523 @@
+use crate::process::{self, Pid, Signal};
+use crate::syslog::{self, Facility, Level};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Ticks of the virtual clock in a second of real time
+pub const TICKS_PER_SECOND: u64 = 100;
+
+/// How long a waiting task goes between looks at its signals
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What a policy knows about a task that's ready to run
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub pid: Pid,
+    pub user: String,
+    pub nice: i32,
+    /// When it was last given the CPU, in dispatches; 0 if never
+    pub last_ran: u64,
+}
+
+/// Decides which ready task runs next
+pub trait Policy: fmt::Debug + Send {
+    fn name(&self) -> &'static str;
+
+    /// Picks one of `ready`, which is never empty. `usage` is the number of
+    /// time slices each user's tasks have had so far.
+    fn pick(&self, ready: &[Candidate], usage: &BTreeMap<String, u64>) -> Pid;
+}
+
+/// Every task in turn
+#[derive(Debug)]
+pub struct RoundRobin;
+
+impl Policy for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round-robin"
+    }
+
+    fn pick(&self, ready: &[Candidate], _usage: &BTreeMap<String, u64>) -> Pid {
+        least_recent(ready.iter())
+    }
+}
+
+/// The least nice task first, taking turns among equally nice ones
+#[derive(Debug)]
+pub struct Priority;
+
+impl Policy for Priority {
+    fn name(&self) -> &'static str {
+        "priority"
+    }
+
+    fn pick(&self, ready: &[Candidate], _usage: &BTreeMap<String, u64>) -> Pid {
+        let nicest = ready.iter().map(|c| c.nice).min().unwrap_or(0);
+        least_recent(ready.iter().filter(|c| c.nice == nicest))
+    }
+}
+
+/// The user who has had the fewest time slices first, however many tasks
+/// they have, taking turns among that user's tasks
+#[derive(Debug)]
+pub struct FairShare;
+
+impl Policy for FairShare {
+    fn name(&self) -> &'static str {
+        "fair-share"
+    }
+
+    fn pick(&self, ready: &[Candidate], usage: &BTreeMap<String, u64>) -> Pid {
+        let used = |c: &Candidate| usage.get(&c.user).copied().unwrap_or(0);
+        let least = ready.iter().map(used).min().unwrap_or(0);
+        least_recent(ready.iter().filter(|c| used(c) == least))
+    }
+}
+
+/// The candidate that has waited longest since it last ran
+fn least_recent<'a>(candidates: impl Iterator<Item = &'a Candidate>) -> Pid {
+    candidates
+        .min_by_key(|c| (c.last_ran, c.pid))
+        .map_or(0, |c| c.pid)
+}
+
+/// Names `policy_named` accepts
+pub const POLICIES: &[&str] = &["round-robin", "priority", "fair-share"];
+
+pub fn policy_named(name: &str) -> Option<Box<dyn Policy>> {
+    match name {
+        "round-robin" => Some(Box::new(RoundRobin)),
+        "priority" => Some(Box::new(Priority)),
+        "fair-share" => Some(Box::new(FairShare)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// Waiting for the CPU
+    Ready,
+    /// Holding the CPU
+    Running,
+    /// Waiting for the clock to reach a tick
+    Sleeping(u64),
+}
+
+#[derive(Debug, Clone)]
+struct Task {
+    user: String,
+    state: TaskState,
+    last_ran: u64,
+}
+
+/// A single CPU shared by tasks that give it up on their own, at the
+/// points where they wait or yield. Nothing in here reads the real time:
+/// the clock only moves when `advance` is called, so the same calls always
+/// give the same schedule.
+#[derive(Debug)]
+pub struct Scheduler {
+    /// Virtual time, in ticks
+    now: u64,
+    policy: Box<dyn Policy>,
+    tasks: BTreeMap<Pid, Task>,
+    running: Option<Pid>,
+    /// CPU hand-outs so far
+    dispatches: u64,
+    /// Time slices each user's tasks have had
+    usage: BTreeMap<String, u64>,
+}
+
+impl Scheduler {
+    pub fn new(policy: Box<dyn Policy>) -> Self {
+        Scheduler {
+            now: 0,
+            policy,
+            tasks: BTreeMap::new(),
+            running: None,
+            dispatches: 0,
+            usage: BTreeMap::new(),
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn policy(&self) -> &'static str {
+        self.policy.name()
+    }
+
+    pub fn set_policy(&mut self, policy: Box<dyn Policy>) {
+        self.policy = policy;
+    }
+
+    pub fn running(&self) -> Option<Pid> {
+        self.running
+    }
+
+    pub fn state(&self, pid: Pid) -> Option<TaskState> {
+        self.tasks.get(&pid).map(|t| t.state)
+    }
+
+    /// Adds a task, ready to run
+    pub fn admit(&mut self, pid: Pid, user: &str) {
+        self.tasks.insert(
+            pid,
+            Task {
+                user: user.to_string(),
+                state: TaskState::Ready,
+                last_ran: 0,
+            },
+        );
+    }
+
+    /// Takes a task off the CPU, if it had it, to wait for the clock to
+    /// reach `until`. It isn't picked again until it calls `ready`.
+    pub fn release(&mut self, pid: Pid, until: u64) {
+        if let Some(task) = self.tasks.get_mut(&pid) {
+            task.state = TaskState::Sleeping(until);
+        }
+        if self.running == Some(pid) {
+            self.running = None;
+        }
+    }
+
+    /// Wants the CPU back after waiting
+    pub fn ready(&mut self, pid: Pid) {
+        if let Some(task) = self.tasks.get_mut(&pid) {
+            if let TaskState::Sleeping(_) = task.state {
+                task.state = TaskState::Ready;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, pid: Pid) {
+        self.tasks.remove(&pid);
+        if self.running == Some(pid) {
+            self.running = None;
+        }
+    }
+
+    pub fn advance(&mut self, ticks: u64) {
+        self.now += ticks;
+    }
+
+    /// Gives a free CPU to the task the policy picks, returning it. `nice`
+    /// looks up a task's niceness.
+    pub fn dispatch(&mut self, nice: impl Fn(Pid) -> i32) -> Option<Pid> {
+        if self.running.is_some() {
+            return None;
+        }
+        let ready: Vec<Candidate> = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| task.state == TaskState::Ready)
+            .map(|(&pid, task)| Candidate {
+                pid,
+                user: task.user.clone(),
+                nice: nice(pid),
+                last_ran: task.last_ran,
+            })
+            .collect();
+        if ready.is_empty() {
+            return None;
+        }
+        let pid = self.policy.pick(&ready, &self.usage);
+        let task = self.tasks.get_mut(&pid)?;
+        self.dispatches += 1;
+        task.state = TaskState::Running;
+        task.last_ran = self.dispatches;
+        *self.usage.entry(task.user.clone()).or_default() += 1;
+        self.running = Some(pid);
+        Some(pid)
+    }
+}
+
+struct Shared {
+    scheduler: Mutex<Scheduler>,
+    /// Woken when the CPU changes hands or the clock moves
+    changed: Condvar,
+}
+
+static SCHEDULER: OnceLock<Shared> = OnceLock::new();
+
+fn shared() -> &'static Shared {
+    SCHEDULER.get_or_init(|| Shared {
+        scheduler: Mutex::new(Scheduler::new(Box::new(RoundRobin))),
+        changed: Condvar::new(),
+    })
+}
+
+fn lock() -> MutexGuard<'static, Scheduler> {
+    shared()
+        .scheduler
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Hands the CPU on if it's free, charging the slice to the task that
+/// gets it, and wakes everyone waiting to see who it was
+fn dispatch(scheduler: &mut Scheduler) {
+    if let Some(pid) = scheduler.dispatch(process::nice) {
+        process::charge(pid, 1);
+    }
+    shared().changed.notify_all();
+}
+
+/// Waits until `pid` holds the CPU
+fn wait_for_cpu(mut scheduler: MutexGuard<'static, Scheduler>, pid: Pid) {
+    while scheduler.running() != Some(pid) && scheduler.state(pid).is_some() {
+        scheduler = shared()
+            .changed
+            .wait(scheduler)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+}
+
+/// Starts the virtual clock, ticking along with real time
+pub fn start() {
+    let _ = syslog::record(
+        Level::Info,
+        Facility::Sched,
+        &format!(
+            "scheduler started: {} ticks a second, policy {}",
+            TICKS_PER_SECOND,
+            policy()
+        ),
+    );
+    std::thread::spawn(|| {
+        let started = Instant::now();
+        let mut ticks = 0;
+        loop {
+            std::thread::sleep(Duration::from_millis(1000 / TICKS_PER_SECOND));
+            let now = (started.elapsed().as_millis() as u64) * TICKS_PER_SECOND / 1000;
+            let mut scheduler = lock();
+            scheduler.advance(now - ticks);
+            ticks = now;
+            dispatch(&mut scheduler);
+        }
+    });
+}
+
+pub fn now() -> u64 {
+    lock().now()
+}
+
+pub fn policy() -> &'static str {
+    lock().policy()
+}
+
+pub fn set_policy(policy: Box<dyn Policy>) {
+    let (old, new) = {
+        let mut scheduler = lock();
+        let old = scheduler.policy();
+        scheduler.set_policy(policy);
+        dispatch(&mut scheduler);
+        (old, scheduler.policy())
+    };
+    let _ = syslog::record(
+        Level::Notice,
+        Facility::Sched,
+        &format!("policy changed from {} to {}", old, new),
+    );
+}
+
+/// Where a task is in the scheduler, if it's a task
+pub fn state(pid: Pid) -> Option<TaskState> {
+    lock().state(pid)
+}
+
+/// Makes a process a task and waits for its first turn on the CPU
+pub fn enter(pid: Pid, user: &str) {
+    let mut scheduler = lock();
+    scheduler.admit(pid, user);
+    dispatch(&mut scheduler);
+    wait_for_cpu(scheduler, pid);
+}
+
+/// Takes a finished task out of the scheduler
+pub fn leave(pid: Pid) {
+    let mut scheduler = lock();
+    scheduler.remove(pid);
+    dispatch(&mut scheduler);
+}
+
+/// Waits `ticks` of the virtual clock, letting other tasks have the CPU
+/// meanwhile, then waits for the CPU again. Signals reach the process
+/// while it waits; it returns early with the one that ends it. A process
+/// that isn't a task just waits for the clock.
+pub fn sleep(pid: Pid, ticks: u64) -> Result<(), Signal> {
+    let until = now() + ticks;
+    loop {
+        let done = {
+            let mut scheduler = lock();
+            scheduler.release(pid, until);
+            dispatch(&mut scheduler);
+            if scheduler.now() < until {
+                scheduler = shared()
+                    .changed
+                    .wait_timeout(scheduler, POLL_INTERVAL)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .0;
+            }
+            scheduler.now() >= until
+        };
+        // Off the CPU and unlocked, so a stopped task holds up nobody else
+        process::checkpoint(pid)?;
+        if done {
+            break;
+        }
+    }
+    let mut scheduler = lock();
+    scheduler.ready(pid);
+    dispatch(&mut scheduler);
+    wait_for_cpu(scheduler, pid);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `slices` time slices of one tick each, every task going back
+    /// to the queue as soon as its slice ends, and returns who ran
+    fn run(scheduler: &mut Scheduler, slices: usize, nice: impl Fn(Pid) -> i32) -> Vec<Pid> {
+        let mut ran = Vec::new();
+        for _ in 0..slices {
+            let pid = scheduler.dispatch(&nice).expect("a task is ready");
+            ran.push(pid);
+            let until = scheduler.now() + 1;
+            scheduler.release(pid, until);
+            scheduler.advance(1);
+            scheduler.ready(pid);
+        }
+        ran
+    }
+
+    fn scheduler(policy: &str, tasks: &[(Pid, &str)]) -> Scheduler {
+        let mut scheduler = Scheduler::new(policy_named(policy).expect("known policy"));
+        for &(pid, user) in tasks {
+            scheduler.admit(pid, user);
+        }
+        scheduler
+    }
+
+    #[test]
+    fn round_robin_takes_turns_in_pid_order() {
+        let mut s = scheduler("round-robin", &[(3, "a"), (1, "a"), (2, "b")]);
+        assert_eq!(run(&mut s, 6, |_| 0), [1, 2, 3, 1, 2, 3]);
+        assert_eq!(s.now(), 6);
+    }
+
+    #[test]
+    fn round_robin_ignores_niceness() {
+        let mut s = scheduler("round-robin", &[(1, "a"), (2, "a")]);
+        assert_eq!(
+            run(&mut s, 4, |pid| if pid == 1 { 19 } else { -20 }),
+            [1, 2, 1, 2]
+        );
+    }
+
+    #[test]
+    fn one_task_holds_the_cpu_until_it_gives_it_up() {
+        let mut s = scheduler("round-robin", &[(1, "a"), (2, "a")]);
+        assert_eq!(s.dispatch(|_| 0), Some(1));
+        s.advance(5);
+        assert_eq!(s.dispatch(|_| 0), None);
+        assert_eq!(s.running(), Some(1));
+        assert_eq!(s.state(2), Some(TaskState::Ready));
+    }
+
+    #[test]
+    fn sleeping_tasks_wait_for_their_tick() {
+        let mut s = scheduler("round-robin", &[(1, "a"), (2, "a")]);
+        assert_eq!(s.dispatch(|_| 0), Some(1));
+        s.release(1, 10);
+        assert_eq!(s.state(1), Some(TaskState::Sleeping(10)));
+        assert_eq!(s.dispatch(|_| 0), Some(2));
+        s.release(2, 3);
+        assert_eq!(s.dispatch(|_| 0), None);
+
+        s.advance(3);
+        s.ready(2);
+        assert_eq!(s.dispatch(|_| 0), Some(2));
+        s.release(2, 20);
+        s.advance(7);
+        s.ready(1);
+        assert_eq!(s.dispatch(|_| 0), Some(1));
+        assert_eq!(s.now(), 10);
+    }
+
+    #[test]
+    fn priority_runs_the_least_nice_task_first() {
+        let nice = |pid| match pid {
+            1 => 10,
+            2 => -5,
+            _ => 0,
+        };
+        let mut s = scheduler("priority", &[(1, "a"), (2, "a"), (3, "a")]);
+        assert_eq!(run(&mut s, 3, nice), [2, 2, 2]);
+
+        s.remove(2);
+        assert_eq!(run(&mut s, 2, nice), [3, 3]);
+        s.remove(3);
+        assert_eq!(run(&mut s, 1, nice), [1]);
+    }
+
+    #[test]
+    fn priority_takes_turns_among_equally_nice_tasks() {
+        let nice = |pid| if pid == 4 { 5 } else { 0 };
+        let mut s = scheduler("priority", &[(1, "a"), (2, "a"), (3, "b"), (4, "b")]);
+        assert_eq!(run(&mut s, 6, nice), [1, 2, 3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn priority_gives_a_sleeping_task_back_its_place() {
+        let nice = |pid| if pid == 1 { -10 } else { 0 };
+        let mut s = scheduler("priority", &[(1, "a"), (2, "a")]);
+        assert_eq!(s.dispatch(nice), Some(1));
+        s.release(1, 2);
+        assert_eq!(run(&mut s, 2, nice), [2, 2]);
+        s.ready(1);
+        assert_eq!(run(&mut s, 2, nice), [1, 1]);
+    }
+
+    #[test]
+    fn fair_share_splits_slices_by_user_not_by_task() {
+        let mut s = scheduler("fair-share", &[(1, "a"), (2, "a"), (3, "a"), (4, "b")]);
+        let ran = run(&mut s, 8, |_| 0);
+        assert_eq!(ran, [1, 4, 2, 4, 3, 4, 1, 4]);
+        assert_eq!(s.usage.get("a"), Some(&4));
+        assert_eq!(s.usage.get("b"), Some(&4));
+    }
+
+    #[test]
+    fn fair_share_lets_a_newcomer_catch_up() {
+        let mut s = scheduler("fair-share", &[(1, "a")]);
+        assert_eq!(run(&mut s, 3, |_| 0), [1, 1, 1]);
+        s.admit(2, "b");
+        assert_eq!(run(&mut s, 5, |_| 0), [2, 2, 2, 1, 2]);
+    }
+
+    #[test]
+    fn changing_policy_keeps_the_clock_and_usage() {
+        let nice = |pid| if pid == 2 { -1 } else { 0 };
+        let mut s = scheduler("round-robin", &[(1, "a"), (2, "b")]);
+        assert_eq!(run(&mut s, 2, nice), [1, 2]);
+        s.set_policy(policy_named("priority").expect("known policy"));
+        assert_eq!(s.policy(), "priority");
+        assert_eq!(run(&mut s, 2, nice), [2, 2]);
+        assert_eq!(s.now(), 4);
+        assert_eq!(s.usage.get("b"), Some(&3));
+    }
+
+    #[test]
+    fn policies_are_named() {
+        for &name in POLICIES {
+            assert_eq!(policy_named(name).map(|p| p.name()), Some(name));
+        }
+        assert!(policy_named("lottery").is_none());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::auth::CurrentUser;
+use crate::process::{self, Pid, INIT_PID};
+use crate::profile;
+use std::collections::BTreeMap;
+
+/// Prompt used when the session has no `PS1` set
+pub const DEFAULT_PS1: &str = "\\u:\\w> ";
+
+/// What the terminal should do after a command finishes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Keep reading commands
+    Continue,
+    /// End the session and go back to the login prompt
+    Logout,
+    /// End the session and quit MiniKern
+    Exit,
+    /// Ctrl-C was pressed: skip the rest of the command line
+    Interrupted,
+}
+
+/// Which startup file is currently being run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupScope {
+    System,
+    User,
+}
+
+#[derive(Debug, Clone)]
+pub struct Alias {
+    pub value: String,
+    /// Defined in the system profile rather than the user's own rc file
+    pub system: bool,
+}
+
+/// State that lives for the duration of one login
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub user: CurrentUser,
+    /// The session's shell process, or the command or background job it's
+    /// running at the moment
+    pub pid: Pid,
+    pub env: BTreeMap<String, String>,
+    pub aliases: BTreeMap<String, Alias>,
+    pub functions: BTreeMap<String, String>,
+    /// `$1`, `$2`, ... while a shell function is running
+    pub positional: Vec<String>,
+    /// Set while the startup files run, so definitions aren't persisted again
+    pub startup: Option<StartupScope>,
+    pub cwd: String,
+    pub last_status: i32,
+    /// Output of the previous command of a pipeline, for commands that
+    /// read input when given no files
+    pub stdin: Option<Vec<u8>>,
+    /// Whether the session's commands run in the foreground of the
+    /// terminal, where Ctrl-C interrupts them
+    pub terminal: bool,
+}
+
+impl Session {
+    /// Starts a login shell for `user`. `process::end_session` ends it.
+    pub fn new(user: CurrentUser) -> Self {
+        let mut env = BTreeMap::new();
+        env.insert("USER".to_string(), user.username.clone());
+        env.insert("HOME".to_string(), profile::user_home(&user.username));
+        env.insert("SHELL".to_string(), "minikern".to_string());
+        env.insert("PS1".to_string(), DEFAULT_PS1.to_string());
+        env.insert("PWD".to_string(), "/".to_string());
+
+        Session {
+            pid: process::spawn(INIT_PID, &user, "minikern"),
+            user,
+            env,
+            aliases: BTreeMap::new(),
+            functions: BTreeMap::new(),
+            positional: Vec::new(),
+            startup: None,
+            cwd: "/".to_string(),
+            last_status: 0,
+            stdin: None,
+            terminal: false,
+        }
+    }
+
+    /// Moves the session into the user's home directory
+    pub fn enter_home(&mut self) {
+        self.cwd = profile::user_home(&self.user.username);
+        self.env.insert("PWD".to_string(), self.cwd.clone());
+    }
+
+    pub fn get_var(&self, name: &str) -> Option<&str> {
+        self.env.get(name).map(|v| v.as_str())
+    }
+
+    pub fn set_var(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if !is_valid_var_name(name) {
+            return Err(format!("'{}' is not a valid variable name.", name));
+        }
+        self.env.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    pub fn unset_var(&mut self, name: &str) -> bool {
+        self.env.remove(name).is_some()
+    }
+}
+
+/// Variable names follow the usual shell rules: a letter or underscore,
+/// then letters, digits or underscores.
+pub fn is_valid_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,534 @@
+use crate::clock;
+use crate::glob;
+use crate::session::{Session, DEFAULT_PS1};
+
+/// A word being built by `tokenize`. `pattern` is the same text with the
+/// quoted characters escaped, so only unquoted `*`, `?` and `[` glob.
+#[derive(Default)]
+struct Word {
+    text: String,
+    pattern: String,
+    started: bool,
+}
+
+impl Word {
+    fn push(&mut self, c: char) {
+        self.started = true;
+        self.text.push(c);
+        self.pattern.push(c);
+    }
+
+    fn push_quoted(&mut self, c: char) {
+        self.started = true;
+        self.text.push(c);
+        self.pattern.push_str(&glob::escape(&c.to_string()));
+    }
+
+    fn push_quoted_str(&mut self, s: &str) {
+        self.started = true;
+        self.text.push_str(s);
+        self.pattern.push_str(&glob::escape(s));
+    }
+
+    /// Adds the finished word to `words`, expanding it if it's a glob
+    /// pattern that matches something.
+    fn finish(self, words: &mut Vec<String>, session: &Session) {
+        if !self.started {
+            return;
+        }
+        if glob::has_magic(&self.pattern) {
+            let matches = glob::expand(&self.pattern, &session.cwd, &session.user);
+            if !matches.is_empty() {
+                words.extend(matches);
+                return;
+            }
+        }
+        words.push(self.text);
+    }
+}
+
+/// Splits a command line into words, honouring quotes and expanding
+/// `$VAR`, `${VAR}`, `$?` and the positional parameters (`$1`..`$9`,
+/// `$#`, `$@`) from the session. Unquoted words containing `*`, `?` or
+/// `[...]` are replaced by the matching paths, if there are any.
+///
+/// Single quotes keep their contents literally, double quotes still expand
+/// variables, and a backslash escapes the next character.
+pub fn tokenize(line: &str, session: &Session) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut current = Word::default();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                std::mem::take(&mut current).finish(&mut words, session);
+            }
+            '\'' => {
+                current.started = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => current.push_quoted(ch),
+                        None => return Err("Unterminated single quote.".into()),
+                    }
+                }
+            }
+            '"' => {
+                current.started = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(ch @ ('"' | '\\' | '$')) => current.push_quoted(ch),
+                            Some(ch) => {
+                                current.push_quoted('\\');
+                                current.push_quoted(ch);
+                            }
+                            None => {
+                                return Err("Unterminated double quote.".into())
+                            }
+                        },
+                        Some('$') => {
+                            let mut value = String::new();
+                            expand_var(&mut chars, &mut value, session);
+                            current.push_quoted_str(&value);
+                        }
+                        Some(ch) => current.push_quoted(ch),
+                        None => return Err("Unterminated double quote.".into()),
+                    }
+                }
+            }
+            '\\' => {
+                current.started = true;
+                if let Some(ch) = chars.next() {
+                    current.push_quoted(ch);
+                }
+            }
+            '$' => {
+                // Variable values are used as-is, not as glob patterns
+                let mut value = String::new();
+                expand_var(&mut chars, &mut value, session);
+                current.push_quoted_str(&value);
+            }
+            ch => current.push(ch),
+        }
+    }
+
+    current.finish(&mut words, session);
+    Ok(words)
+}
+
+/// Expands the variable reference following a `$` into `out`. A `$` that
+/// isn't followed by a name is kept as-is.
+fn expand_var(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    out: &mut String,
+    session: &Session,
+) {
+    match chars.peek() {
+        Some('?') => {
+            chars.next();
+            out.push_str(&session.last_status.to_string());
+        }
+        Some('#') => {
+            chars.next();
+            out.push_str(&session.positional.len().to_string());
+        }
+        Some('@') => {
+            chars.next();
+            out.push_str(&session.positional.join(" "));
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let index = c.to_digit(10).unwrap_or(0) as usize;
+            chars.next();
+            if index > 0 {
+                if let Some(arg) = session.positional.get(index - 1) {
+                    out.push_str(arg);
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut name = String::new();
+            for ch in chars.by_ref() {
+                if ch == '}' {
+                    break;
+                }
+                name.push(ch);
+            }
+            out.push_str(session.get_var(&name).unwrap_or(""));
+        }
+        Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+            let mut name = String::new();
+            while let Some(&ch) = chars.peek() {
+                if ch.is_ascii_alphanumeric() || ch == '_' {
+                    name.push(ch);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            out.push_str(session.get_var(&name).unwrap_or(""));
+        }
+        _ => out.push('$'),
+    }
+}
+
+/// How a statement in a command list is joined to the one before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// First statement, or after `;`: always runs
+    Always,
+    /// After `&&`: runs if the previous status was 0
+    And,
+    /// After `||`: runs if the previous status was non-zero
+    Or,
+}
+
+/// Splits a line at unquoted `;` and `&` into lists of statements joined
+/// by `&&` and `||`, each with whether it runs in the background, that is
+/// whether it was ended by `&`.
+pub fn parse_jobs(line: &str) -> Result<Vec<(String, bool)>, String> {
+    let mut jobs = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let background = match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                continue;
+            }
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                current.push(c);
+                continue;
+            }
+            (c, Some(q)) if c == q => {
+                quote = None;
+                current.push(c);
+                continue;
+            }
+            ('&', None) if chars.peek() == Some(&'&') => {
+                chars.next();
+                current.push_str("&&");
+                continue;
+            }
+            (';', None) => false,
+            ('&', None) => true,
+            _ => {
+                current.push(c);
+                continue;
+            }
+        };
+        let list = std::mem::take(&mut current);
+        if list.trim().is_empty() {
+            if background {
+                return Err("Missing command before '&'.".into());
+            }
+        } else {
+            jobs.push((list, background));
+        }
+    }
+
+    if quote.is_some() {
+        return Err("Unterminated quote.".into());
+    }
+    if !current.trim().is_empty() {
+        jobs.push((current, false));
+    }
+    Ok(jobs)
+}
+
+/// Splits a line into statements separated by unquoted `;`, `&&` and `||`.
+pub fn parse_list(line: &str) -> Result<Vec<(Connector, String)>, String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut connector = Connector::Always;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let next = match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                continue;
+            }
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                current.push(c);
+                continue;
+            }
+            (c, Some(q)) if c == q => {
+                quote = None;
+                current.push(c);
+                continue;
+            }
+            (';', None) => Connector::Always,
+            ('&', None) if chars.peek() == Some(&'&') => {
+                chars.next();
+                Connector::And
+            }
+            ('|', None) if chars.peek() == Some(&'|') => {
+                chars.next();
+                Connector::Or
+            }
+            _ => {
+                current.push(c);
+                continue;
+            }
+        };
+
+        let statement = std::mem::take(&mut current);
+        if statement.trim().is_empty() {
+            if next != Connector::Always || connector != Connector::Always {
+                return Err("Missing command before '&&' or '||'.".into());
+            }
+        } else {
+            statements.push((connector, statement));
+        }
+        connector = next;
+    }
+
+    if quote.is_some() {
+        return Err("Unterminated quote.".into());
+    }
+    if current.trim().is_empty() {
+        if connector != Connector::Always {
+            return Err("Missing command after '&&' or '||'.".into());
+        }
+    } else {
+        statements.push((connector, current));
+    }
+    Ok(statements)
+}
+
+/// Splits a statement into the commands of a pipeline, separated by
+/// unquoted `|`. `parse_list` has already taken out the `||`s.
+pub fn parse_pipeline(statement: &str) -> Result<Vec<String>, String> {
+    let mut stages = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = statement.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (c, Some(q)) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            ('|', None) => stages.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    stages.push(current);
+
+    if stages.len() > 1 && stages.iter().any(|stage| stage.trim().is_empty()) {
+        return Err("Missing command before or after '|'.".into());
+    }
+    Ok(stages)
+}
+
+/// Replaces a leading alias name with its value. Each alias is expanded at
+/// most once, so `alias ls='ls -l'` doesn't loop.
+pub fn expand_aliases(line: &str, session: &Session) -> String {
+    let mut line = line.trim_start().to_string();
+    let mut expanded: Vec<String> = Vec::new();
+
+    loop {
+        let end = line
+            .find(|c: char| c.is_whitespace())
+            .unwrap_or(line.len());
+        let first = &line[..end];
+        if expanded.iter().any(|e| e == first) {
+            return line;
+        }
+        match session.aliases.get(first) {
+            Some(alias) => {
+                expanded.push(first.to_string());
+                line = format!("{}{}", alias.value, &line[end..]);
+            }
+            None => return line,
+        }
+    }
+}
+
+/// Quotes a word so that `tokenize` reads it back unchanged.
+pub fn quote(word: &str) -> String {
+    if !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c))
+    {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Renders the session's `PS1` prompt format.
+///
+/// Supported escapes: `\u` username, `\h` host name, `\w` working
+/// directory, `\$` (`#` for admins, `$` otherwise), `\t` time as
+/// HH:MM:SS (UTC), `\?` last exit status, `\n` newline and `\\`.
+pub fn render_prompt(session: &Session) -> String {
+    let format = session.get_var("PS1").unwrap_or(DEFAULT_PS1);
+    let mut prompt = String::new();
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('u') => prompt.push_str(&session.user.username),
+            Some('h') => prompt.push_str("minikern"),
+            Some('w') => prompt.push_str(&session.cwd),
+            Some('$') => prompt.push(if session.user.is_admin { '#' } else { '$' }),
+            Some('t') => prompt.push_str(&clock::format_hms(clock::unix_now())),
+            Some('?') => prompt.push_str(&session.last_status.to_string()),
+            Some('n') => prompt.push('\n'),
+            Some('\\') => prompt.push('\\'),
+            Some(other) => {
+                prompt.push('\\');
+                prompt.push(other);
+            }
+            None => prompt.push('\\'),
+        }
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::CurrentUser;
+
+    fn session() -> Session {
+        let mut session = Session::new(CurrentUser { username: "alice".to_string(), is_admin: false });
+        session.set_var("NAME", "two words").unwrap();
+        session
+    }
+
+    fn words(line: &str) -> Vec<String> {
+        tokenize(line, &session()).unwrap()
+    }
+
+    #[test]
+    fn splits_words_at_unquoted_whitespace() {
+        assert_eq!(words("  ls   -l /tmp "), ["ls", "-l", "/tmp"]);
+        assert_eq!(words("'a;b'"), ["a;b"]);
+        assert_eq!(words("a\\|b"), ["a|b"]);
+        assert_eq!(words("a\\ b c"), ["a b", "c"]);
+        assert_eq!(words("echo \"x\\\"y\""), ["echo", "x\"y"]);
+        assert_eq!(words("echo \"a\\b\""), ["echo", "a\\b"]);
+        assert_eq!(words("echo '' \"\""), ["echo", "", ""]);
+        assert_eq!(words("a'  'b"), ["a  b"]);
+    }
+
+    #[test]
+    fn expands_variables_outside_single_quotes() {
+        let mut session = session();
+        session.last_status = 3;
+        let words = |line| tokenize(line, &session).unwrap();
+        assert_eq!(words("echo $NAME"), ["echo", "two words"]);
+        assert_eq!(words("echo \"[$NAME]\""), ["echo", "[two words]"]);
+        assert_eq!(words("echo '$NAME'"), ["echo", "$NAME"]);
+        assert_eq!(words("echo \\$NAME"), ["echo", "$NAME"]);
+        assert_eq!(words("${USER}:$? $UNSET."), ["alice:3", "."]);
+        assert_eq!(words("cost $5 $"), ["cost", "", "$"]);
+    }
+
+    #[test]
+    fn unterminated_quotes_are_errors() {
+        assert!(tokenize("echo 'a", &session()).is_err());
+        assert!(tokenize("echo \"a", &session()).is_err());
+        assert!(tokenize("echo \"a\\", &session()).is_err());
+    }
+
+    #[test]
+    fn aliases_expand_once_each() {
+        let mut session = session();
+        for (name, value) in [("ls", "ls -l"), ("ll", "ls -a"), ("a", "b"), ("b", "a x")] {
+            let alias = crate::session::Alias { value: value.to_string(), system: false };
+            session.aliases.insert(name.to_string(), alias);
+        }
+        assert_eq!(expand_aliases("ls /tmp", &session), "ls -l /tmp");
+        assert_eq!(expand_aliases("  ll", &session), "ls -l -a");
+        assert_eq!(expand_aliases("a", &session), "a x");
+        assert_eq!(expand_aliases("lsof ls", &session), "lsof ls");
+        assert_eq!(expand_aliases("echo ls", &session), "echo ls");
+    }
+
+    /// `parse_list` with each statement trimmed
+    fn list(line: &str) -> Result<Vec<(Connector, String)>, String> {
+        let statements = parse_list(line)?;
+        Ok(statements.into_iter().map(|(c, s)| (c, s.trim().to_string())).collect())
+    }
+
+    #[test]
+    fn lists_split_at_unquoted_connectors() {
+        use Connector::{Always, And, Or};
+        let expected = [(Always, "a"), (Always, "b"), (And, "c"), (Or, "d | e")];
+        let expected: Vec<_> = expected.iter().map(|(c, s)| (*c, s.to_string())).collect();
+        assert_eq!(list("a; b && c || d | e;").unwrap(), expected);
+        for quoted in ["echo 'a;b' \\; \"&&\"", "a\\;b", "a \\&\\& b"] {
+            assert_eq!(list(quoted).unwrap(), [(Always, quoted.to_string())]);
+        }
+        assert_eq!(list(";; a").unwrap(), [(Always, "a".to_string())]);
+    }
+
+    #[test]
+    fn connectors_need_a_command_on_both_sides() {
+        for bad in ["a && || b", "&& a", "a ||", "a && ; b", "a; || b", "echo 'a"] {
+            assert!(parse_list(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn pipelines_split_at_unquoted_bars() {
+        let stages = parse_pipeline("ls -l | grep a|wc").unwrap();
+        assert_eq!(stages, ["ls -l ", " grep a", "wc"]);
+        for quoted in ["a\\|b", "grep 'a|b'", "grep \"a|b\""] {
+            assert_eq!(parse_pipeline(quoted).unwrap(), [quoted]);
+        }
+        assert_eq!(parse_pipeline("").unwrap(), [""]);
+        for bad in ["| a", "a |", "a | | b", "a ||"] {
+            assert!(parse_pipeline(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn jobs_end_at_unquoted_semicolons_and_ampersands() {
+        let jobs = parse_jobs("sleep 5 & ls && pwd; echo a || b &").unwrap();
+        let jobs: Vec<(&str, bool)> = jobs.iter().map(|(j, bg)| (j.trim(), *bg)).collect();
+        assert_eq!(jobs, [("sleep 5", true), ("ls && pwd", false), ("echo a || b", true)]);
+
+        assert_eq!(parse_jobs("a&&b").unwrap(), [("a&&b".to_string(), false)]);
+        for quoted in ["echo 'a & b'", "echo \"a;b\"", "a \\& b"] {
+            assert_eq!(parse_jobs(quoted).unwrap(), [(quoted.to_string(), false)]);
+        }
+        assert!(parse_jobs(";;").unwrap().is_empty());
+        for bad in ["& a", "a; &", "a & &", "echo \"a"] {
+            assert!(parse_jobs(bad).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::clock;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+pub const SYSLOG_FILE_PATH: &str = "system.log";
+
+/// Size system.log may reach before it's rotated to system.log.1
+const ROTATE_BYTES: u64 = 256 * 1024;
+
+/// Rotated logs kept, system.log.1 being the newest
+const ROTATED_KEEP: usize = 4;
+
+/// Messages kept in memory for `dmesg`, the oldest dropped first
+const RING_CAPACITY: usize = 1024;
+
+/// How serious a message is, most serious first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Crit,
+    Err,
+    Warning,
+    Notice,
+    Info,
+}
+
+impl Level {
+    pub const ALL: [Level; 5] = [
+        Level::Crit,
+        Level::Err,
+        Level::Warning,
+        Level::Notice,
+        Level::Info,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Crit => "crit",
+            Level::Err => "err",
+            Level::Warning => "warning",
+            Level::Notice => "notice",
+            Level::Info => "info",
+        }
+    }
+
+    /// Accepts the names `syslog` prints, and `error` and `warn`
+    pub fn parse(text: &str) -> Option<Level> {
+        match text.to_lowercase().as_str() {
+            "error" => Some(Level::Err),
+            "warn" => Some(Level::Warning),
+            name => Level::ALL.into_iter().find(|l| l.name() == name),
+        }
+    }
+
+    /// How a message of this level starts on the console
+    fn heading(self) -> &'static str {
+        match self {
+            Level::Crit => "Critical",
+            Level::Err => "Error",
+            Level::Warning => "Warning",
+            Level::Notice => "Notice",
+            Level::Info => "Info",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The part of the system a message comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facility {
+    /// Booting, services and shutting down
+    Kern,
+    /// Logins and accounts
+    Auth,
+    /// The virtual filesystem and its mounts
+    Fs,
+    /// The scheduler
+    Sched,
+    /// Terminal sessions
+    Shell,
+    /// The cron daemon and its jobs
+    Cron,
+}
+
+impl Facility {
+    pub const ALL: [Facility; 6] = [
+        Facility::Kern,
+        Facility::Auth,
+        Facility::Fs,
+        Facility::Sched,
+        Facility::Shell,
+        Facility::Cron,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Facility::Kern => "kern",
+            Facility::Auth => "auth",
+            Facility::Fs => "fs",
+            Facility::Sched => "sched",
+            Facility::Shell => "shell",
+            Facility::Cron => "cron",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Facility> {
+        // Logs from before facilities had names of their own
+        if text == "init" {
+            return Some(Facility::Kern);
+        }
+        Facility::ALL.into_iter().find(|f| f.name() == text)
+    }
+}
+
+impl fmt::Display for Facility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One message of the system log
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub time: u64,
+    pub level: Level,
+    pub facility: Facility,
+    pub message: String,
+}
+
+/// Messages since boot, with how long after it they came. Also held
+/// while writing to the log file, so rotation doesn't race a write.
+static RING: Mutex<VecDeque<(Duration, Entry)>> = Mutex::new(VecDeque::new());
+
+static BOOTED: OnceLock<Instant> = OnceLock::new();
+
+/// Time since boot, counted from the first message
+fn uptime() -> Duration {
+    BOOTED.get_or_init(Instant::now).elapsed()
+}
+
+fn rotated_path(n: usize) -> String {
+    format!("{}.{}", SYSLOG_FILE_PATH, n)
+}
+
+/// Moves system.log to system.log.1, and each older log one further
+/// along, if it has grown too big
+fn rotate_if_full() -> io::Result<()> {
+    match fs::metadata(SYSLOG_FILE_PATH) {
+        Ok(meta) if meta.len() >= ROTATE_BYTES => {}
+        _ => return Ok(()),
+    }
+    let _ = fs::remove_file(rotated_path(ROTATED_KEEP));
+    for n in (1..ROTATED_KEEP).rev() {
+        if let Err(e) = fs::rename(rotated_path(n), rotated_path(n + 1)) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e);
+            }
+        }
+    }
+    fs::rename(SYSLOG_FILE_PATH, rotated_path(1))
+}
+
+/// Logs a message about what MiniKern does on its own rather than on a
+/// user's command. It's kept in memory for `dmesg` and appended to the
+/// system log. Failing to write the log shouldn't stop what's being
+/// logged, so callers usually ignore the error.
+pub fn record(level: Level, facility: Facility, message: &str) -> io::Result<()> {
+    let entry = Entry {
+        time: clock::unix_now(),
+        level,
+        facility,
+        message: message.replace(['\t', '\n', '\r'], " "),
+    };
+    let mut ring = RING.lock().unwrap_or_else(|p| p.into_inner());
+    if ring.len() == RING_CAPACITY {
+        ring.pop_front();
+    }
+    ring.push_back((uptime(), entry.clone()));
+
+    rotate_if_full()?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(SYSLOG_FILE_PATH)?;
+    writeln!(
+        file,
+        "{}\t{}\t{}\t{}",
+        entry.time, entry.level, entry.facility, entry.message
+    )
+}
+
+/// Logs a message and shows it on the console too, for problems whoever
+/// is at the terminal needs to know about now
+pub fn console(level: Level, facility: Facility, message: &str) {
+    let _ = record(level, facility, message);
+    eprintln!("{}: {}", level.heading(), message);
+}
+
+/// The messages logged since boot, oldest first, with how long after boot
+/// each came
+pub fn since_boot() -> Vec<(Duration, Entry)> {
+    let ring = RING.lock().unwrap_or_else(|p| p.into_inner());
+    ring.iter().cloned().collect()
+}
+
+/// Forgets the messages logged since boot. The log file keeps them.
+pub fn clear_since_boot() {
+    RING.lock().unwrap_or_else(|p| p.into_inner()).clear();
+}
+
+/// Reads the whole system log, rotated files included, oldest message
+/// first. Malformed lines are skipped.
+pub fn load_entries() -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let paths = (1..=ROTATED_KEEP)
+        .rev()
+        .map(rotated_path)
+        .chain([SYSLOG_FILE_PATH.to_string()]);
+    for path in paths {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        entries.extend(contents.lines().filter_map(parse_line));
+    }
+    Ok(entries)
+}
+
+fn parse_line(line: &str) -> Option<Entry> {
+    let fields: Vec<&str> = line.splitn(4, '\t').collect();
+    let time = fields.first()?.parse().ok()?;
+    let (level, facility, message) = match fields[1..] {
+        [level, facility, message] if Level::parse(level).is_some() => {
+            (Level::parse(level)?, facility, message.to_string())
+        }
+        // Lines from before messages had levels
+        [facility, message] => (Level::Info, facility, message.to_string()),
+        _ => return None,
+    };
+    Some(Entry {
+        time,
+        level,
+        facility: Facility::parse(facility)?,
+        message,
+    })
+}
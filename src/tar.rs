@@ -0,0 +1,286 @@
+use crate::auth::CurrentUser;
+use crate::vfs::{path, FileType, Metadata, Vfs, VfsError};
+
+// POSIX ustar: a 512-byte header per entry, file contents padded to a whole
+// block, and two zero blocks at the end. MiniKern has no numeric user ids,
+// so uid and gid are 0 and ownership travels in the user and group names.
+
+const BLOCK: usize = 512;
+
+/// An entry read from an archive
+pub struct Entry {
+    /// As stored, relative and `/`-separated
+    pub name: String,
+    pub mode: u16,
+    pub owner: String,
+    pub group: String,
+    pub kind: EntryKind,
+}
+
+pub enum EntryKind {
+    File(Vec<u8>),
+    Directory,
+    /// Links, devices and the like, by type flag. MiniKern has no such
+    /// files.
+    Other(char),
+}
+
+/// Builds an archive in memory
+#[derive(Default)]
+pub struct Builder {
+    out: Vec<u8>,
+}
+
+impl Builder {
+    /// Adds `name`, which is relative to the directory `base`, along with
+    /// everything under it.
+    pub fn append_tree(
+        &mut self,
+        fs: &mut Vfs,
+        who: &CurrentUser,
+        base: &str,
+        name: &str,
+    ) -> Result<(), VfsError> {
+        let name = name.trim_matches('/');
+        let full = path::resolve(base, name);
+        let meta = fs.stat(who, &full)?;
+        match meta.file_type {
+            FileType::Directory => {
+                self.out
+                    .extend_from_slice(&header(&format!("{}/", name), &meta, 0)?);
+                for entry in fs.read_dir(who, &full)? {
+                    self.append_tree(fs, who, base, &path::join(name, &entry.name))?;
+                }
+            }
+            FileType::Regular => {
+                let data = fs.read_file(who, &full)?;
+                self.out
+                    .extend_from_slice(&header(name, &meta, data.len() as u64)?);
+                self.out.extend_from_slice(&data);
+                self.out.resize(self.out.len().next_multiple_of(BLOCK), 0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the archive and returns its bytes
+    pub fn finish(mut self) -> Vec<u8> {
+        self.out.resize(self.out.len() + 2 * BLOCK, 0);
+        self.out
+    }
+}
+
+/// Archives `names`, which are relative to the directory `base`, along with
+/// everything under them.
+pub fn create(
+    fs: &mut Vfs,
+    who: &CurrentUser,
+    base: &str,
+    names: &[String],
+) -> Result<Vec<u8>, VfsError> {
+    let mut builder = Builder::default();
+    for name in names {
+        builder.append_tree(fs, who, base, name)?;
+    }
+    Ok(builder.finish())
+}
+
+fn header(name: &str, meta: &Metadata, size: u64) -> Result<[u8; BLOCK], VfsError> {
+    // Names longer than 100 bytes are split at a `/` into prefix and name
+    let (prefix, name) = split_name(name).ok_or(VfsError::InvalidArgument)?;
+    let mut h = [0u8; BLOCK];
+    put(&mut h[0..100], name);
+    octal(&mut h[100..108], u64::from(meta.mode))?;
+    octal(&mut h[108..116], 0)?;
+    octal(&mut h[116..124], 0)?;
+    octal(&mut h[124..136], size)?;
+    octal(&mut h[136..148], meta.modified)?;
+    h[156] = match meta.file_type {
+        FileType::Regular => b'0',
+        FileType::Directory => b'5',
+    };
+    put(&mut h[257..263], "ustar\0");
+    put(&mut h[263..265], "00");
+    put(&mut h[265..297], &meta.owner);
+    put(&mut h[297..329], &meta.group);
+    put(&mut h[345..500], prefix);
+
+    // The checksum is taken with its own field filled with spaces
+    h[148..156].fill(b' ');
+    let sum: u32 = h.iter().map(|&b| u32::from(b)).sum();
+    octal(&mut h[148..155], u64::from(sum))?;
+    Ok(h)
+}
+
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+    name.match_indices('/')
+        .map(|(i, _)| (&name[..i], &name[i + 1..]))
+        .find(|(prefix, rest)| prefix.len() <= 155 && !rest.is_empty() && rest.len() <= 100)
+}
+
+/// Copies as much of `value` as fits in the field
+fn put(field: &mut [u8], value: &str) {
+    let len = value.len().min(field.len());
+    field[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+/// Zero-padded octal digits followed by a NUL
+fn octal(field: &mut [u8], value: u64) -> Result<(), VfsError> {
+    let width = field.len() - 1;
+    let digits = format!("{:0width$o}", value, width = width);
+    if digits.len() > width {
+        return Err(VfsError::InvalidArgument);
+    }
+    field[..width].copy_from_slice(digits.as_bytes());
+    field[width] = 0;
+    Ok(())
+}
+
+/// Reads the entries of an archive. GNU long names are followed, other
+/// extensions come back as `EntryKind::Other`.
+pub fn parse(bytes: &[u8]) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut long_name = None;
+    let mut pos = 0;
+    while pos + BLOCK <= bytes.len() {
+        let h = &bytes[pos..pos + BLOCK];
+        if h.iter().all(|&b| b == 0) {
+            return Ok(entries);
+        }
+        let stored = read_octal(&h[148..156]).ok_or("bad header checksum")?;
+        let sum: u64 = h[..148]
+            .iter()
+            .chain(&[b' '; 8])
+            .chain(&h[156..])
+            .map(|&b| u64::from(b))
+            .sum();
+        if sum != stored {
+            return Err(format!("bad header checksum at byte {}", pos));
+        }
+
+        let size = read_octal(&h[124..136]).ok_or("bad entry size")?;
+        let start = pos + BLOCK;
+        let end = usize::try_from(size)
+            .ok()
+            .and_then(|size| start.checked_add(size))
+            .filter(|&end| end <= bytes.len())
+            .ok_or("archive is truncated")?;
+        let data = &bytes[start..end];
+        pos = end.next_multiple_of(BLOCK);
+
+        let type_flag = h[156];
+        if type_flag == b'L' {
+            long_name = Some(field(data));
+            continue;
+        }
+        let name = match long_name.take() {
+            Some(name) => name,
+            None if &h[257..262] == b"ustar" && h[345] != 0 => {
+                format!("{}/{}", field(&h[345..500]), field(&h[0..100]))
+            }
+            None => field(&h[0..100]),
+        };
+        let kind = match type_flag {
+            b'5' => EntryKind::Directory,
+            // Old archives mark directories with a trailing slash only
+            b'0' | 0 if name.ends_with('/') => EntryKind::Directory,
+            b'0' | 0 | b'7' => EntryKind::File(data.to_vec()),
+            other => EntryKind::Other(other as char),
+        };
+        entries.push(Entry {
+            name,
+            mode: read_octal(&h[100..108]).unwrap_or(0) as u16,
+            owner: field(&h[265..297]),
+            group: field(&h[297..329]),
+            kind,
+        });
+    }
+    // Some writers leave out the end-of-archive blocks
+    if pos >= bytes.len() {
+        Ok(entries)
+    } else {
+        Err("archive is truncated".into())
+    }
+}
+
+/// A NUL-terminated text field
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Octal digits, optionally surrounded by spaces and NULs
+fn read_octal(bytes: &[u8]) -> Option<u64> {
+    let text = field(bytes);
+    let text = text.trim_matches(' ');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn filesystem() -> (Vfs, CurrentUser) {
+        let fs = Vfs::new(Path::new("/nonexistent/minikern.img"), "root");
+        let root = fs.root_user();
+        (fs, root)
+    }
+
+    #[test]
+    fn archives_survive_a_round_trip() {
+        let (mut fs, root) = filesystem();
+        fs.mkdir(&root, "/tmp/src").unwrap();
+        fs.write_file(&root, "/tmp/src/a.txt", b"hello").unwrap();
+        fs.chmod(&root, "/tmp/src/a.txt", 0o640).unwrap();
+
+        let archive = create(&mut fs, &root, "/tmp", &["src".to_string()]).unwrap();
+        assert_eq!(archive.len() % BLOCK, 0);
+        let entries = parse(&archive).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["src/", "src/a.txt"]);
+        assert!(matches!(entries[0].kind, EntryKind::Directory));
+        assert!(matches!(&entries[1].kind, EntryKind::File(data) if data == b"hello"));
+        assert_eq!(entries[1].mode, 0o640);
+        assert_eq!(entries[1].owner, "root");
+    }
+
+    #[test]
+    fn long_names_are_split_into_a_prefix() {
+        let (mut fs, root) = filesystem();
+        let dir = format!("/tmp/{}", "d".repeat(90));
+        fs.mkdir(&root, &dir).unwrap();
+        let file = format!("{}/{}.txt", dir, "f".repeat(40));
+        fs.write_file(&root, &file, b"x").unwrap();
+
+        let name = file.trim_start_matches("/tmp/").to_string();
+        assert!(name.len() > 100);
+        let archive = create(&mut fs, &root, "/tmp", std::slice::from_ref(&name)).unwrap();
+        let entries = parse(&archive).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, name);
+    }
+
+    #[test]
+    fn damaged_archives_are_refused() {
+        let (mut fs, root) = filesystem();
+        fs.write_file(&root, "/tmp/a.txt", &[b'a'; 600]).unwrap();
+        let archive = create(&mut fs, &root, "/tmp", &["a.txt".to_string()]).unwrap();
+
+        let mut flipped = archive.clone();
+        flipped[0] ^= 1;
+        assert!(matches!(parse(&flipped), Err(e) if e.contains("checksum")));
+
+        let truncated = &archive[..BLOCK + 100];
+        assert!(matches!(parse(truncated), Err(e) if e == "archive is truncated"));
+
+        // A missing end-of-archive marker is fine
+        assert_eq!(parse(&archive[..3 * BLOCK]).unwrap().len(), 1);
+    }
+}
@@ -1,6 +1,31 @@
-use crate::auth::CurrentUser; // Removed `self,`
-use crate::commands;
-use std::io::{self, Write};
+use crate::auth::{self, CurrentUser};
+use crate::console;
+use crate::commands::{
+    self, CommandOutcome, SessionEffect, EXIT_FAILURE, EXIT_NOT_FOUND,
+    EXIT_PERMISSION, EXIT_SUCCESS, EXIT_USAGE,
+};
+use crate::profile::{self, SYSTEM_PROFILE_PATH};
+use crate::line_editor::{Completion, LineEditor};
+use crate::mail;
+use crate::process::{self, Signal};
+use crate::sched;
+use crate::session::{Flow, Session, StartupScope};
+use crate::shell::{self, Connector};
+use crate::syslog::{self, Facility, Level};
+use crate::tty;
+use crate::vfs::{self, path, FileType};
+
+/// Passes Ctrl-C on to the command running in the foreground of the
+/// terminal as `SIGINT`, rather than letting it end MiniKern
+pub fn forward_interrupts() -> std::io::Result<()> {
+    tty::catch_interrupts()?;
+    std::thread::spawn(|| {
+        while tty::wait_for_interrupt().is_ok() {
+            console::host().interrupt_foreground();
+        }
+    });
+    Ok(())
+}
 
 pub fn run_terminal(
     current_user: CurrentUser,
@@ -11,121 +36,486 @@ pub fn run_terminal(
         println!("You have ADMIN privileges.");
     }
     println!("Type 'help' for available commands, 'exit' to quit.");
+    if mail::has_new(&current_user.username) {
+        println!("You have new mail.");
+    }
 
-    loop {
-        print!("{}> ", current_user.username);
-        io::stdout().flush()?;
+    let mut session = Session::new(current_user);
+    session.terminal = true;
+    console::log_in(session.pid, &session.user.username);
+    let _ = syslog::record(
+        Level::Info,
+        Facility::Shell,
+        &format!(
+            "session {} opened for {}",
+            session.pid, session.user.username
+        ),
+    );
+    let result = run_session(&mut session);
+    console::log_out(session.pid);
+    process::end_session(session.pid);
+    let _ = syslog::record(
+        Level::Info,
+        Facility::Shell,
+        &format!(
+            "session {} closed for {}",
+            session.pid, session.user.username
+        ),
+    );
+    result
+}
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let command = input.trim().to_lowercase();
+/// Runs the startup files and then the prompt until the session ends.
+/// Returns whether MiniKern should exit.
+fn run_session(session: &mut Session) -> Result<bool, Box<dyn std::error::Error>> {
+    match profile::ensure_home(&session.user.username) {
+        Ok(()) => session.enter_home(),
+        Err(e) => syslog::console(
+            Level::Warning,
+            Facility::Fs,
+            &format!(
+                "could not create the home directory of {}: {}",
+                session.user.username, e
+            ),
+        ),
+    }
+
+    // System profile first, then the user's own startup file
+    let rc_path = profile::user_rc_path(&session.user.username);
+    let startup_files = [
+        (StartupScope::System, SYSTEM_PROFILE_PATH),
+        (StartupScope::User, rc_path.as_str()),
+    ];
+    for (scope, path) in startup_files {
+        session.startup = Some(scope);
+        let flow = run_startup_file(session, path);
+        session.startup = None;
+        match flow? {
+            // An interrupt only cuts the startup file short
+            Flow::Continue | Flow::Interrupted => {}
+            Flow::Logout => return Ok(false),
+            Flow::Exit => return Ok(true),
+        }
+    }
+
+    let mut editor = LineEditor::default();
+    loop {
+        report_finished_jobs(session);
+        if let Some(signal) = process::pending(session.pid) {
+            println!("Session ended by SIG{}.", signal.name());
+            return Ok(false);
+        }
+        let prompt = shell::render_prompt(session);
+        let complete = |word: &str, first_word: bool| complete(session, word, first_word);
+        let Some(input) = editor.read_line(&prompt, &complete)? else {
+            // End of input, nothing more to read
+            return Ok(true);
+        };
 
-        if command.is_empty() {
+        if input.trim().is_empty() {
             continue;
         }
 
         println!("-----------------------------");
 
-        match command.as_str() {
-            "addusr" => {
-                if current_user.is_admin {
-                    if let Err(e) = commands::addusr::run(&current_user) {
-                        eprintln!("Error adding user: {}", e);
-                    }
-                } else {
-                    println!(
-                        "Error: You must be an admin to add users."
-                    );
-                }
-            }
-            "listusr" => {
-                if let Err(e) = commands::listusr::run() {
-                    eprintln!("Error listing users: {}", e);
-                }
-            }
-            "delusr" => {
-                if current_user.is_admin {
-                    match commands::delusr::run(&current_user) {
-                        Ok(result) => {
-                            match result {
-                                commands::delusr::DeleteResult::NoDelete => {
-                                    // Nothing to do
-                                }
-                                commands::delusr::DeleteResult::OtherUserDeleted(username) => {
-                                    println!("User '{}' was successfully deleted.", username);
-                                }
-                                commands::delusr::DeleteResult::CurrentUserDeleted => {
-                                    println!("Your account has been deleted. Logging out.");
-                                    return Ok(false); // Trigger logout
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Error deleting user: {}", e);
-                        }
-                    }
-                } else {
-                    println!("Error: You must be logged in as an admin to delete users.");
-                }
-            }
-            "chusr" => {
-                match commands::chusr::run(&current_user) {
-                    Ok(result) => {
-                        match result {
-                            commands::chusr::ChusrResult::NoChange => {
-                                // Nothing to do
-                            }
-                            commands::chusr::ChusrResult::PasswordChanged(username) => {
-                                if username == current_user.username {
-                                    println!("Your password has changed. Please log in again.");
-                                    return Ok(false); // Trigger logout
-                                }
-                            }
-                            commands::chusr::ChusrResult::AdminChanged(username) => {
-                                if username == current_user.username {
-                                    println!("Your admin status has changed. Please log in again.");
-                                    return Ok(false); // Trigger logout
-                                }
-                            }
-                            commands::chusr::ChusrResult::BothChanged(username) => {
-                                if username == current_user.username {
-                                    println!("Your account has been modified. Please log in again.");
-                                    return Ok(false); // Trigger logout
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error changing user: {}", e);
-                    }
-                }
-            }
-            "help" => {
-                println!("Available commands:");
-                println!("  addusr    - Add a new user (admin only)");
-                println!("  listusr   - List all users");
-                println!(
-                    "  chusr     - Change user passwords and admin status (users can change their own password)"
-                );
-                println!("  delusr    - Delete a user (admin only)");
-                println!("  logout    - Log out and login as another user");
-                println!("  exit      - Log out and exit the program");
-                println!("  help      - Show this help message");
-            }
-            "logout" => {
-                println!("Logging out. Please log in again.");
-                return Ok(false);
-            }
-            "exit" => {
-                println!("Logging out. Goodbye!");
-                return Ok(true);
-            }
-            _ => {
-                println!("Unknown command: '{}'. Type 'help' for a list of commands.", command);
+        match execute_line(session, &input)? {
+            Flow::Continue | Flow::Interrupted => {}
+            Flow::Logout => return Ok(false),
+            Flow::Exit => return Ok(true),
+        }
+        println!("-----------------------------");
+    }
+}
+
+/// Prints the background jobs that finished since the last prompt, and
+/// forgets them
+fn report_finished_jobs(session: &Session) {
+    for job in process::take_finished(session.pid) {
+        println!(
+            "[{}]  {:<12} {}",
+            job.job.unwrap_or_default(),
+            job.state.describe(),
+            job.command
+        );
+    }
+}
+
+/// Tab completion: registry commands, aliases and functions for the first
+/// word, paths in the virtual filesystem for the others.
+fn complete(session: &Session, word: &str, first_word: bool) -> Vec<Completion> {
+    if !first_word {
+        return complete_path(session, word);
+    }
+    let mut candidates: Vec<Completion> = commands::COMMANDS
+        .iter()
+        .filter(|c| c.name.starts_with(word))
+        .filter(|c| !c.admin_only || session.user.is_admin)
+        .map(|c| Completion {
+            value: c.name.to_string(),
+            note: None,
+        })
+        .collect();
+    for (name, alias) in &session.aliases {
+        if name.starts_with(word) {
+            candidates.push(Completion {
+                value: name.clone(),
+                note: Some(format!(
+                    "({}alias for '{}')",
+                    if alias.system { "system " } else { "" },
+                    alias.value
+                )),
+            });
+        }
+    }
+    for name in session.functions.keys() {
+        if name.starts_with(word) {
+            candidates.push(Completion {
+                value: name.clone(),
+                note: Some("(function)".to_string()),
+            });
+        }
+    }
+    candidates.sort_by(|a, b| a.value.cmp(&b.value));
+    candidates.dedup_by(|a, b| a.value == b.value);
+    candidates
+}
+
+/// Completes the last component of a path. Directories get a trailing `/`.
+fn complete_path(session: &Session, word: &str) -> Vec<Completion> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let Ok(entries) = vfs::lock().read_dir(&session.user, &path::resolve(&session.cwd, dir)) else {
+        return Vec::new();
+    };
+    entries
+        .into_iter()
+        .filter(|e| e.name.starts_with(prefix))
+        .filter(|e| !e.name.starts_with('.') || prefix.starts_with('.'))
+        .map(|e| Completion {
+            value: format!(
+                "{}{}{}",
+                dir,
+                e.name,
+                if e.file_type == FileType::Directory { "/" } else { "" }
+            ),
+            note: None,
+        })
+        .collect()
+}
+
+/// Runs each line of a startup file as if it was typed at the prompt.
+/// Blank lines and lines starting with `#` are skipped.
+fn run_startup_file(
+    session: &mut Session,
+    path: &str,
+) -> Result<Flow, Box<dyn std::error::Error>> {
+    for line in profile::read_lines(&session.user, path)? {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let flow = execute_line(session, line)?;
+        if flow != Flow::Continue {
+            return Ok(flow);
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+/// Nested function calls and alias expansions deeper than this are
+/// treated as runaway recursion.
+const MAX_DEPTH: usize = 32;
+
+/// Runs a command line: one or more statements joined by `;`, `&&` and
+/// `||`, with lists ended by `&` started as background jobs. Each
+/// statement's exit status is stored in `$?`; only I/O failures of the
+/// terminal itself are returned as errors.
+pub fn execute_line(
+    session: &mut Session,
+    line: &str,
+) -> Result<Flow, Box<dyn std::error::Error>> {
+    run_list(session, line, 0)
+}
+
+fn run_list(
+    session: &mut Session,
+    text: &str,
+    depth: usize,
+) -> Result<Flow, Box<dyn std::error::Error>> {
+    if depth > MAX_DEPTH {
+        eprintln!("Error: maximum alias/function nesting depth exceeded.");
+        session.last_status = EXIT_FAILURE;
+        return Ok(Flow::Continue);
+    }
+    let jobs = match shell::parse_jobs(text) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("Syntax error: {}", e);
+            session.last_status = EXIT_USAGE;
+            return Ok(Flow::Continue);
+        }
+    };
+    for (list, background) in jobs {
+        if background {
+            start_job(session, &list);
+            continue;
+        }
+        let flow = run_and_or(session, &list, depth)?;
+        if flow != Flow::Continue {
+            return Ok(flow);
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+/// Runs a list in the background on a copy of the session, as a job of
+/// the session's shell. Anything it does to the copy, like `cd` or ending
+/// the session, stays with the job.
+fn start_job(session: &mut Session, list: &str) {
+    let list = list.trim().to_string();
+    let (pid, job) = process::spawn_job(session.pid, &session.user, &list);
+    let mut background = session.clone();
+    background.pid = pid;
+    background.stdin = None;
+    background.terminal = false;
+    let console = console::current();
+    std::thread::spawn(move || {
+        console::attach(console);
+        let status = match run_list(&mut background, &list, 0) {
+            Ok(_) => background.last_status,
+            Err(_) => EXIT_FAILURE,
+        };
+        process::exit(pid, status);
+    });
+    println!("[{}] {}", job, pid);
+    session.last_status = EXIT_SUCCESS;
+}
+
+/// Runs statements joined by `&&` and `||`
+fn run_and_or(
+    session: &mut Session,
+    text: &str,
+    depth: usize,
+) -> Result<Flow, Box<dyn std::error::Error>> {
+    let statements = match shell::parse_list(text) {
+        Ok(statements) => statements,
+        Err(e) => {
+            eprintln!("Syntax error: {}", e);
+            session.last_status = EXIT_USAGE;
+            return Ok(Flow::Continue);
+        }
+    };
+
+    for (connector, statement) in statements {
+        let run = match connector {
+            Connector::Always => true,
+            Connector::And => session.last_status == EXIT_SUCCESS,
+            Connector::Or => session.last_status != EXIT_SUCCESS,
+        };
+        if !run {
+            continue;
+        }
+        let flow = execute_statement(session, &statement, depth)?;
+        if flow != Flow::Continue {
+            return Ok(flow);
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+/// Runs a statement, which may be a pipeline: each command's output
+/// becomes the input of the next, and the last one's status is the
+/// statement's.
+fn execute_statement(
+    session: &mut Session,
+    statement: &str,
+    depth: usize,
+) -> Result<Flow, Box<dyn std::error::Error>> {
+    let stages = match shell::parse_pipeline(statement) {
+        Ok(stages) => stages,
+        Err(e) => {
+            eprintln!("Syntax error: {}", e);
+            session.last_status = EXIT_USAGE;
+            return Ok(Flow::Continue);
+        }
+    };
+    let Some((last, earlier)) = stages.split_last() else {
+        return Ok(Flow::Continue);
+    };
+
+    let mut input = None;
+    for stage in earlier {
+        session.stdin = input.take();
+        let (flow, output) = console::capture(|| execute_command(session, stage, depth));
+        // A command that ends the session ends the pipeline too
+        let flow = flow?;
+        if flow != Flow::Continue {
+            session.stdin = None;
+            return Ok(flow);
+        }
+        input = Some(output);
+    }
+    session.stdin = input;
+    let flow = execute_command(session, last, depth);
+    session.stdin = None;
+    flow
+}
+
+/// Expands aliases, tokenizes the command and runs the function or
+/// registry command it names.
+fn execute_command(
+    session: &mut Session,
+    command: &str,
+    depth: usize,
+) -> Result<Flow, Box<dyn std::error::Error>> {
+    let expanded = shell::expand_aliases(command, session);
+    let compound = matches!(shell::parse_jobs(&expanded), Ok(jobs) if jobs.len() > 1 || jobs.iter().any(|(_, background)| *background))
+        || matches!(shell::parse_list(&expanded), Ok(list) if list.len() > 1)
+        || matches!(shell::parse_pipeline(&expanded), Ok(stages) if stages.len() > 1);
+    if compound {
+        // The alias expanded to several statements, a pipeline or a job
+        return run_list(session, &expanded, depth + 1);
+    }
+
+    let words = match shell::tokenize(&expanded, session) {
+        Ok(words) => words,
+        Err(e) => {
+            eprintln!("Syntax error: {}", e);
+            session.last_status = EXIT_USAGE;
+            return Ok(Flow::Continue);
+        }
+    };
+    let Some((name, args)) = words.split_first() else {
+        return Ok(Flow::Continue);
+    };
+
+    if let Some(body) = session.functions.get(name).cloned() {
+        let saved = std::mem::replace(&mut session.positional, args.to_vec());
+        let flow = run_list(session, &body, depth + 1);
+        session.positional = saved;
+        return flow;
+    }
+
+    // The command runs as a child process of the shell or job
+    let parent = session.pid;
+    let pid = process::spawn(parent, &session.user, command.trim());
+    // Commands of background jobs take turns on the CPU, unless they're
+    // run by a command that already has it
+    let task = process::in_background(pid) && sched::state(parent).is_none();
+    // Commands typed at the terminal can be interrupted with Ctrl-C, and
+    // so can the commands they run
+    let foreground = session.terminal && console::foreground().is_none();
+    if foreground {
+        tty::clear_interrupt();
+        console::set_foreground(Some(pid));
+    }
+    let outcome = match process::checkpoint(pid) {
+        Ok(()) => {
+            if task {
+                sched::enter(pid, &session.user.username);
             }
+            session.pid = pid;
+            let outcome = run_command(session, name, args);
+            session.pid = parent;
+            outcome
+        }
+        Err(signal) => CommandOutcome::exited(signal.status()),
+    };
+    if task {
+        sched::leave(pid);
+    }
+    let interrupted = foreground
+        && (outcome.status == Signal::Int.status()
+            || process::pending(pid) == Some(Signal::Int));
+    if foreground {
+        console::set_foreground(None);
+        // Unless a prompt already did
+        if tty::clear_interrupt() {
+            tty::echo_interrupt();
+        }
+    }
+    process::exit(pid, outcome.status);
+    session.last_status = outcome.status;
+    // Save whatever the command changed on the filesystem
+    if let Err(e) = vfs::sync() {
+        syslog::console(
+            Level::Err,
+            Facility::Fs,
+            &format!("could not save the filesystem: {}", e),
+        );
+    }
+    if let Some(message) = &outcome.message {
+        if outcome.succeeded() {
+            println!("{}", message);
+        } else {
+            eprintln!("Error: {}: {}", name.to_lowercase(), message);
         }
-        if command != "exit" {
-             println!("-----------------------------");
+    }
+
+    Ok(match outcome.effect {
+        SessionEffect::None if interrupted => Flow::Interrupted,
+        SessionEffect::None => Flow::Continue,
+        SessionEffect::Logout | SessionEffect::IdentityChanged => Flow::Logout,
+        SessionEffect::Exit => Flow::Exit,
+    })
+}
+
+/// Looks a command up in the registry, checks it may be used and runs it.
+/// Errors returned by the command become a failed outcome.
+pub fn run_command(
+    session: &mut Session,
+    name: &str,
+    args: &[String],
+) -> CommandOutcome {
+    let name = name.to_lowercase();
+    let Some(command) = commands::find(&name) else {
+        return CommandOutcome::failure(
+            EXIT_NOT_FOUND,
+            "Unknown command. Type 'help' for a list of commands.",
+        );
+    };
+
+    if let Err(outcome) = refresh_account(session) {
+        return outcome;
+    }
+    if command.admin_only && !session.user.is_admin {
+        return CommandOutcome::failure(
+            EXIT_PERMISSION,
+            "You must be an admin to use this command.",
+        );
+    }
+
+    (command.run)(session, args).unwrap_or_else(|e| {
+        if tty::is_interrupt(e.as_ref()) {
+            // A prompt gave up because of Ctrl-C
+            CommandOutcome::exited(Signal::Int.status())
+        } else {
+            CommandOutcome::failure(EXIT_FAILURE, e.to_string())
+        }
+    })
+}
+
+/// Brings the session's admin status up to date with users.xml, which
+/// other sessions and the admin API may have changed since it logged in.
+/// Fails, ending the session, if the account was deleted or disabled
+/// meanwhile.
+fn refresh_account(session: &mut Session) -> Result<(), CommandOutcome> {
+    let users = auth::load_users()
+        .map_err(|e| CommandOutcome::failure(EXIT_FAILURE, e.to_string()))?;
+    match users.iter().find(|u| u.username == session.user.username) {
+        Some(user) if !user.disabled => {
+            session.user.is_admin = user.is_admin;
+            Ok(())
         }
+        found => Err(CommandOutcome::failure(
+            EXIT_PERMISSION,
+            match found {
+                Some(_) => "Your account has been disabled.",
+                None => "Your account no longer exists.",
+            },
+        )
+        .with_effect(SessionEffect::Logout)),
     }
 }
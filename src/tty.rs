@@ -0,0 +1,454 @@
+use crate::console;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often a prompt waiting for input looks for an interrupt
+const INTERRUPT_POLL_MS: i32 = 100;
+
+/// Set when Ctrl-C is pressed, until a prompt gives up because of it
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Both ends of a pipe the SIGINT handler writes a byte to, so a thread
+/// can wait for interrupts
+static INTERRUPT_PIPE: [AtomicI32; 2] = [AtomicI32::new(-1), AtomicI32::new(-1)];
+
+/// Bytes read from standard input but not used yet. Input is read here
+/// rather than through `io::stdin`, whose buffer would hide typed-ahead
+/// input from `poll`.
+static INPUT: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+
+/// Puts the terminal in non-canonical, no-echo mode for as long as it lives.
+/// Remote clients keep their terminals that way while connected.
+pub struct RawMode {
+    original: Option<libc::termios>,
+}
+
+impl RawMode {
+    pub fn enable() -> Option<RawMode> {
+        let console = console::current();
+        if !console.is_host() {
+            // Cron jobs and services have no terminal at all
+            return console.is_remote().then_some(RawMode { original: None });
+        }
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 {
+                return None;
+            }
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return None;
+            }
+            let mut raw = original;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::IEXTEN | libc::ISIG);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) != 0 {
+                return None;
+            }
+            Some(RawMode {
+                original: Some(original),
+            })
+        }
+    }
+}
+
+/// Turns off echo, but not line editing or Ctrl-C, for as long as it lives
+struct NoEcho {
+    original: libc::termios,
+}
+
+impl NoEcho {
+    fn enable() -> Option<NoEcho> {
+        unsafe {
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return None;
+            }
+            let mut quiet = original;
+            quiet.c_lflag &= !libc::ECHO;
+            quiet.c_lflag |= libc::ECHONL;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &quiet) != 0 {
+                return None;
+            }
+            Some(NoEcho { original })
+        }
+    }
+}
+
+impl Drop for NoEcho {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original);
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if let Some(original) = &self.original {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, original);
+            }
+        }
+    }
+}
+
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Interrupt,
+    Eof,
+    KillLine,
+    Unknown,
+}
+
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    let fd = INTERRUPT_PIPE[1].load(Ordering::SeqCst);
+    if fd >= 0 {
+        let byte = 0u8;
+        unsafe {
+            libc::write(fd, &byte as *const u8 as *const libc::c_void, 1);
+        }
+    }
+}
+
+/// Makes Ctrl-C interrupt prompts instead of ending MiniKern. Prompts
+/// reading with `read_line` then fail with `ErrorKind::Interrupted`, and
+/// `wait_for_interrupt` returns.
+pub fn catch_interrupts() -> io::Result<()> {
+    unsafe {
+        let mut fds = [0; 2];
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // The handler mustn't block if nothing is reading
+        libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK);
+        INTERRUPT_PIPE[0].store(fds[0], Ordering::SeqCst);
+        INTERRUPT_PIPE[1].store(fds[1], Ordering::SeqCst);
+
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_interrupt as extern "C" fn(libc::c_int) as usize;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Blocks until Ctrl-C is pressed
+pub fn wait_for_interrupt() -> io::Result<()> {
+    let fd = INTERRUPT_PIPE[0].load(Ordering::SeqCst);
+    if fd < 0 {
+        return Err(io::Error::other("interrupts aren't being caught"));
+    }
+    let mut byte = 0u8;
+    loop {
+        match unsafe { libc::read(fd, &mut byte as *mut u8 as *mut libc::c_void, 1) } {
+            1 => return Ok(()),
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// Forgets an interrupt no prompt has acted on, so it doesn't cancel the
+/// next one. Returns whether there was one.
+pub fn clear_interrupt() -> bool {
+    console::clear_remote_interrupt().unwrap_or_else(|| INTERRUPTED.swap(false, Ordering::SeqCst))
+}
+
+/// The error prompts fail with when Ctrl-C is pressed
+pub fn interrupted() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "interrupted")
+}
+
+/// Ends the line Ctrl-C was pressed on. The host's terminal echoes the
+/// `^C` itself; a remote client's, in raw mode, doesn't.
+pub fn echo_interrupt() {
+    if console::current().is_remote() {
+        print!("^C");
+    }
+    println!();
+}
+
+/// Whether an error is a prompt giving up because of Ctrl-C
+pub fn is_interrupt(error: &(dyn std::error::Error + 'static)) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::Interrupted)
+}
+
+/// Reads a byte of standard input. An interruptible read fails with
+/// `interrupted()` if Ctrl-C is pressed while it waits.
+fn read_byte_from_stdin(interruptible: bool) -> io::Result<Option<u8>> {
+    match console::read_remote_byte(interruptible) {
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+            echo_interrupt();
+            return Err(interrupted());
+        }
+        // Keys are read until the session ends, which it does once the
+        // client has gone
+        Ok(None) if !interruptible => {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "client disconnected",
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {}
+        read => return read,
+    }
+    loop {
+        let mut input = INPUT.lock().unwrap_or_else(|p| p.into_inner());
+        if interruptible && INTERRUPTED.swap(false, Ordering::SeqCst) {
+            // Like the terminal, drop whatever was typed ahead
+            input.clear();
+            echo_interrupt();
+            return Err(interrupted());
+        }
+        if let Some(byte) = input.pop_front() {
+            return Ok(Some(byte));
+        }
+        drop(input);
+
+        let mut poll = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = if interruptible { INTERRUPT_POLL_MS } else { -1 };
+        let mut buf = [0u8; 1024];
+        let read = unsafe {
+            match libc::poll(&mut poll, 1, timeout) {
+                0 => continue,
+                ready if ready < 0 => -1,
+                _ => libc::read(
+                    libc::STDIN_FILENO,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                ),
+            }
+        };
+        match read {
+            0 => return Ok(None),
+            n if n > 0 => INPUT
+                .lock()
+                .unwrap_or_else(|p| p.into_inner())
+                .extend(&buf[..n as usize]),
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// Waits up to `timeout` for input, returning whether a key can be read
+/// without blocking (end of input counts)
+pub fn wait_for_input(timeout: Duration) -> io::Result<bool> {
+    match console::wait_remote_input(timeout) {
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {}
+        ready => return ready,
+    }
+    if !INPUT.lock().unwrap_or_else(|p| p.into_inner()).is_empty() {
+        return Ok(true);
+    }
+    let mut poll = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().try_into().unwrap_or(i32::MAX);
+    match unsafe { libc::poll(&mut poll, 1, timeout) } {
+        ready if ready >= 0 => Ok(ready > 0),
+        _ => {
+            let e = io::Error::last_os_error();
+            match e.kind() {
+                io::ErrorKind::Interrupted => Ok(false),
+                _ => Err(e),
+            }
+        }
+    }
+}
+
+fn read_byte() -> io::Result<Option<u8>> {
+    read_byte_from_stdin(false)
+}
+
+/// Reads a line of standard input into `buf`, newline included, like
+/// `io::Stdin::read_line`, returning the number of bytes read (0 at end of
+/// input). Fails with `interrupted()` if Ctrl-C is pressed first.
+pub fn read_line(buf: &mut String) -> io::Result<usize> {
+    if console::current().is_remote() {
+        return read_remote_line(buf, true);
+    }
+    let mut bytes = Vec::new();
+    while let Some(byte) = read_byte_from_stdin(true)? {
+        bytes.push(byte);
+        if byte == b'\n' {
+            break;
+        }
+    }
+    buf.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(bytes.len())
+}
+
+/// Reads a line without echoing it, for passwords. The newline isn't
+/// included.
+pub fn read_password() -> io::Result<String> {
+    let mut password = String::new();
+    if !console::current().is_host() {
+        read_remote_line(&mut password, false)?;
+    } else {
+        let _quiet = NoEcho::enable();
+        read_line(&mut password)?;
+    }
+    Ok(password.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// `read_line` for a remote client, whose terminal is in raw mode: does
+/// the echoing and erasing the host's terminal driver would
+fn read_remote_line(buf: &mut String, echo: bool) -> io::Result<usize> {
+    let mut bytes = Vec::new();
+    let mut out = console::stdout();
+    while let Some(byte) = read_byte_from_stdin(true)? {
+        match byte {
+            b'\r' | b'\n' => {
+                bytes.push(b'\n');
+                writeln!(out)?;
+                break;
+            }
+            0x04 if bytes.is_empty() => break,
+            0x7f | 0x08 => {
+                if bytes.is_empty() {
+                    continue;
+                }
+                // Erase a whole character, not just its last byte
+                while let Some(last) = bytes.pop() {
+                    if last & 0xc0 != 0x80 {
+                        break;
+                    }
+                }
+                if echo {
+                    write!(out, "\x08 \x08")?;
+                }
+            }
+            0x15 => {
+                if echo {
+                    let width = String::from_utf8_lossy(&bytes).chars().count();
+                    write!(out, "{}", "\x08 \x08".repeat(width))?;
+                }
+                bytes.clear();
+            }
+            byte if byte < 0x20 => {}
+            byte => {
+                bytes.push(byte);
+                if echo {
+                    out.write_all(&[byte])?;
+                }
+            }
+        }
+    }
+    buf.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(bytes.len())
+}
+
+pub fn read_key() -> io::Result<Key> {
+    let Some(byte) = read_byte()? else {
+        return Ok(Key::Eof);
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x05 => Key::End,
+        0x03 => Key::Interrupt,
+        0x04 => Key::Eof,
+        0x15 => Key::KillLine,
+        0x1b => match read_byte()? {
+            Some(b'[') | Some(b'O') => match read_byte()? {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                Some(b'3') => {
+                    read_byte()?; // trailing '~'
+                    Key::Delete
+                }
+                Some(b'5') => {
+                    read_byte()?;
+                    Key::PageUp
+                }
+                Some(b'6') => {
+                    read_byte()?;
+                    Key::PageDown
+                }
+                _ => Key::Unknown,
+            },
+            _ => Key::Unknown,
+        },
+        b if b < 0x20 => Key::Unknown,
+        b => {
+            // Collect the rest of a multi-byte UTF-8 sequence
+            let len = match b {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![b];
+            for _ in 1..len {
+                if let Some(next) = read_byte()? {
+                    bytes.push(next);
+                }
+            }
+            match String::from_utf8(bytes) {
+                Ok(s) => s.chars().next().map(Key::Char).unwrap_or(Key::Unknown),
+                Err(_) => Key::Unknown,
+            }
+        }
+    };
+    Ok(key)
+}
+
+/// Terminal size as (columns, rows), if stdout is a terminal
+pub fn window_size() -> Option<(usize, usize)> {
+    if !console::current().is_host() {
+        return None;
+    }
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) != 0
+            || size.ws_col == 0
+        {
+            return None;
+        }
+        Some((size.ws_col as usize, size.ws_row as usize))
+    }
+}
@@ -0,0 +1,533 @@
+use crate::accounts::{self, AccountError, FileDisposal};
+use crate::audit::{self, AuditEntry};
+use crate::auth::{load_users, CurrentUser, User};
+use crate::clock;
+use crate::console;
+use crate::profile::{self, HOME_ARCHIVE_DIR_PATH};
+use crate::tty::{self, read_key, Key, RawMode};
+use std::io::{self, Write};
+
+const HELP_USERS: &str =
+    " Up/Down select  a admin  p password  d disable/enable  x delete  l audit log  r reload  q quit";
+const HELP_AUDIT: &str = " Up/Down/PgUp/PgDn scroll  l back to users  q quit";
+
+/// Switches to the alternate screen and restores the normal one on drop
+struct Screen;
+
+impl Screen {
+    fn enter() -> io::Result<Screen> {
+        print!("\x1b[?1049h\x1b[?25l");
+        console::stdout().flush()?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = console::stdout().flush();
+    }
+}
+
+#[derive(PartialEq)]
+enum View {
+    Users,
+    Audit,
+}
+
+/// Full-screen admin console. Every change goes through `accounts`, the
+/// same code `chusr`, `delusr` and `addusr` use, so the console enforces the
+/// root and last-admin rules exactly like the commands do.
+struct Console {
+    actor: CurrentUser,
+    users: Vec<User>,
+    audit: Vec<AuditEntry>,
+    selected: usize,
+    audit_scroll: usize,
+    view: View,
+    status: String,
+}
+
+/// What the console should do after handling a key
+enum Next {
+    Stay,
+    Quit,
+}
+
+pub fn run(actor: CurrentUser) -> Result<(), Box<dyn std::error::Error>> {
+    if !actor.is_admin {
+        return Err("The admin console is only available to admins.".into());
+    }
+    let Some(_raw) = RawMode::enable() else {
+        return Err("The admin console needs an interactive terminal.".into());
+    };
+    let _screen = Screen::enter()?;
+
+    let mut console = Console {
+        actor,
+        users: Vec::new(),
+        audit: Vec::new(),
+        selected: 0,
+        audit_scroll: 0,
+        view: View::Users,
+        status: String::new(),
+    };
+    console.reload()?;
+
+    loop {
+        console.draw(&[])?;
+        let key = read_key()?;
+        match console.handle(key)? {
+            Next::Stay => {}
+            Next::Quit => return Ok(()),
+        }
+    }
+}
+
+impl Console {
+    fn reload(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.users = load_users()?;
+        self.audit = audit::load_entries()?;
+        if self.selected >= self.users.len() {
+            self.selected = self.users.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    fn selected_user(&self) -> Option<&User> {
+        self.users.get(self.selected)
+    }
+
+    fn handle(&mut self, key: Key) -> Result<Next, Box<dyn std::error::Error>> {
+        if self.view == View::Audit {
+            let page = audit_rows();
+            let max_scroll = self.audit.len().saturating_sub(page);
+            match key {
+                Key::Up => self.audit_scroll = self.audit_scroll.saturating_sub(1),
+                Key::Down => self.audit_scroll += 1,
+                Key::PageUp => self.audit_scroll = self.audit_scroll.saturating_sub(page),
+                Key::PageDown => self.audit_scroll += page,
+                Key::Char('l') | Key::Tab => self.view = View::Users,
+                Key::Char('q') | Key::Eof => return Ok(Next::Quit),
+                _ => {}
+            }
+            self.audit_scroll = self.audit_scroll.min(max_scroll);
+            return Ok(Next::Stay);
+        }
+
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down if self.selected + 1 < self.users.len() => self.selected += 1,
+            Key::Home => self.selected = 0,
+            Key::End => self.selected = self.users.len().saturating_sub(1),
+            Key::Char('l') | Key::Tab => {
+                self.view = View::Audit;
+                // Start at the newest entries
+                self.audit_scroll = self.audit.len().saturating_sub(audit_rows());
+            }
+            Key::Char('r') => {
+                self.reload()?;
+                self.status = "Reloaded.".into();
+            }
+            Key::Char('a') => return self.toggle_admin(),
+            Key::Char('p') => return self.reset_password(),
+            Key::Char('d') => return self.toggle_disabled(),
+            Key::Char('x') => return self.delete(),
+            Key::Char('q') | Key::Eof => return Ok(Next::Quit),
+            _ => {}
+        }
+        Ok(Next::Stay)
+    }
+
+    /// Reports the result of an account operation. Changing the console
+    /// user's own account ends the console, just like it ends a terminal
+    /// session.
+    fn finish(
+        &mut self,
+        username: &str,
+        result: Result<(), AccountError>,
+        done: String,
+    ) -> Result<Next, Box<dyn std::error::Error>> {
+        match result {
+            Ok(()) => {
+                self.reload()?;
+                if username == self.actor.username {
+                    self.message("Account changed", &[
+                        "Your own account has been changed.",
+                        "Please log in again.",
+                    ])?;
+                    return Ok(Next::Quit);
+                }
+                self.status = done;
+            }
+            Err(e) => {
+                self.message("Not allowed", &[&e.to_string()])?;
+                self.status = format!("Failed: {}", e);
+            }
+        }
+        Ok(Next::Stay)
+    }
+
+    fn toggle_admin(&mut self) -> Result<Next, Box<dyn std::error::Error>> {
+        let Some(user) = self.selected_user().cloned() else {
+            return Ok(Next::Stay);
+        };
+        let granting = !user.is_admin;
+        let mut question = vec![if granting {
+            format!("Grant admin privileges to '{}'?", user.username)
+        } else {
+            format!("Remove admin privileges from '{}'?", user.username)
+        }];
+        if !granting && user.username == self.actor.username {
+            question.push("Warning: You are removing your own admin privileges.".into());
+        }
+        if !self.confirm("Change admin privileges", &question)? {
+            self.status = "Admin privilege change cancelled.".into();
+            return Ok(Next::Stay);
+        }
+        let result = accounts::set_admin(&self.actor, &user.username, granting);
+        let done = if granting {
+            format!("User '{}' has been granted admin privileges.", user.username)
+        } else {
+            format!("User '{}' admin privileges have been removed.", user.username)
+        };
+        self.finish(&user.username, result, done)
+    }
+
+    fn toggle_disabled(&mut self) -> Result<Next, Box<dyn std::error::Error>> {
+        let Some(user) = self.selected_user().cloned() else {
+            return Ok(Next::Stay);
+        };
+        let disabling = !user.disabled;
+        let question = if disabling {
+            format!("Disable the account '{}'? It will no longer be able to log in.", user.username)
+        } else {
+            format!("Re-enable the account '{}'?", user.username)
+        };
+        if !self.confirm("Account status", &[question])? {
+            self.status = "Cancelled.".into();
+            return Ok(Next::Stay);
+        }
+        let result = accounts::set_disabled(&self.actor, &user.username, disabling);
+        let done = format!(
+            "User '{}' has been {}.",
+            user.username,
+            if disabling { "disabled" } else { "re-enabled" }
+        );
+        self.finish(&user.username, result, done)
+    }
+
+    fn reset_password(&mut self) -> Result<Next, Box<dyn std::error::Error>> {
+        let Some(user) = self.selected_user().cloned() else {
+            return Ok(Next::Stay);
+        };
+        let title = format!("New password for '{}'", user.username);
+        let Some(first) = self.password(&title, "New password:")? else {
+            self.status = "Cancelled.".into();
+            return Ok(Next::Stay);
+        };
+        let Some(second) = self.password(&title, "Confirm password:")? else {
+            self.status = "Cancelled.".into();
+            return Ok(Next::Stay);
+        };
+        if first != second {
+            self.message("Password not changed", &["Passwords do not match."])?;
+            return Ok(Next::Stay);
+        }
+        let result = accounts::set_password(&self.actor, &user.username, &first);
+        let done = format!("Password for '{}' updated successfully.", user.username);
+        self.finish(&user.username, result, done)
+    }
+
+    fn delete(&mut self) -> Result<Next, Box<dyn std::error::Error>> {
+        let Some(user) = self.selected_user().cloned() else {
+            return Ok(Next::Stay);
+        };
+        let question = format!("Permanently delete the user '{}'?", user.username);
+        if !self.confirm("Delete user", &[question])? {
+            self.status = "User deletion cancelled.".into();
+            return Ok(Next::Stay);
+        }
+        let root = self.users[0].username.clone();
+        let files = [
+            format!("What should happen to the files of '{}'?", user.username),
+            String::new(),
+            format!("[g] Give them to {}   [d] Delete them   [n] Cancel", root),
+        ];
+        let files = match self.choose("Delete user", &files, &['g', 'd', 'n'])? {
+            Some('g') => FileDisposal::ReassignTo(root.clone()),
+            Some('d') => FileDisposal::Delete,
+            _ => {
+                self.status = "User deletion cancelled.".into();
+                return Ok(Next::Stay);
+            }
+        };
+        let home = profile::user_home(&user.username);
+        let question = format!("Archive {} to {} before removing it?", home, HOME_ARCHIVE_DIR_PATH);
+        let archive_home = self.confirm("Delete user", &[question])?;
+        let prompt = format!("Password of {} (for verification):", root);
+        let Some(password) = self.password("Delete user", &prompt)? else {
+            self.status = "User deletion cancelled.".into();
+            return Ok(Next::Stay);
+        };
+        let result =
+            accounts::delete_user(&self.actor, &user.username, &password, &files, archive_home);
+        let done = match &result {
+            Ok(Some(archive)) => format!(
+                "User '{}' has been deleted. Home archived to {}.",
+                user.username, archive
+            ),
+            _ => format!("User '{}' has been deleted.", user.username),
+        };
+        self.finish(&user.username, result.map(|_| ()), done)
+    }
+
+    fn confirm(&self, title: &str, lines: &[String]) -> io::Result<bool> {
+        let mut body: Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+        body.push("");
+        body.push("[y] Yes   [n] No");
+        loop {
+            self.draw_dialog(title, &body)?;
+            match read_key()? {
+                Key::Char('y') | Key::Char('Y') => return Ok(true),
+                Key::Char('n') | Key::Char('N') | Key::Char('q') | Key::Interrupt | Key::Eof => {
+                    return Ok(false)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Shows a dialog until one of `keys` is pressed. Returns `None` if
+    /// cancelled with Ctrl-C.
+    fn choose(&self, title: &str, lines: &[String], keys: &[char]) -> io::Result<Option<char>> {
+        let body: Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+        loop {
+            self.draw_dialog(title, &body)?;
+            match read_key()? {
+                Key::Char(c) if keys.contains(&c.to_ascii_lowercase()) => {
+                    return Ok(Some(c.to_ascii_lowercase()))
+                }
+                Key::Interrupt | Key::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+
+    fn message(&self, title: &str, lines: &[&str]) -> io::Result<()> {
+        let mut body = lines.to_vec();
+        body.push("");
+        body.push("Press any key to continue");
+        self.draw_dialog(title, &body)?;
+        read_key()?;
+        Ok(())
+    }
+
+    /// Reads a password inside a dialog, showing `*` for each character.
+    /// Returns `None` if cancelled with Ctrl-C.
+    fn password(&self, title: &str, prompt: &str) -> io::Result<Option<String>> {
+        let mut input = String::new();
+        loop {
+            let masked = format!("{} {}", prompt, "*".repeat(input.chars().count()));
+            self.draw_dialog(title, &[&masked, "", "Enter to confirm, Ctrl-C to cancel"])?;
+            match read_key()? {
+                Key::Enter if !input.is_empty() => return Ok(Some(input)),
+                Key::Char(c) => input.push(c),
+                Key::Backspace => {
+                    input.pop();
+                }
+                Key::Interrupt | Key::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+
+    fn draw_dialog(&self, title: &str, body: &[&str]) -> io::Result<()> {
+        let (cols, _) = screen_size();
+        let inner = body
+            .iter()
+            .map(|l| l.chars().count())
+            .chain(std::iter::once(title.chars().count() + 2))
+            .max()
+            .unwrap_or(0)
+            .min(cols.saturating_sub(6));
+        let mut dialog = vec![format!("┌─ {} {}┐", title, "─".repeat(inner.saturating_sub(title.chars().count() + 1)))];
+        for line in body {
+            dialog.push(format!("│ {} │", pad(line, inner)));
+        }
+        dialog.push(format!("└{}┘", "─".repeat(inner + 2)));
+        self.draw(&dialog)
+    }
+
+    /// Draws the current view, with `overlay` lines centred on top of it.
+    fn draw(&self, overlay: &[String]) -> io::Result<()> {
+        let (cols, rows) = screen_size();
+        let body_rows = rows.saturating_sub(4);
+        let mut lines: Vec<String> = Vec::with_capacity(rows);
+
+        let title = format!(
+            " MiniKern admin console - {} ",
+            if self.view == View::Users { "users" } else { "audit log" }
+        );
+        let who = format!("logged in as {} ", self.actor.username);
+        let gap = cols.saturating_sub(title.chars().count() + who.chars().count());
+        lines.push(format!("\x1b[7m{}{}{}\x1b[0m", title, " ".repeat(gap), who));
+
+        match self.view {
+            View::Users => self.users_view(cols, body_rows, &mut lines),
+            View::Audit => self.audit_view(cols, body_rows, &mut lines),
+        }
+
+        while lines.len() < rows.saturating_sub(2) {
+            lines.push(String::new());
+        }
+        lines.truncate(rows.saturating_sub(2));
+        lines.push(truncate(&self.status, cols));
+        let help = if self.view == View::Users { HELP_USERS } else { HELP_AUDIT };
+        lines.push(format!("\x1b[7m{}\x1b[0m", pad(help, cols)));
+
+        if !overlay.is_empty() {
+            let width = overlay.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+            let top = rows.saturating_sub(overlay.len()) / 2;
+            let left = cols.saturating_sub(width) / 2;
+            for (i, line) in overlay.iter().enumerate() {
+                if let Some(target) = lines.get_mut(top + i) {
+                    *target = format!("{}\x1b[1m{}\x1b[0m", " ".repeat(left), line);
+                }
+            }
+        }
+
+        let mut out = console::stdout();
+        write!(out, "\x1b[H")?;
+        for (i, line) in lines.iter().enumerate() {
+            write!(out, "{}\x1b[K", line)?;
+            if i + 1 < lines.len() {
+                write!(out, "\r\n")?;
+            }
+        }
+        out.flush()
+    }
+
+    fn users_view(&self, cols: usize, body_rows: usize, lines: &mut Vec<String>) {
+        let left_width = (cols / 2).clamp(20, 44);
+        let right_width = cols.saturating_sub(left_width + 3);
+
+        let mut left = vec![format!("  {:<18} {:<6} {}", "USER", "ROLE", "STATUS")];
+        // Keep the selected row visible when the list is longer than the screen
+        let visible = body_rows.saturating_sub(2).max(1);
+        let first = (self.selected + 1).saturating_sub(visible);
+        for (i, user) in self.users.iter().enumerate().skip(first).take(visible) {
+            let row = pad(
+                &format!(
+                    "  {:<18} {:<6} {}",
+                    truncate(&user.username, 18),
+                    if user.is_admin { "Admin" } else { "User" },
+                    if user.disabled { "disabled" } else { "active" }
+                ),
+                left_width,
+            );
+            left.push(if i == self.selected {
+                format!("\x1b[7m{}\x1b[0m", row)
+            } else {
+                row
+            });
+        }
+
+        let mut right = Vec::new();
+        if let Some(user) = self.selected_user() {
+            right.push(format!("Username : {}", user.username));
+            right.push(format!(
+                "Role     : {}",
+                if user.is_admin { "Admin" } else { "User" }
+            ));
+            right.push(format!(
+                "Root     : {}",
+                if accounts::is_root(&self.users, &user.username) { "yes" } else { "no" }
+            ));
+            right.push(format!(
+                "Status   : {}",
+                if user.disabled { "disabled" } else { "active" }
+            ));
+            right.push(String::new());
+            right.push("Recent activity:".to_string());
+            let recent: Vec<&AuditEntry> = self
+                .audit
+                .iter()
+                .rev()
+                .filter(|e| e.actor == user.username || e.target == user.username)
+                .take(body_rows.saturating_sub(8))
+                .collect();
+            if recent.is_empty() {
+                right.push("  (none)".to_string());
+            }
+            for entry in recent {
+                // Month, day and time are enough next to the user details
+                let time = clock::format_datetime(entry.time);
+                right.push(format!(
+                    "  {}  {} by {}",
+                    &time[5..16],
+                    entry.action,
+                    entry.actor
+                ));
+            }
+        }
+
+        lines.push(String::new());
+        for i in 0..left.len().max(right.len()).min(body_rows) {
+            let left_cell = left.get(i).cloned().unwrap_or_else(|| " ".repeat(left_width));
+            let left_cell = if left_cell.starts_with('\x1b') {
+                left_cell
+            } else {
+                pad(&left_cell, left_width)
+            };
+            let right_cell = right.get(i).map(|r| truncate(r, right_width)).unwrap_or_default();
+            lines.push(format!("{} │ {}", left_cell, right_cell));
+        }
+    }
+
+    fn audit_view(&self, cols: usize, body_rows: usize, lines: &mut Vec<String>) {
+        lines.push(String::new());
+        if self.audit.is_empty() {
+            lines.push("  No audit entries.".to_string());
+            return;
+        }
+        let visible = body_rows.saturating_sub(1).max(1);
+        for entry in self.audit.iter().skip(self.audit_scroll).take(visible) {
+            lines.push(truncate(&format!("  {}", audit_line(entry)), cols));
+        }
+    }
+}
+
+fn audit_line(entry: &AuditEntry) -> String {
+    let mut line = format!(
+        "{}  {:<10} {:<13} {}",
+        clock::format_datetime(entry.time),
+        entry.actor,
+        entry.action,
+        entry.target
+    );
+    if !entry.detail.is_empty() {
+        line.push_str(&format!(" ({})", entry.detail));
+    }
+    line
+}
+
+/// Number of audit entries that fit on screen: everything except the title,
+/// blank line, status and help rows
+fn audit_rows() -> usize {
+    screen_size().1.saturating_sub(5).max(1)
+}
+
+fn screen_size() -> (usize, usize) {
+    tty::window_size().unwrap_or((80, 24))
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+fn pad(text: &str, width: usize) -> String {
+    let text = truncate(text, width);
+    let len = text.chars().count();
+    format!("{}{}", text, " ".repeat(width - len))
+}
@@ -0,0 +1,61 @@
+use super::{DirEntry, FileType, VfsError};
+use std::fmt;
+
+/// What a backend knows about one of its files. Ownership isn't part of
+/// it: the mount decides who owns everything in a mounted filesystem.
+#[derive(Debug, Clone)]
+pub struct Attributes {
+    /// Unique within the backend
+    pub ino: u64,
+    pub file_type: FileType,
+    /// Bytes for files, number of entries for directories
+    pub size: u64,
+    pub mode: u16,
+    pub created: u64,
+    pub modified: u64,
+    pub accessed: u64,
+    pub changed: u64,
+}
+
+/// A filesystem that can be mounted into the tree. Paths are relative to
+/// the backend's root, `/`-separated and already normalized, with `""` for
+/// the root itself. Permissions are checked by the `Vfs` before a backend
+/// is called, so backends only report what their own storage refuses.
+pub trait Backend: fmt::Debug + Send {
+    /// Short name shown in the mount table, e.g. `hostfs`
+    fn kind(&self) -> &'static str;
+
+    /// Where the files come from, shown in the mount table
+    fn source(&self) -> String;
+
+    fn attributes(&self, path: &str) -> Result<Attributes, VfsError>;
+
+    /// Entries of a directory, sorted by name
+    fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, VfsError>;
+
+    /// Up to `len` bytes from `offset`; empty at end of file
+    fn read_at(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>, VfsError>;
+
+    /// Writes at `offset`, growing the file if needed
+    fn write_at(&mut self, path: &str, offset: u64, data: &[u8]) -> Result<(), VfsError>;
+
+    /// Creates an empty file, which must not exist yet
+    fn create(&mut self, path: &str) -> Result<(), VfsError>;
+
+    fn truncate(&mut self, path: &str) -> Result<(), VfsError>;
+
+    fn mkdir(&mut self, path: &str) -> Result<(), VfsError>;
+
+    fn remove_file(&mut self, path: &str) -> Result<(), VfsError>;
+
+    /// Removes an empty directory
+    fn remove_dir(&mut self, path: &str) -> Result<(), VfsError>;
+
+    /// Moves within the backend, replacing a file or empty directory at `to`
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), VfsError>;
+
+    fn set_mode(&mut self, path: &str, mode: u16) -> Result<(), VfsError>;
+
+    /// Sets the access and modification times to now
+    fn touch(&mut self, path: &str) -> Result<(), VfsError>;
+}
@@ -0,0 +1,299 @@
+use super::inode::{FileType, Ino, InodeData, ROOT_INO};
+use super::{path, Node, Vfs, VfsError};
+use crate::auth::CurrentUser;
+use crate::clock;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where `fsck` puts files it finds outside every directory
+const LOST_AND_FOUND: &str = "/lost+found";
+
+/// Something `fsck` found wrong with the filesystem
+pub struct Problem {
+    /// The file's path, `inode N` for a file outside the tree, or the
+    /// quota concerned
+    pub subject: String,
+    pub description: String,
+    pub repaired: bool,
+}
+
+/// What `fsck` checked and found
+pub struct FsckReport {
+    /// Files and directories in the filesystem
+    pub inodes: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl Vfs {
+    /// Checks the filesystem's own consistency (admins only): directory
+    /// entries pointing nowhere, inodes no directory points at, link counts,
+    /// owners missing from `users`, and quotas that don't match usage. With
+    /// `repair` each problem is fixed as it's found. Mounted filesystems
+    /// aren't checked.
+    pub fn fsck(
+        &mut self,
+        who: &CurrentUser,
+        users: &[String],
+        repair: bool,
+    ) -> Result<FsckReport, VfsError> {
+        if !who.is_admin {
+            return Err(VfsError::NotPermitted);
+        }
+        let mut problems = Vec::new();
+        self.check_entries(repair, &mut problems)?;
+        let detached = self.check_orphans(repair, &mut problems)?;
+        self.check_links(&detached, repair, &mut problems)?;
+        self.check_owners(users, repair, &mut problems)?;
+        self.check_quotas(users, repair, &mut problems);
+        if problems.iter().any(|p| p.repaired) {
+            self.dirty = true;
+        }
+        Ok(FsckReport {
+            inodes: self.inodes.len(),
+            problems,
+        })
+    }
+
+    /// The path of every inode reachable from the root, by the first name
+    /// found for it
+    fn tree_paths(&self) -> BTreeMap<Ino, String> {
+        let mut paths = BTreeMap::from([(ROOT_INO, "/".to_string())]);
+        let mut pending = vec![ROOT_INO];
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = self.entries(dir) else {
+                continue;
+            };
+            for (name, &ino) in entries {
+                if paths.contains_key(&ino) || !self.inodes.contains_key(&ino) {
+                    continue;
+                }
+                paths.insert(ino, path::join(&paths[&dir], name));
+                pending.push(ino);
+            }
+        }
+        paths
+    }
+
+    /// How to name an inode in a report
+    fn subject(&self, paths: &BTreeMap<Ino, String>, ino: Ino) -> String {
+        paths
+            .get(&ino)
+            .cloned()
+            .unwrap_or_else(|| format!("inode {}", ino))
+    }
+
+    /// Entries naming inodes that don't exist are removed
+    fn check_entries(&mut self, repair: bool, problems: &mut Vec<Problem>) -> Result<(), VfsError> {
+        let paths = self.tree_paths();
+        let mut dangling = Vec::new();
+        for inode in self.inodes.values() {
+            if let InodeData::Directory(entries) = &inode.data {
+                for (name, ino) in entries {
+                    if !self.inodes.contains_key(ino) {
+                        dangling.push((inode.ino, name.clone(), *ino));
+                    }
+                }
+            }
+        }
+        for (dir, name, ino) in dangling {
+            if repair {
+                self.entries_mut(dir)?.remove(&name);
+            }
+            problems.push(Problem {
+                subject: path::join(&self.subject(&paths, dir), &name),
+                description: format!("entry points at missing inode {}", ino),
+                repaired: repair,
+            });
+        }
+        Ok(())
+    }
+
+    /// Inodes outside the tree. Files that were deleted while open and
+    /// never freed are freed; anything else goes in `/lost+found`, named
+    /// after its inode number. Returns the ones left where they are.
+    fn check_orphans(
+        &mut self,
+        repair: bool,
+        problems: &mut Vec<Problem>,
+    ) -> Result<BTreeSet<Ino>, VfsError> {
+        let paths = self.tree_paths();
+        let orphans: BTreeSet<Ino> = self
+            .inodes
+            .keys()
+            .copied()
+            .filter(|ino| !paths.contains_key(ino))
+            .filter(|&ino| !self.open_files.values().any(|f| f.node == Node::Inode(ino)))
+            .collect();
+        // Those inside orphaned directories come back with their directory
+        let contained: BTreeSet<Ino> = orphans
+            .iter()
+            .filter_map(|ino| self.entries(*ino).ok())
+            .flat_map(|entries| entries.values().copied())
+            .collect();
+
+        let mut detached = orphans.clone();
+        for &ino in orphans.difference(&contained) {
+            let inode = self.inode(ino)?;
+            let deleted = inode.nlink == 0 && inode.file_type() == FileType::Regular;
+            let description = if deleted {
+                "deleted file was never freed".to_string()
+            } else {
+                format!("not in any directory, owned by {}", inode.owner)
+            };
+            if repair {
+                if deleted {
+                    self.free(ino);
+                } else {
+                    self.adopt(ino)?;
+                }
+                detached.remove(&ino);
+            }
+            problems.push(Problem {
+                subject: format!("inode {}", ino),
+                description,
+                repaired: repair,
+            });
+        }
+        if repair {
+            // Brought back along with their directories
+            detached.clear();
+        }
+        Ok(detached)
+    }
+
+    /// Links an orphaned inode into `/lost+found`, creating it if needed
+    fn adopt(&mut self, ino: Ino) -> Result<(), VfsError> {
+        let root = self.root_user();
+        let found = match self.lookup(None, LOST_AND_FOUND) {
+            Ok(found) => found,
+            Err(VfsError::NotFound) => {
+                self.mkdir(&root, LOST_AND_FOUND)?;
+                self.chmod(&root, LOST_AND_FOUND, 0o700)?;
+                self.lookup(None, LOST_AND_FOUND)?
+            }
+            Err(e) => return Err(e),
+        };
+        // Nothing points at it yet, whatever the inode says
+        self.inode_mut(ino)?.nlink = 0;
+        self.link(found, &format!("#{}", ino), ino)
+    }
+
+    /// Each inode's link count has to be the number of entries pointing at
+    /// it, plus one for the root
+    fn check_links(
+        &mut self,
+        detached: &BTreeSet<Ino>,
+        repair: bool,
+        problems: &mut Vec<Problem>,
+    ) -> Result<(), VfsError> {
+        let mut counts: BTreeMap<Ino, u32> = BTreeMap::from([(ROOT_INO, 1)]);
+        for inode in self.inodes.values() {
+            if let InodeData::Directory(entries) = &inode.data {
+                for ino in entries.values() {
+                    *counts.entry(*ino).or_default() += 1;
+                }
+            }
+        }
+        let paths = self.tree_paths();
+        let wrong: Vec<(Ino, u32, u32)> = self
+            .inodes
+            .values()
+            .filter(|inode| !detached.contains(&inode.ino))
+            .map(|inode| {
+                (
+                    inode.ino,
+                    inode.nlink,
+                    counts.get(&inode.ino).copied().unwrap_or(0),
+                )
+            })
+            .filter(|(_, nlink, count)| nlink != count)
+            .collect();
+        for (ino, nlink, count) in wrong {
+            if repair {
+                self.inode_mut(ino)?.nlink = count;
+            }
+            problems.push(Problem {
+                subject: self.subject(&paths, ino),
+                description: format!("link count is {}, should be {}", nlink, count),
+                repaired: repair,
+            });
+        }
+        Ok(())
+    }
+
+    /// Files of accounts that no longer exist go to the root account, like
+    /// the directories `disown` keeps
+    fn check_owners(
+        &mut self,
+        users: &[String],
+        repair: bool,
+        problems: &mut Vec<Problem>,
+    ) -> Result<(), VfsError> {
+        let paths = self.tree_paths();
+        let unknown: Vec<(Ino, String)> = self
+            .inodes
+            .values()
+            .filter(|inode| !users.contains(&inode.owner))
+            .map(|inode| (inode.ino, inode.owner.clone()))
+            .collect();
+        for (ino, owner) in unknown {
+            if repair {
+                let root_owner = self.root_owner.clone();
+                let inode = self.inode_mut(ino)?;
+                if inode.group == owner {
+                    inode.group = root_owner.clone();
+                }
+                inode.owner = root_owner;
+                inode.changed = clock::unix_now();
+            }
+            problems.push(Problem {
+                subject: self.subject(&paths, ino),
+                description: format!("owned by unknown user '{}'", owner),
+                repaired: repair,
+            });
+        }
+        Ok(())
+    }
+
+    /// Quotas of accounts that no longer exist are dropped, and grace
+    /// periods have to be running exactly while usage is over a soft limit
+    fn check_quotas(&mut self, users: &[String], repair: bool, problems: &mut Vec<Problem>) {
+        let now = clock::unix_now();
+        for (username, quota) in self.quotas.clone() {
+            let subject = format!("quota of {}", username);
+            if !users.contains(&username) {
+                if repair {
+                    self.quotas.remove(&username);
+                }
+                problems.push(Problem {
+                    subject,
+                    description: "user does not exist".to_string(),
+                    repaired: repair,
+                });
+                continue;
+            }
+
+            let usage = self.usage(&username);
+            let mut fixed = quota;
+            fixed.bytes.replace(&quota.bytes, usage.bytes, now);
+            fixed.inodes.replace(&quota.inodes, usage.inodes, now);
+            for (what, old, new) in [
+                ("bytes", quota.bytes, fixed.bytes),
+                ("files", quota.inodes, fixed.inodes),
+            ] {
+                let description = match (old.over_since, new.over_since) {
+                    (Some(_), None) => "grace period running while under the soft limit",
+                    (None, Some(_)) => "over the soft limit with no grace period running",
+                    _ => continue,
+                };
+                problems.push(Problem {
+                    subject: subject.clone(),
+                    description: format!("{}: {}", what, description),
+                    repaired: repair,
+                });
+            }
+            if repair && fixed != quota {
+                self.quotas.insert(username, fixed);
+            }
+        }
+    }
+}
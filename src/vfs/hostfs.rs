@@ -0,0 +1,181 @@
+use super::backend::{Attributes, Backend};
+use super::{perm, DirEntry, FileType, VfsError};
+use std::fs::{self, File, FileTimes, OpenOptions, Permissions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A directory on the host, for `mount --bind-host`. Nothing outside it
+/// can be reached, not even through symlinks; files that are neither
+/// regular files nor directories are left out.
+#[derive(Debug)]
+pub struct HostFs {
+    /// Canonical, so symlinks can be checked against it
+    root: PathBuf,
+}
+
+impl HostFs {
+    pub fn new(dir: &Path) -> Result<Self, VfsError> {
+        let root = fs::canonicalize(dir).map_err(host_error)?;
+        if !root.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        Ok(HostFs { root })
+    }
+
+    /// The host path for `path`, as long as it stays inside the root once
+    /// symlinks are followed. A missing file is checked by its parent.
+    fn host_path(&self, path: &str) -> Result<PathBuf, VfsError> {
+        let joined = self.root.join(path);
+        let resolved = match fs::canonicalize(&joined) {
+            Ok(resolved) => resolved,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let parent = joined.parent().unwrap_or(&self.root);
+                fs::canonicalize(parent).map_err(host_error)?
+            }
+            Err(e) => return Err(host_error(e)),
+        };
+        if resolved.starts_with(&self.root) {
+            Ok(joined)
+        } else {
+            Err(VfsError::PermissionDenied)
+        }
+    }
+
+    fn file(&self, path: &str, options: &OpenOptions) -> Result<File, VfsError> {
+        options.open(self.host_path(path)?).map_err(host_error)
+    }
+}
+
+impl Backend for HostFs {
+    fn kind(&self) -> &'static str {
+        "hostfs"
+    }
+
+    fn source(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    fn attributes(&self, path: &str) -> Result<Attributes, VfsError> {
+        let host = self.host_path(path)?;
+        let meta = fs::metadata(&host).map_err(host_error)?;
+        let (file_type, size) = if meta.is_dir() {
+            let entries = fs::read_dir(&host).map(|d| d.count()).unwrap_or(0);
+            (FileType::Directory, entries as u64)
+        } else if meta.is_file() {
+            (FileType::Regular, meta.len())
+        } else {
+            return Err(VfsError::InvalidArgument);
+        };
+        let seconds = |t: i64| t.max(0) as u64;
+        let created = meta
+            .created()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(seconds(meta.ctime()), |d| d.as_secs());
+        Ok(Attributes {
+            ino: meta.ino(),
+            file_type,
+            size,
+            mode: (meta.mode() as u16) & perm::MODE_MASK,
+            created,
+            modified: seconds(meta.mtime()),
+            accessed: seconds(meta.atime()),
+            changed: seconds(meta.ctime()),
+        })
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, VfsError> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(self.host_path(path)?).map_err(host_error)? {
+            let entry = entry.map_err(host_error)?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let child = if path.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", path, name)
+            };
+            // Follows symlinks, and skips those leading outside
+            if let Ok(attributes) = self.attributes(&child) {
+                entries.push(DirEntry {
+                    name,
+                    file_type: attributes.file_type,
+                });
+            }
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    fn read_at(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>, VfsError> {
+        let mut file = self.file(path, OpenOptions::new().read(true))?;
+        file.seek(SeekFrom::Start(offset)).map_err(host_error)?;
+        let mut data = Vec::new();
+        file.take(len as u64)
+            .read_to_end(&mut data)
+            .map_err(host_error)?;
+        Ok(data)
+    }
+
+    fn write_at(&mut self, path: &str, offset: u64, data: &[u8]) -> Result<(), VfsError> {
+        let mut file = self.file(path, OpenOptions::new().write(true))?;
+        file.seek(SeekFrom::Start(offset)).map_err(host_error)?;
+        file.write_all(data).map_err(host_error)
+    }
+
+    fn create(&mut self, path: &str) -> Result<(), VfsError> {
+        self.file(path, OpenOptions::new().write(true).create_new(true))
+            .map(|_| ())
+    }
+
+    fn truncate(&mut self, path: &str) -> Result<(), VfsError> {
+        self.file(path, OpenOptions::new().write(true).truncate(true))
+            .map(|_| ())
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<(), VfsError> {
+        fs::create_dir(self.host_path(path)?).map_err(host_error)
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), VfsError> {
+        fs::remove_file(self.host_path(path)?).map_err(host_error)
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), VfsError> {
+        fs::remove_dir(self.host_path(path)?).map_err(host_error)
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), VfsError> {
+        fs::rename(self.host_path(from)?, self.host_path(to)?).map_err(host_error)
+    }
+
+    fn set_mode(&mut self, path: &str, mode: u16) -> Result<(), VfsError> {
+        let permissions = Permissions::from_mode(u32::from(mode));
+        fs::set_permissions(self.host_path(path)?, permissions).map_err(host_error)
+    }
+
+    fn touch(&mut self, path: &str) -> Result<(), VfsError> {
+        let now = SystemTime::now();
+        let file = self.file(path, OpenOptions::new().read(true))?;
+        file.set_times(FileTimes::new().set_accessed(now).set_modified(now))
+            .map_err(host_error)
+    }
+}
+
+/// The `VfsError` for what the host said
+fn host_error(e: io::Error) -> VfsError {
+    match e.raw_os_error() {
+        Some(libc::ENOENT) => VfsError::NotFound,
+        Some(libc::EEXIST) => VfsError::AlreadyExists,
+        Some(libc::ENOTDIR) => VfsError::NotADirectory,
+        Some(libc::EISDIR) => VfsError::IsADirectory,
+        Some(libc::ENOTEMPTY) => VfsError::DirectoryNotEmpty,
+        Some(libc::EACCES | libc::EPERM) => VfsError::PermissionDenied,
+        Some(libc::EROFS) => VfsError::ReadOnly,
+        Some(libc::EXDEV) => VfsError::CrossDevice,
+        _ => VfsError::Io(e.to_string()),
+    }
+}
@@ -0,0 +1,280 @@
+use super::inode::{FileType, Ino, Inode, InodeData, ROOT_INO};
+use super::perm::{DEFAULT_DIR_MODE, DEFAULT_FILE_MODE};
+use super::quota::{Limit, Quota, DEFAULT_GRACE_PERIOD};
+use super::VfsError;
+use std::collections::BTreeMap;
+
+// Image layout, all integers little-endian:
+//
+//   magic "MKFS", version u32, next inode number u64, inode count u64
+//   then per inode:
+//     ino u64, type u8 (0 file, 1 directory), nlink u32, mode u16,
+//     owner and group as length u32 + bytes,
+//     created, modified, accessed, changed u64
+//     file:      length u64, bytes
+//     directory: entry count u64, then per entry name length u32, name, ino u64
+//   then the quotas: grace period u64, quota count u64, and per user
+//     name as length u32 + bytes,
+//     for bytes and then inodes: soft u64, hard u64, over since u64 (0 if not)
+//
+// Version 1 images had no mode, owner or group; everything in them is
+// given to the root account when they're loaded. Version 2 had no quotas.
+
+const MAGIC: &[u8; 4] = b"MKFS";
+pub(super) const VERSION: u32 = 3;
+
+const TYPE_REGULAR: u8 = 0;
+const TYPE_DIRECTORY: u8 = 1;
+
+pub fn encode(
+    inodes: &BTreeMap<Ino, Inode>,
+    next_ino: Ino,
+    quotas: &BTreeMap<String, Quota>,
+    grace_period: u64,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&next_ino.to_le_bytes());
+    out.extend_from_slice(&(inodes.len() as u64).to_le_bytes());
+
+    for inode in inodes.values() {
+        encode_inode(&mut out, inode);
+    }
+    encode_quotas(&mut out, quotas, grace_period);
+    out
+}
+
+pub(super) fn encode_inode(out: &mut Vec<u8>, inode: &Inode) {
+    out.extend_from_slice(&inode.ino.to_le_bytes());
+    out.push(match inode.file_type() {
+        FileType::Regular => TYPE_REGULAR,
+        FileType::Directory => TYPE_DIRECTORY,
+    });
+    out.extend_from_slice(&inode.nlink.to_le_bytes());
+    out.extend_from_slice(&inode.mode.to_le_bytes());
+    for name in [&inode.owner, &inode.group] {
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+    }
+    for time in [inode.created, inode.modified, inode.accessed, inode.changed] {
+        out.extend_from_slice(&time.to_le_bytes());
+    }
+    match &inode.data {
+        InodeData::Regular(bytes) => {
+            out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            out.extend_from_slice(bytes);
+        }
+        InodeData::Directory(entries) => {
+            out.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+            for (name, ino) in entries {
+                out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+                out.extend_from_slice(name.as_bytes());
+                out.extend_from_slice(&ino.to_le_bytes());
+            }
+        }
+    }
+}
+
+pub(super) fn encode_quotas(out: &mut Vec<u8>, quotas: &BTreeMap<String, Quota>, grace_period: u64) {
+    out.extend_from_slice(&grace_period.to_le_bytes());
+    out.extend_from_slice(&(quotas.len() as u64).to_le_bytes());
+    for (name, quota) in quotas {
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        for limit in [quota.bytes, quota.inodes] {
+            for value in [limit.soft, limit.hard, limit.over_since.unwrap_or(0)] {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+}
+
+pub(super) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], VfsError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| VfsError::Corrupt("unexpected end of image".into()))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, VfsError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, VfsError> {
+        let mut buf = [0; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn u32(&mut self) -> Result<u32, VfsError> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    pub(super) fn u64(&mut self) -> Result<u64, VfsError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    /// A length that has to fit in what's left of the image
+    fn len(&mut self, len: u64) -> Result<usize, VfsError> {
+        usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= self.bytes.len() - self.pos)
+            .ok_or_else(|| VfsError::Corrupt("length past end of image".into()))
+    }
+
+    fn string(&mut self) -> Result<String, VfsError> {
+        let len = self.u32()?;
+        let len = self.len(len as u64)?;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| VfsError::Corrupt("name is not UTF-8".into()))
+    }
+}
+
+/// A parsed image
+pub struct Decoded {
+    pub inodes: BTreeMap<Ino, Inode>,
+    pub next_ino: Ino,
+    pub quotas: BTreeMap<String, Quota>,
+    pub grace_period: u64,
+    /// Written before images recorded modes and owners
+    pub legacy: bool,
+}
+
+/// Parses an image and checks that every directory entry points at an
+/// existing inode. `root_owner` owns everything in old images that didn't
+/// record owners.
+pub fn decode(bytes: &[u8], root_owner: &str) -> Result<Decoded, VfsError> {
+    let mut r = Reader::new(bytes);
+    if r.take(4)? != MAGIC {
+        return Err(VfsError::Corrupt("not a MiniKern filesystem image".into()));
+    }
+    let version = r.u32()?;
+    if !(1..=VERSION).contains(&version) {
+        return Err(VfsError::Corrupt(format!("unsupported image version {}", version)));
+    }
+    let next_ino = r.u64()?;
+    let count = r.u64()?;
+
+    let mut inodes = BTreeMap::new();
+    for _ in 0..count {
+        let inode = decode_inode(&mut r, version, root_owner)?;
+        inodes.insert(inode.ino, inode);
+    }
+
+    match inodes.get(&ROOT_INO).map(Inode::file_type) {
+        Some(FileType::Directory) => {}
+        _ => return Err(VfsError::Corrupt("root directory is missing".into())),
+    }
+    for inode in inodes.values() {
+        if inode.ino >= next_ino {
+            return Err(VfsError::Corrupt(format!("inode {} is out of range", inode.ino)));
+        }
+        if let InodeData::Directory(entries) = &inode.data {
+            if let Some((name, _)) = entries.iter().find(|(_, ino)| !inodes.contains_key(ino)) {
+                return Err(VfsError::Corrupt(format!("dangling entry '{}'", name)));
+            }
+        }
+    }
+
+    let (quotas, grace_period) = if version >= 3 {
+        decode_quotas(&mut r)?
+    } else {
+        (BTreeMap::new(), DEFAULT_GRACE_PERIOD)
+    };
+    Ok(Decoded {
+        inodes,
+        next_ino,
+        quotas,
+        grace_period,
+        legacy: version == 1,
+    })
+}
+
+/// One inode as written by `encode_inode`, in an image of `version`
+pub(super) fn decode_inode(r: &mut Reader, version: u32, root_owner: &str) -> Result<Inode, VfsError> {
+    let ino = r.u64()?;
+    let file_type = r.u8()?;
+    let nlink = r.u32()?;
+    let (mode, owner, group) = if version >= 2 {
+        (Some(r.u16()?), r.string()?, r.string()?)
+    } else {
+        (None, root_owner.to_string(), root_owner.to_string())
+    };
+    let (created, modified, accessed, changed) = (r.u64()?, r.u64()?, r.u64()?, r.u64()?);
+    let data = match file_type {
+        TYPE_REGULAR => {
+            let len = r.u64()?;
+            let len = r.len(len)?;
+            InodeData::Regular(r.take(len)?.to_vec())
+        }
+        TYPE_DIRECTORY => {
+            let mut entries = BTreeMap::new();
+            for _ in 0..r.u64()? {
+                let name = r.string()?;
+                entries.insert(name, r.u64()?);
+            }
+            InodeData::Directory(entries)
+        }
+        other => {
+            return Err(VfsError::Corrupt(format!(
+                "inode {} has unknown type {}",
+                ino, other
+            )))
+        }
+    };
+    let mode = mode.unwrap_or(match data {
+        InodeData::Regular(_) => DEFAULT_FILE_MODE,
+        InodeData::Directory(_) => DEFAULT_DIR_MODE,
+    });
+    Ok(Inode {
+        ino,
+        data,
+        owner,
+        group,
+        mode,
+        nlink,
+        created,
+        modified,
+        accessed,
+        changed,
+    })
+}
+
+/// The quotas and grace period written by `encode_quotas`
+pub(super) fn decode_quotas(r: &mut Reader) -> Result<(BTreeMap<String, Quota>, u64), VfsError> {
+    let grace_period = r.u64()?;
+    let mut quotas = BTreeMap::new();
+    for _ in 0..r.u64()? {
+        let name = r.string()?;
+        let mut limit = || -> Result<Limit, VfsError> {
+            let (soft, hard, over_since) = (r.u64()?, r.u64()?, r.u64()?);
+            Ok(Limit {
+                soft,
+                hard,
+                over_since: (over_since != 0).then_some(over_since),
+            })
+        };
+        let (bytes, inodes) = (limit()?, limit()?);
+        quotas.insert(name, Quota { bytes, inodes });
+    }
+    Ok((quotas, grace_period))
+}
@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+
+pub type Ino = u64;
+
+/// Inode number of the root directory
+pub const ROOT_INO: Ino = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Regular,
+    Directory,
+}
+
+#[derive(Debug, Clone)]
+pub enum InodeData {
+    Regular(Vec<u8>),
+    /// Entry name to inode number. `.` and `..` aren't stored.
+    Directory(BTreeMap<String, Ino>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Inode {
+    pub ino: Ino,
+    pub data: InodeData,
+    /// Username of the owning account, and the owning group
+    pub owner: String,
+    pub group: String,
+    /// Permission bits, e.g. `0o755`
+    pub mode: u16,
+    /// Number of directory entries pointing at this inode
+    pub nlink: u32,
+    /// Unix timestamps of creation, last content change, last access and
+    /// last metadata change
+    pub created: u64,
+    pub modified: u64,
+    pub accessed: u64,
+    pub changed: u64,
+}
+
+impl Inode {
+    pub fn new(ino: Ino, file_type: FileType, owner: &str, mode: u16, now: u64) -> Self {
+        Inode {
+            ino,
+            data: match file_type {
+                FileType::Regular => InodeData::Regular(Vec::new()),
+                FileType::Directory => InodeData::Directory(BTreeMap::new()),
+            },
+            owner: owner.to_string(),
+            // New files belong to their owner's private group
+            group: owner.to_string(),
+            mode,
+            nlink: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+            changed: now,
+        }
+    }
+
+    pub fn file_type(&self) -> FileType {
+        match self.data {
+            InodeData::Regular(_) => FileType::Regular,
+            InodeData::Directory(_) => FileType::Directory,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        match &self.data {
+            InodeData::Regular(bytes) => bytes.len() as u64,
+            InodeData::Directory(entries) => entries.len() as u64,
+        }
+    }
+}
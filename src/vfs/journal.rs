@@ -0,0 +1,244 @@
+use super::image::{self, Reader};
+use super::inode::{Ino, Inode};
+use super::quota::Quota;
+use super::VfsError;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+// Journal layout, all integers little-endian: records one after another,
+// each
+//
+//   magic "MKJR", payload length u64, payload, checksum u64 (FNV-1a of the
+//   payload)
+//
+// and each payload
+//
+//   next inode number u64, the quotas as in the image,
+//   written inode count u64, then the inodes as in the image,
+//   freed inode count u64, then their numbers u64
+//
+// A record only counts once all of it, checksum included, is on disk. A
+// crash while one is being appended leaves a torn record at the end, which
+// is dropped at the next boot along with everything after it.
+
+const MAGIC: &[u8; 4] = b"MKJR";
+
+/// The journal kept next to an image, e.g. `minikern.img.journal`
+pub fn path_for(image_path: &Path) -> PathBuf {
+    let mut path = image_path.as_os_str().to_owned();
+    path.push(".journal");
+    PathBuf::from(path)
+}
+
+/// What changed in the filesystem between two syncs
+pub struct Record {
+    pub next_ino: Ino,
+    pub quotas: BTreeMap<String, Quota>,
+    pub grace_period: u64,
+    /// Inodes as they are now, contents included
+    pub written: Vec<Inode>,
+    pub freed: Vec<Ino>,
+}
+
+pub fn encode(
+    next_ino: Ino,
+    quotas: &BTreeMap<String, Quota>,
+    grace_period: u64,
+    written: &[&Inode],
+    freed: &[Ino],
+) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&next_ino.to_le_bytes());
+    image::encode_quotas(&mut payload, quotas, grace_period);
+    payload.extend_from_slice(&(written.len() as u64).to_le_bytes());
+    for inode in written {
+        image::encode_inode(&mut payload, inode);
+    }
+    payload.extend_from_slice(&(freed.len() as u64).to_le_bytes());
+    for ino in freed {
+        payload.extend_from_slice(&ino.to_le_bytes());
+    }
+
+    let mut out = Vec::with_capacity(payload.len() + 20);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    out.extend_from_slice(&payload);
+    out.extend_from_slice(&checksum(&payload).to_le_bytes());
+    out
+}
+
+/// Adds an encoded record to the end of the journal, and waits for it to
+/// reach the disk
+pub fn append(path: &Path, record: &[u8]) -> Result<(), VfsError> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(record)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// The complete records in the journal, oldest first, and the journal's
+/// length in bytes. A missing journal has no records.
+pub fn read(path: &Path) -> Result<(Vec<Record>, u64), VfsError> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(e.into()),
+    };
+    let mut records = Vec::new();
+    let mut rest = bytes.as_slice();
+    while let Some((payload, after)) = next_payload(rest) {
+        records.push(decode(payload)?);
+        rest = after;
+    }
+    Ok((records, bytes.len() as u64))
+}
+
+/// Empties the journal, once everything in it is in the image
+pub fn clear(path: &Path) -> Result<(), VfsError> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Splits the first record's payload off `bytes`, if the whole record is
+/// there and its checksum matches
+fn next_payload(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let rest = bytes.strip_prefix(MAGIC)?;
+    let (len, rest) = rest.split_first_chunk::<8>()?;
+    let len = usize::try_from(u64::from_le_bytes(*len)).ok()?;
+    if rest.len() < len {
+        return None;
+    }
+    let (payload, rest) = rest.split_at(len);
+    let (sum, rest) = rest.split_first_chunk::<8>()?;
+    (u64::from_le_bytes(*sum) == checksum(payload)).then_some((payload, rest))
+}
+
+fn decode(payload: &[u8]) -> Result<Record, VfsError> {
+    let mut r = Reader::new(payload);
+    let next_ino = r.u64()?;
+    let (quotas, grace_period) = image::decode_quotas(&mut r)?;
+    let mut written = Vec::new();
+    for _ in 0..r.u64()? {
+        written.push(image::decode_inode(&mut r, image::VERSION, "")?);
+    }
+    let mut freed = Vec::new();
+    for _ in 0..r.u64()? {
+        freed.push(r.u64()?);
+    }
+    Ok(Record {
+        next_ino,
+        quotas,
+        grace_period,
+        written,
+        freed,
+    })
+}
+
+/// 64-bit FNV-1a
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::inode::{FileType, InodeData};
+    use crate::vfs::quota::Limit;
+
+    /// A journal of the test's own on the host, removed first
+    fn journal(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("minikern-journal-{}-{}", std::process::id(), name));
+        clear(&path).unwrap();
+        path
+    }
+
+    fn file(ino: Ino, data: &[u8]) -> Inode {
+        let mut inode = Inode::new(ino, FileType::Regular, "alice", 0o640, 1000);
+        inode.data = InodeData::Regular(data.to_vec());
+        inode
+    }
+
+    /// Three records: writing inode 7, freeing it, then writing inode 8
+    fn records() -> [Vec<u8>; 3] {
+        let quotas = BTreeMap::from([(
+            "alice".to_string(),
+            Quota {
+                bytes: Limit::new(10, 20),
+                ..Quota::default()
+            },
+        )]);
+        [
+            encode(8, &BTreeMap::new(), 60, &[&file(7, b"first")], &[]),
+            encode(8, &quotas, 90, &[], &[7]),
+            encode(9, &quotas, 90, &[&file(8, b"second")], &[]),
+        ]
+    }
+
+    #[test]
+    fn records_read_back_in_order() {
+        let path = journal("order");
+        for record in records() {
+            append(&path, &record).unwrap();
+        }
+
+        let (read_back, len) = read(&path).unwrap();
+        assert_eq!(len, records().iter().map(|r| r.len() as u64).sum());
+        assert_eq!(read_back.len(), 3);
+        let [first, second, third] = &read_back[..] else {
+            unreachable!()
+        };
+        assert_eq!((first.next_ino, first.grace_period), (8, 60));
+        assert_eq!(first.written.len(), 1);
+        assert!(matches!(&first.written[0].data, InodeData::Regular(d) if d == b"first"));
+        assert_eq!(first.written[0].owner, "alice");
+        assert!(first.quotas.is_empty());
+        assert_eq!(second.freed, [7]);
+        assert_eq!(second.quotas["alice"].bytes.hard, 20);
+        assert_eq!(third.next_ino, 9);
+        clear(&path).unwrap();
+    }
+
+    #[test]
+    fn a_torn_record_at_the_end_is_dropped() {
+        let path = journal("torn");
+        let [first, second, _] = records();
+        for cut in 0..second.len() {
+            let mut bytes = first.clone();
+            bytes.extend_from_slice(&second[..cut]);
+            std::fs::write(&path, &bytes).unwrap();
+            let (read_back, len) = read(&path).unwrap();
+            assert_eq!(read_back.len(), 1, "cut at {}", cut);
+            assert_eq!(len, bytes.len() as u64);
+        }
+        clear(&path).unwrap();
+    }
+
+    #[test]
+    fn a_damaged_record_ends_the_journal() {
+        let path = journal("damaged");
+        let [first, second, third] = records();
+        // A flipped byte in the payload, then in the checksum itself
+        for at in [20, second.len() - 1] {
+            let mut damaged = second.clone();
+            damaged[at] ^= 0x40;
+            let bytes = [first.clone(), damaged, third.clone()].concat();
+            std::fs::write(&path, bytes).unwrap();
+            assert_eq!(read(&path).unwrap().0.len(), 1, "flipped byte {}", at);
+        }
+        clear(&path).unwrap();
+    }
+
+    #[test]
+    fn a_missing_journal_has_no_records() {
+        let (records, len) = read(&journal("missing")).unwrap();
+        assert!(records.is_empty());
+        assert_eq!(len, 0);
+    }
+}
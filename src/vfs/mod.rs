@@ -0,0 +1,1444 @@
+pub mod backend;
+mod fsck;
+pub mod hostfs;
+mod image;
+mod inode;
+mod journal;
+mod mount;
+pub mod path;
+pub mod perm;
+pub mod quota;
+
+pub use inode::{FileType, Ino};
+pub use mount::MountOptions;
+
+use crate::auth::CurrentUser;
+use crate::clock;
+use inode::{Inode, InodeData, ROOT_INO};
+use mount::{Mount, MountPath};
+use quota::{Quota, Usage};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// Disk image holding the whole virtual filesystem, next to users.xml
+pub const IMAGE_FILE_PATH: &str = "minikern.img";
+
+/// Size the journal may grow to before the next sync writes a checkpoint
+const JOURNAL_LIMIT: u64 = 1 << 20;
+
+/// Directories every filesystem starts with, and their modes
+const BASE_DIRS: &[(&str, u16)] = &[
+    ("/etc", 0o755),
+    ("/etc/skel", 0o755),
+    ("/home", 0o755),
+    ("/mnt", 0o755),
+    ("/tmp", 0o1777),
+];
+
+#[derive(Debug)]
+pub enum VfsError {
+    NotFound,
+    AlreadyExists,
+    NotADirectory,
+    IsADirectory,
+    DirectoryNotEmpty,
+    /// Empty names, `.`/`..` as a target, moving a directory into itself...
+    InvalidArgument,
+    BadDescriptor,
+    /// The permission bits don't allow it
+    PermissionDenied,
+    /// Only the owner or an admin may do it (chmod, chown, ...)
+    NotPermitted,
+    /// The owner's hard limit, or soft limit after the grace period
+    QuotaExceeded,
+    /// The path is in a filesystem mounted read-only
+    ReadOnly,
+    /// A mount point, or a file in a mount that is in use
+    Busy,
+    /// Moving between two mounted filesystems
+    CrossDevice,
+    /// The disk image couldn't be read or written
+    Io(String),
+    /// The disk image isn't a valid MiniKern image
+    Corrupt(String),
+    /// Another MiniKern process has the disk image mounted, the one with
+    /// this PID if it could be told
+    Locked(Option<u32>),
+}
+
+impl fmt::Display for VfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VfsError::NotFound => write!(f, "No such file or directory"),
+            VfsError::AlreadyExists => write!(f, "File exists"),
+            VfsError::NotADirectory => write!(f, "Not a directory"),
+            VfsError::IsADirectory => write!(f, "Is a directory"),
+            VfsError::DirectoryNotEmpty => write!(f, "Directory not empty"),
+            VfsError::InvalidArgument => write!(f, "Invalid argument"),
+            VfsError::BadDescriptor => write!(f, "Bad file descriptor"),
+            VfsError::PermissionDenied => write!(f, "Permission denied"),
+            VfsError::NotPermitted => write!(f, "Operation not permitted"),
+            VfsError::QuotaExceeded => write!(f, "Disk quota exceeded"),
+            VfsError::ReadOnly => write!(f, "Read-only file system"),
+            VfsError::Busy => write!(f, "Device or resource busy"),
+            VfsError::CrossDevice => write!(f, "Invalid cross-device link"),
+            VfsError::Io(msg) => write!(f, "I/O error: {}", msg),
+            VfsError::Corrupt(msg) => write!(f, "Corrupt filesystem image: {}", msg),
+            VfsError::Locked(Some(pid)) => {
+                write!(f, "Image in use by another MiniKern process (pid {})", pid)
+            }
+            VfsError::Locked(None) => write!(f, "Image in use by another MiniKern process"),
+        }
+    }
+}
+
+impl std::error::Error for VfsError {}
+
+impl From<std::io::Error> for VfsError {
+    fn from(e: std::io::Error) -> Self {
+        VfsError::Io(e.to_string())
+    }
+}
+
+pub type Fd = u32;
+
+/// How a file is opened. `WRITE` creates the file if needed and truncates
+/// it; with `append` set, writes always go to the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenFlags {
+    pub read: bool,
+    pub write: bool,
+    pub create: bool,
+    pub truncate: bool,
+    pub append: bool,
+}
+
+impl OpenFlags {
+    pub const READ: OpenFlags = OpenFlags {
+        read: true,
+        write: false,
+        create: false,
+        truncate: false,
+        append: false,
+    };
+    pub const WRITE: OpenFlags = OpenFlags {
+        read: false,
+        write: true,
+        create: true,
+        truncate: true,
+        append: false,
+    };
+}
+
+/// What a descriptor refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Inode(Ino),
+    Mounted(MountPath),
+}
+
+#[derive(Debug)]
+struct OpenFile {
+    node: Node,
+    offset: usize,
+    flags: OpenFlags,
+}
+
+/// What `stat` reports about a file or directory
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub ino: Ino,
+    pub file_type: FileType,
+    /// Bytes for files, number of entries for directories
+    pub size: u64,
+    pub nlink: u32,
+    pub owner: String,
+    pub group: String,
+    pub mode: u16,
+    pub created: u64,
+    pub modified: u64,
+    pub accessed: u64,
+    pub changed: u64,
+}
+
+impl Metadata {
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FileType::Directory
+    }
+
+    /// `ls -l` style type and permissions, e.g. `drwxr-xr-x`
+    pub fn mode_string(&self) -> String {
+        perm::mode_string(self.file_type, self.mode)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub file_type: FileType,
+}
+
+/// An in-memory filesystem of inodes, saved to a single disk image.
+/// Paths given to it are absolute; commands resolve relative paths against
+/// the session's working directory with `path::resolve` first. Every
+/// operation is checked against the permissions of the user doing it, with
+/// admins allowed everything, like root.
+#[derive(Debug)]
+pub struct Vfs {
+    inodes: BTreeMap<Ino, Inode>,
+    next_ino: Ino,
+    open_files: BTreeMap<Fd, OpenFile>,
+    next_fd: Fd,
+    image_path: PathBuf,
+    /// Username of the root account, which owns the system directories
+    root_owner: String,
+    quotas: BTreeMap<String, Quota>,
+    /// Seconds usage may stay over a soft limit
+    grace_period: u64,
+    /// Other filesystems mounted into the tree, by mount point. They only
+    /// last until MiniKern exits.
+    mounts: BTreeMap<String, Mount>,
+    /// Whether there are changes that haven't been saved yet. Access times
+    /// alone don't count; they're saved with the next change.
+    dirty: bool,
+    /// Inodes changed or freed since the last sync, for the next journal
+    /// record
+    changed: BTreeSet<Ino>,
+    /// Bytes in the journal since the image was last written
+    journaled: u64,
+}
+
+impl Vfs {
+    /// An empty filesystem with just the base directories, owned by the
+    /// root account
+    pub fn new(image_path: &Path, root_owner: &str) -> Self {
+        let now = clock::unix_now();
+        let mut root = Inode::new(ROOT_INO, FileType::Directory, root_owner, perm::DEFAULT_DIR_MODE, now);
+        root.nlink = 1;
+        let mut vfs = Vfs {
+            inodes: BTreeMap::from([(ROOT_INO, root)]),
+            next_ino: ROOT_INO + 1,
+            open_files: BTreeMap::new(),
+            next_fd: 3,
+            image_path: image_path.to_path_buf(),
+            root_owner: root_owner.to_string(),
+            quotas: BTreeMap::new(),
+            grace_period: quota::DEFAULT_GRACE_PERIOD,
+            mounts: BTreeMap::new(),
+            dirty: true,
+            changed: BTreeSet::new(),
+            journaled: 0,
+        };
+        let root_user = vfs.root_user();
+        for (dir, mode) in BASE_DIRS {
+            // Can't fail on a fresh filesystem
+            let _ = vfs.mkdir(&root_user, dir);
+            let _ = vfs.chmod(&root_user, dir, *mode);
+        }
+        vfs
+    }
+
+    /// Loads the filesystem from its disk image, or creates a fresh one if
+    /// there's no image yet.
+    pub fn load(image_path: &Path, root_owner: &str) -> Result<Self, VfsError> {
+        let bytes = match std::fs::read(image_path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                // A journal without its image has nothing to apply to
+                journal::clear(&journal::path_for(image_path))?;
+                return Ok(Vfs::new(image_path, root_owner));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let decoded = image::decode(&bytes, root_owner)?;
+        let mut vfs = Vfs {
+            inodes: decoded.inodes,
+            next_ino: decoded.next_ino,
+            open_files: BTreeMap::new(),
+            next_fd: 3,
+            image_path: image_path.to_path_buf(),
+            root_owner: root_owner.to_string(),
+            quotas: decoded.quotas,
+            grace_period: decoded.grace_period,
+            mounts: BTreeMap::new(),
+            dirty: false,
+            changed: BTreeSet::new(),
+            journaled: 0,
+        };
+        if decoded.legacy {
+            vfs.upgrade_legacy();
+        }
+        Ok(vfs)
+    }
+
+    /// Old images left everything to root. Give the base directories their
+    /// usual modes, and each home directory to the user it's named after.
+    fn upgrade_legacy(&mut self) {
+        let root_user = self.root_user();
+        for (dir, mode) in BASE_DIRS {
+            let _ = self.chmod(&root_user, dir, *mode);
+        }
+        let homes = self.lookup(None, "/home").and_then(|ino| Ok(self.entries(ino)?.clone()));
+        for (name, ino) in homes.unwrap_or_default() {
+            self.give_tree(ino, &name);
+        }
+        self.dirty = true;
+    }
+
+    /// Applies the records in the journal, which hold whatever was synced
+    /// after the image was last written. Returns how many there were.
+    fn replay(&mut self) -> Result<usize, VfsError> {
+        let (records, len) = journal::read(&journal::path_for(&self.image_path))?;
+        for record in &records {
+            self.next_ino = record.next_ino;
+            self.quotas = record.quotas.clone();
+            self.grace_period = record.grace_period;
+            for inode in &record.written {
+                self.inodes.insert(inode.ino, inode.clone());
+            }
+            for ino in &record.freed {
+                self.inodes.remove(ino);
+            }
+        }
+        self.journaled = len;
+        self.dirty |= !records.is_empty();
+        Ok(records.len())
+    }
+
+    fn give_tree(&mut self, ino: Ino, username: &str) {
+        let children: Vec<Ino> = match self.inodes.get_mut(&ino) {
+            Some(inode) => {
+                self.changed.insert(ino);
+                inode.owner = username.to_string();
+                inode.group = username.to_string();
+                match &inode.data {
+                    InodeData::Directory(entries) => entries.values().copied().collect(),
+                    InodeData::Regular(_) => Vec::new(),
+                }
+            }
+            None => return,
+        };
+        for child in children {
+            self.give_tree(child, username);
+        }
+    }
+
+    /// Saves anything that changed since the last sync as one record at
+    /// the end of the journal, so a crash keeps either all of it or none.
+    /// The image itself is only rewritten by a checkpoint, once the journal
+    /// has grown past `JOURNAL_LIMIT`.
+    pub fn sync(&mut self) -> Result<(), VfsError> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if self.journaled >= JOURNAL_LIMIT || !self.image_path.exists() {
+            return self.checkpoint();
+        }
+        let written: Vec<&Inode> = self
+            .changed
+            .iter()
+            .filter_map(|ino| self.inodes.get(ino))
+            .collect();
+        let freed: Vec<Ino> = self
+            .changed
+            .iter()
+            .copied()
+            .filter(|ino| !self.inodes.contains_key(ino))
+            .collect();
+        let record = journal::encode(self.next_ino, &self.quotas, self.grace_period, &written, &freed);
+        journal::append(&journal::path_for(&self.image_path), &record)?;
+        self.journaled += record.len() as u64;
+        self.changed.clear();
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Writes the whole image and empties the journal. The new image
+    /// replaces the old one in a single rename, so a crash leaves one or
+    /// the other, and the journal is only emptied once it's on disk.
+    pub fn checkpoint(&mut self) -> Result<(), VfsError> {
+        if !self.dirty && self.journaled == 0 {
+            return Ok(());
+        }
+        let mut tmp = self.image_path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(&image::encode(&self.inodes, self.next_ino, &self.quotas, self.grace_period))?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.image_path)?;
+        journal::clear(&journal::path_for(&self.image_path))?;
+        self.journaled = 0;
+        self.changed.clear();
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// The root account, for work the system does on its own behalf
+    pub fn root_user(&self) -> CurrentUser {
+        CurrentUser {
+            username: self.root_owner.clone(),
+            is_admin: true,
+        }
+    }
+
+    fn inode(&self, ino: Ino) -> Result<&Inode, VfsError> {
+        self.inodes
+            .get(&ino)
+            .ok_or_else(|| VfsError::Corrupt(format!("missing inode {}", ino)))
+    }
+
+    /// An inode to change, which goes in the next journal record
+    fn inode_mut(&mut self, ino: Ino) -> Result<&mut Inode, VfsError> {
+        let inode = self
+            .inodes
+            .get_mut(&ino)
+            .ok_or_else(|| VfsError::Corrupt(format!("missing inode {}", ino)))?;
+        self.changed.insert(ino);
+        Ok(inode)
+    }
+
+    fn entries(&self, ino: Ino) -> Result<&BTreeMap<String, Ino>, VfsError> {
+        match &self.inode(ino)?.data {
+            InodeData::Directory(entries) => Ok(entries),
+            InodeData::Regular(_) => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn entries_mut(&mut self, ino: Ino) -> Result<&mut BTreeMap<String, Ino>, VfsError> {
+        match &mut self.inode_mut(ino)?.data {
+            InodeData::Directory(entries) => Ok(entries),
+            InodeData::Regular(_) => Err(VfsError::NotADirectory),
+        }
+    }
+
+    /// Groups listed in `/etc/group`
+    pub fn groups(&self) -> Vec<(String, Vec<String>)> {
+        let contents = self
+            .lookup(None, perm::GROUP_FILE_PATH)
+            .and_then(|ino| self.inode(ino));
+        match contents.map(|inode| &inode.data) {
+            Ok(InodeData::Regular(bytes)) => perm::parse_groups(&String::from_utf8_lossy(bytes)),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether a user is in a group, counting their private group
+    pub fn in_group(&self, username: &str, group: &str) -> bool {
+        username == group
+            || self
+                .groups()
+                .iter()
+                .any(|(name, members)| name == group && members.iter().any(|m| m == username))
+    }
+
+    fn permits(&self, who: &CurrentUser, inode: &Inode, want: u16) -> bool {
+        self.permits_as(who, &inode.owner, &inode.group, inode.mode, want)
+    }
+
+    fn permits_as(&self, who: &CurrentUser, owner: &str, group: &str, mode: u16, want: u16) -> bool {
+        if who.is_admin {
+            return true;
+        }
+        let shift = if owner == who.username {
+            6
+        } else if self.in_group(&who.username, group) {
+            3
+        } else {
+            0
+        };
+        (mode >> shift) & want == want
+    }
+
+    fn check(&self, who: &CurrentUser, ino: Ino, want: u16) -> Result<(), VfsError> {
+        if self.permits(who, self.inode(ino)?, want) {
+            Ok(())
+        } else {
+            Err(VfsError::PermissionDenied)
+        }
+    }
+
+    /// Finds the inode a path points at. With a user, every directory on
+    /// the way has to be searchable by them; `None` is the system itself.
+    fn lookup(&self, who: Option<&CurrentUser>, path: &str) -> Result<Ino, VfsError> {
+        let path = path::resolve("/", path);
+        let mut ino = ROOT_INO;
+        for name in path::components(&path) {
+            let next = *self.entries(ino)?.get(name).ok_or(VfsError::NotFound)?;
+            if let Some(who) = who {
+                self.check(who, ino, perm::EXEC)?;
+            }
+            ino = next;
+        }
+        Ok(ino)
+    }
+
+    /// Looks up the directory a new entry would go in, and the entry's name
+    fn parent_of(&self, who: &CurrentUser, path: &str) -> Result<(Ino, String), VfsError> {
+        let path = path::resolve("/", path);
+        let (parent, name) = path::split_parent(&path).ok_or(VfsError::InvalidArgument)?;
+        if !path::is_valid_name(&name) {
+            return Err(VfsError::InvalidArgument);
+        }
+        let parent = self.lookup(Some(who), &parent)?;
+        self.entries(parent)?;
+        Ok((parent, name))
+    }
+
+    /// Adding an entry needs write and search permission on the directory
+    fn check_create(&self, who: &CurrentUser, parent: Ino) -> Result<(), VfsError> {
+        self.check(who, parent, perm::WRITE | perm::EXEC)
+    }
+
+    /// Removing an entry needs the same, and in a sticky directory the user
+    /// also has to own the entry or the directory.
+    fn check_remove(&self, who: &CurrentUser, parent: Ino, ino: Ino) -> Result<(), VfsError> {
+        self.check_create(who, parent)?;
+        let dir = self.inode(parent)?;
+        if dir.mode & perm::STICKY != 0
+            && !who.is_admin
+            && dir.owner != who.username
+            && self.inode(ino)?.owner != who.username
+        {
+            return Err(VfsError::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    fn allocate(&mut self, file_type: FileType, owner: &str) -> Result<Ino, VfsError> {
+        self.charge(owner, 0, 1)?;
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        let mode = match file_type {
+            FileType::Regular => perm::DEFAULT_FILE_MODE,
+            FileType::Directory => perm::DEFAULT_DIR_MODE,
+        };
+        self.inodes
+            .insert(ino, Inode::new(ino, file_type, owner, mode, clock::unix_now()));
+        self.changed.insert(ino);
+        Ok(ino)
+    }
+
+    /// Checks that `owner` may take on `bytes` and `inodes` more
+    fn charge(&mut self, owner: &str, bytes: u64, inodes: u64) -> Result<(), VfsError> {
+        if bytes == 0 && inodes == 0 {
+            return Ok(());
+        }
+        let Some(mut quota) = self.quotas.get(owner).copied() else {
+            return Ok(());
+        };
+        let usage = self.usage(owner);
+        let now = clock::unix_now();
+        let admitted = quota.bytes.admit(usage.bytes + bytes, now, self.grace_period)
+            && quota.inodes.admit(usage.inodes + inodes, now, self.grace_period);
+        if self.quotas.get(owner) != Some(&quota) {
+            self.quotas.insert(owner.to_string(), quota);
+            self.dirty = true;
+        }
+        if admitted {
+            Ok(())
+        } else {
+            Err(VfsError::QuotaExceeded)
+        }
+    }
+
+    fn link(&mut self, parent: Ino, name: &str, ino: Ino) -> Result<(), VfsError> {
+        let now = clock::unix_now();
+        self.entries_mut(parent)?.insert(name.to_string(), ino);
+        let dir = self.inode_mut(parent)?;
+        dir.modified = now;
+        dir.changed = now;
+        let inode = self.inode_mut(ino)?;
+        inode.nlink += 1;
+        inode.changed = now;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Removes a directory entry, freeing the inode when it was the last
+    /// link to it and no descriptor has it open.
+    fn unlink_entry(&mut self, parent: Ino, name: &str) -> Result<(), VfsError> {
+        let now = clock::unix_now();
+        let ino = self
+            .entries_mut(parent)?
+            .remove(name)
+            .ok_or(VfsError::NotFound)?;
+        let dir = self.inode_mut(parent)?;
+        dir.modified = now;
+        dir.changed = now;
+        let inode = self.inode_mut(ino)?;
+        inode.nlink = inode.nlink.saturating_sub(1);
+        inode.changed = now;
+        if inode.nlink == 0 && !self.open_files.values().any(|f| f.node == Node::Inode(ino)) {
+            self.free(ino);
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn stat(&self, who: &CurrentUser, path: &str) -> Result<Metadata, VfsError> {
+        if let Some(at) = self.mount_path(who, path)? {
+            return self.stat_mounted(&at);
+        }
+        let inode = self.inode(self.lookup(Some(who), path)?)?;
+        Ok(Metadata {
+            ino: inode.ino,
+            file_type: inode.file_type(),
+            size: inode.size(),
+            nlink: inode.nlink,
+            owner: inode.owner.clone(),
+            group: inode.group.clone(),
+            mode: inode.mode,
+            created: inode.created,
+            modified: inode.modified,
+            accessed: inode.accessed,
+            changed: inode.changed,
+        })
+    }
+
+    pub fn exists(&self, who: &CurrentUser, path: &str) -> bool {
+        self.stat(who, path).is_ok()
+    }
+
+    /// Checks that the user has the `perm::READ`/`WRITE`/`EXEC` bits in
+    /// `want` on a path
+    pub fn access(&self, who: &CurrentUser, path: &str, want: u16) -> Result<(), VfsError> {
+        if let Some(at) = self.mount_path(who, path)? {
+            return self.access_mounted(who, &at, want);
+        }
+        let ino = self.lookup(Some(who), path)?;
+        self.check(who, ino, want)
+    }
+
+    /// Entries of a directory, sorted by name
+    pub fn read_dir(&self, who: &CurrentUser, path: &str) -> Result<Vec<DirEntry>, VfsError> {
+        if let Some(at) = self.mount_path(who, path)? {
+            return self.read_dir_mounted(who, &at);
+        }
+        let ino = self.lookup(Some(who), path)?;
+        let entries = self.entries(ino)?;
+        self.check(who, ino, perm::READ)?;
+        entries
+            .iter()
+            .map(|(name, &ino)| {
+                Ok(DirEntry {
+                    name: name.clone(),
+                    file_type: self.inode(ino)?.file_type(),
+                })
+            })
+            .collect()
+    }
+
+    /// Sets a file's access and modification times to now, creating an
+    /// empty file if it doesn't exist.
+    pub fn touch(&mut self, who: &CurrentUser, path: &str) -> Result<(), VfsError> {
+        if let Some(at) = self.mount_path(who, path)? {
+            return self.touch_mounted(who, &at);
+        }
+        let ino = match self.lookup(Some(who), path) {
+            Ok(ino) => ino,
+            Err(VfsError::NotFound) => {
+                let (parent, name) = self.parent_of(who, path)?;
+                self.check_create(who, parent)?;
+                let ino = self.allocate(FileType::Regular, &who.username)?;
+                return self.link(parent, &name, ino);
+            }
+            Err(e) => return Err(e),
+        };
+        if self.inode(ino)?.owner != who.username {
+            self.check(who, ino, perm::WRITE)?;
+        }
+        let now = clock::unix_now();
+        let inode = self.inode_mut(ino)?;
+        inode.accessed = now;
+        inode.modified = now;
+        inode.changed = now;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn mkdir(&mut self, who: &CurrentUser, path: &str) -> Result<(), VfsError> {
+        if let Some(at) = self.mount_path(who, path)? {
+            return self.mkdir_mounted(who, &at);
+        }
+        let (parent, name) = self.parent_of(who, path)?;
+        if self.entries(parent)?.contains_key(&name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        self.check_create(who, parent)?;
+        let ino = self.allocate(FileType::Directory, &who.username)?;
+        self.link(parent, &name, ino)
+    }
+
+    /// Creates a directory along with any missing parents. Succeeds if it
+    /// already exists.
+    pub fn mkdir_all(&mut self, who: &CurrentUser, path: &str) -> Result<(), VfsError> {
+        let path = path::resolve("/", path);
+        let mut current = String::from("/");
+        for name in path::components(&path) {
+            current = path::join(&current, name);
+            match self.mkdir(who, &current) {
+                Ok(()) => {}
+                Err(VfsError::AlreadyExists) if self.stat(who, &current)?.is_dir() => {}
+                Err(VfsError::AlreadyExists) => return Err(VfsError::NotADirectory),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes an empty directory
+    pub fn rmdir(&mut self, who: &CurrentUser, path: &str) -> Result<(), VfsError> {
+        if let Some(at) = self.mount_path(who, path)? {
+            return self.rmdir_mounted(who, &at);
+        }
+        let (parent, name) = self.parent_of(who, path)?;
+        let ino = *self.entries(parent)?.get(&name).ok_or(VfsError::NotFound)?;
+        if !self.entries(ino)?.is_empty() {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        self.check_remove(who, parent, ino)?;
+        self.unlink_entry(parent, &name)
+    }
+
+    /// Removes a file. Directories need `rmdir`.
+    pub fn unlink(&mut self, who: &CurrentUser, path: &str) -> Result<(), VfsError> {
+        if let Some(at) = self.mount_path(who, path)? {
+            return self.unlink_mounted(who, &at);
+        }
+        let (parent, name) = self.parent_of(who, path)?;
+        let ino = *self.entries(parent)?.get(&name).ok_or(VfsError::NotFound)?;
+        if self.inode(ino)?.file_type() == FileType::Directory {
+            return Err(VfsError::IsADirectory);
+        }
+        self.check_remove(who, parent, ino)?;
+        self.unlink_entry(parent, &name)
+    }
+
+    /// Removes a directory and everything in it
+    pub fn remove_tree(&mut self, who: &CurrentUser, dir: &str) -> Result<(), VfsError> {
+        for entry in self.read_dir(who, dir)? {
+            let child = path::join(dir, &entry.name);
+            match entry.file_type {
+                FileType::Directory => self.remove_tree(who, &child)?,
+                FileType::Regular => self.unlink(who, &child)?,
+            }
+        }
+        self.rmdir(who, dir)
+    }
+
+    /// Moves a file or directory. An existing file at `to` is replaced, as
+    /// is an empty directory when moving a directory. Moves between mounted
+    /// filesystems fail with `CrossDevice`.
+    pub fn rename(&mut self, who: &CurrentUser, from: &str, to: &str) -> Result<(), VfsError> {
+        let from = path::resolve("/", from);
+        let to = path::resolve("/", to);
+        match (self.mount_path(who, &from)?, self.mount_path(who, &to)?) {
+            (None, None) if self.holds_mount(&from) => return Err(VfsError::Busy),
+            (None, None) => {}
+            (Some(from), Some(to)) => return self.rename_mounted(who, &from, &to),
+            (Some(from), None) if from.is_root() => return Err(VfsError::Busy),
+            _ => return Err(VfsError::CrossDevice),
+        }
+        let (from_parent, from_name) = self.parent_of(who, &from)?;
+        let (to_parent, to_name) = self.parent_of(who, &to)?;
+        let ino = *self
+            .entries(from_parent)?
+            .get(&from_name)
+            .ok_or(VfsError::NotFound)?;
+        if from == to {
+            return Ok(());
+        }
+        let is_dir = self.inode(ino)?.file_type() == FileType::Directory;
+        if is_dir && to.starts_with(&format!("{}/", from)) {
+            return Err(VfsError::InvalidArgument);
+        }
+        self.check_remove(who, from_parent, ino)?;
+        self.check_create(who, to_parent)?;
+
+        if let Some(&existing) = self.entries(to_parent)?.get(&to_name) {
+            match (is_dir, self.inode(existing)?.file_type()) {
+                (false, FileType::Directory) => return Err(VfsError::IsADirectory),
+                (true, FileType::Regular) => return Err(VfsError::NotADirectory),
+                (true, FileType::Directory) if !self.entries(existing)?.is_empty() => {
+                    return Err(VfsError::DirectoryNotEmpty)
+                }
+                _ => {}
+            }
+            self.check_remove(who, to_parent, existing)?;
+            self.unlink_entry(to_parent, &to_name)?;
+        }
+
+        self.link(to_parent, &to_name, ino)?;
+        self.unlink_entry(from_parent, &from_name)
+    }
+
+    /// Changes the permission bits. Only the owner or an admin may.
+    pub fn chmod(&mut self, who: &CurrentUser, path: &str, mode: u16) -> Result<(), VfsError> {
+        if let Some(at) = self.mount_path(who, path)? {
+            return self.chmod_mounted(who, &at, mode);
+        }
+        let ino = self.lookup(Some(who), path)?;
+        let inode = self.inode_mut(ino)?;
+        if !who.is_admin && inode.owner != who.username {
+            return Err(VfsError::NotPermitted);
+        }
+        inode.mode = mode & perm::MODE_MASK;
+        inode.changed = clock::unix_now();
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Changes the owner and/or group. Only admins can give files away;
+    /// owners may change the group to one they're in. Files in a mounted
+    /// filesystem belong to whoever the mount says.
+    pub fn chown(
+        &mut self,
+        who: &CurrentUser,
+        path: &str,
+        owner: Option<&str>,
+        group: Option<&str>,
+    ) -> Result<(), VfsError> {
+        if self.mount_path(who, path)?.is_some() {
+            return Err(VfsError::NotPermitted);
+        }
+        let ino = self.lookup(Some(who), path)?;
+        if !who.is_admin {
+            let inode = self.inode(ino)?;
+            let gives_away = owner.is_some_and(|owner| owner != inode.owner);
+            let foreign_group = group.is_some_and(|group| !self.in_group(&who.username, group));
+            if inode.owner != who.username || gives_away || foreign_group {
+                return Err(VfsError::NotPermitted);
+            }
+        }
+        let inode = self.inode(ino)?;
+        if let Some(owner) = owner.filter(|owner| *owner != inode.owner) {
+            let size = inode.size();
+            self.charge(owner, size, 1)?;
+        }
+        let inode = self.inode_mut(ino)?;
+        if let Some(owner) = owner {
+            inode.owner = owner.to_string();
+        }
+        if let Some(group) = group {
+            inode.group = group.to_string();
+        }
+        inode.changed = clock::unix_now();
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Deals with the files of a deleted account: they're given to `heir`,
+    /// or deleted when there's no heir. Directories that still hold other
+    /// users' files are kept and given to the root account. The user is
+    /// also taken out of every group. Returns the number of files and
+    /// directories affected.
+    pub fn disown(&mut self, username: &str, heir: Option<&str>) -> Result<usize, VfsError> {
+        let affected = match heir {
+            Some(heir) => {
+                let mut count = 0;
+                for inode in self.inodes.values_mut() {
+                    if inode.owner == username {
+                        inode.owner = heir.to_string();
+                        self.changed.insert(inode.ino);
+                        count += 1;
+                    }
+                }
+                count
+            }
+            None => self.remove_owned(ROOT_INO, username)?,
+        };
+
+        // Files left in the user's private group go to their owner's
+        for inode in self.inodes.values_mut() {
+            if inode.group == username {
+                inode.group = inode.owner.clone();
+                self.changed.insert(inode.ino);
+            }
+        }
+
+        self.rename_member(username, None)?;
+        self.quotas.remove(username);
+        self.dirty = true;
+        Ok(affected)
+    }
+
+    /// Follows an account rename: everything owned by `old`, or in its
+    /// private group, now belongs to `new`, and so do its group
+    /// memberships.
+    pub fn rename_account(&mut self, old: &str, new: &str) -> Result<(), VfsError> {
+        for inode in self.inodes.values_mut() {
+            if inode.owner == old {
+                inode.owner = new.to_string();
+                self.changed.insert(inode.ino);
+            }
+            if inode.group == old {
+                inode.group = new.to_string();
+                self.changed.insert(inode.ino);
+            }
+        }
+        self.rename_member(old, Some(new))?;
+        if let Some(quota) = self.quotas.remove(old) {
+            self.quotas.insert(new.to_string(), quota);
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Renames `username` in every group's member list, or drops them
+    /// when `new` is `None`.
+    fn rename_member(&mut self, username: &str, new: Option<&str>) -> Result<(), VfsError> {
+        let mut groups = self.groups();
+        if !groups.iter().any(|(_, members)| members.iter().any(|m| m == username)) {
+            return Ok(());
+        }
+        for (_, members) in &mut groups {
+            for member in std::mem::take(members) {
+                match new {
+                    _ if member != username => members.push(member),
+                    Some(new) => members.push(new.to_string()),
+                    None => {}
+                }
+            }
+        }
+        let root = self.root_user();
+        self.write_file(&root, perm::GROUP_FILE_PATH, perm::format_groups(&groups).as_bytes())
+    }
+
+    fn remove_owned(&mut self, dir: Ino, username: &str) -> Result<usize, VfsError> {
+        let mut removed = 0;
+        for (name, ino) in self.entries(dir)?.clone() {
+            let is_dir = self.inode(ino)?.file_type() == FileType::Directory;
+            if is_dir {
+                removed += self.remove_owned(ino, username)?;
+            }
+            if self.inode(ino)?.owner != username {
+                continue;
+            }
+            if is_dir && !self.entries(ino)?.is_empty() {
+                self.inode_mut(ino)?.owner = self.root_owner.clone();
+            } else {
+                self.unlink_entry(dir, &name)?;
+            }
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Bytes in the regular files a user owns, and how many files and
+    /// directories they own
+    pub fn usage(&self, username: &str) -> Usage {
+        let mut usage = Usage::default();
+        for inode in self.inodes.values().filter(|i| i.owner == username) {
+            usage.inodes += 1;
+            if inode.file_type() == FileType::Regular {
+                usage.bytes += inode.size();
+            }
+        }
+        usage
+    }
+
+    /// A user's quota. Users without one have no limits.
+    pub fn quota(&self, username: &str) -> Quota {
+        self.quotas.get(username).copied().unwrap_or_default()
+    }
+
+    /// Sets a user's limits (admins only). Limits of 0 remove the quota.
+    pub fn set_quota(
+        &mut self,
+        who: &CurrentUser,
+        username: &str,
+        mut quota: Quota,
+    ) -> Result<(), VfsError> {
+        if !who.is_admin {
+            return Err(VfsError::NotPermitted);
+        }
+        if quota.is_unlimited() {
+            self.quotas.remove(username);
+        } else {
+            let (usage, old, now) = (self.usage(username), self.quota(username), clock::unix_now());
+            quota.bytes.replace(&old.bytes, usage.bytes, now);
+            quota.inodes.replace(&old.inodes, usage.inodes, now);
+            self.quotas.insert(username.to_string(), quota);
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn grace_period(&self) -> u64 {
+        self.grace_period
+    }
+
+    pub fn set_grace_period(&mut self, who: &CurrentUser, seconds: u64) -> Result<(), VfsError> {
+        if !who.is_admin {
+            return Err(VfsError::NotPermitted);
+        }
+        self.grace_period = seconds;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn open(&mut self, who: &CurrentUser, path: &str, flags: OpenFlags) -> Result<Fd, VfsError> {
+        if let Some(at) = self.mount_path(who, path)? {
+            self.open_mounted(who, &at, flags)?;
+            return Ok(self.add_descriptor(Node::Mounted(at), flags));
+        }
+        let ino = match self.lookup(Some(who), path) {
+            Ok(ino) => {
+                let want = if flags.read { perm::READ } else { 0 }
+                    | if flags.write { perm::WRITE } else { 0 };
+                if self.inode(ino)?.file_type() == FileType::Regular {
+                    self.check(who, ino, want)?;
+                }
+                ino
+            }
+            Err(VfsError::NotFound) if flags.create => {
+                let (parent, name) = self.parent_of(who, path)?;
+                self.check_create(who, parent)?;
+                let ino = self.allocate(FileType::Regular, &who.username)?;
+                self.link(parent, &name, ino)?;
+                ino
+            }
+            Err(e) => return Err(e),
+        };
+
+        let inode = self.inode_mut(ino)?;
+        let InodeData::Regular(bytes) = &mut inode.data else {
+            return Err(VfsError::IsADirectory);
+        };
+        if flags.truncate && !bytes.is_empty() {
+            bytes.clear();
+            let now = clock::unix_now();
+            inode.modified = now;
+            inode.changed = now;
+            self.dirty = true;
+        }
+
+        Ok(self.add_descriptor(Node::Inode(ino), flags))
+    }
+
+    fn add_descriptor(&mut self, node: Node, flags: OpenFlags) -> Fd {
+        let fd = self.next_fd;
+        self.next_fd += 1;
+        self.open_files.insert(
+            fd,
+            OpenFile {
+                node,
+                offset: 0,
+                flags,
+            },
+        );
+        fd
+    }
+
+    /// Reads up to `len` bytes from the descriptor's current offset. An
+    /// empty result means end of file.
+    pub fn read(&mut self, fd: Fd, len: usize) -> Result<Vec<u8>, VfsError> {
+        let file = self.open_files.get(&fd).ok_or(VfsError::BadDescriptor)?;
+        if !file.flags.read {
+            return Err(VfsError::BadDescriptor);
+        }
+        let (ino, offset) = match &file.node {
+            Node::Inode(ino) => (*ino, file.offset),
+            Node::Mounted(at) => {
+                let (at, offset) = (at.clone(), file.offset);
+                let chunk = self.read_mounted(&at, offset, len)?;
+                if let Some(file) = self.open_files.get_mut(&fd) {
+                    file.offset = offset + chunk.len();
+                }
+                return Ok(chunk);
+            }
+        };
+        let inode = self.inode_mut(ino)?;
+        let InodeData::Regular(bytes) = &inode.data else {
+            return Err(VfsError::IsADirectory);
+        };
+        let start = offset.min(bytes.len());
+        let end = start.saturating_add(len).min(bytes.len());
+        let chunk = bytes[start..end].to_vec();
+        inode.accessed = clock::unix_now();
+        if let Some(file) = self.open_files.get_mut(&fd) {
+            file.offset = end;
+        }
+        Ok(chunk)
+    }
+
+    /// Writes at the descriptor's offset, or at the end of the file when it
+    /// was opened for appending. Returns the number of bytes written.
+    pub fn write(&mut self, fd: Fd, data: &[u8]) -> Result<usize, VfsError> {
+        let file = self.open_files.get(&fd).ok_or(VfsError::BadDescriptor)?;
+        if !file.flags.write {
+            return Err(VfsError::BadDescriptor);
+        }
+        let (ino, offset, append) = match &file.node {
+            Node::Inode(ino) => (*ino, file.offset, file.flags.append),
+            Node::Mounted(at) => {
+                let (at, offset, append) = (at.clone(), file.offset, file.flags.append);
+                let end = self.write_mounted(&at, offset, append, data)?;
+                if let Some(file) = self.open_files.get_mut(&fd) {
+                    file.offset = end;
+                }
+                return Ok(data.len());
+            }
+        };
+        let inode = self.inode(ino)?;
+        let len = inode.size() as usize;
+        let start = if append { len } else { offset };
+        let end = start + data.len();
+        let owner = inode.owner.clone();
+        self.charge(&owner, end.saturating_sub(len) as u64, 0)?;
+
+        let inode = self.inode_mut(ino)?;
+        let InodeData::Regular(bytes) = &mut inode.data else {
+            return Err(VfsError::IsADirectory);
+        };
+        if bytes.len() < end {
+            bytes.resize(end, 0);
+        }
+        bytes[start..end].copy_from_slice(data);
+        let now = clock::unix_now();
+        inode.modified = now;
+        inode.changed = now;
+        self.dirty = true;
+        if let Some(file) = self.open_files.get_mut(&fd) {
+            file.offset = end;
+        }
+        Ok(data.len())
+    }
+
+    pub fn close(&mut self, fd: Fd) -> Result<(), VfsError> {
+        let file = self.open_files.remove(&fd).ok_or(VfsError::BadDescriptor)?;
+        // Free a file that was unlinked while it was still open
+        let Node::Inode(ino) = file.node else {
+            return Ok(());
+        };
+        let orphaned = self.inodes.get(&ino).map(|i| i.nlink == 0).unwrap_or(false);
+        if orphaned && !self.open_files.values().any(|f| f.node == Node::Inode(ino)) {
+            self.free(ino);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    fn free(&mut self, ino: Ino) {
+        self.inodes.remove(&ino);
+        self.changed.insert(ino);
+    }
+
+    /// Reads a whole file
+    pub fn read_file(&mut self, who: &CurrentUser, path: &str) -> Result<Vec<u8>, VfsError> {
+        let fd = self.open(who, path, OpenFlags::READ)?;
+        let result = self.read(fd, usize::MAX);
+        self.close(fd)?;
+        result
+    }
+
+    /// Replaces a file's contents, creating it if needed
+    pub fn write_file(&mut self, who: &CurrentUser, path: &str, data: &[u8]) -> Result<(), VfsError> {
+        // Check the quota before the old contents are truncated away.
+        // Mounted filesystems don't count against quotas.
+        let mounted = !matches!(self.mount_path(who, path), Ok(None));
+        if let Some(ino) = self.lookup(Some(who), path).ok().filter(|_| !mounted) {
+            let inode = self.inode(ino)?;
+            let (owner, len) = (inode.owner.clone(), inode.size());
+            self.charge(&owner, (data.len() as u64).saturating_sub(len), 0)?;
+        }
+        let fd = self.open(who, path, OpenFlags::WRITE)?;
+        let result = self.write(fd, data);
+        self.close(fd)?;
+        result.map(|_| ())
+    }
+}
+
+static VFS: OnceLock<Mutex<Vfs>> = OnceLock::new();
+
+/// Held for as long as the image is mounted, so no other process mounts
+/// it too and overwrites its changes with their own
+static IMAGE_LOCK: OnceLock<File> = OnceLock::new();
+
+/// Where the lock on an image is taken. The image itself is replaced on
+/// every checkpoint, so it can't hold the lock.
+fn lock_path_for(image_path: &Path) -> PathBuf {
+    let mut path = image_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// Takes the lock on the image for the rest of the process, failing if
+/// another process holds it. The lock file names the holder's PID.
+fn lock_image(image_path: &Path) -> Result<(), VfsError> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path_for(image_path))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::WouldBlock {
+            return Err(e.into());
+        }
+        let mut holder = String::new();
+        let _ = file.read_to_string(&mut holder);
+        return Err(VfsError::Locked(holder.trim().parse().ok()));
+    }
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}", std::process::id())?;
+    let _ = IMAGE_LOCK.set(file);
+    Ok(())
+}
+
+/// Loads the filesystem image at boot, replaying the journal left by a
+/// crash into it. Every session shares the one filesystem through `lock`.
+/// `root_owner` is the root account's username. Returns the number of
+/// journal records replayed. Fails with `VfsError::Locked` if another
+/// MiniKern process has the image mounted.
+pub fn mount_root(image_path: &Path, root_owner: &str) -> Result<usize, VfsError> {
+    lock_image(image_path)?;
+    let mut vfs = Vfs::load(image_path, root_owner)?;
+    let replayed = vfs.replay()?;
+    VFS.set(Mutex::new(vfs))
+        .map_err(|_| VfsError::Io("root filesystem is already mounted".into()))?;
+    checkpoint()?;
+    Ok(replayed)
+}
+
+/// Locks the shared filesystem. Panics if `mount_root` hasn't run yet.
+pub fn lock() -> MutexGuard<'static, Vfs> {
+    VFS.get()
+        .expect("root filesystem not mounted")
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Saves any pending changes to the journal
+pub fn sync() -> Result<(), VfsError> {
+    lock().sync()
+}
+
+/// Writes everything to the disk image, for a clean shutdown
+pub fn checkpoint() -> Result<(), VfsError> {
+    lock().checkpoint()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root() -> CurrentUser {
+        CurrentUser { username: "root".to_string(), is_admin: true }
+    }
+
+    fn user(name: &str) -> CurrentUser {
+        CurrentUser { username: name.to_string(), is_admin: false }
+    }
+
+    /// A directory of the test's own on the host, emptied first
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("minikern-vfs-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A filesystem that's never saved, with a home directory for each of
+    /// `users`
+    fn filesystem(users: &[&str]) -> Vfs {
+        let mut fs = Vfs::new(Path::new("/nonexistent/minikern.img"), "root");
+        for name in users {
+            let home = path::join("/home", name);
+            fs.mkdir(&root(), &home).unwrap();
+            fs.chown(&root(), &home, Some(name), Some(name)).unwrap();
+        }
+        fs
+    }
+
+    #[test]
+    fn a_fresh_filesystem_has_the_base_directories() {
+        let fs = Vfs::new(Path::new("/nonexistent/minikern.img"), "root");
+        for (dir, mode) in BASE_DIRS {
+            let meta = fs.stat(&root(), dir).unwrap();
+            assert!(meta.is_dir(), "{}", dir);
+            assert_eq!(meta.mode, *mode, "{}", dir);
+            assert_eq!(meta.owner, "root", "{}", dir);
+        }
+        assert_eq!(fs.stat(&root(), "/tmp").unwrap().mode_string(), "drwxrwxrwt");
+    }
+
+    #[test]
+    fn files_survive_a_checkpoint() {
+        let dir = scratch("checkpoint");
+        let image = dir.join("minikern.img");
+        let mut fs = Vfs::new(&image, "root");
+        fs.mkdir(&root(), "/home/alice").unwrap();
+        fs.chown(&root(), "/home/alice", Some("alice"), Some("alice")).unwrap();
+        fs.write_file(&user("alice"), "/home/alice/notes", b"milk\neggs\n").unwrap();
+        fs.chmod(&user("alice"), "/home/alice/notes", 0o600).unwrap();
+        fs.checkpoint().unwrap();
+
+        let mut loaded = Vfs::load(&image, "root").unwrap();
+        assert_eq!(loaded.read_file(&user("alice"), "/home/alice/notes").unwrap(), b"milk\neggs\n");
+        let meta = loaded.stat(&root(), "/home/alice/notes").unwrap();
+        assert_eq!((meta.owner.as_str(), meta.mode), ("alice", 0o600));
+        assert_eq!(loaded.read_dir(&root(), "/home").unwrap().len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn synced_changes_are_replayed_from_the_journal() {
+        let dir = scratch("journal");
+        let image = dir.join("minikern.img");
+        let mut fs = Vfs::new(&image, "root");
+        fs.checkpoint().unwrap();
+        fs.write_file(&root(), "/etc/motd", b"hello").unwrap();
+        fs.sync().unwrap();
+        fs.write_file(&root(), "/etc/issue", b"unsynced").unwrap();
+
+        let mut loaded = Vfs::load(&image, "root").unwrap();
+        assert!(!loaded.exists(&root(), "/etc/motd"));
+        assert_eq!(loaded.replay().unwrap(), 1);
+        assert_eq!(loaded.read_file(&root(), "/etc/motd").unwrap(), b"hello");
+        assert!(!loaded.exists(&root(), "/etc/issue"));
+
+        // A torn record after it is ignored
+        let journal = journal::path_for(&image);
+        let mut bytes = std::fs::read(&journal).unwrap();
+        bytes.extend_from_slice(b"MKJR\x40");
+        std::fs::write(&journal, bytes).unwrap();
+        let mut loaded = Vfs::load(&image, "root").unwrap();
+        assert_eq!(loaded.replay().unwrap(), 1);
+        assert!(loaded.exists(&root(), "/etc/motd"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_damaged_image_is_refused() {
+        let dir = scratch("damaged");
+        let image = dir.join("minikern.img");
+        std::fs::write(&image, b"not an image at all").unwrap();
+        assert!(matches!(Vfs::load(&image, "root"), Err(VfsError::Corrupt(_))));
+
+        let mut fs = Vfs::new(&image, "root");
+        fs.write_file(&root(), "/etc/motd", b"hello").unwrap();
+        fs.checkpoint().unwrap();
+        let bytes = std::fs::read(&image).unwrap();
+        std::fs::write(&image, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(Vfs::load(&image, "root").is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn descriptors_read_from_their_offset() {
+        let mut fs = filesystem(&[]);
+        fs.write_file(&root(), "/tmp/f", b"hello").unwrap();
+        let fd = fs.open(&root(), "/tmp/f", OpenFlags::READ).unwrap();
+        assert_eq!(fs.read(fd, 2).unwrap(), b"he");
+        assert_eq!(fs.read(fd, 10).unwrap(), b"llo");
+        assert!(fs.read(fd, 10).unwrap().is_empty());
+        fs.close(fd).unwrap();
+        assert!(matches!(fs.read(fd, 1), Err(VfsError::BadDescriptor)));
+
+        // An open file outlives its last name until it's closed
+        let fd = fs.open(&root(), "/tmp/f", OpenFlags::READ).unwrap();
+        fs.unlink(&root(), "/tmp/f").unwrap();
+        assert!(!fs.exists(&root(), "/tmp/f"));
+        assert_eq!(fs.read(fd, 10).unwrap(), b"hello");
+        fs.close(fd).unwrap();
+    }
+
+    #[test]
+    fn owner_group_and_other_bits_decide_access() {
+        let mut fs = filesystem(&["alice", "bob", "carol"]);
+        fs.write_file(&root(), perm::GROUP_FILE_PATH, b"staff:bob\n").unwrap();
+        fs.write_file(&user("alice"), "/home/alice/plan", b"v1").unwrap();
+        fs.chown(&root(), "/home/alice/plan", None, Some("staff")).unwrap();
+        fs.chmod(&user("alice"), "/home/alice/plan", 0o640).unwrap();
+
+        assert!(fs.read_file(&user("alice"), "/home/alice/plan").is_ok());
+        assert!(fs.read_file(&user("bob"), "/home/alice/plan").is_ok());
+        assert!(matches!(fs.write_file(&user("bob"), "/home/alice/plan", b"v2"), Err(VfsError::PermissionDenied)));
+        assert!(matches!(fs.read_file(&user("carol"), "/home/alice/plan"), Err(VfsError::PermissionDenied)));
+        assert!(fs.write_file(&root(), "/home/alice/plan", b"v2").is_ok());
+
+        // Nor can anyone else add files to a home directory
+        assert!(matches!(fs.write_file(&user("bob"), "/home/alice/new", b""), Err(VfsError::PermissionDenied)));
+    }
+
+    #[test]
+    fn a_directory_without_search_permission_hides_its_files() {
+        let mut fs = filesystem(&["alice", "bob"]);
+        fs.write_file(&user("alice"), "/home/alice/notes", b"hi").unwrap();
+        fs.chmod(&user("alice"), "/home/alice", 0o700).unwrap();
+        assert!(matches!(fs.stat(&user("bob"), "/home/alice/notes"), Err(VfsError::PermissionDenied)));
+        assert!(matches!(fs.read_dir(&user("bob"), "/home/alice"), Err(VfsError::PermissionDenied)));
+        assert!(fs.stat(&user("alice"), "/home/alice/notes").is_ok());
+    }
+
+    #[test]
+    fn only_owners_and_admins_change_modes_and_owners() {
+        let mut fs = filesystem(&["alice", "bob"]);
+        fs.write_file(&user("alice"), "/home/alice/f", b"").unwrap();
+        assert!(matches!(fs.chmod(&user("bob"), "/home/alice/f", 0o666), Err(VfsError::NotPermitted)));
+        assert!(matches!(fs.chown(&user("alice"), "/home/alice/f", Some("bob"), None), Err(VfsError::NotPermitted)));
+        assert!(matches!(fs.chown(&user("alice"), "/home/alice/f", None, Some("staff")), Err(VfsError::NotPermitted)));
+        fs.chmod(&user("alice"), "/home/alice/f", 0o604).unwrap();
+        fs.chown(&root(), "/home/alice/f", Some("bob"), None).unwrap();
+        assert_eq!(fs.stat(&root(), "/home/alice/f").unwrap().owner, "bob");
+    }
+
+    #[test]
+    fn only_the_owner_removes_a_file_from_sticky_tmp() {
+        let mut fs = filesystem(&["alice", "bob"]);
+        fs.write_file(&user("alice"), "/tmp/a", b"alice's").unwrap();
+        fs.write_file(&user("bob"), "/tmp/b", b"bob's").unwrap();
+
+        assert!(matches!(fs.unlink(&user("bob"), "/tmp/a"), Err(VfsError::PermissionDenied)));
+        assert!(matches!(fs.rename(&user("bob"), "/tmp/a", "/tmp/c"), Err(VfsError::PermissionDenied)));
+        // Nor may bob replace it with a file of his
+        assert!(matches!(fs.rename(&user("bob"), "/tmp/b", "/tmp/a"), Err(VfsError::PermissionDenied)));
+        assert_eq!(fs.read_file(&user("alice"), "/tmp/a").unwrap(), b"alice's");
+
+        fs.unlink(&user("bob"), "/tmp/b").unwrap();
+        fs.rename(&user("alice"), "/tmp/a", "/tmp/c").unwrap();
+        fs.unlink(&root(), "/tmp/c").unwrap();
+    }
+
+    #[test]
+    fn without_the_sticky_bit_anyone_who_can_write_removes() {
+        let mut fs = filesystem(&["alice", "bob"]);
+        fs.chmod(&root(), "/tmp", 0o777).unwrap();
+        fs.write_file(&user("alice"), "/tmp/a", b"").unwrap();
+        fs.unlink(&user("bob"), "/tmp/a").unwrap();
+        assert!(!fs.exists(&root(), "/tmp/a"));
+    }
+
+    #[test]
+    fn writes_go_over_the_soft_limit_only_until_the_grace_period_ends() {
+        let mut fs = filesystem(&["alice"]);
+        let alice = user("alice");
+        let quota = Quota { bytes: quota::Limit::new(10, 20), ..Quota::default() };
+        assert!(matches!(fs.set_quota(&alice, "alice", quota), Err(VfsError::NotPermitted)));
+        fs.set_quota(&root(), "alice", quota).unwrap();
+
+        fs.write_file(&alice, "/home/alice/a", &[b'a'; 15]).unwrap();
+        assert!(fs.quota("alice").bytes.over_since.is_some());
+        fs.write_file(&alice, "/home/alice/b", &[b'b'; 3]).unwrap();
+        let over_hard = fs.write_file(&alice, "/home/alice/c", &[b'c'; 5]);
+        assert!(matches!(over_hard, Err(VfsError::QuotaExceeded)));
+        assert_eq!(fs.usage("alice").bytes, 18);
+
+        fs.set_grace_period(&root(), 0).unwrap();
+        let after_grace = fs.write_file(&alice, "/home/alice/d", b"d");
+        assert!(matches!(after_grace, Err(VfsError::QuotaExceeded)));
+
+        // Getting back under the soft limit makes room again
+        fs.unlink(&alice, "/home/alice/a").unwrap();
+        fs.write_file(&alice, "/home/alice/d", b"d").unwrap();
+        assert_eq!(fs.quota("alice").bytes.over_since, None);
+    }
+}
@@ -0,0 +1,418 @@
+use super::backend::{Attributes, Backend};
+use super::{path, perm, DirEntry, FileType, Metadata, Node, OpenFlags, Vfs, VfsError};
+use crate::auth::CurrentUser;
+
+/// How a filesystem is mounted. Everything in it appears to belong to
+/// `owner` and `group`, with the backend's own permission bits.
+#[derive(Debug, Clone)]
+pub struct MountOptions {
+    pub read_only: bool,
+    pub owner: String,
+    pub group: String,
+}
+
+#[derive(Debug)]
+pub(super) struct Mount {
+    backend: Box<dyn Backend>,
+    options: MountOptions,
+}
+
+/// A line of the mount table
+pub struct MountInfo {
+    pub point: String,
+    pub kind: &'static str,
+    pub source: String,
+    pub options: MountOptions,
+}
+
+/// A path inside a mounted filesystem: the mount point, and the path
+/// relative to the backend's root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct MountPath {
+    point: String,
+    rel: String,
+}
+
+impl MountPath {
+    pub(super) fn is_root(&self) -> bool {
+        self.rel.is_empty()
+    }
+
+    /// The directory holding this path, `None` for the mount's root
+    fn parent(&self) -> Option<MountPath> {
+        let (parent, _) = self.rel.rsplit_once('/').unwrap_or(("", &self.rel));
+        (!self.is_root()).then(|| MountPath {
+            point: self.point.clone(),
+            rel: parent.to_string(),
+        })
+    }
+}
+
+/// Whether `path` is strictly below the directory `dir`
+fn within(path: &str, dir: &str) -> bool {
+    dir != "/" && path.starts_with(&format!("{}/", dir))
+}
+
+impl Vfs {
+    /// Mounts `backend` on the directory `point` (admins only), creating
+    /// the directory if it's missing. Mounts can't be nested or stacked.
+    pub fn mount(
+        &mut self,
+        who: &CurrentUser,
+        point: &str,
+        backend: Box<dyn Backend>,
+        options: MountOptions,
+    ) -> Result<(), VfsError> {
+        if !who.is_admin {
+            return Err(VfsError::NotPermitted);
+        }
+        let point = path::resolve("/", point);
+        let taken = self
+            .mounts
+            .keys()
+            .any(|p| *p == point || within(&point, p) || within(p, &point));
+        if point == "/" || taken {
+            return Err(VfsError::Busy);
+        }
+        self.mkdir_all(who, &point)?;
+        let ino = self.lookup(Some(who), &point)?;
+        self.entries(ino)?;
+        if backend.attributes("")?.file_type != FileType::Directory {
+            return Err(VfsError::NotADirectory);
+        }
+        self.mounts.insert(point, Mount { backend, options });
+        Ok(())
+    }
+
+    /// Removes a mount (admins only), unless a file in it is still open
+    pub fn umount(&mut self, who: &CurrentUser, point: &str) -> Result<(), VfsError> {
+        if !who.is_admin {
+            return Err(VfsError::NotPermitted);
+        }
+        let point = path::resolve("/", point);
+        if !self.mounts.contains_key(&point) {
+            return Err(VfsError::InvalidArgument);
+        }
+        let in_use = self
+            .open_files
+            .values()
+            .any(|f| matches!(&f.node, Node::Mounted(at) if at.point == point));
+        if in_use {
+            return Err(VfsError::Busy);
+        }
+        self.mounts.remove(&point);
+        Ok(())
+    }
+
+    /// The mounted filesystems, by mount point
+    pub fn mounts(&self) -> Vec<MountInfo> {
+        self.mounts
+            .iter()
+            .map(|(point, mount)| MountInfo {
+                point: point.clone(),
+                kind: mount.backend.kind(),
+                source: mount.backend.source(),
+                options: mount.options.clone(),
+            })
+            .collect()
+    }
+
+    /// Whether a mount point is somewhere below `dir`, which then can't
+    /// be moved
+    pub(super) fn holds_mount(&self, dir: &str) -> bool {
+        self.mounts.keys().any(|p| within(p, dir))
+    }
+
+    /// Where `path` is if it's in a mounted filesystem. Every directory on
+    /// the way has to be searchable by the user.
+    pub(super) fn mount_path(
+        &self,
+        who: &CurrentUser,
+        path: &str,
+    ) -> Result<Option<MountPath>, VfsError> {
+        let path = path::resolve("/", path);
+        let Some((point, mount)) = self
+            .mounts
+            .iter()
+            .find(|(point, _)| path == **point || within(&path, point))
+        else {
+            return Ok(None);
+        };
+        self.lookup(Some(who), point)?;
+        let rel = path[point.len()..].trim_start_matches('/').to_string();
+        let names = path::components(&rel);
+        for depth in 0..names.len() {
+            let attributes = mount.backend.attributes(&names[..depth].join("/"))?;
+            if attributes.file_type != FileType::Directory {
+                return Err(VfsError::NotADirectory);
+            }
+            self.check_mounted(who, mount, &attributes, perm::EXEC)?;
+        }
+        Ok(Some(MountPath {
+            point: point.clone(),
+            rel,
+        }))
+    }
+
+    fn mount_of(&self, at: &MountPath) -> Result<&Mount, VfsError> {
+        self.mounts.get(&at.point).ok_or(VfsError::NotFound)
+    }
+
+    /// The mount, for a change to it. Read-only mounts refuse.
+    fn writable_mount(&mut self, at: &MountPath) -> Result<&mut Mount, VfsError> {
+        let mount = self.mounts.get_mut(&at.point).ok_or(VfsError::NotFound)?;
+        if mount.options.read_only {
+            return Err(VfsError::ReadOnly);
+        }
+        Ok(mount)
+    }
+
+    fn check_mounted(
+        &self,
+        who: &CurrentUser,
+        mount: &Mount,
+        attributes: &Attributes,
+        want: u16,
+    ) -> Result<(), VfsError> {
+        let MountOptions { owner, group, .. } = &mount.options;
+        if self.permits_as(who, owner, group, attributes.mode, want) {
+            Ok(())
+        } else {
+            Err(VfsError::PermissionDenied)
+        }
+    }
+
+    /// Adding an entry next to `at` needs write and search permission on
+    /// its directory
+    fn check_create_mounted(&self, who: &CurrentUser, at: &MountPath) -> Result<(), VfsError> {
+        let parent = at.parent().ok_or(VfsError::AlreadyExists)?;
+        let mount = self.mount_of(at)?;
+        let attributes = mount.backend.attributes(&parent.rel)?;
+        self.check_mounted(who, mount, &attributes, perm::WRITE | perm::EXEC)
+    }
+
+    /// Removing needs the same. Everything has the same owner, so in a
+    /// sticky directory only that owner and admins may remove entries.
+    fn check_remove_mounted(&self, who: &CurrentUser, at: &MountPath) -> Result<(), VfsError> {
+        if at.is_root() {
+            return Err(VfsError::Busy);
+        }
+        self.check_create_mounted(who, at)?;
+        let mount = self.mount_of(at)?;
+        let parent = mount
+            .backend
+            .attributes(&at.parent().ok_or(VfsError::Busy)?.rel)?;
+        if parent.mode & perm::STICKY != 0 && !who.is_admin && mount.options.owner != who.username {
+            return Err(VfsError::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    pub(super) fn stat_mounted(&self, at: &MountPath) -> Result<Metadata, VfsError> {
+        let mount = self.mount_of(at)?;
+        let attributes = mount.backend.attributes(&at.rel)?;
+        Ok(Metadata {
+            ino: attributes.ino,
+            file_type: attributes.file_type,
+            size: attributes.size,
+            nlink: 1,
+            owner: mount.options.owner.clone(),
+            group: mount.options.group.clone(),
+            mode: attributes.mode,
+            created: attributes.created,
+            modified: attributes.modified,
+            accessed: attributes.accessed,
+            changed: attributes.changed,
+        })
+    }
+
+    pub(super) fn access_mounted(
+        &self,
+        who: &CurrentUser,
+        at: &MountPath,
+        want: u16,
+    ) -> Result<(), VfsError> {
+        let mount = self.mount_of(at)?;
+        let attributes = mount.backend.attributes(&at.rel)?;
+        self.check_mounted(who, mount, &attributes, want)
+    }
+
+    pub(super) fn read_dir_mounted(
+        &self,
+        who: &CurrentUser,
+        at: &MountPath,
+    ) -> Result<Vec<DirEntry>, VfsError> {
+        let mount = self.mount_of(at)?;
+        let attributes = mount.backend.attributes(&at.rel)?;
+        if attributes.file_type != FileType::Directory {
+            return Err(VfsError::NotADirectory);
+        }
+        self.check_mounted(who, mount, &attributes, perm::READ)?;
+        mount.backend.read_dir(&at.rel)
+    }
+
+    pub(super) fn touch_mounted(
+        &mut self,
+        who: &CurrentUser,
+        at: &MountPath,
+    ) -> Result<(), VfsError> {
+        match self.mount_of(at)?.backend.attributes(&at.rel) {
+            Ok(_) => {
+                if self.mount_of(at)?.options.owner != who.username {
+                    self.access_mounted(who, at, perm::WRITE)?;
+                }
+                self.writable_mount(at)?.backend.touch(&at.rel)
+            }
+            Err(VfsError::NotFound) => {
+                self.check_create_mounted(who, at)?;
+                self.writable_mount(at)?.backend.create(&at.rel)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub(super) fn mkdir_mounted(
+        &mut self,
+        who: &CurrentUser,
+        at: &MountPath,
+    ) -> Result<(), VfsError> {
+        if self.mount_of(at)?.backend.attributes(&at.rel).is_ok() {
+            return Err(VfsError::AlreadyExists);
+        }
+        self.check_create_mounted(who, at)?;
+        self.writable_mount(at)?.backend.mkdir(&at.rel)
+    }
+
+    pub(super) fn rmdir_mounted(
+        &mut self,
+        who: &CurrentUser,
+        at: &MountPath,
+    ) -> Result<(), VfsError> {
+        if self.mount_of(at)?.backend.attributes(&at.rel)?.file_type != FileType::Directory {
+            return Err(VfsError::NotADirectory);
+        }
+        self.check_remove_mounted(who, at)?;
+        self.writable_mount(at)?.backend.remove_dir(&at.rel)
+    }
+
+    pub(super) fn unlink_mounted(
+        &mut self,
+        who: &CurrentUser,
+        at: &MountPath,
+    ) -> Result<(), VfsError> {
+        if self.mount_of(at)?.backend.attributes(&at.rel)?.file_type == FileType::Directory {
+            return Err(VfsError::IsADirectory);
+        }
+        self.check_remove_mounted(who, at)?;
+        self.writable_mount(at)?.backend.remove_file(&at.rel)
+    }
+
+    /// Moves within one mounted filesystem; moving between filesystems is
+    /// a copy, which is up to the caller.
+    pub(super) fn rename_mounted(
+        &mut self,
+        who: &CurrentUser,
+        from: &MountPath,
+        to: &MountPath,
+    ) -> Result<(), VfsError> {
+        if from.is_root() || to.is_root() {
+            return Err(VfsError::Busy);
+        }
+        if from.point != to.point {
+            return Err(VfsError::CrossDevice);
+        }
+        let source = self.mount_of(from)?.backend.attributes(&from.rel)?;
+        if from == to {
+            return Ok(());
+        }
+        if source.file_type == FileType::Directory && within(&to.rel, &from.rel) {
+            return Err(VfsError::InvalidArgument);
+        }
+        self.check_remove_mounted(who, from)?;
+        self.check_create_mounted(who, to)?;
+        if self.mount_of(to)?.backend.attributes(&to.rel).is_ok() {
+            self.check_remove_mounted(who, to)?;
+        }
+        self.writable_mount(from)?
+            .backend
+            .rename(&from.rel, &to.rel)
+    }
+
+    pub(super) fn chmod_mounted(
+        &mut self,
+        who: &CurrentUser,
+        at: &MountPath,
+        mode: u16,
+    ) -> Result<(), VfsError> {
+        let mount = self.mount_of(at)?;
+        mount.backend.attributes(&at.rel)?;
+        if !who.is_admin && mount.options.owner != who.username {
+            return Err(VfsError::NotPermitted);
+        }
+        self.writable_mount(at)?
+            .backend
+            .set_mode(&at.rel, mode & perm::MODE_MASK)
+    }
+
+    /// Gets a file ready to be read or written through a descriptor
+    pub(super) fn open_mounted(
+        &mut self,
+        who: &CurrentUser,
+        at: &MountPath,
+        flags: OpenFlags,
+    ) -> Result<(), VfsError> {
+        match self.mount_of(at)?.backend.attributes(&at.rel) {
+            Ok(attributes) => {
+                if attributes.file_type == FileType::Directory {
+                    return Err(VfsError::IsADirectory);
+                }
+                let want = if flags.read { perm::READ } else { 0 }
+                    | if flags.write { perm::WRITE } else { 0 };
+                self.check_mounted(who, self.mount_of(at)?, &attributes, want)?;
+                if flags.write {
+                    let mount = self.writable_mount(at)?;
+                    if flags.truncate && attributes.size > 0 {
+                        mount.backend.truncate(&at.rel)?;
+                    }
+                }
+                Ok(())
+            }
+            Err(VfsError::NotFound) if flags.create => {
+                self.check_create_mounted(who, at)?;
+                self.writable_mount(at)?.backend.create(&at.rel)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub(super) fn read_mounted(
+        &self,
+        at: &MountPath,
+        offset: usize,
+        len: usize,
+    ) -> Result<Vec<u8>, VfsError> {
+        self.mount_of(at)?
+            .backend
+            .read_at(&at.rel, offset as u64, len)
+    }
+
+    /// Writes at `offset`, or at the end when appending. Returns the offset
+    /// after the data.
+    pub(super) fn write_mounted(
+        &mut self,
+        at: &MountPath,
+        offset: usize,
+        append: bool,
+        data: &[u8],
+    ) -> Result<usize, VfsError> {
+        let start = if append {
+            self.mount_of(at)?.backend.attributes(&at.rel)?.size as usize
+        } else {
+            offset
+        };
+        self.writable_mount(at)?
+            .backend
+            .write_at(&at.rel, start as u64, data)?;
+        Ok(start + data.len())
+    }
+}
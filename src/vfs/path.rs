@@ -0,0 +1,52 @@
+/// Turns `path` into a normalized absolute path, resolving it against `cwd`
+/// when it's relative. `.` and `..` are resolved lexically.
+pub fn resolve(cwd: &str, path: &str) -> String {
+    let joined = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", cwd, path)
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Path components of a normalized absolute path
+pub fn components(path: &str) -> Vec<&str> {
+    path.split('/').filter(|p| !p.is_empty()).collect()
+}
+
+/// Splits a normalized absolute path into its parent directory and final
+/// name. The root has no parent.
+pub fn split_parent(path: &str) -> Option<(String, String)> {
+    let parts = components(path);
+    let (name, parents) = parts.split_last()?;
+    Some((format!("/{}", parents.join("/")), name.to_string()))
+}
+
+/// Last component of a path, or `/` for the root
+pub fn file_name(path: &str) -> &str {
+    components(path).last().copied().unwrap_or("/")
+}
+
+pub fn join(dir: &str, name: &str) -> String {
+    if dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+/// File names can't contain `/` or NUL, and `.`/`..` are reserved
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\0'])
+}
@@ -0,0 +1,162 @@
+use super::FileType;
+
+/// Groups and their members, one `name:member,member` line per group.
+/// Every user is also in a private group with their own name, which isn't
+/// listed here.
+pub const GROUP_FILE_PATH: &str = "/etc/group";
+
+/// Permission bits asked for by an operation
+pub const READ: u16 = 0o4;
+pub const WRITE: u16 = 0o2;
+pub const EXEC: u16 = 0o1;
+
+/// Only the owner of an entry (or of the directory) may remove or rename
+/// entries in a directory with this bit, as in `/tmp`
+pub const STICKY: u16 = 0o1000;
+
+/// Bits `chmod` can set
+pub const MODE_MASK: u16 = 0o1777;
+
+pub const DEFAULT_FILE_MODE: u16 = 0o644;
+pub const DEFAULT_DIR_MODE: u16 = 0o755;
+
+/// `ls -l` style mode string, e.g. `drwxr-xr-x`
+pub fn mode_string(file_type: FileType, mode: u16) -> String {
+    let mut out = String::with_capacity(10);
+    out.push(match file_type {
+        FileType::Directory => 'd',
+        FileType::Regular => '-',
+    });
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        out.push(if bits & READ != 0 { 'r' } else { '-' });
+        out.push(if bits & WRITE != 0 { 'w' } else { '-' });
+        out.push(if bits & EXEC != 0 { 'x' } else { '-' });
+    }
+    if mode & STICKY != 0 {
+        let last = if mode & EXEC != 0 { 't' } else { 'T' };
+        out.pop();
+        out.push(last);
+    }
+    out
+}
+
+/// Parses the contents of `/etc/group`. Blank lines and `#` comments are
+/// skipped.
+pub fn parse_groups(text: &str) -> Vec<(String, Vec<String>)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, members) = line.split_once(':').unwrap_or((line, ""));
+            if name.is_empty() {
+                return None;
+            }
+            let members = members
+                .split(',')
+                .map(str::trim)
+                .filter(|m| !m.is_empty())
+                .map(String::from)
+                .collect();
+            Some((name.to_string(), members))
+        })
+        .collect()
+}
+
+pub fn format_groups(groups: &[(String, Vec<String>)]) -> String {
+    groups
+        .iter()
+        .map(|(name, members)| format!("{}:{}\n", name, members.join(",")))
+        .collect()
+}
+
+/// Applies a `chmod` mode to `current`: either octal (`755`, `1777`) or
+/// symbolic clauses like `u+x`, `go-w`, `a=r` or `+t`, separated by commas.
+pub fn parse_mode(spec: &str, current: u16) -> Result<u16, String> {
+    let invalid = || format!("Invalid mode '{}'.", spec);
+    if !spec.is_empty() && spec.chars().all(|c| c.is_digit(8)) {
+        let mode = u16::from_str_radix(spec, 8).map_err(|_| invalid())?;
+        return if mode & !MODE_MASK == 0 { Ok(mode) } else { Err(invalid()) };
+    }
+
+    let mut mode = current & MODE_MASK;
+    for clause in spec.split(',') {
+        let op_at = clause.find(['+', '-', '=']).ok_or_else(invalid)?;
+        let (who, rest) = clause.split_at(op_at);
+        let mut who_mask = 0;
+        for c in who.chars() {
+            who_mask |= match c {
+                'u' => 0o700,
+                'g' => 0o070,
+                'o' => 0o007,
+                'a' => 0o777,
+                _ => return Err(invalid()),
+            };
+        }
+        if who.is_empty() {
+            who_mask = 0o777;
+        }
+
+        let mut chars = rest.chars();
+        let op = chars.next().ok_or_else(invalid)?;
+        let mut bits = 0;
+        let mut sticky = false;
+        for c in chars {
+            match c {
+                'r' => bits |= 0o444,
+                'w' => bits |= 0o222,
+                'x' => bits |= 0o111,
+                't' => sticky = true,
+                _ => return Err(invalid()),
+            }
+        }
+        let bits = (bits & who_mask) | if sticky { STICKY } else { 0 };
+        mode = match op {
+            '+' => mode | bits,
+            '-' => mode & !bits,
+            _ => (mode & !who_mask) | bits,
+        };
+    }
+    Ok(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_octal_and_symbolic_modes() {
+        assert_eq!(parse_mode("755", 0o600), Ok(0o755));
+        assert_eq!(parse_mode("1777", 0o600), Ok(0o1777));
+        assert_eq!(parse_mode("u+x", 0o644), Ok(0o744));
+        assert_eq!(parse_mode("go-w", 0o666), Ok(0o644));
+        assert_eq!(parse_mode("a=r", 0o755), Ok(0o444));
+        assert_eq!(parse_mode("+x", 0o644), Ok(0o755));
+        assert_eq!(parse_mode("u=rwx,g=,o=", 0o777), Ok(0o700));
+        assert_eq!(parse_mode("+t", 0o777), Ok(0o1777));
+        assert_eq!(parse_mode("-t", 0o1777), Ok(0o777));
+        for bad in ["", "8", "17777", "u", "u+q", "z+r", "rw"] {
+            assert!(parse_mode(bad, 0o644).is_err(), "{:?} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn mode_strings_show_the_sticky_bit() {
+        assert_eq!(mode_string(FileType::Regular, 0o640), "-rw-r-----");
+        assert_eq!(mode_string(FileType::Directory, 0o1777), "drwxrwxrwt");
+        assert_eq!(mode_string(FileType::Directory, 0o1776), "drwxrwxrwT");
+    }
+
+    #[test]
+    fn parses_the_group_file() {
+        let groups = parse_groups("# staff\nstaff: alice, bob\n\nempty:\n:nameless\n");
+        assert_eq!(
+            groups,
+            [
+                ("staff".to_string(), vec!["alice".to_string(), "bob".to_string()]),
+                ("empty".to_string(), vec![]),
+            ]
+        );
+        assert_eq!(parse_groups(&format_groups(&groups)), groups);
+    }
+}
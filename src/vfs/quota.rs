@@ -0,0 +1,212 @@
+/// How long usage may stay over a soft limit, unless an admin changes it
+pub const DEFAULT_GRACE_PERIOD: u64 = 7 * 86_400;
+
+/// Soft and hard limit on one resource, 0 meaning no limit. Usage may go
+/// over the soft limit for the grace period; the hard limit is never
+/// exceeded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limit {
+    pub soft: u64,
+    pub hard: u64,
+    /// When usage went over the soft limit
+    pub over_since: Option<u64>,
+}
+
+impl Limit {
+    pub fn new(soft: u64, hard: u64) -> Self {
+        Limit {
+            soft,
+            hard,
+            over_since: None,
+        }
+    }
+
+    /// Whether usage may grow to `wanted`. Going over the soft limit
+    /// starts the grace period, and coming back under it ends it.
+    pub fn admit(&mut self, wanted: u64, now: u64, grace_period: u64) -> bool {
+        if self.hard > 0 && wanted > self.hard {
+            return false;
+        }
+        if self.soft == 0 || wanted <= self.soft {
+            self.over_since = None;
+            return true;
+        }
+        match self.over_since {
+            None => {
+                self.over_since = Some(now);
+                true
+            }
+            Some(since) => now < since.saturating_add(grace_period),
+        }
+    }
+
+    /// Takes over the grace period of the limit this one replaces, or
+    /// starts one now if `used` is already over the new soft limit
+    pub fn replace(&mut self, old: &Limit, used: u64, now: u64) {
+        self.over_since = if self.soft == 0 || used <= self.soft {
+            None
+        } else {
+            Some(old.over_since.unwrap_or(now))
+        };
+    }
+
+    /// Seconds of grace left for `used`, `None` when it isn't over the
+    /// soft limit
+    pub fn grace_left(&self, used: u64, now: u64, grace_period: u64) -> Option<u64> {
+        if self.soft == 0 || used <= self.soft {
+            return None;
+        }
+        let since = self.over_since.unwrap_or(now);
+        Some(since.saturating_add(grace_period).saturating_sub(now))
+    }
+}
+
+/// A user's limits on bytes stored and on files and directories owned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    pub bytes: Limit,
+    pub inodes: Limit,
+}
+
+impl Quota {
+    pub fn is_unlimited(&self) -> bool {
+        self.bytes.soft == 0
+            && self.bytes.hard == 0
+            && self.inodes.soft == 0
+            && self.inodes.hard == 0
+    }
+}
+
+/// What a user's files take up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub bytes: u64,
+    pub inodes: u64,
+}
+
+/// Parses a size such as `512`, `10K`, `1.5M` or `2G` (powers of 1024).
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let invalid = || format!("'{}' is not a valid size.", text);
+    let upper = text.to_ascii_uppercase();
+    let (number, unit) = match upper.strip_suffix(['K', 'M', 'G', 'T']) {
+        Some(number) => (number, upper.chars().last()),
+        None => (upper.as_str(), None),
+    };
+    let factor: u64 = match unit {
+        None => 1,
+        Some('K') => 1 << 10,
+        Some('M') => 1 << 20,
+        Some('G') => 1 << 30,
+        _ => 1 << 40,
+    };
+    if let Ok(n) = number.parse::<u64>() {
+        return n.checked_mul(factor).ok_or_else(invalid);
+    }
+    match number.parse::<f64>() {
+        Ok(n) if n >= 0.0 && n.is_finite() && unit.is_some() => Ok((n * factor as f64) as u64),
+        _ => Err(invalid()),
+    }
+}
+
+/// Formats a byte count the way `parse_size` reads it: `512`, `1.5K`, `10M`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 && value.fract() != 0.0 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRACE: u64 = 100;
+
+    #[test]
+    fn the_hard_limit_is_never_exceeded() {
+        let mut limit = Limit::new(0, 10);
+        assert!(limit.admit(10, 0, GRACE));
+        assert!(!limit.admit(11, 0, GRACE));
+        assert!(Limit::default().admit(u64::MAX, 0, GRACE));
+    }
+
+    #[test]
+    fn the_soft_limit_may_be_exceeded_for_the_grace_period() {
+        let mut limit = Limit::new(10, 20);
+        assert!(limit.admit(10, 1000, GRACE));
+        assert_eq!(limit.over_since, None);
+
+        assert!(limit.admit(15, 1000, GRACE));
+        assert_eq!(limit.over_since, Some(1000));
+        assert!(limit.admit(18, 1099, GRACE));
+        assert_eq!(limit.over_since, Some(1000));
+        assert!(!limit.admit(18, 1100, GRACE));
+        assert!(!limit.admit(21, 1000, GRACE));
+
+        // Coming back under ends the grace period, so a new one can start
+        assert!(limit.admit(5, 1200, GRACE));
+        assert_eq!(limit.over_since, None);
+        assert!(limit.admit(15, 1300, GRACE));
+        assert_eq!(limit.over_since, Some(1300));
+    }
+
+    #[test]
+    fn a_new_limit_keeps_the_grace_period_already_running() {
+        let old = Limit {
+            soft: 10,
+            hard: 0,
+            over_since: Some(500),
+        };
+        let mut new = Limit::new(12, 0);
+        new.replace(&old, 15, 900);
+        assert_eq!(new.over_since, Some(500));
+
+        let mut new = Limit::new(20, 0);
+        new.replace(&old, 15, 900);
+        assert_eq!(new.over_since, None);
+
+        let mut new = Limit::new(12, 0);
+        new.replace(&Limit::default(), 15, 900);
+        assert_eq!(new.over_since, Some(900));
+    }
+
+    #[test]
+    fn grace_left_counts_down_to_zero() {
+        let limit = Limit {
+            soft: 10,
+            hard: 0,
+            over_since: Some(1000),
+        };
+        assert_eq!(limit.grace_left(5, 1050, GRACE), None);
+        assert_eq!(limit.grace_left(15, 1040, GRACE), Some(60));
+        assert_eq!(limit.grace_left(15, 5000, GRACE), Some(0));
+        assert_eq!(Limit::new(10, 0).grace_left(15, 7, GRACE), Some(GRACE));
+    }
+
+    #[test]
+    fn sizes_read_and_print_with_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10K"), Ok(10 << 10));
+        assert_eq!(parse_size("1.5m"), Ok(3 << 19));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        for bad in ["", "K", "1.5", "-1", "ten", "1X", "99999999999T"] {
+            assert!(parse_size(bad).is_err(), "{}", bad);
+        }
+
+        assert_eq!(format_size(512), "512");
+        assert_eq!(format_size(1536), "1.5K");
+        assert_eq!(format_size(10 << 20), "10M");
+        assert_eq!(parse_size(&format_size(3 << 29)), Ok(3 << 29));
+    }
+}
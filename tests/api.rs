@@ -0,0 +1,119 @@
+//! Runs the HTTP admin API as a service of `minikern serve` and makes
+//! requests of it over TCP
+
+mod common;
+
+use common::{hash, http, Server};
+
+const ROOT_TOKEN: &str = "mk_root";
+const SPARE_TOKEN: &str = "mk_spare";
+const ALICE_TOKEN: &str = "mk_alice";
+
+/// A server with the API on 127.0.0.1, and tokens for root (two of them)
+/// and alice, who isn't an admin. Returns the API's address too.
+fn start(name: &str) -> (Server, String) {
+    let api = format!("127.0.0.1:{}", common::free_port());
+    let config = format!("[api]\nlisten = {}\n", api);
+    let tokens: String = [
+        ("aaaa0001", ROOT_TOKEN, "root"),
+        ("aaaa0002", SPARE_TOKEN, "root"),
+        ("aaaa0003", ALICE_TOKEN, "alice"),
+    ]
+    .iter()
+    .map(|(id, secret, owner)| format!("{}\t{}\t{}\t0\ttest\n", id, hash(secret), owner))
+    .collect();
+    let server = Server::start_with(name, &[("minikern.conf", &config), ("api_tokens", &tokens)]);
+    (server, api)
+}
+
+fn users_xml(server: &Server) -> String {
+    std::fs::read_to_string(server.dir.join("users.xml")).unwrap()
+}
+
+#[test]
+fn requests_need_a_live_admin_token() {
+    let (server, api) = start("api-auth");
+    let get = |token| http(&api, "GET", "/users", token, "");
+
+    assert_eq!(get(None).0, 401);
+    assert_eq!(get(Some("mk_wrong")).0, 401);
+    assert_eq!(get(Some(ALICE_TOKEN)).0, 403);
+    let (status, body) = get(Some(ROOT_TOKEN));
+    assert_eq!(status, 200);
+    assert!(body.contains("\"alice\""), "{}", body);
+    assert_eq!(get(Some(SPARE_TOKEN)).0, 200);
+
+    let mut root = server.login("root");
+    let revoked = root.run("apitoken revoke aaaa0002", "root:/home/root> ");
+    assert!(revoked.contains("aaaa0002"), "{}", revoked);
+    assert_eq!(get(Some(SPARE_TOKEN)).0, 401);
+    assert_eq!(get(Some(ROOT_TOKEN)).0, 200);
+}
+
+#[test]
+fn requests_are_routed_by_method_and_path() {
+    let (_server, api) = start("api-routes");
+    let request = |method, path| http(&api, method, path, Some(ROOT_TOKEN), "");
+
+    let (status, body) = request("GET", "/users/alice");
+    assert_eq!(status, 200);
+    assert!(body.contains("\"name\": \"alice\""), "{}", body);
+    assert!(request("GET", "/users/%61lice").1.contains("\"alice\""));
+    assert_eq!(request("GET", "/users/nobody").0, 404);
+    assert_eq!(request("GET", "/audit?limit=5").0, 200);
+    assert_eq!(request("GET", "/audit?limit=five").0, 400);
+    assert_eq!(request("PUT", "/users").0, 405);
+    assert_eq!(request("POST", "/audit").0, 405);
+    assert_eq!(request("GET", "/groups").0, 404);
+    assert_eq!(request("GET", "/users/alice/extra").0, 404);
+
+    let (status, body) = http(
+        &api,
+        "POST",
+        "/users",
+        Some(ROOT_TOKEN),
+        r#"{"username": "carol", "password": "pw", "admin": false}"#,
+    );
+    assert_eq!(status, 201, "{}", body);
+    assert!(body.contains("\"carol\""), "{}", body);
+}
+
+#[test]
+fn bad_bodies_are_refused() {
+    let (_server, api) = start("api-bodies");
+    let patch = |body| http(&api, "PATCH", "/users/alice", Some(ROOT_TOKEN), body).0;
+
+    assert_eq!(patch("{\"admin\": tru}"), 400);
+    assert_eq!(patch("[true]"), 400);
+    assert_eq!(patch("{\"admin\": \"yes\"}"), 400);
+    assert_eq!(patch("{\"shell\": \"/bin/sh\"}"), 400);
+    assert_eq!(patch("{}"), 400);
+}
+
+#[test]
+fn a_refused_change_leaves_the_account_alone() {
+    let (server, api) = start("api-partial");
+    let before = users_xml(&server);
+
+    // Root can't be demoted, so the new password mustn't be kept either
+    let (status, body) = http(
+        &api,
+        "PATCH",
+        "/users/root",
+        Some(ROOT_TOKEN),
+        r#"{"password": "changed", "admin": false}"#,
+    );
+    assert_eq!(status, 409, "{}", body);
+    assert_eq!(users_xml(&server), before);
+
+    let (status, body) = http(
+        &api,
+        "PATCH",
+        "/users/alice",
+        Some(ROOT_TOKEN),
+        r#"{"password": "changed", "admin": true}"#,
+    );
+    assert_eq!(status, 200, "{}", body);
+    assert!(body.contains("\"admin\": true"), "{}", body);
+    assert!(users_xml(&server).contains(&hash("changed")));
+}
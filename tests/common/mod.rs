@@ -0,0 +1,214 @@
+//! Boots `minikern serve` in a directory of its own for the integration
+//! tests, and talks to it over TCP
+
+#![allow(dead_code)] // Each test binary uses its own share of this
+
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Every account's password
+pub const PASSWORD: &str = "hunter2";
+
+/// How long to wait for output that should come
+pub const PATIENCE: Duration = Duration::from_secs(10);
+
+pub fn hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret))
+}
+
+/// A port nothing is listening on, for a service's `listen`
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// A server booted in a directory of its own, killed when dropped
+pub struct Server {
+    child: Child,
+    /// Where clients log in
+    pub address: String,
+    /// Its working directory, with users.xml and the disk image
+    pub dir: PathBuf,
+}
+
+impl Server {
+    /// Boots with `root` as the admin and `alice` and `bob` as users
+    pub fn start(name: &str) -> Server {
+        Server::start_with(name, &[])
+    }
+
+    /// Boots like `start`, with `files` (name and contents) also in its
+    /// directory, such as minikern.conf
+    pub fn start_with(name: &str, files: &[(&str, &str)]) -> Server {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("serve-{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let hash = hash(PASSWORD);
+        let users: String = [("root", "yes"), ("alice", "no"), ("bob", "no")]
+            .iter()
+            .map(|(name, admin)| {
+                format!(
+                    "<{0}><password>{1}</password><isadmin>{2}</isadmin></{0}>",
+                    name, hash, admin
+                )
+            })
+            .collect();
+        std::fs::write(dir.join("users.xml"), format!("<users>{}</users>", users)).unwrap();
+        for (file, contents) in files {
+            std::fs::write(dir.join(file), contents).unwrap();
+        }
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_minikern"))
+            .args(["serve", "--listen", "127.0.0.1:0"])
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .unwrap();
+        let mut boot = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        let address = loop {
+            line.clear();
+            if boot.read_line(&mut line).unwrap() == 0 {
+                let _ = child.kill();
+                panic!("the server exited before it was listening");
+            }
+            assert!(!line.starts_with("[FAILED]"), "{}", line);
+            if let Some(rest) = line.split("Listening on ").nth(1) {
+                break rest.trim().trim_end_matches('.').to_string();
+            }
+        };
+        // Keep the pipe drained so the server never blocks writing to it
+        std::thread::spawn(move || std::io::copy(&mut boot, &mut std::io::sink()));
+        Server {
+            child,
+            address,
+            dir,
+        }
+    }
+
+    pub fn connect(&self) -> Client {
+        let stream = TcpStream::connect(&self.address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        Client {
+            stream,
+            seen: String::new(),
+        }
+    }
+
+    /// Connects and logs in as `user`, waiting for the first prompt
+    pub fn login(&self, user: &str) -> Client {
+        let mut client = self.connect();
+        client.expect("Username: > ");
+        client.send(user);
+        client.expect("Password: > ");
+        client.send(PASSWORD);
+        client.expect(&format!("{0}:/home/{0}> ", user));
+        client
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A terminal session on the server
+pub struct Client {
+    pub stream: TcpStream,
+    /// Output read but not yet expected
+    seen: String,
+}
+
+impl Client {
+    pub fn send(&mut self, line: &str) {
+        self.stream
+            .write_all(format!("{}\r", line).as_bytes())
+            .unwrap();
+    }
+
+    /// Reads until `text` arrives, returning everything up to and
+    /// including it
+    pub fn expect(&mut self, text: &str) -> String {
+        self.expect_within(text, PATIENCE)
+            .unwrap_or_else(|| panic!("expected {:?}, got {:?}", text, self.seen))
+    }
+
+    pub fn expect_within(&mut self, text: &str, limit: Duration) -> Option<String> {
+        let deadline = Instant::now() + limit;
+        loop {
+            if let Some(at) = self.seen.find(text) {
+                let rest = self.seen.split_off(at + text.len());
+                return Some(std::mem::replace(&mut self.seen, rest));
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            let mut buf = [0u8; 4096];
+            match self.stream.read(&mut buf) {
+                Ok(0) => return None,
+                Ok(n) => self.seen.push_str(&String::from_utf8_lossy(&buf[..n])),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(e) => panic!("reading from the server: {}", e),
+            }
+        }
+    }
+
+    /// Runs a command and returns its output, up to the next prompt
+    pub fn run(&mut self, command: &str, prompt: &str) -> String {
+        self.send(command);
+        let output = self.expect(prompt);
+        output
+            .trim_end_matches(prompt)
+            .lines()
+            .skip(1)
+            .filter(|line| !line.starts_with("-----"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Makes one HTTP request, returning the status and body of the response
+pub fn http(
+    address: &str,
+    method: &str,
+    path: &str,
+    token: Option<&str>,
+    body: &str,
+) -> (u16, String) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(PATIENCE)).unwrap();
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n",
+        method,
+        path,
+        address,
+        body.len()
+    );
+    if let Some(token) = token {
+        request.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    write!(stream, "{}\r\n{}", request, body).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .unwrap_or_else(|| panic!("bad response {:?}", response));
+    (status, body.to_string())
+}
@@ -0,0 +1,73 @@
+//! Runs `minikern serve` on a port of its own and logs clients in over TCP
+
+mod common;
+
+use common::{Client, Server, PATIENCE};
+use std::io::Write;
+use std::net::Shutdown;
+use std::time::{Duration, Instant};
+
+#[test]
+fn clients_get_sessions_of_their_own() {
+    let server = Server::start("isolated");
+    let mut alice = server.login("alice");
+    let mut bob = server.login("bob");
+
+    alice.run("cd /tmp", "alice:/tmp> ");
+    alice.run("set COLOR=red", "alice:/tmp> ");
+    assert_eq!(alice.run("echo $COLOR", "alice:/tmp> "), "red");
+
+    assert_eq!(bob.run("pwd", "bob:/home/bob> "), "/home/bob");
+    assert_eq!(bob.run("echo [$COLOR]", "bob:/home/bob> "), "[]");
+
+    // One client waiting doesn't hold up the other
+    alice.send("sleep 30");
+    assert_eq!(bob.run("echo still here", "bob:/home/bob> "), "still here");
+    alice.stream.write_all(b"\x03").unwrap();
+    alice.expect("alice:/tmp> ");
+}
+
+#[test]
+fn ctrl_c_interrupts_only_that_clients_command() {
+    let server = Server::start("interrupt");
+    let mut alice = server.login("alice");
+    let mut bob = server.login("bob");
+
+    alice.send("sleep 30");
+    bob.send("sleep 30");
+    let started = Instant::now();
+    alice.stream.write_all(b"\x03").unwrap();
+    let output = alice.expect("alice:/home/alice> ");
+    assert!(output.contains("^C"), "{:?}", output);
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    assert!(
+        bob.expect_within("bob:/home/bob> ", Duration::from_secs(1))
+            .is_none(),
+        "bob's sleep was interrupted too"
+    );
+    bob.stream.write_all(b"\x03").unwrap();
+    bob.expect("bob:/home/bob> ");
+    assert_eq!(alice.run("echo back", "alice:/home/alice> "), "back");
+}
+
+#[test]
+fn hanging_up_ends_the_session() {
+    let server = Server::start("hangup");
+    let alice = server.login("alice");
+    let mut bob = server.login("bob");
+    let who = |bob: &mut Client| bob.run("who", "bob:/home/bob> ");
+    assert!(who(&mut bob).contains("alice"));
+
+    alice.stream.shutdown(Shutdown::Both).unwrap();
+    drop(alice);
+    let deadline = Instant::now() + PATIENCE;
+    while who(&mut bob).contains("alice") {
+        assert!(Instant::now() < deadline, "alice is still logged in");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    // The server goes on taking new clients
+    let mut alice = server.login("alice");
+    assert_eq!(alice.run("pwd", "alice:/home/alice> "), "/home/alice");
+}